@@ -18,6 +18,7 @@ export default defineConfig({
                     {text: '简介', link: '/started'},
                     {text: '命令行参数', link: '/started/argument'},
                     {text: '优化选项', link: '/started/optimizer'},
+                    {text: '语言服务器', link: '/started/lsp'},
                 ]
             },
             {
@@ -27,10 +28,13 @@ export default defineConfig({
                     {text: '依赖导入', link: '/example/import'},
                     {text: '表达式', link: '/example/expression'},
                     {text: '函数', link: '/example/function'},
+                    {text: '类', link: '/example/class'},
                     {text: '判断语句', link: '/example/conditional'},
                     {text: '循环语句', link: '/example/loop'},
                     {text: '变量', link: '/example/value'},
+                    {text: '类型标注', link: '/example/annotation'},
                     {text: '线程安全', link: '/example/sync'},
+                    {text: '异常处理', link: '/example/exception'},
                 ]
             },
             {
@@ -40,6 +44,8 @@ export default defineConfig({
                     {text: 'system', link: '/api/system'},
                     {text: 'math', link: '/api/math'},
                     {text: 'type', link: '/api/type'},
+                    {text: 'string', link: '/api/string'},
+                    {text: 'fs', link: '/api/fs'},
                     {text: 'cffi', link: '/api/cffi'},
                 ]
             },
@@ -0,0 +1,201 @@
+/* OpenEX 嵌入接口, 该文件由 cbindgen 生成, 请勿手动修改 */
+
+#ifndef OPENEX_H
+#define OPENEX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum OpenExStatus {
+  Success = 0,
+  ParseError = 2,
+  RuntimeError = 3,
+  FfiError = 4,
+} OpenExStatus;
+
+typedef enum ValueTag {
+  Int = 0,
+  Bool = 1,
+  Float = 2,
+  String = 3,
+  Ref = 4,
+  Null = 5,
+  Array = 6,
+  Map = 7,
+} ValueTag;
+
+typedef struct OpenEX OpenEX;
+
+typedef struct CArray {
+  size_t len;
+  struct CValue *items;
+} CArray;
+
+typedef struct CMap {
+  size_t len;
+  const char **keys;
+  struct CValue *values;
+} CMap;
+
+typedef union ValueData {
+  int64_t i;
+  bool b;
+  double f;
+  const char *s;
+  struct CArray a;
+  struct CMap m;
+} ValueData;
+
+typedef struct CValue {
+  enum ValueTag tag;
+  union ValueData data;
+} CValue;
+
+// 宿主提供的本地函数回调
+// `args` 为长度 `arg_count` 的参数数组, 仅在回调期间有效
+// 回调需要向 `out_result` 写入返回值, 返回非 `Success` 时脚本中会抛出 `NativeException`
+// 在 C 中可以为 NULL, 注册时传入 NULL 会返回 `FfiError`
+typedef enum OpenExStatus (*OpenExNativeCallback)(const struct CValue *args,
+                                                  size_t arg_count,
+                                                  struct CValue *out_result,
+                                                  void *user_data);
+
+// 禁用交互实例中指定的本地库 (如 `fs`), 用于沙箱环境下的宿主程序.
+// 需要在 `openex_compile` 之前调用, 被禁用的库会从该实例中移除, 脚本也无法导入.
+// # Safety
+// `name` 是一个有效的 C 字符串指针(以 NUL 结尾)
+enum OpenExStatus openex_disable_library(struct OpenEX *handle_raw,
+                                         const char *name);
+
+// 禁止交互实例中的脚本声明 `function native("库", "符号")` 形式的动态库函数, 用于沙箱环境下的宿主程序.
+// 需要在 `openex_compile` 之前调用, 声明动态库函数的脚本会编译失败.
+// # Safety
+enum OpenExStatus openex_disable_foreign(struct OpenEX *handle_raw);
+
+// 开启或关闭交互实例中的单项优化 (如 `const-prop`), 用于排查优化导致的错误结果.
+// 编译期优化需要在 `openex_compile` 之前设置, `call-memo` 对之后的函数调用生效.
+// # Safety
+// `pass` 是一个有效的 C 字符串指针(以 NUL 结尾)
+enum OpenExStatus openex_set_optimization(struct OpenEX *handle_raw,
+                                          const char *pass,
+                                          bool enabled);
+
+// 设置之后每次执行脚本 (`openex_run` 与 `openex_call_function`) 时的执行限制, 参数为 0 表示不限制.
+// 指令数、超时与 `max_length` 按每次执行单独计算, `max_length` 限制累计新建的数组与映射元素数、字符串字节数及任意精度整数字节数.
+// 超出限制时执行返回 `RuntimeError`, 错误信息中的异常类型为 `LimitExceeded`.
+// # Safety
+enum OpenExStatus openex_set_limits(struct OpenEX *handle_raw,
+                                    uint64_t max_instructions,
+                                    size_t max_call_depth,
+                                    size_t max_length,
+                                    uint64_t timeout_ms);
+
+// 向交互实例注册一个由宿主实现的本地函数, 需要在 `openex_compile` 之前调用.
+// 脚本需要在名为 `module` 的源文件中通过 `function native name(...);` 声明该函数.
+// # Safety
+// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+// `callback` 在交互实例的整个生命周期内都必须可以调用, `user_data` 原样传递给回调
+enum OpenExStatus openex_register_native(struct OpenEX *handle_raw,
+                                         const char *module,
+                                         const char *name,
+                                         size_t arity,
+                                         OpenExNativeCallback callback,
+                                         void *user_data);
+
+// 对其他语言提供的本地接口, 该函数负责初始化编译前端环境并编译 `OpenEX` 标准库.
+// # Safety
+// `lib_path` 是一个有效的 C 字符串指针(以 NUL 结尾) 其字符串内容为标准库的路径
+// `lib_path` 可以为 NULL, 为NULL时解释器会在当前目录查找标准库实现
+// `opt_level` 为优化等级 (0-2), 超过最高等级时按最高等级处理
+//
+// 函数返回值不应该被调用者修改内部的任何字段, 仅当句柄传递给各功能函数
+// C 函数定义: `void *openex_init(const char *lib_path, unsigned char opt_level)`;
+struct OpenEX *openex_init(const char *lib_path,
+                           uint8_t opt_level);
+
+// 往当前编译器句柄添加一个源文件
+// # Safety
+// # Panics
+enum OpenExStatus openex_add_file(struct OpenEX *handle_raw, const char *source, const char *name);
+
+// 开始编译所有已经添加的源文件
+// # Safety
+// # Panics
+// 返回编译状态
+enum OpenExStatus openex_compile(struct OpenEX *handle_raw);
+
+// 初始化执行引擎环境, 必须编译完所有文件后再调用此函数, 否则新加入的函数无法被添加到执行引擎环境
+// 此函数只能调用一次, 多次调用会导致未定义行为
+// 在调用此函数前不得运行执行引擎
+// # Safety
+// # Panics
+enum OpenExStatus openex_initialize_executor(struct OpenEX *handle_raw);
+
+// 执行入口文件 (第一个非库源文件) 的全局代码, 全局变量在此之后才具有初始值
+// 需要在 `openex_initialize_executor` 之后调用, 出现未捕获的运行时错误时返回 `RuntimeError`
+// # Safety
+enum OpenExStatus openex_run(struct OpenEX *handle_raw);
+
+// 调用一个 `OpenEX` 函数, 需要指定文件名和函数名
+// # Safety
+// # Panics
+// `args_ptr` 和 `arg_count` 必须是有效的, 如没有参数要传递 `arg_count` 标记为 0 即可
+// `out_result` 为 NULL 代表不需要接受返回值
+enum OpenExStatus openex_call_function(struct OpenEX *handle_raw,
+                                       const char *file,
+                                       const char *func,
+                                       const struct CValue *args_ptr,
+                                       size_t arg_count,
+                                       struct CValue *out_result);
+
+// 读取最近一次失败的错误信息, 编译与运行时错误包含出错的文件与行列位置
+// 没有错误时返回 NULL, 返回的字符串由句柄管理, 在下一次出错或释放句柄前有效
+// # Safety
+const char *openex_last_error(const struct OpenEX *handle_raw);
+
+// 读取最近一次 `openex_compile` 输出的全部错误与警告, 以字典数组的形式写入 `out_result`
+// 字典包含 severity code message file line column end_line end_column notes suggestion
+// # Safety
+// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+enum OpenExStatus openex_get_diagnostics(const struct OpenEX *handle_raw,
+                                         struct CValue *out_result);
+
+// 按名称读取模块中的全局变量, 需要在 `openex_initialize_executor` 之后调用
+// # Safety
+// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+enum OpenExStatus openex_get_global(struct OpenEX *handle_raw,
+                                    const char *module,
+                                    const char *name,
+                                    struct CValue *out_result);
+
+// 按名称修改模块中的全局变量, 需要在 `openex_initialize_executor` 之后调用
+// # Safety
+// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+// `value` 指向一个有效的值, 其内容会被复制, 内存仍由调用者管理
+enum OpenExStatus openex_set_global(struct OpenEX *handle_raw,
+                                    const char *module,
+                                    const char *name,
+                                    const struct CValue *value);
+
+// 列出模块中定义的函数名, 以字符串数组的形式写入 `out_result`, 匿名函数不会列出
+// # Safety
+// `module` 是有效的 C 字符串指针(以 NUL 结尾)
+// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+enum OpenExStatus openex_list_functions(struct OpenEX *handle_raw,
+                                        const char *module,
+                                        struct CValue *out_result);
+
+// 释放掉 `OpenEX` 句柄环境占用的资源
+// # Safety
+// # Panics
+enum OpenExStatus openex_free(struct OpenEX *handle_raw);
+
+// 用于释放 `OpenEX` 值传递句柄
+// 该函数不释放句柄本身, 而是释放 `OpenEX` 内值具体包含的字符串等额外数据, 句柄本身是由 cffi 管理.
+// # Safety
+// # Panics
+enum OpenExStatus openex_free_c_value(struct CValue *c_val);
+
+#endif  /* OPENEX_H */
@@ -0,0 +1,40 @@
+use getopts_macro::getopts_options;
+use openex::compiler::Compiler;
+use openex::lsp::Server;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn main() {
+    let options = getopts_options! {
+        -l, --lib=DIR       "Set libraries directory (default: <workspace>/lib or ./lib)";
+        -v, --version       "Print version";
+        -h, --help          "Print help";
+    };
+    let m = match options.parse(std::env::args().skip(1)) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(2)
+        }
+    };
+    if m.opt_present("help") {
+        let brief = "Usage: openex-lsp [OPTIONS]\n\n\
+            Language server for OpenEX, speaking LSP over stdin and stdout.";
+        print!("{}", options.usage(brief));
+        exit(1)
+    }
+    if m.opt_present("version") {
+        println!("openex-lsp {}", Compiler::get_version());
+        return;
+    }
+
+    let mut server = Server::new(m.opt_str("lib").map(PathBuf::from));
+    let code = server
+        .run(&mut io::stdin().lock(), &mut io::stdout().lock())
+        .unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            1
+        });
+    exit(code)
+}
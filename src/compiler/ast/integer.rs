@@ -0,0 +1,172 @@
+use dashu::base::BitTest;
+use dashu::float::DBig;
+use dashu::integer::IBig;
+use std::cmp::Ordering;
+
+use crate::compiler::ast::ssa_ir::Operand;
+use crate::compiler::ast::vm_ir::Value;
+
+// 整数在 i64 范围内时使用 Value::Int, 超出时自动提升为 Value::BigInt
+// 常量折叠与执行引擎共用此处的运算, 以保证两者的结果一致
+
+/// 整数之间的二元运算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rmd,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl IntOp {
+    // 两个操作数均为 i64 时的快速路径, 溢出时返回 None
+    fn small(self, left: i64, right: i64) -> Option<i64> {
+        match self {
+            Self::Add => left.checked_add(right),
+            Self::Sub => left.checked_sub(right),
+            Self::Mul => left.checked_mul(right),
+            Self::Div => left.checked_div(right),
+            Self::Rmd => left.checked_rem(right),
+            Self::BitAnd => Some(left & right),
+            Self::BitOr => Some(left | right),
+            Self::BitXor => Some(left ^ right),
+            Self::Shl => u32::try_from(right)
+                .ok()
+                .and_then(|shift| left.checked_shl(shift))
+                .filter(|result| result >> right == left),
+            Self::Shr => u32::try_from(right).ok().map(|shift| left >> shift.min(63)),
+        }
+    }
+
+    fn big(self, left: IBig, right: IBig) -> Option<IBig> {
+        match self {
+            Self::Add => Some(left + right),
+            Self::Sub => Some(left - right),
+            Self::Mul => Some(left * right),
+            Self::Div | Self::Rmd if right == IBig::ZERO => None,
+            Self::Div => Some(left / right),
+            Self::Rmd => Some(left % right),
+            Self::BitAnd => Some(left & right),
+            Self::BitOr => Some(left | right),
+            Self::BitXor => Some(left ^ right),
+            Self::Shl => Some(left << usize::try_from(&right).ok()?),
+            Self::Shr => Some(left >> usize::try_from(&right).ok()?),
+        }
+    }
+
+    /// 运算结果字节数的上限, 执行限制借此在进行耗时的任意精度运算之前检查长度额度
+    #[must_use]
+    pub fn result_bytes(self, left: &Value, right: &Value) -> usize {
+        let (l, r) = (left.bit_len(), right.bit_len());
+        let bits = match self {
+            Self::Add | Self::Sub | Self::BitAnd | Self::BitOr | Self::BitXor => l.max(r) + 1,
+            Self::Mul => l.saturating_add(r),
+            Self::Div | Self::Rmd | Self::Shr => l,
+            Self::Shl => right
+                .to_ibig()
+                .and_then(|shift| usize::try_from(&shift).ok())
+                .map_or(usize::MAX, |shift| l.saturating_add(shift)),
+        };
+        bits.div_ceil(8)
+    }
+
+    /// 对两个整数值进行运算, 结果能以 i64 表示时为 `Int`, 否则为 `BigInt`
+    /// 任一操作数不是整数、除数为零或移位位数为负时返回 None
+    #[must_use]
+    pub fn apply(self, left: &Value, right: &Value) -> Option<Value> {
+        if let (Value::Int(l), Value::Int(r)) = (left, right)
+            && let Some(result) = self.small(*l, *r)
+        {
+            return Some(Value::Int(result));
+        }
+        let result = self.big(left.to_ibig()?, right.to_ibig()?)?;
+        Some(Value::from_ibig(result))
+    }
+}
+
+impl Value {
+    /// 由任意精度整数构造整数值, 能以 i64 表示时降级为 `Int`
+    #[must_use]
+    pub fn from_ibig(value: IBig) -> Self {
+        i64::try_from(&value).map_or(Self::BigInt(value), Self::Int)
+    }
+
+    /// 整数值的任意精度表示, 不是整数时返回 None
+    #[must_use]
+    pub fn to_ibig(&self) -> Option<IBig> {
+        match self {
+            Self::Int(i) => Some(IBig::from(*i)),
+            Self::BigInt(i) => Some(i.clone()),
+            _ => None,
+        }
+    }
+
+    /// 整数值的二进制位数, 不是整数时为 0
+    #[must_use]
+    pub fn bit_len(&self) -> usize {
+        match self {
+            Self::Int(i) => (u64::BITS - i.unsigned_abs().leading_zeros()) as usize,
+            Self::BigInt(i) => i.bit_len(),
+            _ => 0,
+        }
+    }
+
+    /// 整数或浮点数值的浮点表示, 用于整数与浮点数的混合运算
+    #[must_use]
+    pub fn to_dbig(&self) -> Option<DBig> {
+        match self {
+            Self::Int(i) => Some(DBig::from(*i)),
+            Self::BigInt(i) => Some(DBig::from(i.clone())),
+            Self::Float(f) => Some(f.clone()),
+            _ => None,
+        }
+    }
+
+    /// 比较两个数值, 整数之间精确比较, 与浮点数比较时整数转换为浮点数
+    /// 任一操作数不是数值时返回 None
+    #[must_use]
+    pub fn compare_number(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(l), Self::Int(r)) => Some(l.cmp(r)),
+            (Self::Int(_) | Self::BigInt(_), Self::Int(_) | Self::BigInt(_)) => {
+                Some(self.to_ibig()?.cmp(&other.to_ibig()?))
+            }
+            _ => self.to_dbig()?.partial_cmp(&other.to_dbig()?),
+        }
+    }
+}
+
+impl Operand {
+    /// 整数常量, 能以 i64 表示时为 `ImmNum`
+    #[must_use]
+    pub fn from_ibig(value: IBig) -> Self {
+        Self::from_int_value(Value::from_ibig(value)).unwrap()
+    }
+
+    /// 整数值对应的常量, 不是整数时返回 None
+    #[must_use]
+    pub fn from_int_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Int(i) => Some(Self::ImmNum(i)),
+            Value::BigInt(i) => Some(Self::ImmBigNum(i)),
+            _ => None,
+        }
+    }
+
+    /// 数值常量对应的运行时值, 常量折叠借此与执行引擎使用相同的运算
+    #[must_use]
+    pub fn number_value(&self) -> Option<Value> {
+        match self {
+            Self::ImmNum(i) => Some(Value::Int(*i)),
+            Self::ImmBigNum(i) => Some(Value::BigInt(i.clone())),
+            Self::ImmFlot(f) => Some(Value::Float(f.clone())),
+            _ => None,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod integer;
 pub mod ssa_ir;
 pub mod vm_ir;
 
@@ -63,15 +64,46 @@ pub enum ASTExprTree {
         name: Box<ASTExprTree>, // 必须为 Var
         args: Vec<ASTExprTree>,
     },
+    Slice {
+        // arr[start:end], 省略的边界为 None
+        token: Token,
+        target: Box<ASTExprTree>,
+        start: Option<Box<ASTExprTree>>,
+        end: Option<Box<ASTExprTree>>,
+    },
+    Lambda {
+        // function (a, b) { ... } 匿名函数, 按值捕获外层局部变量
+        token: Token,
+        args: Vec<ASTExprTree>,
+        body: Vec<ASTStmtTree>,
+    },
+    Array {
+        // [1, 2] 表达式中的数组字面量
+        token: Token,
+        elements: Vec<ASTExprTree>,
+    },
+    Map {
+        // {"key": value} 表达式中的字典字面量
+        token: Token,
+        entries: Vec<(ASTExprTree, ASTExprTree)>,
+    },
+}
+
+// 函数的类型标注 function f(a: int, b): bool, 未标注的位置为 None
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TypeHints {
+    pub args: Vec<Option<Token>>, // 与形参一一对应
+    pub ret: Option<Token>,       // 返回值类型
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ASTStmtTree {
     Root(Vec<ASTStmtTree>),
     Block(Vec<ASTStmtTree>),
     Var {
         // var x = 5;
         name: Token,
+        annotation: Option<Token>, // 类型标注 var x: int
         value: Option<ASTExprTree>,
     },
     Expr(ASTExprTree),               // 表达式语句：a + b;
@@ -89,11 +121,20 @@ pub enum ASTStmtTree {
         name: Token,
         sync: bool,
         args: Vec<ASTExprTree>,
+        hints: TypeHints,
         body: Vec<ASTStmtTree>,
     },
     NativeFunction {
         name: Token,
         args: Vec<ASTExprTree>,
+        hints: TypeHints,
+        foreign: Option<(Token, Token)>, // native("库", "符号") 声明的动态库与符号名
+    },
+    Class {
+        // class Name { var field = value; function method() {} }
+        name: Token,
+        fields: Vec<ASTStmtTree>,  // 字段声明 (var 语句)
+        methods: Vec<ASTStmtTree>, // 方法定义 (Function)
     },
     If {
         // if (cond) { then } elif (cond) { elif_ } else { else_ }
@@ -103,18 +144,63 @@ pub enum ASTStmtTree {
     },
     Array {
         token: Token,
+        annotation: Option<Token>,
         elements: Vec<ASTExprTree>,
     },
     ArrayFill {
         token: Token,
+        annotation: Option<Token>,
         value: ASTExprTree,
         count: ASTExprTree,
     },
+    Map {
+        // var token = { key: value, ... }
+        token: Token,
+        annotation: Option<Token>,
+        entries: Vec<(ASTExprTree, ASTExprTree)>,
+    },
+    Try {
+        // try { body } catch (name) { catch_body }
+        token: Token,
+        body: Vec<ASTStmtTree>,
+        catch_name: Token,
+        catch_body: Vec<ASTStmtTree>,
+    },
+    Throw(Token, ASTExprTree), // throw x;
     Break(Token),
     Continue(Token),
     Empty, // 空语句需要剔除
 }
 
+impl ASTStmtTree {
+    // 语句的定位 token, 用于生成行号表
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Self::Var { name: token, .. }
+            | Self::Function { name: token, .. }
+            | Self::NativeFunction { name: token, .. }
+            | Self::Class { name: token, .. }
+            | Self::Import(token, ..)
+            | Self::Loop { token, .. }
+            | Self::Array { token, .. }
+            | Self::ArrayFill { token, .. }
+            | Self::Map { token, .. }
+            | Self::Try { token, .. }
+            | Self::Throw(token, _)
+            | Self::Break(token)
+            | Self::Continue(token) => Some(token),
+            Self::Expr(expr) | Self::Return(Some(expr)) | Self::If { cond: expr, .. } => {
+                Some(expr.token())
+            }
+            Self::Root(_)
+            | Self::Block(_)
+            | Self::Context(_)
+            | Self::Return(None)
+            | Self::Empty => None,
+        }
+    }
+}
+
 impl ASTExprTree {
     pub fn token(&self) -> &Token {
         match self {
@@ -123,7 +209,11 @@ impl ASTExprTree {
             | Self::Var(token)
             | Self::This(token)
             | Self::Expr { token, .. }
-            | Self::Unary { token, .. } => token,
+            | Self::Unary { token, .. }
+            | Self::Slice { token, .. }
+            | Self::Lambda { token, .. }
+            | Self::Array { token, .. }
+            | Self::Map { token, .. } => token,
         }
     }
 }
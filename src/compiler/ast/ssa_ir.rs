@@ -1,11 +1,14 @@
 use dashu::float::FBig;
 use dashu::float::round::mode::HalfAway;
+use dashu::integer::IBig;
 use linked_hash_map::LinkedHashMap;
 use slotmap::{DefaultKey, SlotMap};
 use smol_str::SmolStr;
 use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
 
-use crate::compiler::lexer::Token;
+use crate::compiler::lexer::{SourcePos, Token};
+use crate::library::ffi::ForeignFunc;
 
 #[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct LocalAddr {
@@ -20,6 +23,7 @@ pub enum Operand {
     This,
     ImmBool(bool),
     ImmNum(i64),
+    ImmBigNum(IBig), // 超出 i64 范围的整数字面量或折叠结果
     ImmFlot(FBig<HalfAway, 10>),
     ImmStr(SmolStr),
     Call(SmolStr),
@@ -39,12 +43,35 @@ pub enum ValueGuessType {
     This,
     Unknown,
     Array,
+    Map,
+    Function,        // 函数值 (闭包或函数引用)
+    Object(SmolStr), // 类实例 (类名)
+}
+
+// 类型标注中使用的类型名, 用于类型不匹配的错误信息
+impl Display for ValueGuessType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::Number => write!(f, "int"),
+            Self::String => write!(f, "string"),
+            Self::Float => write!(f, "float"),
+            Self::Null => write!(f, "null"),
+            Self::Ref | Self::This => write!(f, "ref"),
+            Self::Unknown => write!(f, "any"),
+            Self::Array => write!(f, "array"),
+            Self::Map => write!(f, "map"),
+            Self::Function => write!(f, "function"),
+            Self::Object(name) => write!(f, "{name}"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Value {
     pub(crate) variable: bool,        // 是否被重赋值
     pub(crate) type_: ValueGuessType, // 猜测类型
+    pub(crate) declared: bool,        // 类型是否由标注给出, 为真时赋值不会改变类型
     token: Token,                     // 变量名 token
 }
 
@@ -58,18 +85,26 @@ pub enum OpCode {
     StoreLocal(Option<LocalAddr>, DefaultKey, Operand), // 将一个变量加载到栈顶
     LoadArrayLocal(Option<LocalAddr>, DefaultKey, usize), // 将指定栈顶元素组合成数组加载到局部变量表
     LoadArrayGlobal(Option<LocalAddr>, DefaultKey, usize), // 将指定栈顶元素组合成数组加载到全局变量表
-    SetArrayLocal(Option<LocalAddr>, DefaultKey),          // 将栈顶元素设置进数组指定索引
-    SetArrayGlobal(Option<LocalAddr>, DefaultKey),         // 将栈顶元素设置进数组指定索引
-    Push(Option<LocalAddr>, Operand),                      // 将值压入操作栈
-    Pop(Option<LocalAddr>, usize),                         // 弹出操作栈顶部的值
-    AddLocalImm(Option<LocalAddr>, DefaultKey, i64),       // 局部变量 += 立即数
-    Call(Option<LocalAddr>, SmolStr),                      // 函数调用
-    Jump(Option<LocalAddr>, Option<LocalAddr>),            // 无条件跳转
+    LoadMapLocal(Option<LocalAddr>, DefaultKey, usize), // 将指定栈顶键值对组合成字典加载到局部变量表
+    LoadMapGlobal(Option<LocalAddr>, DefaultKey, usize), // 将指定栈顶键值对组合成字典加载到全局变量表
+    SetArrayLocal(Option<LocalAddr>, DefaultKey),        // 将栈顶元素设置进数组指定索引
+    SetArrayGlobal(Option<LocalAddr>, DefaultKey),       // 将栈顶元素设置进数组指定索引
+    Push(Option<LocalAddr>, Operand),                    // 将值压入操作栈
+    Pop(Option<LocalAddr>, usize),                       // 弹出操作栈顶部的值
+    AddLocalImm(Option<LocalAddr>, DefaultKey, i64),     // 局部变量 += 立即数
+    Call(Option<LocalAddr>, SmolStr, usize),             // 函数调用 (被调用者) (实参个数)
+    MakeClosure(Option<LocalAddr>, SmolStr, usize), // 以栈顶的捕获值创建闭包 (函数路径) (捕获数量)
+    Jump(Option<LocalAddr>, Option<LocalAddr>),     // 无条件跳转
     JumpTrue(Option<LocalAddr>, Option<LocalAddr>, Operand), // 栈顶结果为真则跳转
     JumpFalse(Option<LocalAddr>, Option<LocalAddr>, Operand), // 栈顶结构为假则跳转
-    LazyJump(Option<LocalAddr>, Option<LocalAddr>, bool),  // 懒跳转 (是否是 break)
-    Return(Option<LocalAddr>),                             // 栈顶结果返回
-    Nop(Option<LocalAddr>),                                // 空操作
+    LazyJump(Option<LocalAddr>, Option<LocalAddr>, bool), // 懒跳转 (是否是 break)
+    Return(Option<LocalAddr>),                      // 栈顶结果返回
+    Nop(Option<LocalAddr>),                         // 空操作
+    Echo(Option<LocalAddr>),                        // 交互模式回显栈顶结果
+    Try(Option<LocalAddr>, Option<LocalAddr>),      // 注册异常处理器 (处理器入口)
+    EndTry(Option<LocalAddr>),                      // 注销最近注册的异常处理器
+    Catch(Option<LocalAddr>),                       // 异常处理器入口, 将捕获的异常压入栈顶
+    Throw(Option<LocalAddr>),                       // 将栈顶元素作为异常抛出
 
     Pos(Option<LocalAddr>), // +
     Neg(Option<LocalAddr>), // -
@@ -115,6 +150,16 @@ pub enum OpCode {
     Ref(Option<LocalAddr>),                       // .
     AIndex(Option<LocalAddr>),                    // 数组索引
     GetIndexLocal(Option<LocalAddr>, DefaultKey), // 从局部数组取索引值
+    NewObject(Option<LocalAddr>, SmolStr, usize), // 以栈顶的字段名与初始值创建对象 (类路径) (字段数量)
+    NewArray(Option<LocalAddr>, usize),           // 以栈顶元素创建数组并压入栈顶 (元素数量)
+    NewMap(Option<LocalAddr>, usize),             // 以栈顶键值对创建字典并压入栈顶 (键值对数量)
+    GetField(Option<LocalAddr>, SmolStr),         // 取出栈顶对象的字段值
+    SetField(Option<LocalAddr>, SmolStr),         // 将次栈顶的值写入栈顶对象的字段
+    GetMethod(Option<LocalAddr>, SmolStr),        // 将栈顶对象的方法绑定接收者后作为闭包压入栈顶
+
+    // 在数组变量上原地调用 type 库函数 (函数名) (数组之外的实参个数)
+    ArrayCallLocal(Option<LocalAddr>, DefaultKey, SmolStr, usize),
+    ArrayCallGlobal(Option<LocalAddr>, DefaultKey, SmolStr, usize),
 }
 
 impl OpCode {
@@ -124,9 +169,10 @@ impl OpCode {
             self.set_id(*new_addr);
         }
 
-        // 重定位 Jump 和 JumpTrue 的跳转目标
+        // 重定位 Jump, JumpTrue 的跳转目标与 Try 的处理器入口
         match self {
-            Self::JumpTrue(_, target, ..)
+            Self::Try(_, target)
+            | Self::JumpTrue(_, target, ..)
             | Self::JumpFalse(_, target, ..)
             | Self::Jump(_, target)
             | Self::LazyJump(_, target, ..) => {
@@ -145,6 +191,7 @@ impl OpCode {
 #[derive(Debug, PartialEq, Clone)]
 pub struct OpCodeTable {
     pub(crate) opcodes: LinkedHashMap<LocalAddr, OpCode>,
+    pub(crate) positions: HashMap<LocalAddr, SourcePos>, // IR 对应的源码位置
     alloc_addr: LocalAddr,
 }
 
@@ -159,6 +206,7 @@ impl OpCodeTable {
     pub fn new() -> Self {
         Self {
             opcodes: LinkedHashMap::new(),
+            positions: HashMap::new(),
             alloc_addr: LocalAddr { offset: 0 },
         }
     }
@@ -210,6 +258,9 @@ impl OpCodeTable {
             new_op.relocate_addr(&old_to_new);
 
             let new_addr = old_to_new[old_addr];
+            if let Some(pos) = code.positions.get(old_addr) {
+                self.positions.insert(new_addr, *pos);
+            }
             self.opcodes.insert(new_addr, new_op);
             last_addr = Some(new_addr);
         }
@@ -222,6 +273,31 @@ impl OpCodeTable {
         )
     }
 
+    // 为尚未记录源码位置的 IR 标记位置, 内层表达式先标记因此位置更精确
+    pub fn mark_pos(&mut self, token: &Token) {
+        self.mark_pos_since(LocalAddr { offset: 0 }, token);
+    }
+
+    // 仅标记 start 之后新加入的 IR
+    pub fn mark_pos_since(&mut self, start: LocalAddr, token: &Token) {
+        for addr in self.opcodes.keys().rev() {
+            if addr.offset < start.offset {
+                break;
+            }
+            self.positions.entry(*addr).or_insert_with(|| token.pos());
+        }
+    }
+
+    #[must_use]
+    pub const fn next_addr(&self) -> LocalAddr {
+        self.alloc_addr
+    }
+
+    #[must_use]
+    pub fn get_pos(&self, addr: LocalAddr) -> Option<SourcePos> {
+        self.positions.get(&addr).copied()
+    }
+
     pub fn find_code_mut(&mut self, key: LocalAddr) -> Option<&mut OpCode> {
         self.opcodes.get_mut(&key)
     }
@@ -264,6 +340,21 @@ impl LocalMap {
     pub fn get_index(&self, key: DefaultKey) -> Option<&usize> {
         self.locals.get(&key)
     }
+
+    /// 按变量表索引顺序列出各变量的名称
+    #[must_use]
+    pub fn names(&self, values: &ValueAlloc) -> Vec<(SmolStr, usize)> {
+        let mut names: Vec<(SmolStr, usize)> = self
+            .locals
+            .iter()
+            .filter_map(|(key, index)| {
+                let name = SmolStr::new(values.find_value(*key)?.token.text());
+                Some((name, *index))
+            })
+            .collect();
+        names.sort_by_key(|(_, index)| *index);
+        names
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -316,6 +407,18 @@ impl ValueAlloc {
             variable: false,
             token,
             type_,
+            declared: false,
+        };
+        self.values.insert(va)
+    }
+
+    /// 分配带类型标注的变量, 其类型在之后的赋值中保持不变
+    pub fn alloc_declared(&mut self, token: Token, type_: ValueGuessType) -> DefaultKey {
+        let va = Value {
+            variable: false,
+            token,
+            type_,
+            declared: true,
         };
         self.values.insert(va)
     }
@@ -335,7 +438,9 @@ impl ValueAlloc {
 pub struct Code {
     codes: OpCodeTable,
     pub(crate) funcs: Vec<Function>,
-    root: bool, // 是否是根脚本上下文 (true: 根上下文|false: 函数上下文)
+    pub(crate) foreign: Vec<ForeignFunc>, // 动态库函数的声明, 随字节码保存以便重新绑定
+    pub(crate) globals: Vec<(SmolStr, usize)>, // 全局变量名及其在全局变量表中的索引
+    root: bool,                           // 是否是根脚本上下文 (true: 根上下文|false: 函数上下文)
 }
 
 impl Code {
@@ -344,6 +449,8 @@ impl Code {
         Self {
             codes: OpCodeTable::new(),
             funcs: Vec::new(),
+            foreign: Vec::new(),
+            globals: Vec::new(),
             root,
         }
     }
@@ -352,6 +459,11 @@ impl Code {
         &mut self.codes
     }
 
+    #[must_use]
+    pub const fn code_table(&self) -> &OpCodeTable {
+        &self.codes
+    }
+
     pub fn add_function(&mut self, func: Function) {
         self.funcs.push(func);
     }
@@ -377,13 +489,18 @@ macro_rules! match_opcodes {
             | OpCode::StoreLocal($slot, ..)
             | OpCode::LoadArrayLocal($slot, ..)
             | OpCode::LoadArrayGlobal($slot, ..)
+            | OpCode::LoadMapLocal($slot, ..)
+            | OpCode::LoadMapGlobal($slot, ..)
             | OpCode::SetArrayLocal($slot, ..)
             | OpCode::SetArrayGlobal($slot, ..)
+            | OpCode::ArrayCallLocal($slot, ..)
+            | OpCode::ArrayCallGlobal($slot, ..)
             | OpCode::LazyJump($slot, ..)
             | OpCode::Push($slot, ..)
             | OpCode::Pop($slot, ..)
             | OpCode::AddLocalImm($slot, ..)
             | OpCode::Call($slot, ..)
+            | OpCode::MakeClosure($slot, ..)
             | OpCode::Jump($slot, ..)
             | OpCode::JumpTrue($slot, ..)
             | OpCode::JumpFalse($slot, ..)
@@ -423,6 +540,17 @@ macro_rules! match_opcodes {
             | OpCode::Ref($slot)
             | OpCode::AIndex($slot)
             | OpCode::GetIndexLocal($slot, ..)
+            | OpCode::NewObject($slot, ..)
+            | OpCode::NewArray($slot, _)
+            | OpCode::NewMap($slot, _)
+            | OpCode::GetField($slot, ..)
+            | OpCode::SetField($slot, ..)
+            | OpCode::GetMethod($slot, ..)
+            | OpCode::Echo($slot)
+            | OpCode::Try($slot, ..)
+            | OpCode::EndTry($slot)
+            | OpCode::Catch($slot)
+            | OpCode::Throw($slot)
             | OpCode::Nop($slot) => $stmt,
         }
     };
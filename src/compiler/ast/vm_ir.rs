@@ -1,11 +1,18 @@
 use dashu::float::round::mode::HalfAway;
 use dashu::float::{DBig, FBig};
+use dashu::integer::IBig;
+use linked_hash_map::LinkedHashMap;
 use smol_str::{SmolStr, ToSmolStr};
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 use crate::compiler::ast::ssa_ir::{Code, LocalAddr, LocalMap, OpCode, OpCodeTable, Operand};
 use crate::compiler::ast::vm_ir::Types::{Bool, Float, Null, Number, Ref, String};
+use crate::compiler::lexer::SourcePos;
+use crate::library::ffi::ForeignFunc;
+use crate::library::type_lib::array_func_index;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -21,18 +28,35 @@ pub enum ByteCode {
     StoreGlobal(usize),            // 将全局变量加载到栈顶 (变量表索引)
     LoadArrayGlobal(usize, usize), // 将数组变量加载到全局变量表 (变量表索引) (数组大小)
     LoadArray(usize, usize),       // 将数组变量加载到局部变量表 (变量表索引) (数组大小)
+    LoadMapGlobal(usize, usize),   // 将字典变量加载到全局变量表 (变量表索引) (键值对数量)
+    LoadMap(usize, usize),         // 将字典变量加载到局部变量表 (变量表索引) (键值对数量)
     SetArrayGlobal(usize),         // 取出栈顶的数据和索引, 将其设置到指定全局变量表数组上
     SetArray(usize),               // 取出栈顶的数据和索引, 将其设置到指定局部变量表数组上
+    ArrayCallGlobal(usize, usize), // 在全局数组变量上原地调用 type 库函数 (变量表索引) (函数编号)
+    ArrayCall(usize, usize),       // 在局部数组变量上原地调用 type 库函数 (变量表索引) (函数编号)
     Jump(usize),                   // 无条件跳转 (pc位置)
     JumpTrue(usize),               // 栈顶条件跳转 (pc位置)
     JumpFalse(usize),              // 栈顶反转条件跳转 (pc位置)
-    Call,                          // 函数调用 (要求栈上最少有两个引用)
-    CallConst(usize),              // 函数调用 (常量表索引)
+    Call(usize),                   // 调用栈顶的函数值 (实参个数)
+    CallConst(usize, usize),       // 函数调用 (常量表索引) (实参个数)
+    MakeClosure(usize, usize),     // 以栈顶的捕获值创建闭包 (函数路径常量表索引) (捕获数量)
     Nol,                           // 空操作
     GetRef,                        // 拼接引用路径
     Return,                        // 退出当前栈帧 (并将栈顶元素压入父栈帧操作栈)
+    Echo,                          // 弹出栈顶元素并回显 (仅交互模式)
+    Try(usize),                    // 注册异常处理器 (处理器 pc位置)
+    EndTry,                        // 注销最近注册的异常处理器
+    Catch,                         // 将捕获的异常压入栈顶
+    Throw,                         // 将栈顶元素作为异常抛出
     GetIndex,                      // 取出数组的元素并压入栈顶 (会消费掉操作栈里的数组和索引)
     GetIndexLocal(usize),          // 取出局部数组指定索引的元素并压入栈顶
+    GetIndexGlobal(usize),         // 取出全局数组指定索引的元素并压入栈顶
+    NewObject(usize, usize),       // 以栈顶的字段名与初始值创建对象 (类路径常量表索引) (字段数量)
+    NewArray(usize),               // 以栈顶元素创建数组并压入栈顶 (元素数量)
+    NewMap(usize),                 // 以栈顶键值对创建字典并压入栈顶 (键值对数量)
+    GetField(usize),               // 取出栈顶对象的字段值 (字段名常量表索引)
+    SetField(usize),               // 将次栈顶的值写入栈顶对象的字段 (字段名常量表索引)
+    GetMethod(usize),              // 将栈顶对象的方法绑定为闭包 (方法名常量表索引)
     Pos,
     Neg,
     Add,
@@ -69,18 +93,54 @@ pub enum ByteCode {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Int(i64),
+    BigInt(IBig), // 超出 i64 范围的整数, 能以 i64 表示时总是使用 Int
     Bool(bool),
     Float(FBig<HalfAway, 10>),
     String(SmolStr),
     Ref(SmolStr),
-    Array(usize, Vec<Value>),
+    Array(Vec<Value>),
+    Map(LinkedHashMap<SmolStr, Value>),
+    Closure(Arc<Closure>),
+    Object(Arc<Object>),
     Null,
 }
 
+// 匿名函数创建的闭包, 调用时捕获的值会作为隐藏函数的前几个参数传入
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub path: SmolStr,        // 隐藏函数的完整路径
+    pub captures: Vec<Value>, // 创建时按值捕获的变量
+}
+
+// 类实例, 以引用语义在变量与线程间共享
+#[derive(Debug)]
+pub struct Object {
+    pub class: SmolStr,                               // 类的完整路径 (脚本名/类名)
+    pub fields: Mutex<LinkedHashMap<SmolStr, Value>>, // 字段表, 按声明顺序排列
+}
+
+impl Object {
+    /// 类名, 即类路径中脚本名之后的部分
+    #[must_use]
+    pub fn class_name(&self) -> &str {
+        self.class
+            .rsplit_once('/')
+            .map_or(self.class.as_str(), |(_, name)| name)
+    }
+}
+
+// 对象按引用比较, 两个对象仅当为同一实例时相等
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int(i) => write!(f, "{i}"),
+            Self::BigInt(i) => write!(f, "{i}"),
             Self::Bool(b) => write!(f, "{b}"),
             Self::Float(x) => {
                 // 避免科学计数法，保留合理精度
@@ -90,15 +150,34 @@ impl Display for Value {
                     write!(f, "{x}")
                 }
             }
-            Self::Array(_len, arrays) => {
+            Self::Array(arrays) => {
                 write!(f, "[")?;
                 for var in arrays {
                     write!(f, "{var}, ")?;
                 }
                 write!(f, "]")
             }
+            Self::Map(map) => {
+                write!(f, "{{")?;
+                for (key, var) in map {
+                    write!(f, "{key}: {var}, ")?;
+                }
+                write!(f, "}}")
+            }
             Self::String(s) => write!(f, "{s}"),
             Self::Ref(r) => write!(f, "{r}"),
+            Self::Closure(closure) => write!(f, "<closure:{}>", closure.path),
+            Self::Object(object) => {
+                write!(f, "{} {{", object.class_name())?;
+                // 对象引用自身时无法再次加锁, 不再展开其字段
+                let Ok(fields) = object.fields.try_lock() else {
+                    return write!(f, "...}}");
+                };
+                for (key, var) in fields.iter() {
+                    write!(f, "{key}: {var}, ")?;
+                }
+                write!(f, "}}")
+            }
             Self::Null => write!(f, "null"),
         }
     }
@@ -129,8 +208,9 @@ pub struct IrFunction {
     pub name: SmolStr,
     pub filename: SmolStr,
     pub args: usize,
-    pub locals: usize,   // 局部变量表大小
-    pub is_native: bool, // 是否是本地函数
+    pub locals: usize,         // 局部变量表大小
+    pub is_native: bool,       // 是否是本地函数
+    pub lines: Vec<SourcePos>, // 行号表, 与 codes 一一对应
 }
 
 impl IrFunction {
@@ -172,16 +252,28 @@ fn opcode_to_vmir(code: OpCode) -> ByteCode {
         OpCode::BLeft(_) => ByteCode::BLeft,
         OpCode::BRight(_) => ByteCode::BRight,
         OpCode::Equ(_) => ByteCode::Equ,
-        OpCode::Call(_, _imm) => ByteCode::Call,
+        OpCode::Call(_, _, argc) => ByteCode::Call(argc),
         OpCode::Pop(_, len) => ByteCode::Pop(len),
+        OpCode::NewArray(_, len) => ByteCode::NewArray(len),
+        OpCode::NewMap(_, len) => ByteCode::NewMap(len),
         OpCode::Ref(_) => ByteCode::GetRef,
         OpCode::Nop(_) => ByteCode::Nol,
         OpCode::Return(_) => ByteCode::Return,
+        OpCode::Echo(_) => ByteCode::Echo,
+        OpCode::EndTry(_) => ByteCode::EndTry,
+        OpCode::Catch(_) => ByteCode::Catch,
+        OpCode::Throw(_) => ByteCode::Throw,
         OpCode::Rmd(_) => ByteCode::Rmd,
         OpCode::Pos(_) => ByteCode::Pos,
         OpCode::Neg(_) => ByteCode::Neg,
         OpCode::AIndex(_) => ByteCode::GetIndex,
-        OpCode::GetIndexLocal(_, _) => unreachable!(),
+        OpCode::GetIndexLocal(_, _)
+        | OpCode::ArrayCallLocal(..)
+        | OpCode::ArrayCallGlobal(..)
+        | OpCode::NewObject(..)
+        | OpCode::GetField(..)
+        | OpCode::SetField(..)
+        | OpCode::GetMethod(..) => unreachable!(),
         c => {
             dbg!(c);
             todo!()
@@ -189,6 +281,35 @@ fn opcode_to_vmir(code: OpCode) -> ByteCode {
     }
 }
 
+// 记录一条 SSA IR 生成的字节码在行号表与 pc 映射中的信息
+fn record_lines(
+    lines: &mut Vec<SourcePos>,
+    pc_map: &mut HashMap<usize, usize>,
+    addrs: &[LocalAddr],
+    pc: usize,
+    codes_len: usize,
+    pos: SourcePos,
+) {
+    for addr in addrs {
+        pc_map.insert(addr.offset, pc);
+    }
+    lines.resize(codes_len, pos);
+}
+
+// Push + Call 会合并为一条 CallConst, SSA 逻辑地址与 pc 并不一一对应, 生成后需重定位跳转目标
+fn relocate_jumps(codes: &mut [ByteCode], pc_map: &HashMap<usize, usize>) {
+    let len = codes.len();
+    for code in codes.iter_mut() {
+        if let ByteCode::Jump(target)
+        | ByteCode::JumpTrue(target)
+        | ByteCode::JumpFalse(target)
+        | ByteCode::Try(target) = code
+        {
+            *target = pc_map.get(target).copied().unwrap_or(len);
+        }
+    }
+}
+
 fn is_call_const_operand(operand: &Operand) -> bool {
     matches!(
         operand,
@@ -214,6 +335,7 @@ impl IrFunction {
             locals,
             filename,
             is_native,
+            lines: vec![],
         }
     }
 
@@ -227,16 +349,24 @@ impl IrFunction {
         constant_table: &mut ConstantTable,
     ) {
         let mut codes_builder: Vec<ByteCode> = Vec::new();
+        let mut lines: Vec<SourcePos> = Vec::new();
+        let mut pc_map: HashMap<usize, usize> = HashMap::new();
+        let mut pos = SourcePos::default();
+        let positions = table.positions;
         let entries: Vec<(LocalAddr, OpCode)> = table.opcodes.into_iter().collect();
         let mut i = 0;
         while i < entries.len() {
-            let (_addr, op) = &entries[i];
+            let (addr, op) = &entries[i];
+            let pc = codes_builder.len();
+            pos = positions.get(addr).copied().unwrap_or(pos);
             if let OpCode::Push(_, imm) = op {
-                if let Some((_, OpCode::Call(_, _))) = entries.get(i + 1)
+                if let Some((call_addr, OpCode::Call(_, _, argc))) = entries.get(i + 1)
                     && is_call_const_operand(imm)
                 {
                     let index = constant_table.add_operand(imm.clone(), code0);
-                    codes_builder.push(ByteCode::CallConst(index));
+                    codes_builder.push(ByteCode::CallConst(index, *argc));
+                    let len = codes_builder.len();
+                    record_lines(&mut lines, &mut pc_map, &[*addr, *call_addr], pc, len, pos);
                     i += 2;
                     continue;
                 }
@@ -290,6 +420,14 @@ impl IrFunction {
                     let index = globals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::LoadArrayGlobal(*index, len));
                 }
+                OpCode::LoadMapLocal(_, key, len) => {
+                    let index = locals.get_index(key).unwrap();
+                    codes_builder.push(ByteCode::LoadMap(*index, len));
+                }
+                OpCode::LoadMapGlobal(_, key, len) => {
+                    let index = globals.get_index(key).unwrap();
+                    codes_builder.push(ByteCode::LoadMapGlobal(*index, len));
+                }
                 OpCode::AddLocalImm(_, key, imm) => {
                     if let Some(index) = locals.get_index(key) {
                         codes_builder.push(ByteCode::AddLocalImm(*index, imm));
@@ -307,10 +445,40 @@ impl IrFunction {
                     let index = globals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::SetArrayGlobal(*index));
                 }
+                OpCode::ArrayCallLocal(_, key, name, _) => {
+                    let index = locals.get_index(key).unwrap();
+                    let func = array_func_index(&name).unwrap();
+                    codes_builder.push(ByteCode::ArrayCall(*index, func));
+                }
+                OpCode::ArrayCallGlobal(_, key, name, _) => {
+                    let index = globals.get_index(key).unwrap();
+                    let func = array_func_index(&name).unwrap();
+                    codes_builder.push(ByteCode::ArrayCallGlobal(*index, func));
+                }
                 OpCode::GetIndexLocal(_, key) => {
                     let index = locals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::GetIndexLocal(*index));
                 }
+                OpCode::MakeClosure(_, path, len) => {
+                    let index = constant_table.add_operand(Operand::Reference(path), code0);
+                    codes_builder.push(ByteCode::MakeClosure(index, len));
+                }
+                OpCode::NewObject(_, path, len) => {
+                    let index = constant_table.add_operand(Operand::Reference(path), code0);
+                    codes_builder.push(ByteCode::NewObject(index, len));
+                }
+                OpCode::GetField(_, name) => {
+                    let index = constant_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::GetField(index));
+                }
+                OpCode::SetField(_, name) => {
+                    let index = constant_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::SetField(index));
+                }
+                OpCode::GetMethod(_, name) => {
+                    let index = constant_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::GetMethod(index));
+                }
                 OpCode::Jump(_, addr) | OpCode::LazyJump(_, addr, ..) => {
                     codes_builder.push(ByteCode::Jump(addr.unwrap().offset));
                 }
@@ -322,13 +490,20 @@ impl IrFunction {
                     let addr_some = addr.unwrap();
                     codes_builder.push(ByteCode::JumpFalse(addr_some.offset));
                 }
+                OpCode::Try(_, addr) => {
+                    codes_builder.push(ByteCode::Try(addr.unwrap().offset));
+                }
                 c => {
                     codes_builder.push(opcode_to_vmir(c));
                 }
             }
+            let len = codes_builder.len();
+            record_lines(&mut lines, &mut pc_map, &[*addr], pc, len, pos);
             i += 1;
         }
+        relocate_jumps(&mut codes_builder, &pc_map);
         self.codes = codes_builder;
+        self.lines = lines;
     }
 }
 
@@ -338,7 +513,10 @@ pub struct VMIRTable {
     constant_table: &'static [Value],
     functions: Vec<IrFunction>,
     codes: Vec<ByteCode>,
-    globals: usize, // 全局变量表大小
+    lines: Vec<SourcePos>,               // 根代码行号表
+    globals: usize,                      // 全局变量表大小
+    foreign: Vec<ForeignFunc>,           // 动态库函数的声明
+    global_names: Vec<(SmolStr, usize)>, // 全局变量名及其索引
 }
 
 impl Default for VMIRTable {
@@ -354,10 +532,27 @@ impl VMIRTable {
             constant_table: &[],
             functions: vec![],
             codes: vec![],
+            lines: vec![],
             globals: 0,
+            foreign: vec![],
+            global_names: vec![],
         }
     }
 
+    #[must_use]
+    pub fn get_foreign(&self) -> &[ForeignFunc] {
+        &self.foreign
+    }
+
+    /// 按名称查找全局变量在全局变量表中的索引
+    #[must_use]
+    pub fn find_global(&self, name: &str) -> Option<usize> {
+        self.global_names
+            .iter()
+            .find(|(global, _)| global == name)
+            .map(|(_, index)| *index)
+    }
+
     #[must_use]
     pub fn get_functions(&self) -> Vec<IrFunction> {
         self.functions.clone()
@@ -378,6 +573,11 @@ impl VMIRTable {
         self.codes.clone()
     }
 
+    #[must_use]
+    pub fn clone_lines(&self) -> Vec<SourcePos> {
+        self.lines.clone()
+    }
+
     pub const fn set_constant_table(&mut self, constant_table: &'static [Value]) {
         self.constant_table = constant_table;
     }
@@ -392,16 +592,23 @@ impl VMIRTable {
     ) {
         let entries: Vec<(LocalAddr, OpCode)> = table.opcodes.clone().into_iter().collect();
         let mut codes_builder: Vec<ByteCode> = Vec::new();
+        let mut lines: Vec<SourcePos> = Vec::new();
+        let mut pc_map: HashMap<usize, usize> = HashMap::new();
+        let mut pos = SourcePos::default();
         self.globals = locals.now_index;
         let mut i = 0;
         while i < entries.len() {
-            let (_addr, op) = &entries[i];
+            let (addr, op) = &entries[i];
+            let pc = codes_builder.len();
+            pos = table.get_pos(*addr).unwrap_or(pos);
             if let OpCode::Push(_, imm) = op {
-                if let Some((_, OpCode::Call(_, _))) = entries.get(i + 1)
+                if let Some((call_addr, OpCode::Call(_, _, argc))) = entries.get(i + 1)
                     && is_call_const_operand(imm)
                 {
                     let index = const_table.add_operand(imm.clone(), code0);
-                    codes_builder.push(ByteCode::CallConst(index));
+                    codes_builder.push(ByteCode::CallConst(index, *argc));
+                    let len = codes_builder.len();
+                    record_lines(&mut lines, &mut pc_map, &[*addr, *call_addr], pc, len, pos);
                     i += 2;
                     continue;
                 }
@@ -437,10 +644,17 @@ impl VMIRTable {
                     let addr_some = addr.unwrap();
                     codes_builder.push(ByteCode::JumpFalse(addr_some.offset));
                 }
+                OpCode::Try(_, addr) => {
+                    codes_builder.push(ByteCode::Try(addr.unwrap().offset));
+                }
                 OpCode::LoadArrayGlobal(_, key, len) | OpCode::LoadArrayLocal(_, key, len) => {
                     let index = locals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::LoadArrayGlobal(*index, len));
                 }
+                OpCode::LoadMapGlobal(_, key, len) | OpCode::LoadMapLocal(_, key, len) => {
+                    let index = locals.get_index(key).unwrap();
+                    codes_builder.push(ByteCode::LoadMapGlobal(*index, len));
+                }
                 OpCode::AddLocalImm(_, key, imm) => {
                     let index = locals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::AddGlobalImm(*index, imm));
@@ -449,13 +663,47 @@ impl VMIRTable {
                     let index = locals.get_index(key).unwrap();
                     codes_builder.push(ByteCode::SetArrayGlobal(*index));
                 }
+                OpCode::ArrayCallLocal(_, key, name, _)
+                | OpCode::ArrayCallGlobal(_, key, name, _) => {
+                    let index = locals.get_index(key).unwrap();
+                    let func = array_func_index(&name).unwrap();
+                    codes_builder.push(ByteCode::ArrayCallGlobal(*index, func));
+                }
+                OpCode::GetIndexLocal(_, key) => {
+                    let index = locals.get_index(key).unwrap();
+                    codes_builder.push(ByteCode::GetIndexGlobal(*index));
+                }
+                OpCode::MakeClosure(_, path, len) => {
+                    let index = const_table.add_operand(Operand::Reference(path), code0);
+                    codes_builder.push(ByteCode::MakeClosure(index, len));
+                }
+                OpCode::NewObject(_, path, len) => {
+                    let index = const_table.add_operand(Operand::Reference(path), code0);
+                    codes_builder.push(ByteCode::NewObject(index, len));
+                }
+                OpCode::GetField(_, name) => {
+                    let index = const_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::GetField(index));
+                }
+                OpCode::SetField(_, name) => {
+                    let index = const_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::SetField(index));
+                }
+                OpCode::GetMethod(_, name) => {
+                    let index = const_table.add_operand(Operand::ImmStr(name), code0);
+                    codes_builder.push(ByteCode::GetMethod(index));
+                }
                 c => {
                     codes_builder.push(opcode_to_vmir(c));
                 }
             }
+            let len = codes_builder.len();
+            record_lines(&mut lines, &mut pc_map, &[*addr], pc, len, pos);
             i += 1;
         }
+        relocate_jumps(&mut codes_builder, &pc_map);
         self.codes = codes_builder;
+        self.lines = lines;
     }
 }
 
@@ -471,7 +719,7 @@ impl ConstantTable {
     fn element_to_value((types, value): (Types, SmolStr)) -> Value {
         match types {
             String => Value::String(value),
-            Number => Value::Int(value.parse::<i64>().unwrap()),
+            Number => Value::from_ibig(IBig::from_str(&value).unwrap()),
             Float => Value::Float(DBig::from_str(&value).unwrap()),
             Bool => Value::Bool(value == "true"),
             Ref => Value::Ref(value.to_smolstr()),
@@ -493,6 +741,7 @@ impl ConstantTable {
             Operand::This => (Ref, SmolStr::new_static("this")),
             Operand::ImmStr(imm) => (String, imm),
             Operand::ImmNum(imm) => (Number, imm.to_smolstr()),
+            Operand::ImmBigNum(imm) => (Number, imm.to_smolstr()),
             Operand::ImmFlot(imm) => (Float, imm.to_smolstr()),
             Operand::ImmBool(imm) => (Bool, imm.to_smolstr()),
             _ => unreachable!(),
@@ -535,5 +784,7 @@ pub fn ssa_to_vm(mut code: Code, locals: &LocalMap, filename: &SmolStr) -> VMIRT
     }
     let static_codes: &'static [Value] = Box::leak(const_table.element.into_boxed_slice());
     vm_table.set_constant_table(static_codes);
+    vm_table.foreign = code.foreign;
+    vm_table.global_names = code.globals;
     vm_table
 }
@@ -0,0 +1,703 @@
+use dashu::float::DBig;
+use dashu::integer::IBig;
+use linked_hash_map::LinkedHashMap;
+use smol_str::{SmolStr, ToSmolStr};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::ValueTag;
+use crate::compiler::Compiler;
+use crate::compiler::ast::vm_ir::{ByteCode, Value};
+use crate::compiler::file::{SourceFile, module_name};
+use crate::compiler::lexer::SourcePos;
+use crate::library::ffi::{ForeignFunc, ForeignSignature};
+use crate::library::type_lib::ARRAY_FUNCS;
+use crate::runtime::{MetadataUnit, MethodInfo};
+
+// 字节码文件格式 (.exb), 所有整数均为小端序
+//
+// magic      : b"OEXB"
+// format     : u16          文件格式版本
+// version    : str          编译器版本 (Compiler::get_version)
+// unit_count : u32
+// units      : [Unit]
+//
+// Unit     : name:str file:str library:u8 globals:u64 constants:[Value] functions:[Function]
+//            root:[ByteCode] root_lines:[Pos] foreign:[Foreign]
+// Function : name:str filename:str sync:u8 native:u8 args:u64 locals:u64 codes:[ByteCode] lines:[Pos]
+// Foreign  : name:str library:str symbol:str args:[u8] ret:u8   参数与返回值的 ValueTag, 255 表示未标注
+// Pos      : line:u64 column:u64   与指令一一对应的源码位置
+// str      : u32 长度 + UTF-8 数据, 数组均为 u32 长度前缀
+
+const MAGIC: &[u8; 4] = b"OEXB";
+// 动态库函数未标注类型的参数或返回值
+const UNTYPED_TAG: u8 = 255;
+const FORMAT_VERSION: u16 = 9;
+
+pub const BYTECODE_EXTENSION: &str = "exb";
+
+#[derive(Debug)]
+pub enum BytecodeError {
+    BadMagic,                     // 不是字节码文件
+    IncompatibleFormat(u16),      // 文件格式版本不兼容
+    IncompatibleVersion(SmolStr), // 编译器版本不兼容
+    UnexpectedEof,                // 文件被截断
+    IllegalTag(&'static str, u8), // 非法的类型标记
+    IllegalData(&'static str),    // 非法的数据内容
+}
+
+impl Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not an OpenEX bytecode file"),
+            Self::IncompatibleFormat(format) => write!(
+                f,
+                "incompatible bytecode format {format}, expected {FORMAT_VERSION}"
+            ),
+            Self::IncompatibleVersion(version) => write!(
+                f,
+                "bytecode was built by OpenEX v{version}, but this is OpenEX v{}",
+                Compiler::get_version()
+            ),
+            Self::UnexpectedEof => write!(f, "unexpected end of bytecode file"),
+            Self::IllegalTag(kind, tag) => write!(f, "illegal {kind} tag {tag}"),
+            Self::IllegalData(kind) => write!(f, "illegal {kind} data"),
+        }
+    }
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    const fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("bytecode section too large");
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.buf.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+
+    fn i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(u8::from(value));
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.buf.extend_from_slice(value.as_bytes());
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Int(i) => {
+                self.u8(0);
+                self.i64(*i);
+            }
+            Value::Bool(b) => {
+                self.u8(1);
+                self.bool(*b);
+            }
+            Value::Float(f) => {
+                self.u8(2);
+                self.str(&f.to_smolstr());
+            }
+            Value::String(s) => {
+                self.u8(3);
+                self.str(s);
+            }
+            Value::Ref(r) => {
+                self.u8(4);
+                self.str(r);
+            }
+            Value::Array(elements) => {
+                self.u8(5);
+                self.len(elements.len());
+                for element in elements {
+                    self.value(element);
+                }
+            }
+            Value::Map(map) => {
+                self.u8(7);
+                self.len(map.len());
+                for (key, element) in map {
+                    self.str(key);
+                    self.value(element);
+                }
+            }
+            Value::Null => self.u8(6),
+            Value::BigInt(i) => {
+                self.u8(8);
+                self.str(&i.to_smolstr());
+            }
+            // 闭包与对象只在运行时创建, 不会出现在常量表中
+            Value::Closure(_) | Value::Object(_) => unreachable!(),
+        }
+    }
+
+    fn foreign_tag(&mut self, tag: Option<ValueTag>) {
+        self.u8(tag.map_or(UNTYPED_TAG, |tag| tag as u8));
+    }
+
+    fn codes(&mut self, codes: &[ByteCode]) {
+        self.len(codes.len());
+        for code in codes {
+            self.code(*code);
+        }
+    }
+
+    fn lines(&mut self, lines: &[SourcePos]) {
+        self.len(lines.len());
+        for pos in lines {
+            self.usize(pos.line);
+            self.usize(pos.column);
+        }
+    }
+
+    fn code(&mut self, code: ByteCode) {
+        match code {
+            ByteCode::Push(index) => self.code_index(0, index),
+            ByteCode::Pop(len) => self.code_index(1, len),
+            ByteCode::AddLocalImm(index, imm) => {
+                self.code_index(2, index);
+                self.i64(imm);
+            }
+            ByteCode::AddGlobalImm(index, imm) => {
+                self.code_index(3, index);
+                self.i64(imm);
+            }
+            ByteCode::Load(index) => self.code_index(4, index),
+            ByteCode::Store(index) => self.code_index(5, index),
+            ByteCode::LoadGlobal(index) => self.code_index(6, index),
+            ByteCode::StoreGlobal(index) => self.code_index(7, index),
+            ByteCode::LoadArrayGlobal(index, len) => {
+                self.code_index(8, index);
+                self.usize(len);
+            }
+            ByteCode::LoadArray(index, len) => {
+                self.code_index(9, index);
+                self.usize(len);
+            }
+            ByteCode::SetArrayGlobal(index) => self.code_index(10, index),
+            ByteCode::SetArray(index) => self.code_index(11, index),
+            ByteCode::Jump(pc) => self.code_index(12, pc),
+            ByteCode::JumpTrue(pc) => self.code_index(13, pc),
+            ByteCode::JumpFalse(pc) => self.code_index(14, pc),
+            ByteCode::Call(argc) => self.code_index(15, argc),
+            ByteCode::CallConst(index, argc) => {
+                self.code_index(16, index);
+                self.usize(argc);
+            }
+            ByteCode::Nol => self.u8(17),
+            ByteCode::GetRef => self.u8(18),
+            ByteCode::Return => self.u8(19),
+            ByteCode::GetIndex => self.u8(20),
+            ByteCode::GetIndexLocal(index) => self.code_index(21, index),
+            ByteCode::Pos => self.u8(22),
+            ByteCode::Neg => self.u8(23),
+            ByteCode::Add => self.u8(24),
+            ByteCode::Sub => self.u8(25),
+            ByteCode::Mul => self.u8(26),
+            ByteCode::Div => self.u8(27),
+            ByteCode::And => self.u8(28),
+            ByteCode::Or => self.u8(29),
+            ByteCode::Rmd => self.u8(30),
+            ByteCode::Equ => self.u8(31),
+            ByteCode::NotEqu => self.u8(32),
+            ByteCode::BigEqu => self.u8(33),
+            ByteCode::LesEqu => self.u8(34),
+            ByteCode::Big => self.u8(35),
+            ByteCode::Less => self.u8(36),
+            ByteCode::SAdd => self.u8(37),
+            ByteCode::SSub => self.u8(38),
+            ByteCode::Not => self.u8(39),
+            ByteCode::AddS => self.u8(40),
+            ByteCode::SubS => self.u8(41),
+            ByteCode::MulS => self.u8(42),
+            ByteCode::DivS => self.u8(43),
+            ByteCode::RmdS => self.u8(44),
+            ByteCode::BitAnd => self.u8(45),
+            ByteCode::BitOr => self.u8(46),
+            ByteCode::BitXor => self.u8(47),
+            ByteCode::BAndS => self.u8(48),
+            ByteCode::BOrS => self.u8(49),
+            ByteCode::BXorS => self.u8(50),
+            ByteCode::BLeft => self.u8(51),
+            ByteCode::BRight => self.u8(52),
+            ByteCode::Echo => self.u8(53),
+            ByteCode::Try(pc) => self.code_index(54, pc),
+            ByteCode::EndTry => self.u8(55),
+            ByteCode::Catch => self.u8(56),
+            ByteCode::Throw => self.u8(57),
+            ByteCode::GetIndexGlobal(index) => self.code_index(58, index),
+            ByteCode::LoadMapGlobal(index, len) => {
+                self.code_index(59, index);
+                self.usize(len);
+            }
+            ByteCode::LoadMap(index, len) => {
+                self.code_index(60, index);
+                self.usize(len);
+            }
+            ByteCode::MakeClosure(index, len) => {
+                self.code_index(61, index);
+                self.usize(len);
+            }
+            ByteCode::NewObject(index, len) => {
+                self.code_index(62, index);
+                self.usize(len);
+            }
+            ByteCode::GetField(index) => self.code_index(63, index),
+            ByteCode::SetField(index) => self.code_index(64, index),
+            ByteCode::GetMethod(index) => self.code_index(65, index),
+            ByteCode::NewArray(len) => self.code_index(66, len),
+            ByteCode::NewMap(len) => self.code_index(67, len),
+            ByteCode::ArrayCall(index, func) => {
+                self.code_index(68, index);
+                self.usize(func);
+            }
+            ByteCode::ArrayCallGlobal(index, func) => {
+                self.code_index(69, index);
+                self.usize(func);
+            }
+        }
+    }
+
+    fn code_index(&mut self, tag: u8, index: usize) {
+        self.u8(tag);
+        self.usize(index);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, index: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], BytecodeError> {
+        let end = self
+            .index
+            .checked_add(len)
+            .ok_or(BytecodeError::UnexpectedEof)?;
+        let bytes = self
+            .data
+            .get(self.index..end)
+            .ok_or(BytecodeError::UnexpectedEof)?;
+        self.index = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BytecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, BytecodeError> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn usize(&mut self) -> Result<usize, BytecodeError> {
+        usize::try_from(u64::from_le_bytes(self.array()?))
+            .map_err(|_| BytecodeError::IllegalData("index"))
+    }
+
+    fn i64(&mut self) -> Result<i64, BytecodeError> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, BytecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(BytecodeError::IllegalTag("bool", tag)),
+        }
+    }
+
+    fn str(&mut self) -> Result<SmolStr, BytecodeError> {
+        let len = self.len()?;
+        let bytes = self.bytes(len)?;
+        std::str::from_utf8(bytes)
+            .map(SmolStr::new)
+            .map_err(|_| BytecodeError::IllegalData("string"))
+    }
+
+    fn foreign_tag(&mut self) -> Result<Option<ValueTag>, BytecodeError> {
+        match self.u8()? {
+            0 => Ok(Some(ValueTag::Int)),
+            1 => Ok(Some(ValueTag::Bool)),
+            2 => Ok(Some(ValueTag::Float)),
+            3 => Ok(Some(ValueTag::String)),
+            UNTYPED_TAG => Ok(None),
+            tag => Err(BytecodeError::IllegalTag("foreign type", tag)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, BytecodeError> {
+        match self.u8()? {
+            0 => Ok(Value::Int(self.i64()?)),
+            1 => Ok(Value::Bool(self.bool()?)),
+            2 => DBig::from_str(&self.str()?)
+                .map(Value::Float)
+                .map_err(|_| BytecodeError::IllegalData("float")),
+            3 => Ok(Value::String(self.str()?)),
+            4 => Ok(Value::Ref(self.str()?)),
+            5 => {
+                let count = self.len()?;
+                let mut elements = Vec::new();
+                for _ in 0..count {
+                    elements.push(self.value()?);
+                }
+                Ok(Value::Array(elements))
+            }
+            6 => Ok(Value::Null),
+            7 => {
+                let count = self.len()?;
+                let mut map = LinkedHashMap::new();
+                for _ in 0..count {
+                    let key = self.str()?;
+                    map.insert(key, self.value()?);
+                }
+                Ok(Value::Map(map))
+            }
+            8 => IBig::from_str(&self.str()?)
+                .map(Value::from_ibig)
+                .map_err(|_| BytecodeError::IllegalData("integer")),
+            tag => Err(BytecodeError::IllegalTag("value", tag)),
+        }
+    }
+
+    fn codes(&mut self) -> Result<Vec<ByteCode>, BytecodeError> {
+        let count = self.len()?;
+        let mut codes = Vec::new();
+        for _ in 0..count {
+            codes.push(self.code()?);
+        }
+        Ok(codes)
+    }
+
+    fn lines(&mut self) -> Result<Vec<SourcePos>, BytecodeError> {
+        let count = self.len()?;
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            let line = self.usize()?;
+            let column = self.usize()?;
+            lines.push(SourcePos { line, column });
+        }
+        Ok(lines)
+    }
+
+    fn code(&mut self) -> Result<ByteCode, BytecodeError> {
+        Ok(match self.u8()? {
+            0 => ByteCode::Push(self.usize()?),
+            1 => ByteCode::Pop(self.usize()?),
+            2 => ByteCode::AddLocalImm(self.usize()?, self.i64()?),
+            3 => ByteCode::AddGlobalImm(self.usize()?, self.i64()?),
+            4 => ByteCode::Load(self.usize()?),
+            5 => ByteCode::Store(self.usize()?),
+            6 => ByteCode::LoadGlobal(self.usize()?),
+            7 => ByteCode::StoreGlobal(self.usize()?),
+            8 => ByteCode::LoadArrayGlobal(self.usize()?, self.usize()?),
+            9 => ByteCode::LoadArray(self.usize()?, self.usize()?),
+            10 => ByteCode::SetArrayGlobal(self.usize()?),
+            11 => ByteCode::SetArray(self.usize()?),
+            12 => ByteCode::Jump(self.usize()?),
+            13 => ByteCode::JumpTrue(self.usize()?),
+            14 => ByteCode::JumpFalse(self.usize()?),
+            15 => ByteCode::Call(self.usize()?),
+            16 => ByteCode::CallConst(self.usize()?, self.usize()?),
+            17 => ByteCode::Nol,
+            18 => ByteCode::GetRef,
+            19 => ByteCode::Return,
+            20 => ByteCode::GetIndex,
+            21 => ByteCode::GetIndexLocal(self.usize()?),
+            22 => ByteCode::Pos,
+            23 => ByteCode::Neg,
+            24 => ByteCode::Add,
+            25 => ByteCode::Sub,
+            26 => ByteCode::Mul,
+            27 => ByteCode::Div,
+            28 => ByteCode::And,
+            29 => ByteCode::Or,
+            30 => ByteCode::Rmd,
+            31 => ByteCode::Equ,
+            32 => ByteCode::NotEqu,
+            33 => ByteCode::BigEqu,
+            34 => ByteCode::LesEqu,
+            35 => ByteCode::Big,
+            36 => ByteCode::Less,
+            37 => ByteCode::SAdd,
+            38 => ByteCode::SSub,
+            39 => ByteCode::Not,
+            40 => ByteCode::AddS,
+            41 => ByteCode::SubS,
+            42 => ByteCode::MulS,
+            43 => ByteCode::DivS,
+            44 => ByteCode::RmdS,
+            45 => ByteCode::BitAnd,
+            46 => ByteCode::BitOr,
+            47 => ByteCode::BitXor,
+            48 => ByteCode::BAndS,
+            49 => ByteCode::BOrS,
+            50 => ByteCode::BXorS,
+            51 => ByteCode::BLeft,
+            52 => ByteCode::BRight,
+            53 => ByteCode::Echo,
+            54 => ByteCode::Try(self.usize()?),
+            55 => ByteCode::EndTry,
+            56 => ByteCode::Catch,
+            57 => ByteCode::Throw,
+            58 => ByteCode::GetIndexGlobal(self.usize()?),
+            59 => ByteCode::LoadMapGlobal(self.usize()?, self.usize()?),
+            60 => ByteCode::LoadMap(self.usize()?, self.usize()?),
+            61 => ByteCode::MakeClosure(self.usize()?, self.usize()?),
+            62 => ByteCode::NewObject(self.usize()?, self.usize()?),
+            63 => ByteCode::GetField(self.usize()?),
+            64 => ByteCode::SetField(self.usize()?),
+            65 => ByteCode::GetMethod(self.usize()?),
+            66 => ByteCode::NewArray(self.usize()?),
+            67 => ByteCode::NewMap(self.usize()?),
+            68 => ByteCode::ArrayCall(self.usize()?, self.usize()?),
+            69 => ByteCode::ArrayCallGlobal(self.usize()?, self.usize()?),
+            tag => return Err(BytecodeError::IllegalTag("bytecode", tag)),
+        })
+    }
+
+    const fn is_end(&self) -> bool {
+        self.index >= self.data.len()
+    }
+}
+
+// 操作数须落在对应的表内: 常量索引小于常量表长度, 跳转目标不超过指令序列末尾,
+// 局部变量与全局变量槽位小于各自的表长度, 否则执行时会越界
+fn check_operands(
+    codes: &[ByteCode],
+    consts: usize,
+    locals: usize,
+    globals: usize,
+) -> Result<(), BytecodeError> {
+    for code in codes {
+        let valid = match *code {
+            ByteCode::Push(index)
+            | ByteCode::CallConst(index, _)
+            | ByteCode::MakeClosure(index, _)
+            | ByteCode::NewObject(index, _)
+            | ByteCode::GetField(index)
+            | ByteCode::SetField(index)
+            | ByteCode::GetMethod(index) => index < consts,
+            ByteCode::Jump(pc)
+            | ByteCode::JumpTrue(pc)
+            | ByteCode::JumpFalse(pc)
+            | ByteCode::Try(pc) => pc <= codes.len(),
+            ByteCode::AddLocalImm(index, _)
+            | ByteCode::Load(index)
+            | ByteCode::Store(index)
+            | ByteCode::LoadArray(index, _)
+            | ByteCode::SetArray(index)
+            | ByteCode::GetIndexLocal(index)
+            | ByteCode::LoadMap(index, _) => index < locals,
+            ByteCode::AddGlobalImm(index, _)
+            | ByteCode::LoadGlobal(index)
+            | ByteCode::StoreGlobal(index)
+            | ByteCode::LoadArrayGlobal(index, _)
+            | ByteCode::SetArrayGlobal(index)
+            | ByteCode::GetIndexGlobal(index)
+            | ByteCode::LoadMapGlobal(index, _) => index < globals,
+            ByteCode::ArrayCall(index, func) => index < locals && func < ARRAY_FUNCS.len(),
+            ByteCode::ArrayCallGlobal(index, func) => index < globals && func < ARRAY_FUNCS.len(),
+            _ => true,
+        };
+        if !valid {
+            return Err(BytecodeError::IllegalData("operand"));
+        }
+    }
+    Ok(())
+}
+
+/// 将所有已编译的源文件 (包括标准库) 序列化为字节码文件内容
+/// # Panics
+/// 存在未编译的源文件时触发
+#[must_use]
+pub fn encode_units(files: &[SourceFile]) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.buf.extend_from_slice(MAGIC);
+    writer.u16(FORMAT_VERSION);
+    writer.str(Compiler::get_version());
+    writer.len(files.len());
+
+    for file in files {
+        let vm_ir = file.ir_table.as_ref().expect("source file is not compiled");
+        writer.str(file.module_name());
+        writer.str(&file.name);
+        writer.bool(file.is_library);
+        writer.usize(vm_ir.get_locals_len());
+
+        let constant_table = vm_ir.get_constant_table();
+        writer.len(constant_table.len());
+        for value in constant_table {
+            writer.value(value);
+        }
+
+        let functions = vm_ir.get_functions();
+        writer.len(functions.len());
+        for func in functions {
+            writer.str(&func.name);
+            writer.str(&func.filename);
+            writer.bool(func.sync);
+            writer.bool(func.is_native);
+            writer.usize(func.args);
+            writer.usize(func.locals);
+            writer.codes(&func.codes);
+            writer.lines(&func.lines);
+        }
+
+        writer.codes(&vm_ir.clone_codes());
+        writer.lines(&vm_ir.clone_lines());
+
+        let foreign = vm_ir.get_foreign();
+        writer.len(foreign.len());
+        for func in foreign {
+            writer.str(&func.name);
+            writer.str(&func.library);
+            writer.str(&func.symbol);
+            writer.len(func.signature.args.len());
+            for tag in &func.signature.args {
+                writer.foreign_tag(*tag);
+            }
+            writer.foreign_tag(func.signature.ret);
+        }
+    }
+    writer.buf
+}
+
+/// 从字节码文件内容直接构建执行引擎元数据单元, 不经过编译前端
+/// 常量表与单元名称的内存会被解控, 与解释器生命周期相同
+/// # Errors
+/// 文件格式或编译器版本不兼容, 以及文件内容损坏 (包括越界的操作数) 时返回错误
+pub fn decode_units(data: &[u8]) -> Result<Vec<MetadataUnit<'static>>, BytecodeError> {
+    let mut reader = Reader::new(data);
+    if reader.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(BytecodeError::BadMagic);
+    }
+    let format = reader.u16()?;
+    if format != FORMAT_VERSION {
+        return Err(BytecodeError::IncompatibleFormat(format));
+    }
+    let version = reader.str()?;
+    if version != Compiler::get_version() {
+        return Err(BytecodeError::IncompatibleVersion(version));
+    }
+
+    let unit_count = reader.len()?;
+    let mut units = Vec::new();
+    for _ in 0..unit_count {
+        let name = reader.str()?;
+        let file = reader.str()?;
+        let library = reader.bool()?;
+        let globals = reader.usize()?;
+
+        let const_count = reader.len()?;
+        let mut constant_table = Vec::new();
+        for _ in 0..const_count {
+            constant_table.push(reader.value()?);
+        }
+
+        let func_count = reader.len()?;
+        let mut methods = Vec::new();
+        for _ in 0..func_count {
+            let func_name = reader.str()?;
+            let filename = reader.str()?;
+            let sync = reader.bool()?;
+            let is_native = reader.bool()?;
+            let args = reader.usize()?;
+            let locals = reader.usize()?;
+            let codes = reader.codes()?;
+            let lines = reader.lines()?;
+            check_operands(&codes, constant_table.len(), locals, globals)?;
+            methods.push(MethodInfo {
+                name: func_name,
+                r_name: module_name(&filename),
+                codes,
+                lines,
+                locals,
+                is_native,
+                sync,
+                args,
+            });
+        }
+        let root_code = reader.codes()?;
+        let root_lines = reader.lines()?;
+        // 全局代码以全局变量表作为局部变量表
+        check_operands(&root_code, constant_table.len(), globals, globals)?;
+
+        let foreign_count = reader.len()?;
+        let mut foreign = Vec::new();
+        for _ in 0..foreign_count {
+            let name = reader.str()?;
+            let library = reader.str()?;
+            let symbol = reader.str()?;
+            let arg_count = reader.len()?;
+            let mut args = Vec::new();
+            for _ in 0..arg_count {
+                args.push(reader.foreign_tag()?);
+            }
+            let ret = reader.foreign_tag()?;
+            foreign.push(ForeignFunc {
+                name,
+                library,
+                symbol,
+                signature: ForeignSignature { args, ret },
+            });
+        }
+
+        units.push(MetadataUnit {
+            constant_table: Box::leak(constant_table.into_boxed_slice()),
+            methods,
+            names: Box::leak(name.to_string().into_boxed_str()),
+            file,
+            source: None,
+            globals,
+            root_code,
+            root_lines,
+            library,
+            foreign,
+        });
+    }
+
+    if !reader.is_end() {
+        return Err(BytecodeError::IllegalData("trailing"));
+    }
+    if !units.iter().any(|unit| !unit.library) {
+        return Err(BytecodeError::IllegalData("entry unit"));
+    }
+    Ok(units)
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::compiler::Compiler;
+use crate::compiler::lexer::{SourcePos, Token};
+use crate::compiler::lints::Lint;
+
+pub struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown message format `{}`", self.0)
+    }
+}
+
+/// 编译诊断的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    #[default]
+    Human, // 带有源码高亮的文本, 错误输出到 stderr, 警告输出到 stdout
+    Json,   // 每行一个 JSON 对象, 全部输出到 stderr
+    Silent, // 不输出, 只能通过 `Compiler::diagnostics` 读取, 供语言服务器等宿主使用
+}
+
+impl FromStr for MessageFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(Error(s.into())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// 源码区间, 行号与列号均从 1 开始, 结束列不包含在区间内
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// 覆盖整个 token 的区间
+    #[must_use]
+    pub fn from_token(token: &Token) -> Self {
+        Self {
+            line: token.line + 1,
+            column: token.column,
+            end_line: token.line + 1,
+            end_column: token.column + token.text().chars().count().max(1),
+        }
+    }
+
+    /// 只包含一个字符的区间
+    #[must_use]
+    pub const fn from_pos(pos: SourcePos) -> Self {
+        Self {
+            line: pos.line + 1,
+            column: pos.column,
+            end_line: pos.line + 1,
+            end_column: pos.column + 1,
+        }
+    }
+}
+
+/// 编译器输出的一条错误或警告
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>, // 警告对应的 lint 名, 可以通过 `-A` 关闭
+    pub message: String,
+    pub file: String,
+    pub span: Span,
+    pub notes: Vec<String>,         // 补充说明
+    pub suggestion: Option<String>, // 建议的修改方式
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn error(file: &str, span: Span, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: None,
+            message,
+            file: file.to_string(),
+            span,
+            notes: vec![],
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    pub fn warning(file: &str, span: Span, message: String, lint: Lint) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code: Some(lint.name()),
+            ..Self::error(file, span, message)
+        }
+    }
+
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// 带有源码高亮的文本形式, source 为诊断所在文件的内容
+    #[must_use]
+    pub fn render(&self, source: &str) -> String {
+        let mut output = match self.severity {
+            Severity::Error => format!(
+                "SyntaxError({}-line: {} column: {}): {}\n",
+                self.file, self.span.line, self.span.column, self.message
+            ),
+            Severity::Warning => format!("warning: {}\n", self.message),
+        };
+        output.push_str(&Compiler::highlight_line_and_column(
+            source,
+            self.span.line - 1,
+            self.span.column,
+        ));
+        for note in &self.notes {
+            output.push_str(&format!("\nnote: {note}"));
+        }
+        if let Some(suggestion) = &self.suggestion {
+            output.push_str(&format!("\nhelp: {suggestion}"));
+        }
+        output
+    }
+
+    /// 单行 JSON 形式
+    /// # Panics
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
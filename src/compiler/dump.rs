@@ -0,0 +1,551 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use slotmap::DefaultKey;
+
+use crate::compiler::ast::ssa_ir::{Code, LocalAddr, LocalMap, OpCode, OpCodeTable, Operand};
+use crate::compiler::ast::vm_ir::{ByteCode, IrFunction, VMIRTable, Value};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
+use crate::compiler::lexer::{SourcePos, Token, TokenType};
+use crate::compiler::semantic::block_leaders;
+use crate::library::type_lib::ARRAY_FUNCS;
+
+// 各中间表示的文本格式, 用于排查编译器与优化器问题, 输出格式保持稳定以便用于快照测试
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitKind {
+    Ast,      // 语法树
+    Ssa,      // 优化后的 SSA IR
+    Bytecode, // 最终生成的字节码
+}
+
+impl FromStr for EmitKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ast" => Ok(Self::Ast),
+            "ssa" => Ok(Self::Ssa),
+            "bytecode" => Ok(Self::Bytecode),
+            _ => Err(format!(
+                "unknown emit kind `{s}`, expected ast|ssa|bytecode"
+            )),
+        }
+    }
+}
+
+fn indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    indent(out, depth);
+    out.push_str(text);
+    out.push('\n');
+}
+
+/// 以缩进树的形式输出语法树
+#[must_use]
+pub fn dump_ast(tree: &ASTStmtTree) -> String {
+    let mut out = String::new();
+    stmt_ast(&mut out, tree, 0);
+    out
+}
+
+fn stmts_ast(out: &mut String, label: &str, stmts: &[ASTStmtTree], depth: usize) {
+    line(out, depth, label);
+    for stmt in stmts {
+        stmt_ast(out, stmt, depth + 1);
+    }
+}
+
+fn stmt_ast(out: &mut String, tree: &ASTStmtTree, depth: usize) {
+    match tree {
+        ASTStmtTree::Root(stmts) => stmts_ast(out, "Root", stmts, depth),
+        ASTStmtTree::Block(stmts) => stmts_ast(out, "Block", stmts, depth),
+        ASTStmtTree::Context(stmts) => stmts_ast(out, "Context", stmts, depth),
+        ASTStmtTree::Var {
+            name,
+            annotation,
+            value,
+        } => {
+            let name = annotated(name, annotation.as_ref());
+            line(out, depth, &format!("Var {name}"));
+            if let Some(value) = value {
+                expr_ast(out, value, depth + 1);
+            }
+        }
+        ASTStmtTree::Expr(expr) => {
+            line(out, depth, "Expr");
+            expr_ast(out, expr, depth + 1);
+        }
+        ASTStmtTree::Return(expr) => {
+            line(out, depth, "Return");
+            if let Some(expr) = expr {
+                expr_ast(out, expr, depth + 1);
+            }
+        }
+        ASTStmtTree::Import(_, use_name, import_name) => {
+            line(out, depth, &format!("Import {import_name} as {use_name}"));
+        }
+        ASTStmtTree::Loop {
+            cond,
+            body,
+            is_easy,
+            ..
+        } => {
+            line(out, depth, if *is_easy { "Loop (easy)" } else { "Loop" });
+            line(out, depth + 1, "cond");
+            expr_ast(out, cond, depth + 2);
+            stmts_ast(out, "body", body, depth + 1);
+        }
+        ASTStmtTree::Function {
+            name,
+            sync,
+            args,
+            hints,
+            body,
+        } => {
+            let sync = if *sync { "sync " } else { "" };
+            let name = annotated(name, hints.ret.as_ref());
+            line(out, depth, &format!("{sync}Function {name}"));
+            args_ast(out, args, &hints.args, depth + 1);
+            stmts_ast(out, "body", body, depth + 1);
+        }
+        ASTStmtTree::NativeFunction {
+            name,
+            args,
+            hints,
+            foreign,
+        } => {
+            let name = annotated(name, hints.ret.as_ref());
+            let foreign = foreign.as_ref().map_or(String::new(), |(library, symbol)| {
+                format!("({:?}, {:?}) ", library.text(), symbol.text())
+            });
+            line(out, depth, &format!("NativeFunction {foreign}{name}"));
+            args_ast(out, args, &hints.args, depth + 1);
+        }
+        ASTStmtTree::Class {
+            name,
+            fields,
+            methods,
+        } => {
+            line(out, depth, &format!("Class {}", name.text()));
+            stmts_ast(out, "fields", fields, depth + 1);
+            stmts_ast(out, "methods", methods, depth + 1);
+        }
+        ASTStmtTree::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            line(out, depth, "If");
+            line(out, depth + 1, "cond");
+            expr_ast(out, cond, depth + 2);
+            stmts_ast(out, "then", then_body, depth + 1);
+            if !else_body.is_empty() {
+                stmts_ast(out, "else", else_body, depth + 1);
+            }
+        }
+        ASTStmtTree::Array {
+            token,
+            annotation,
+            elements,
+        } => {
+            let name = annotated(token, annotation.as_ref());
+            line(out, depth, &format!("Array {name}"));
+            for element in elements {
+                expr_ast(out, element, depth + 1);
+            }
+        }
+        ASTStmtTree::ArrayFill {
+            token,
+            annotation,
+            value,
+            count,
+        } => {
+            let name = annotated(token, annotation.as_ref());
+            line(out, depth, &format!("ArrayFill {name}"));
+            expr_ast(out, value, depth + 1);
+            expr_ast(out, count, depth + 1);
+        }
+        ASTStmtTree::Map {
+            token,
+            annotation,
+            entries,
+        } => {
+            let name = annotated(token, annotation.as_ref());
+            line(out, depth, &format!("Map {name}"));
+            for (key, value) in entries {
+                line(out, depth + 1, "entry");
+                expr_ast(out, key, depth + 2);
+                expr_ast(out, value, depth + 2);
+            }
+        }
+        ASTStmtTree::Try {
+            body,
+            catch_name,
+            catch_body,
+            ..
+        } => {
+            line(out, depth, "Try");
+            stmts_ast(out, "body", body, depth + 1);
+            stmts_ast(
+                out,
+                &format!("catch {}", catch_name.text()),
+                catch_body,
+                depth + 1,
+            );
+        }
+        ASTStmtTree::Throw(_, expr) => {
+            line(out, depth, "Throw");
+            expr_ast(out, expr, depth + 1);
+        }
+        ASTStmtTree::Break(_) => line(out, depth, "Break"),
+        ASTStmtTree::Continue(_) => line(out, depth, "Continue"),
+        ASTStmtTree::Empty => line(out, depth, "Empty"),
+    }
+}
+
+// 带类型标注的名称输出为 name: type
+fn annotated(name: &Token, annotation: Option<&Token>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}: {}", name.text(), annotation.text()),
+        None => name.text().to_string(),
+    }
+}
+
+fn args_ast(out: &mut String, args: &[ASTExprTree], hints: &[Option<Token>], depth: usize) {
+    for (i, arg) in args.iter().enumerate() {
+        match arg {
+            ASTExprTree::Var(token) => {
+                let hint = hints.get(i).and_then(Option::as_ref);
+                line(out, depth, &format!("arg {}", annotated(token, hint)));
+            }
+            arg => expr_ast(out, arg, depth),
+        }
+    }
+}
+
+fn expr_ast(out: &mut String, tree: &ASTExprTree, depth: usize) {
+    match tree {
+        ASTExprTree::Literal(token) => {
+            if token.t_type == TokenType::LiteralString {
+                line(out, depth, &format!("Literal {:?}", token.text()));
+            } else {
+                line(out, depth, &format!("Literal {}", token.text()));
+            }
+        }
+        ASTExprTree::Var(token) => line(out, depth, &format!("Var {}", token.text())),
+        ASTExprTree::This(_) => line(out, depth, "This"),
+        ASTExprTree::Expr {
+            op, left, right, ..
+        } => {
+            line(out, depth, &format!("{op:?}"));
+            expr_ast(out, left, depth + 1);
+            expr_ast(out, right, depth + 1);
+        }
+        ASTExprTree::Unary { op, code, .. } => {
+            line(out, depth, &format!("{op:?}"));
+            expr_ast(out, code, depth + 1);
+        }
+        ASTExprTree::Call { name, args } => {
+            line(out, depth, "Call");
+            expr_ast(out, name, depth + 1);
+            for arg in args {
+                expr_ast(out, arg, depth + 1);
+            }
+        }
+        ASTExprTree::Slice {
+            target, start, end, ..
+        } => {
+            line(out, depth, "Slice");
+            expr_ast(out, target, depth + 1);
+            for (label, bound) in [("start", start), ("end", end)] {
+                match bound {
+                    Some(bound) => {
+                        line(out, depth + 1, label);
+                        expr_ast(out, bound, depth + 2);
+                    }
+                    None => line(out, depth + 1, &format!("{label} <none>")),
+                }
+            }
+        }
+        ASTExprTree::Lambda { args, body, .. } => {
+            line(out, depth, "Lambda");
+            args_ast(out, args, &[], depth + 1);
+            stmts_ast(out, "body", body, depth + 1);
+        }
+        ASTExprTree::Array { elements, .. } => {
+            line(out, depth, "Array");
+            for element in elements {
+                expr_ast(out, element, depth + 1);
+            }
+        }
+        ASTExprTree::Map { entries, .. } => {
+            line(out, depth, "Map");
+            for (key, value) in entries {
+                expr_ast(out, key, depth + 1);
+                expr_ast(out, value, depth + 2);
+            }
+        }
+    }
+}
+
+// 变量以其所在变量表的索引表示, 局部变量为 l<n>, 全局变量为 g<n>
+struct SlotNames<'a> {
+    locals: Option<&'a LocalMap>,
+    globals: &'a LocalMap,
+}
+
+impl SlotNames<'_> {
+    fn name(&self, key: DefaultKey) -> String {
+        if let Some(index) = self.locals.and_then(|locals| locals.get_index(key)) {
+            format!("l{index}")
+        } else if let Some(index) = self.globals.get_index(key) {
+            format!("g{index}")
+        } else {
+            format!("{key:?}")
+        }
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Val(key) => self.name(*key),
+            Operand::Library(name) => format!("lib:{name}"),
+            Operand::Null => String::from("null"),
+            Operand::This => String::from("this"),
+            Operand::ImmBool(b) => b.to_string(),
+            Operand::ImmNum(n) => n.to_string(),
+            Operand::ImmBigNum(n) => n.to_string(),
+            Operand::ImmFlot(f) => f.to_string(),
+            Operand::ImmStr(s) => format!("{s:?}"),
+            Operand::Call(name) => format!("call:{name}"),
+            Operand::Reference(name) => format!("ref:{name}"),
+            Operand::Expression(left, right, op) => format!(
+                "({} {} {})",
+                self.operand(left),
+                opcode_name(op),
+                self.operand(right)
+            ),
+            Operand::ImmNumFlot => String::from("<number>"),
+        }
+    }
+
+    fn opcode(&self, op: &OpCode) -> String {
+        let name = opcode_name(op);
+        let target = |target: &Option<_>| match target {
+            Some(LocalAddr { offset }) => format!("%{offset}"),
+            None => String::from("<none>"),
+        };
+        match op {
+            OpCode::LoadGlobal(_, key, operand)
+            | OpCode::StoreGlobal(_, key, operand)
+            | OpCode::LoadLocal(_, key, operand)
+            | OpCode::StoreLocal(_, key, operand) => {
+                format!("{name} {}, {}", self.name(*key), self.operand(operand))
+            }
+            OpCode::LoadArrayLocal(_, key, len)
+            | OpCode::LoadArrayGlobal(_, key, len)
+            | OpCode::LoadMapLocal(_, key, len)
+            | OpCode::LoadMapGlobal(_, key, len) => format!("{name} {}, {len}", self.name(*key)),
+            OpCode::SetArrayLocal(_, key)
+            | OpCode::SetArrayGlobal(_, key)
+            | OpCode::GetIndexLocal(_, key) => format!("{name} {}", self.name(*key)),
+            OpCode::AddLocalImm(_, key, imm) => format!("{name} {}, {imm}", self.name(*key)),
+            OpCode::Push(_, operand) => format!("{name} {}", self.operand(operand)),
+            OpCode::Pop(_, len) | OpCode::NewArray(_, len) | OpCode::NewMap(_, len) => {
+                format!("{name} {len}")
+            }
+            OpCode::Call(_, callee, argc) => format!("{name} {callee}, {argc}"),
+            OpCode::ArrayCallLocal(_, key, callee, argc)
+            | OpCode::ArrayCallGlobal(_, key, callee, argc) => {
+                format!("{name} {}, {callee}, {argc}", self.name(*key))
+            }
+            OpCode::MakeClosure(_, path, len) | OpCode::NewObject(_, path, len) => {
+                format!("{name} {path}, {len}")
+            }
+            OpCode::GetField(_, field)
+            | OpCode::SetField(_, field)
+            | OpCode::GetMethod(_, field) => format!("{name} {field}"),
+            OpCode::Jump(_, to) | OpCode::Try(_, to) => format!("{name} -> {}", target(to)),
+            OpCode::JumpTrue(_, to, operand) | OpCode::JumpFalse(_, to, operand) => {
+                format!("{name} -> {}, {}", target(to), self.operand(operand))
+            }
+            OpCode::LazyJump(_, to, is_break) => {
+                let kind = if *is_break { "break" } else { "continue" };
+                format!("{name} -> {}, {kind}", target(to))
+            }
+            _ => name,
+        }
+    }
+}
+
+// 操作码名称即其变体名
+fn opcode_name(op: &OpCode) -> String {
+    let debug = format!("{op:?}");
+    debug.split('(').next().unwrap_or_default().to_string()
+}
+
+fn ssa_table(out: &mut String, table: &OpCodeTable, names: &SlotNames) {
+    let mut order: Vec<_> = table.opcodes.keys().copied().collect();
+    order.sort_unstable_by_key(|addr| addr.offset);
+    let leaders = block_leaders(table);
+    let mut block = 0;
+    for addr in order {
+        if leaders.contains(&addr) {
+            line(out, 1, &format!("block {block}:"));
+            block += 1;
+        }
+        let op = &table.opcodes[&addr];
+        let pos = table
+            .get_pos(addr)
+            .map_or_else(String::new, |pos| format!("  ; {}", pos_text(pos)));
+        line(
+            out,
+            2,
+            &format!("%{:<4} {}{pos}", addr.offset, names.opcode(op)),
+        );
+    }
+}
+
+fn pos_text(pos: SourcePos) -> String {
+    format!("{}:{}", pos.line + 1, pos.column)
+}
+
+/// 输出优化后的 SSA IR, 包括每条 IR 的逻辑地址与基本块边界
+#[must_use]
+pub fn dump_ssa(code: &Code, globals: &LocalMap) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "root (globals {}):", globals.now_index);
+    let names = SlotNames {
+        locals: None,
+        globals,
+    };
+    ssa_table(&mut out, code.code_table(), &names);
+    for func in &code.funcs {
+        let sync = if func.sync { "sync " } else { "" };
+        let Some(table) = &func.codes else {
+            let _ = writeln!(
+                out,
+                "{sync}function native {}(args {})",
+                func.name, func.args
+            );
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "{sync}function {}(args {}, locals {}):",
+            func.name, func.args, func.locals.now_index
+        );
+        let names = SlotNames {
+            locals: Some(&func.locals),
+            globals,
+        };
+        ssa_table(&mut out, table, &names);
+    }
+    out
+}
+
+fn const_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{s:?}"),
+        Value::Ref(r) => format!("ref:{r}"),
+        value => value.to_string(),
+    }
+}
+
+fn bytecode_list(out: &mut String, codes: &[ByteCode], lines: &[SourcePos], consts: &[Value]) {
+    for (pc, code) in codes.iter().enumerate() {
+        let text = match code {
+            ByteCode::Jump(target) => format!("Jump -> {target:04}"),
+            ByteCode::JumpTrue(target) => format!("JumpTrue -> {target:04}"),
+            ByteCode::JumpFalse(target) => format!("JumpFalse -> {target:04}"),
+            ByteCode::Try(target) => format!("Try -> {target:04}"),
+            ByteCode::Push(index) => match consts.get(*index) {
+                Some(value) => format!("Push #{index} ({})", const_text(value)),
+                None => format!("Push #{index}"),
+            },
+            ByteCode::CallConst(index, argc) => match consts.get(*index) {
+                Some(value) => format!("CallConst #{index} ({}), {argc}", const_text(value)),
+                None => format!("CallConst #{index}, {argc}"),
+            },
+            ByteCode::MakeClosure(index, len) | ByteCode::NewObject(index, len) => {
+                let name = if matches!(code, ByteCode::MakeClosure(..)) {
+                    "MakeClosure"
+                } else {
+                    "NewObject"
+                };
+                match consts.get(*index) {
+                    Some(value) => format!("{name} #{index} ({}), {len}", const_text(value)),
+                    None => format!("{name} #{index}, {len}"),
+                }
+            }
+            ByteCode::GetField(index) | ByteCode::SetField(index) | ByteCode::GetMethod(index) => {
+                let name = match code {
+                    ByteCode::GetField(_) => "GetField",
+                    ByteCode::SetField(_) => "SetField",
+                    _ => "GetMethod",
+                };
+                match consts.get(*index) {
+                    Some(value) => format!("{name} #{index} ({})", const_text(value)),
+                    None => format!("{name} #{index}"),
+                }
+            }
+            ByteCode::ArrayCall(index, func) | ByteCode::ArrayCallGlobal(index, func) => {
+                let name = if matches!(code, ByteCode::ArrayCall(..)) {
+                    "ArrayCall"
+                } else {
+                    "ArrayCallGlobal"
+                };
+                match ARRAY_FUNCS.get(*func) {
+                    Some((callee, _)) => format!("{name} {index}, {func} ({callee})"),
+                    None => format!("{name} {index}, {func}"),
+                }
+            }
+            // 其余字节码的操作数均为变量表索引或数量, 直接沿用其调试格式
+            code => format!("{code:?}").replacen('(', " ", 1).replace(')', ""),
+        };
+        let pos = lines
+            .get(pc)
+            .map_or_else(String::new, |pos| format!("  ; {}", pos_text(*pos)));
+        line(out, 1, &format!("{pc:04}  {text}{pos}"));
+    }
+}
+
+/// 输出最终生成的字节码, 常量表索引与跳转目标均已解析
+#[must_use]
+pub fn dump_bytecode(table: &VMIRTable) -> String {
+    let mut out = String::new();
+    let consts = table.get_constant_table();
+    out.push_str("constants:\n");
+    for (index, value) in consts.iter().enumerate() {
+        line(&mut out, 1, &format!("#{index} = {}", const_text(value)));
+    }
+    let _ = writeln!(out, "root (globals {}):", table.get_locals_len());
+    bytecode_list(&mut out, &table.clone_codes(), &table.clone_lines(), consts);
+    for func in table.get_functions() {
+        function_bytecode(&mut out, &func, consts);
+    }
+    out
+}
+
+fn function_bytecode(out: &mut String, func: &IrFunction, consts: &[Value]) {
+    let sync = if func.sync { "sync " } else { "" };
+    if func.is_native {
+        let _ = writeln!(
+            out,
+            "{sync}function native {}(args {})",
+            func.name, func.args
+        );
+        return;
+    }
+    let _ = writeln!(
+        out,
+        "{sync}function {}(args {}, locals {}):",
+        func.name, func.args, func.locals
+    );
+    bytecode_list(out, &func.codes, &func.lines, consts);
+}
@@ -1,26 +1,67 @@
-use smol_str::ToSmolStr;
+use smol_str::{SmolStr, ToSmolStr};
 use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
 
 use crate::compiler::ast::vm_ir::{VMIRTable, ssa_to_vm};
-use crate::compiler::lexer::{LexerAnalysis, LexerError, Token};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dump::{EmitKind, dump_ast, dump_bytecode, dump_ssa};
+use crate::compiler::lexer::{LexerAnalysis, LexerError, SourcePos, Token};
 use crate::compiler::lints::Lint;
+use crate::compiler::optimize::{Optimizations, Pass};
 use crate::compiler::parser::ParserError::LexError;
 use crate::compiler::parser::symbol_table::SymbolTable;
 use crate::compiler::parser::{Parser, ParserError};
 use crate::compiler::semantic::{
-    Semantic, const_prop_linear, eliminate_dead_locals, local_arith_peephole,
+    Semantic, SemanticState, const_prop_linear, eliminate_dead_locals, local_arith_peephole,
 };
 use crate::compiler::{Compiler, CompilerData};
 
+/// 源文件的默认扩展名, 导入路径省略扩展名时自动补全
+pub const SOURCE_EXTENSION: &str = "exf";
+
+/// 按路径分量规范化路径, 去除 `.` 并抵消 `..`, 不访问文件系统
+#[must_use]
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// 由文件名得到模块名: 规范化后的路径去掉扩展名, 如 `./net/http.exf` 对应 `net/http`
+/// 函数的运行时路径为 `模块名/函数名`
+#[must_use]
+pub fn module_name(file_name: &str) -> SmolStr {
+    normalize_path(Path::new(file_name))
+        .with_extension("")
+        .to_string_lossy()
+        .to_smolstr()
+}
+
 #[derive(Debug, Clone)]
 pub struct SourceFile {
     pub name: String,
+    module: SmolStr, // 模块名, 导入与函数路径均以此为准
     data: String,
     pub(crate) compiled: bool,
     pub is_library: bool,
     pub lexer: LexerAnalysis,
     pub(crate) c_data: CompilerData,
     pub ir_table: Option<Box<VMIRTable>>,
+    pub(crate) session: Option<Box<SemanticState>>, // 交互模式下保留的语义状态
+    pub(crate) emitted: Option<String>,             // 按 `Compiler::set_emit` 输出的中间表示
+    pub(crate) warnings: Vec<Diagnostic>,           // 编译时产生的警告, 由编译器统一输出
 }
 
 impl SourceFile {
@@ -28,29 +69,74 @@ impl SourceFile {
         let data0 = data.clone();
 
         Self {
+            module: module_name(&name),
             name,
             data,
             lexer: LexerAnalysis::new(data0),
             c_data: CompilerData {
                 symbol_table: SymbolTable::new(),
                 lints,
+                optimize: Optimizations::default(),
             },
             ir_table: None,
             is_library,
             compiled: false,
+            session: None,
+            emitted: None,
+            warnings: vec![],
         }
     }
 
+    /// 创建交互模式源文件, 每次通过 `feed` 输入的源码共享先前定义的全局变量与函数,
+    /// 且表达式语句会回显其结果
+    #[must_use]
+    pub fn interactive(name: String, lints: HashSet<Lint>) -> Self {
+        let mut file = Self::new(name, String::new(), lints, false);
+        file.session = Some(Box::default());
+        file
+    }
+
+    /// 模块名, 即规范化后去掉扩展名的文件路径
+    #[must_use]
+    pub fn module_name(&self) -> &str {
+        &self.module
+    }
+
+    /// 是否为交互模式会话文件
+    #[must_use]
+    pub const fn is_interactive(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// 以新的源码替换当前内容并标记为待编译
+    pub fn feed(&mut self, data: String) {
+        self.lexer = LexerAnalysis::new(data.clone());
+        self.data = data;
+        self.compiled = false;
+    }
+
     pub fn peek_token(&mut self) -> Result<Token, ParserError> {
         match self.lexer.next_token() {
             Ok(lexeme) => Ok(lexeme),
             Err(err) => match err {
                 LexerError::Eof => Err(ParserError::Eof),
-                err => Err(LexError(err)),
+                err => Err(LexError(
+                    err,
+                    SourcePos {
+                        line: self.lexer.get_now_line(),
+                        column: self.lexer.get_now_column(),
+                    },
+                )),
             },
         }
     }
 
+    /// 编译后的符号表, 记录了文件中全部符号的定义与引用位置
+    #[must_use]
+    pub const fn symbol_table(&self) -> &SymbolTable {
+        &self.c_data.symbol_table
+    }
+
     pub fn has_warnings(&self, lint: Lint) -> bool {
         self.c_data.lints.contains(&lint)
     }
@@ -59,16 +145,42 @@ impl SourceFile {
         &self.data
     }
 
+    /// 编译时输出的中间表示文本, 库文件不会输出
+    #[must_use]
+    pub fn get_emitted(&self) -> Option<&str> {
+        self.emitted.as_deref()
+    }
+
+    /// 语法错误时不再进行语义分析, 以免未能解析的定义引起更多错误
     /// # Errors
-    pub fn compiler(&mut self, compiler: &mut Compiler) -> Result<VMIRTable, ParserError> {
+    /// 返回按出现顺序排列的全部语法错误或语义错误
+    pub fn compiler(&mut self, compiler: &mut Compiler) -> Result<VMIRTable, Vec<ParserError>> {
+        let emit = compiler.get_emit().filter(|_| !self.is_library);
+        self.warnings.clear();
         let parser = Parser::new(self);
         let ast_tree = parser.parser()?;
+        if emit == Some(EmitKind::Ast) {
+            self.emitted = Some(dump_ast(&ast_tree));
+        }
         let mut semantic = Semantic::new(self, compiler);
         let mut ssa_ir = semantic.semantic(ast_tree)?;
-        const_prop_linear(&mut ssa_ir.0);
-        local_arith_peephole(&mut ssa_ir.0);
-        eliminate_dead_locals(&mut ssa_ir.0);
+        let optimize = self.c_data.optimize;
+        if optimize.is_enabled(Pass::ConstProp) {
+            const_prop_linear(&mut ssa_ir.0);
+        }
+        if optimize.is_enabled(Pass::ArithPeephole) {
+            local_arith_peephole(&mut ssa_ir.0);
+        }
+        if optimize.is_enabled(Pass::DeadLocals) {
+            eliminate_dead_locals(&mut ssa_ir.0);
+        }
+        if emit == Some(EmitKind::Ssa) {
+            self.emitted = Some(dump_ssa(&ssa_ir.0, &ssa_ir.1));
+        }
         let vm_ir = ssa_to_vm(ssa_ir.0, &ssa_ir.1, &self.name.to_smolstr());
+        if emit == Some(EmitKind::Bytecode) {
+            self.emitted = Some(dump_bytecode(&vm_ir));
+        }
         Ok(vm_ir)
     }
 }
@@ -0,0 +1,449 @@
+use std::collections::HashSet;
+
+use crate::compiler::Compiler;
+use crate::compiler::diagnostic::{Diagnostic, Span};
+use crate::compiler::file::SourceFile;
+use crate::compiler::lexer::{LexerAnalysis, LexerError, Token, TokenType};
+use crate::compiler::parser::Parser;
+
+// 源码格式化: 先经过语法分析确认源码合法, 再按保留注释的 token 流重新输出源码
+// 缩进统一为 4 个空格, 规范运算符两侧的空格与花括号的位置,
+// 表达式中原有的换行与语句之间的单个空行会被保留, 格式化的结果再次格式化时保持不变
+
+const INDENT: &str = "    ";
+
+// 带有源码区间的 token
+struct Item {
+    token: Token,
+    start: usize,    // 起始字节偏移
+    end: usize,      // 结束字节偏移
+    line: usize,     // 起始行 (从 0 开始)
+    end_line: usize, // 结束行, 块注释与字符串可能跨行
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Paren,   // ( )
+    Bracket, // [ ]
+    Map,     // 字典字面量 { }
+    Block,   // 代码块 { }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Word,
+    Open(Group),
+    Close(Group),
+    Comma,
+    Semi,
+    Colon,
+    Dot,
+    Binary,
+    Prefix,
+    Postfix,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    group: Group,
+    indent: usize,           // 开括号所在行的缩进, 闭括号位于行首时使用
+    content: usize,          // 括号内新起一行的缩进
+    inline: bool,            // 代码块保持在一行内
+    statement: bool,         // 语句的代码块, 闭括号同时结束语句
+    head: Option<TokenType>, // 开括号所在语句的首个 token
+}
+
+/// 格式化一个源文件, name 仅用于诊断信息
+/// # Errors
+/// 源码存在语法错误时返回全部错误的诊断, 此时不会格式化
+pub fn format_source(name: &str, source: &str) -> Result<String, Vec<Diagnostic>> {
+    let mut file = SourceFile::new(name.to_string(), source.to_string(), HashSet::new(), false);
+    if let Err(errors) = Parser::new(&mut file).parser() {
+        return Err(errors
+            .into_iter()
+            .filter_map(|error| Compiler::error_diagnostic(error, &file))
+            .collect());
+    }
+    let items = lex(source).map_err(|token| vec![changed(name, token.as_ref())])?;
+    let formatted = Printer::new(source, &items).print();
+
+    // 格式化只应改变空白, 否则说明格式化器存在缺陷, 不能输出改变了程序的结果
+    let output = lex(&formatted).map_err(|token| vec![changed(name, token.as_ref())])?;
+    let significant = |items: &[Item]| {
+        items
+            .iter()
+            .map(|item| {
+                (
+                    item.token.t_type,
+                    item.token.text().trim_end().replace('\r', ""),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let (before, after) = (significant(&items), significant(&output));
+    if let Some(index) =
+        (0..before.len().max(after.len())).find(|i| before.get(*i) != after.get(*i))
+    {
+        return Err(vec![changed(
+            name,
+            items.get(index).map(|item| &item.token),
+        )]);
+    }
+    Ok(formatted)
+}
+
+fn changed(name: &str, token: Option<&Token>) -> Diagnostic {
+    let span = token.map_or(
+        Span {
+            line: 1,
+            column: 1,
+            end_line: 1,
+            end_column: 1,
+        },
+        Span::from_token,
+    );
+    Diagnostic::error(
+        name,
+        span,
+        String::from("formatting would change the source."),
+    )
+}
+
+// 读取包含注释在内的全部 token, 出错时返回出错前的最后一个 token
+fn lex(source: &str) -> Result<Vec<Item>, Option<Token>> {
+    let mut lexer = LexerAnalysis::new(source.to_string()).with_comments();
+    let mut items: Vec<Item> = vec![];
+    loop {
+        let token = match lexer.next_token() {
+            Ok(token) => token,
+            Err(LexerError::Eof) => break,
+            Err(_) => return Err(items.pop().map(|item| item.token)),
+        };
+        let end = lexer.offset();
+        let start = source[..token.index]
+            .chars()
+            .next_back()
+            .map_or(token.index, |c| token.index - c.len_utf8());
+        let line = source[..start].matches('\n').count();
+        let end_line = line + source[start..end].matches('\n').count();
+        items.push(Item {
+            token,
+            start,
+            end,
+            line,
+            end_line,
+        });
+    }
+    Ok(items)
+}
+
+fn is_line_comment(item: &Item) -> bool {
+    item.token.t_type == TokenType::Comment && item.token.text().as_bytes()[1] == b'/'
+}
+
+// 与前一个语句的代码块相连的关键字, 如 `} else {` `} catch (e) {`
+const fn is_continuation(t_type: TokenType) -> bool {
+    matches!(t_type, TokenType::Else | TokenType::Elif | TokenType::Catch)
+}
+
+// 以代码块为主体的语句
+const fn has_body(t_type: TokenType) -> bool {
+    matches!(
+        t_type,
+        TokenType::Function
+            | TokenType::Class
+            | TokenType::If
+            | TokenType::While
+            | TokenType::For
+            | TokenType::Try
+    )
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    items: &'a [Item],
+    pairs: Vec<Option<usize>>, // 括号对应的另一半
+    out: String,
+    frames: Vec<Frame>,
+    line_indent: usize,              // 当前输出行的缩进
+    stmt_start: bool,                // 下一个 token 开始一个新的语句
+    head: Option<TokenType>,         // 当前语句的首个 token
+    newline: bool,                   // 上一个 token 之后必须换行
+    last: Option<(Role, TokenType)>, // 上一个非注释 token
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, items: &'a [Item]) -> Self {
+        let mut pairs = vec![None; items.len()];
+        let mut stack = vec![];
+        for (index, item) in items.iter().enumerate() {
+            match item.token.t_type {
+                TokenType::LP => stack.push(index),
+                TokenType::LR => {
+                    if let Some(open) = stack.pop() {
+                        pairs[open] = Some(index);
+                        pairs[index] = Some(open);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            source,
+            items,
+            pairs,
+            out: String::new(),
+            frames: vec![Frame {
+                group: Group::Block,
+                indent: 0,
+                content: 0,
+                inline: false,
+                statement: false,
+                head: None,
+            }],
+            line_indent: 0,
+            stmt_start: true,
+            head: None,
+            newline: false,
+            last: None,
+        }
+    }
+
+    fn top(&self) -> Frame {
+        *self.frames.last().unwrap()
+    }
+
+    fn print(mut self) -> String {
+        for index in 0..self.items.len() {
+            let role = self.role(index);
+            if index > 0 {
+                self.separate(index, role);
+            }
+            self.write(index);
+            self.update(index, role);
+        }
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    // 上一个 token 能否结束一个操作数, 决定 `-` `++` `[` 的含义
+    fn after_operand(&self) -> bool {
+        if self.stmt_start {
+            return false;
+        }
+        match self.last {
+            Some((Role::Word, t_type)) => matches!(
+                t_type,
+                TokenType::Identifier
+                    | TokenType::Number
+                    | TokenType::Float
+                    | TokenType::LiteralString
+                    | TokenType::True
+                    | TokenType::False
+                    | TokenType::Null
+                    | TokenType::This
+            ),
+            Some((Role::Close(_) | Role::Postfix, _)) => true,
+            _ => false,
+        }
+    }
+
+    // 花括号在语句开头, 或跟随在 `)` 与函数名, 类名, 返回类型等标识符之后时为代码块, 否则为字典
+    fn is_block(&self) -> bool {
+        self.stmt_start
+            || matches!(
+                self.last,
+                Some((Role::Close(Group::Paren), _))
+                    | Some((
+                        Role::Word,
+                        TokenType::Identifier
+                            | TokenType::Else
+                            | TokenType::Try
+                            | TokenType::While
+                            | TokenType::Function
+                    ))
+            )
+    }
+
+    fn role(&self, index: usize) -> Role {
+        let token = &self.items[index].token;
+        match token.t_type {
+            TokenType::Comment => Role::Comment,
+            TokenType::End => Role::Semi,
+            TokenType::LP => Role::Open(match token.text() {
+                "(" => Group::Paren,
+                "[" => Group::Bracket,
+                _ if self.is_block() => Group::Block,
+                _ => Group::Map,
+            }),
+            TokenType::LR => Role::Close(self.top().group),
+            TokenType::Operator => match token.text() {
+                "," => Role::Comma,
+                ":" => Role::Colon,
+                "." => Role::Dot,
+                "!" => Role::Prefix,
+                "++" | "--" if self.after_operand() => Role::Postfix,
+                "++" | "--" => Role::Prefix,
+                "+" | "-" if !self.after_operand() => Role::Prefix,
+                _ => Role::Binary,
+            },
+            _ => Role::Word,
+        }
+    }
+
+    // 两个 token 位于同一行时之间是否需要空格
+    fn spaced(&self, prev: Role, prev_token: &Token, role: Role, token: &Token) -> bool {
+        match (prev, role) {
+            (_, Role::Comma | Role::Semi | Role::Colon | Role::Dot | Role::Postfix) => false,
+            (Role::Open(Group::Block), Role::Close(Group::Block)) => false,
+            (Role::Open(Group::Block), _) | (_, Role::Close(Group::Block)) => true,
+            (Role::Open(_) | Role::Dot, _) | (_, Role::Close(_)) => false,
+            // `- -x` 不能写成 `--x`
+            (Role::Prefix, _) => {
+                matches!(prev_token.text(), "+" | "-")
+                    && token.text().starts_with(prev_token.text())
+            }
+            // 切片 `a[1:3]` 的冒号两侧不加空格
+            (Role::Colon, _) => self.top().group != Group::Bracket,
+            (Role::Comment, _) | (_, Role::Comment) => true,
+            (_, Role::Open(Group::Paren)) => !matches!(
+                (prev, prev_token.t_type),
+                (Role::Word, TokenType::Identifier | TokenType::Native) | (Role::Close(_), _)
+            ),
+            (_, Role::Open(Group::Bracket)) => !self.after_operand(),
+            _ => true,
+        }
+    }
+
+    // 输出 token 之前的空白
+    fn separate(&mut self, index: usize, role: Role) {
+        let (prev, item) = (&self.items[index - 1], &self.items[index]);
+        let prev_role = match prev.token.t_type {
+            TokenType::Comment => Role::Comment,
+            _ => self.last.map_or(Role::Word, |(role, _)| role),
+        };
+        let gap = item.line - prev.end_line;
+        let top = self.top();
+        let newline = if is_line_comment(prev) {
+            true
+        } else if role == Role::Comment {
+            gap > 0
+        } else if role == Role::Close(Group::Block) && !top.inline {
+            true
+        } else if self.newline {
+            !(role == Role::Semi
+                || is_continuation(item.token.t_type)
+                    && matches!(self.last, Some((Role::Close(Group::Block), _))))
+        } else {
+            gap > 0
+                && !matches!(
+                    role,
+                    Role::Comma
+                        | Role::Semi
+                        | Role::Colon
+                        | Role::Postfix
+                        | Role::Open(Group::Block)
+                )
+                && !is_continuation(item.token.t_type)
+        };
+
+        if !newline {
+            // 注释之后仍需换行, 其他 token 则取消了换行 (如 `;` 与 `} else`)
+            if role != Role::Comment {
+                self.newline = false;
+            }
+            if self.spaced(prev_role, &prev.token, role, &item.token) {
+                self.out.push(' ');
+            }
+            return;
+        }
+        self.newline = false;
+        // 语句之间最多保留一个空行, 代码块的开头与结尾不保留空行
+        let blank = gap > 1
+            && top.group == Group::Block
+            && (self.stmt_start || role == Role::Comment)
+            && prev_role != Role::Open(Group::Block)
+            && !matches!(role, Role::Close(_));
+        self.out.push('\n');
+        if blank {
+            self.out.push('\n');
+        }
+        self.line_indent = match role {
+            Role::Close(_) => top.indent,
+            _ if top.group != Group::Block => top.content,
+            _ if self.stmt_start || is_continuation(item.token.t_type) => top.content,
+            _ => top.content + 1,
+        };
+        for _ in 0..self.line_indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn write(&mut self, index: usize) {
+        let item = &self.items[index];
+        let text = &self.source[item.start..item.end];
+        match item.token.t_type {
+            _ if is_line_comment(item) => self.out.push_str(text.trim_end()),
+            TokenType::Comment => self.out.push_str(&text.replace('\r', "")),
+            _ => self.out.push_str(text),
+        }
+    }
+
+    fn update(&mut self, index: usize, role: Role) {
+        let t_type = self.items[index].token.t_type;
+        if role == Role::Comment {
+            return;
+        }
+        if self.stmt_start && role != Role::Semi && !matches!(role, Role::Close(_)) {
+            if !is_continuation(t_type) {
+                self.head = Some(t_type);
+            }
+            self.stmt_start = false;
+        }
+        self.last = Some((role, t_type));
+        match role {
+            Role::Open(group) => {
+                let top = self.top();
+                let statement = group == Group::Block
+                    && top.group == Group::Block
+                    && self
+                        .head
+                        .is_some_and(|head| has_body(head) || head == TokenType::LP);
+                let close = self.pairs[index];
+                let empty = close == Some(index + 1);
+                let same_line =
+                    close.is_some_and(|close| self.items[close].line == self.items[index].end_line);
+                let inline = group == Group::Block && (empty || !statement && same_line);
+                self.frames.push(Frame {
+                    group,
+                    indent: self.line_indent,
+                    content: self.line_indent + 1,
+                    inline,
+                    statement,
+                    head: self.head,
+                });
+                if group == Group::Block {
+                    self.stmt_start = true;
+                    self.head = None;
+                    self.newline = !inline;
+                }
+            }
+            Role::Close(_) if self.frames.len() > 1 => {
+                let frame = self.frames.pop().unwrap();
+                if frame.group == Group::Block {
+                    self.head = frame.head;
+                    self.stmt_start = frame.statement;
+                    self.newline = frame.statement;
+                }
+            }
+            Role::Semi if self.top().group == Group::Block => {
+                self.stmt_start = true;
+                self.newline = !self.top().inline;
+            }
+            _ => {}
+        }
+    }
+}
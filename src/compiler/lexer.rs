@@ -1,5 +1,6 @@
 use dashu::float::round::mode::HalfAway;
 use dashu::float::{DBig, FBig};
+use dashu::integer::IBig;
 use smol_str::{SmolStr, SmolStrBuilder};
 use std::char;
 use std::fmt::Debug;
@@ -15,6 +16,7 @@ pub struct LexerAnalysis {
     data_index: usize,
     now_line: usize,
     now_column: usize,
+    comments: bool, // 是否以 token 的形式返回注释
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,6 +28,13 @@ pub struct Token {
     data: SmolStr,
 }
 
+// 源码位置 (行号从 0 开始, 列号与 Token 一致)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourcePos {
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Debug)]
 pub enum LexerError {
     UnexpectedCharacter(Option<char>),
@@ -44,6 +53,7 @@ pub enum TokenType {
     LP,
     LR,
     End,
+    Comment, // 注释, 仅在保留注释时产生
 
     // 关键字类型
     For,
@@ -64,9 +74,13 @@ pub enum TokenType {
     Native,
     From,
     Sync,
+    Try,
+    Catch,
+    Throw,
+    Class,
 }
 
-const KEYWORDS: [(&str, TokenType); 18] = [
+pub(crate) const KEYWORDS: [(&str, TokenType); 22] = [
     ("for", TokenType::For),
     ("while", TokenType::While),
     ("if", TokenType::If),
@@ -85,6 +99,10 @@ const KEYWORDS: [(&str, TokenType); 18] = [
     ("native", TokenType::Native),
     ("from", TokenType::From),
     ("sync", TokenType::Sync),
+    ("try", TokenType::Try),
+    ("catch", TokenType::Catch),
+    ("throw", TokenType::Throw),
+    ("class", TokenType::Class),
 ];
 
 impl Token {
@@ -109,7 +127,8 @@ impl Token {
         DBig::from_str(&self.data).unwrap()
     }
 
-    pub fn value_number(&self) -> i64 {
+    // 整数字面量不限制大小, 超出 i64 范围时由调用者提升为任意精度整数
+    pub fn value_number(&self) -> IBig {
         self.data
             .strip_prefix("0x")
             .or_else(|| self.data.strip_prefix("0X"))
@@ -124,14 +143,14 @@ impl Token {
                                     .strip_prefix("0o")
                                     .or_else(|| self.data.strip_prefix("0O"))
                                     .map_or_else(
-                                        || self.data.parse::<i64>().unwrap(),
-                                        |oct| i64::from_str_radix(oct, 8).unwrap(),
+                                        || IBig::from_str(&self.data).unwrap(),
+                                        |oct| IBig::from_str_radix(oct, 8).unwrap(),
                                     )
                             },
-                            |bin| i64::from_str_radix(bin, 2).unwrap(),
+                            |bin| IBig::from_str_radix(bin, 2).unwrap(),
                         )
                 },
-                |hex| i64::from_str_radix(hex, 16).unwrap(),
+                |hex| IBig::from_str_radix(hex, 16).unwrap(),
             )
     }
 
@@ -146,6 +165,26 @@ impl Token {
     pub fn text(&self) -> &str {
         &self.data
     }
+
+    /// 复制源码位置, 生成文本不同的标识符 (用于编译器合成的名称)
+    #[must_use]
+    pub fn with_text(&self, data: SmolStr) -> Self {
+        Self::new(
+            data,
+            self.line,
+            self.column,
+            self.index,
+            TokenType::Identifier,
+        )
+    }
+
+    #[must_use]
+    pub const fn pos(&self) -> SourcePos {
+        SourcePos {
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 const fn is_lp(c: char) -> bool {
@@ -164,9 +203,23 @@ impl LexerAnalysis {
             now_column: 0,
             data_index: 0,
             cache: None,
+            comments: false,
         }
     }
 
+    /// 保留源码中的注释, 用于格式化等需要还原源码的场景
+    #[must_use]
+    pub(crate) const fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
+    }
+
+    // 注释 token 的内容为包含注释符号在内的原文
+    fn comment(&self, line: usize, column: usize, data_index: usize) -> Token {
+        let text = &self.data[data_index - 1..self.offset()];
+        Token::new(text.into(), line, column, data_index, TokenType::Comment)
+    }
+
     fn next_char(&mut self) -> char {
         self.cache.take().unwrap_or_else(|| {
             self.data[self.data_index..]
@@ -404,6 +457,7 @@ impl LexerAnalysis {
             n_char = self.next_char();
             match n_char {
                 '"' => break,
+                '\0' => return Err(LexerError::IllegalLiteral),
                 '\\' => {
                     n_char = self.next_char();
                     match n_char {
@@ -514,21 +568,29 @@ impl LexerAnalysis {
                     }
                 }
                 self.cache = Some(c);
+                if self.comments {
+                    return Ok(self.comment(line, column, data_index));
+                }
                 self.next_token()
             }
             '*' => {
+                // 块注释中的换行同样计入行号, 使之后的 token 位置正确
+                let mut last = '\0';
                 loop {
                     c = self.next_char();
-                    if c == '*' {
-                        c = self.next_char();
-                        if c == '/' {
-                            break;
-                        } else if c == '\0' {
-                            return Err(Eof);
+                    match c {
+                        '\0' => return Err(Eof),
+                        '/' if last == '*' => break,
+                        '\n' => {
+                            self.now_line += 1;
+                            self.now_column = 0;
                         }
-                    } else if c == '\0' {
-                        return Err(Eof);
+                        _ => {}
                     }
+                    last = c;
+                }
+                if self.comments {
+                    return Ok(self.comment(line, column, data_index));
                 }
                 self.next_token()
             }
@@ -567,6 +629,11 @@ impl LexerAnalysis {
         self.now_column
     }
 
+    // 已读取内容的字节偏移, 不包含预读的字符 (读到末尾时预读的是 '\0')
+    pub(crate) fn offset(&self) -> usize {
+        self.data_index - self.cache.filter(|c| *c != '\0').map_or(0, char::len_utf8)
+    }
+
     pub fn next_token(&mut self) -> Result<Token, LexerError> {
         self.skip_whitespace()?;
 
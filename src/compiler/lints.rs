@@ -15,6 +15,16 @@ macro_rules! declare_lint {
             $($name),+
         }
 
+        impl Lint {
+            /// 命令行参数 `-A` 中使用的名称
+            #[must_use]
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$name => $opt,)+
+                }
+            }
+        }
+
         impl FromStr for Lint {
             type Err = Error;
 
@@ -36,5 +46,6 @@ declare_lint! {
     UnusedValue "unused-value",
     UnusedLibrary "unused-library",
     UnusedExpression "unused-expression",
+    UnusedResult "unused-result",
     SyncRecursion "sync-recursion",
 }
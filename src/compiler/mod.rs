@@ -1,16 +1,30 @@
-use std::collections::HashSet;
+use smol_str::SmolStr;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::compiler::ast::ASTExprTree;
+use crate::compiler::diagnostic::{Diagnostic, MessageFormat, Severity, Span};
+use crate::compiler::dump::EmitKind;
 use crate::compiler::file::SourceFile;
 use crate::compiler::lexer::{LexerError, Token};
 use crate::compiler::lints::Lint;
+use crate::compiler::optimize::Optimizations;
 use crate::compiler::parser::ParserError;
 use crate::compiler::parser::symbol_table::SymbolTable;
+use crate::compiler::semantic::Signature;
+use crate::library::{NativeRegistry, SharedNatives};
 
 pub mod ast;
+pub mod bytecode;
+pub mod diagnostic;
+pub mod dump;
 pub mod file;
+pub mod format;
 pub mod lexer;
 pub mod lints;
+mod module;
+pub mod optimize;
 pub mod parser;
 mod semantic;
 
@@ -18,11 +32,22 @@ mod semantic;
 pub struct CompilerData {
     symbol_table: SymbolTable,
     lints: HashSet<Lint>,
+    optimize: Optimizations,
 }
 
 #[derive(Debug, Clone)]
 pub struct Compiler {
     files: Vec<SourceFile>,
+    natives: SharedNatives,
+    emit: Option<EmitKind>,
+    optimize: Optimizations,
+    signatures: HashMap<SmolStr, Signature>, // 已编译函数的类型签名 (函数路径), 用于检查跨文件调用
+    search_paths: Vec<PathBuf>,              // 导入的搜索路径, 默认取自 OPENEX_PATH
+    imports: HashMap<SmolStr, Vec<SmolStr>>, // 模块间的导入关系, 用于检测循环导入
+    packages: HashMap<SmolStr, PathBuf>,     // 依赖包名对应的入口文件, 可直接以包名导入
+    last_error: Option<String>,              // 最近一次编译错误的完整信息, 供嵌入接口读取
+    diagnostics: Vec<Diagnostic>,            // 最近一次编译输出的错误与警告
+    message_format: MessageFormat,
 }
 
 impl Default for Compiler {
@@ -33,8 +58,20 @@ impl Default for Compiler {
 
 impl Compiler {
     #[must_use]
-    pub const fn new() -> Self {
-        Self { files: vec![] }
+    pub fn new() -> Self {
+        Self {
+            files: vec![],
+            natives: SharedNatives::default(),
+            emit: None,
+            optimize: Optimizations::default(),
+            signatures: HashMap::new(),
+            search_paths: module::env_search_paths(),
+            imports: HashMap::new(),
+            packages: HashMap::new(),
+            last_error: None,
+            diagnostics: vec![],
+            message_format: MessageFormat::default(),
+        }
     }
 
     #[must_use]
@@ -50,18 +87,96 @@ impl Compiler {
         &mut self.files
     }
 
+    /// 该编译器实例的本地函数注册表, 执行时与运行时共享
     #[must_use]
-    /// # Panics
-    pub fn find_file(&self, path: &str) -> Option<&SourceFile> {
+    pub const fn natives(&self) -> &SharedNatives {
+        &self.natives
+    }
+
+    /// 需要在 `load_libraries` 与 `compile` 之前注册宿主提供的本地函数
+    pub fn natives_mut(&mut self) -> &mut NativeRegistry {
+        Arc::make_mut(&mut self.natives)
+    }
+
+    /// 设置之后编译的源文件所启用的优化, 运行时记忆调用也以此为准
+    pub const fn set_optimizations(&mut self, optimize: Optimizations) {
+        self.optimize = optimize;
+    }
+
+    #[must_use]
+    pub const fn get_optimizations(&self) -> Optimizations {
+        self.optimize
+    }
+
+    /// 编译时保留指定的中间表示文本, 编译后通过 `emitted` 取出
+    pub const fn set_emit(&mut self, emit: Option<EmitKind>) {
+        self.emit = emit;
+    }
+
+    #[must_use]
+    pub const fn get_emit(&self) -> Option<EmitKind> {
+        self.emit
+    }
+
+    /// 按文件顺序拼接所有非库文件输出的中间表示, 每个文件以 `== 文件名 ==` 开头
+    #[must_use]
+    pub fn emitted(&self) -> String {
+        let mut output = String::new();
         for file in &self.files {
-            if file.name.as_str().split('.').next().unwrap() == path {
-                return Some(file);
+            if let Some(text) = file.get_emitted() {
+                output.push_str(&format!("== {} ==\n{text}", file.name));
             }
         }
-        None
+        output
+    }
+
+    /// 最近一次编译失败时输出的全部错误信息, 包含出错的文件与位置
+    #[must_use]
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// 最近一次编译输出的错误与警告, 包含按需导入的模块中的诊断, 按输出顺序排列
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 最近一次编译输出的错误数
+    #[must_use]
+    pub fn error_count(&self) -> usize {
+        self.count_diagnostics(Severity::Error)
+    }
+
+    /// 最近一次编译输出的警告数
+    #[must_use]
+    pub fn warning_count(&self) -> usize {
+        self.count_diagnostics(Severity::Warning)
+    }
+
+    fn count_diagnostics(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
     }
 
-    fn highlight_line_and_column(data: &str, target_line: usize, target_column: usize) -> String {
+    /// 设置编译诊断的输出格式, 默认为带有源码高亮的文本
+    pub const fn set_message_format(&mut self, format: MessageFormat) {
+        self.message_format = format;
+    }
+
+    /// 按模块名 (规范化后去掉扩展名的文件路径) 查找已加载的源文件
+    #[must_use]
+    pub fn find_file(&self, module: &str) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.module_name() == module)
+    }
+
+    pub(crate) fn highlight_line_and_column(
+        data: &str,
+        target_line: usize,
+        target_column: usize,
+    ) -> String {
         let target_line_content = data.lines().nth(target_line);
         let Some(line_content) = target_line_content else {
             return format!("error: unknown line {target_line}");
@@ -87,22 +202,8 @@ impl Compiler {
         output
     }
 
-    fn dump_error_info(message: &str, line: usize, column: usize, file: &SourceFile) {
-        eprintln!(
-            "SyntaxError({}-line: {} column: {}): {}",
-            file.name,
-            line + 1,
-            column,
-            message
-        );
-        eprintln!(
-            "{}",
-            Self::highlight_line_and_column(file.get_data(), line, column)
-        );
-    }
-
-    fn dump_lexer_error(lex_error: &LexerError, file: &SourceFile) {
-        let message: String = match lex_error {
+    fn lexer_message(lex_error: &LexerError) -> String {
+        match lex_error {
             LexerError::UnexpectedCharacter(c) => {
                 format!("unexpected character {}", c.unwrap())
             }
@@ -111,121 +212,188 @@ impl Compiler {
                 format!("illegal escape character {char}")
             }
             LexerError::Eof => String::from("EOF"),
-        };
-        Self::dump_error_info(
-            &message,
-            file.lexer.get_now_line(),
-            file.lexer.get_now_column(),
-            file,
-        );
+        }
     }
 
-    fn dump_parser_error(error: ParserError, file: &SourceFile) {
-        let line: usize;
-        let column: usize;
+    // 占位错误已在别处输出, 不返回诊断
+    pub(crate) fn error_diagnostic(error: ParserError, file: &SourceFile) -> Option<Diagnostic> {
+        let span: Span;
         let message: String;
+        let mut notes = vec![];
+        let mut suggestion = None;
 
         match error {
-            ParserError::LexError(lex_error) => {
-                Self::dump_lexer_error(&lex_error, file);
-                return;
+            ParserError::LexError(lex_error, pos) => {
+                span = Span::from_pos(pos);
+                message = Self::lexer_message(&lex_error);
             }
             ParserError::Eof | ParserError::Empty | ParserError::RuntimeError(..) => {
-                return;
+                return None;
             }
             ParserError::IdentifierExpected(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = "<identifier> expected.".parse().unwrap();
             }
             ParserError::NotAStatement(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = "<statement> expected.".parse().unwrap();
             }
             ParserError::Expected(token, c) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = format!("'{c}' expected.");
+                suggestion = Some(format!("insert '{c}' before '{}'", token.text()));
             }
             ParserError::MissingStatement(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("statement is incomplete.");
             }
             ParserError::IllegalArgument(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("illegal argument.");
             }
             ParserError::MissingFunctionBody(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("missing function body.");
             }
             ParserError::MissingCondition(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("missing condition.");
             }
             ParserError::IllegalExpression(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("illegal combination of expressions.");
             }
             ParserError::IllegalKey(key) => {
-                line = key.line;
-                column = key.column;
+                span = Span::from_token(&key);
                 message = String::from("illegal key.");
             }
             ParserError::BackOutsideLoop(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("back statement outside loop.");
             }
             ParserError::SymbolDefined(name) => {
-                line = name.line;
-                column = name.column;
+                span = Span::from_token(&name);
                 message = String::from("type already defined."); // 沿用 Pro 版本的彩蛋
             }
             ParserError::IllegalTypeCombination(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("illegal type combination.");
             }
             ParserError::UnableResolveSymbols(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("unable to resolve symbols.");
             }
             ParserError::NoNativeImplement(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("no native implement.");
             }
             ParserError::NotFoundLibrary(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("not found import library.");
             }
             ParserError::MissingLoopBody(token) => {
-                line = token.line;
-                column = token.column;
+                span = Span::from_token(&token);
                 message = String::from("missing loop body.");
             }
+            ParserError::MissingCatchBlock(token) => {
+                span = Span::from_token(&token);
+                message = String::from("missing catch block.");
+            }
+            ParserError::ImportCycle(token, cycle) => {
+                span = Span::from_token(&token);
+                message = format!("import cycle detected: {}.", cycle.join(" -> "));
+                notes = cycle
+                    .windows(2)
+                    .map(|edge| format!("'{}' imports '{}'", edge[0], edge[1]))
+                    .collect();
+            }
+            ParserError::TypeMismatch(token, expected, found) => {
+                span = Span::from_token(&token);
+                message = format!("mismatched types: expected '{expected}', found '{found}'.");
+            }
+            ParserError::ForeignFunction(token, msg) => {
+                span = Span::from_token(&token);
+                message = format!("cannot bind native function: {msg}.");
+            }
+            ParserError::DuplicateImport(token, name, spec) => {
+                span = Span::from_token(&token);
+                message = format!("import name '{name}' is already bound to another module.");
+                suggestion = Some(format!(
+                    "import it under another name: import <name> from \"{spec}\";"
+                ));
+            }
+            ParserError::LibraryStatement(token) => {
+                span = Span::from_token(&token);
+                message =
+                    String::from("top-level statement in an imported module is never executed.");
+                notes.push(String::from(
+                    "imported modules only provide functions and classes",
+                ));
+                suggestion = Some(String::from("move the statement into a function"));
+            }
+            ParserError::ArgumentCount(token, expected, found) => {
+                span = Span::from_token(&token);
+                message = format!("expected {expected} argument(s), found {found}.");
+            }
         }
 
-        Self::dump_error_info(&message, line, column, file);
+        Some(Diagnostic {
+            notes,
+            suggestion,
+            ..Diagnostic::error(&file.name, span, message)
+        })
     }
 
-    pub fn warning_info_expr(source_file: &SourceFile, msg: &str, expr: &ASTExprTree, lint: Lint) {
+    // 按输出格式输出诊断, 错误同时追加到最近一次编译的错误信息中
+    fn report(&mut self, diagnostic: Diagnostic, file: &SourceFile) {
+        let rendered = diagnostic.render(file.get_data());
+        match self.message_format {
+            MessageFormat::Human if diagnostic.is_error() => eprintln!("{rendered}"),
+            MessageFormat::Human => println!("{rendered}"),
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json()),
+            MessageFormat::Silent => {}
+        }
+        if diagnostic.is_error() {
+            match self.last_error.as_mut() {
+                Some(last_error) => {
+                    last_error.push('\n');
+                    last_error.push_str(&rendered);
+                }
+                None => self.last_error = Some(rendered),
+            }
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    // 输出一个文件编译时产生的全部警告与错误
+    fn report_file(&mut self, file: &mut SourceFile, errors: Vec<ParserError>) {
+        for warning in std::mem::take(&mut file.warnings) {
+            self.report(warning, file);
+        }
+        for error in errors {
+            if let Some(diagnostic) = Self::error_diagnostic(error, file) {
+                self.report(diagnostic, file);
+            }
+        }
+    }
+
+    pub fn warning_info_expr(
+        source_file: &mut SourceFile,
+        msg: &str,
+        expr: &ASTExprTree,
+        lint: Lint,
+    ) {
         if !source_file.has_warnings(lint) {
             let token: &Token = match expr {
                 ASTExprTree::Var(token)
                 | ASTExprTree::Literal(token)
                 | ASTExprTree::This(token)
                 | ASTExprTree::Expr { token, .. }
-                | ASTExprTree::Unary { token, .. } => token,
+                | ASTExprTree::Unary { token, .. }
+                | ASTExprTree::Slice { token, .. }
+                | ASTExprTree::Lambda { token, .. }
+                | ASTExprTree::Array { token, .. }
+                | ASTExprTree::Map { token, .. } => token,
                 ASTExprTree::Call { name: e_name, .. } => match e_name.as_ref() {
                     ASTExprTree::Var(token) => token,
                     _ => {
@@ -233,34 +401,54 @@ impl Compiler {
                     }
                 },
             };
-            println!("warning: {msg}");
-            println!(
-                "{}",
-                Self::highlight_line_and_column(source_file.get_data(), token.line, token.column)
+            let warning = Diagnostic::warning(
+                &source_file.name,
+                Span::from_token(token),
+                msg.to_string(),
+                lint,
             );
+            source_file.warnings.push(warning);
         }
     }
 
+    /// 编译所有尚未编译的文件, 一个文件出错后仍会继续编译其余文件以输出全部错误
     /// # Errors
+    /// 任一文件编译失败时返回错误, 错误信息可由 `last_error` 读取
     /// # Panics
     #[warn(clippy::result_unit_err)]
     pub fn compile(&mut self) -> Result<(), ()> {
         let mut compiler = self.clone();
+        compiler.last_error = None;
+        compiler.diagnostics.clear();
+        let mut failed = false;
         for file in &mut self.files {
             if file.compiled {
                 continue;
             }
-            let vm_ir = file.compiler(&mut compiler);
-            let vm_ir = match vm_ir {
-                Ok(_) => vm_ir.unwrap(),
-                Err(error) => {
-                    Self::dump_parser_error(error, file);
-                    return Err(());
+            file.c_data.optimize = self.optimize;
+            match file.compiler(&mut compiler) {
+                Ok(vm_ir) => {
+                    compiler.report_file(file, vec![]);
+                    file.ir_table = Some(Box::new(vm_ir));
+                    file.compiled = true;
                 }
-            };
-            file.ir_table = Some(Box::new(vm_ir));
-            file.compiled = true;
+                Err(errors) => {
+                    compiler.report_file(file, errors);
+                    failed = true;
+                }
+            }
+        }
+        self.last_error = compiler.last_error;
+        self.diagnostics = compiler.diagnostics;
+        if failed {
+            return Err(());
         }
+        // 按需加载的模块追加在已有文件之后
+        let loaded = compiler.files.split_off(self.files.len());
+        self.files.extend(loaded);
+        self.signatures = compiler.signatures;
+        self.imports = compiler.imports;
+        self.natives = compiler.natives;
         Ok(())
     }
 }
@@ -0,0 +1,163 @@
+use smol_str::{SmolStr, ToSmolStr};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compiler::Compiler;
+use crate::compiler::file::{SOURCE_EXTENSION, SourceFile, module_name, normalize_path};
+use crate::compiler::lexer::Token;
+use crate::compiler::parser::ParserError;
+
+// 导入搜索路径的环境变量, 格式与系统的 PATH 相同
+const SEARCH_PATH_ENV: &str = "OPENEX_PATH";
+
+pub fn env_search_paths() -> Vec<PathBuf> {
+    std::env::var_os(SEARCH_PATH_ENV)
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
+
+impl Compiler {
+    /// 追加一个导入搜索路径, 在 `OPENEX_PATH` 提供的路径之后查找
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.search_paths.push(path.into());
+    }
+
+    /// 导入搜索路径, 按查找顺序排列
+    #[must_use]
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+
+    /// 登记一个依赖包, 脚本中 `import 包名;` 导入其入口文件
+    pub fn add_package(&mut self, name: &str, entry: impl Into<PathBuf>) {
+        self.packages.insert(name.to_smolstr(), entry.into());
+    }
+
+    /// 解析导入语句并返回被导入的模块名, 尚未加载的源文件会立即编译
+    /// 查找顺序: 已加载的同名模块, 依赖包, 相对导入文件所在目录, 各个搜索路径
+    pub(crate) fn import_module(
+        &mut self,
+        importer: &SourceFile,
+        token: &Token,
+        spec: &str,
+    ) -> Result<SmolStr, ParserError> {
+        let Some((module, path)) = self.resolve_import(importer, spec) else {
+            return Err(ParserError::NotFoundLibrary(token.clone()));
+        };
+        self.add_import(importer.module_name(), &module, token)?;
+        if let Some(path) = path {
+            self.load_module(importer, token, &path)?;
+        }
+        Ok(module)
+    }
+
+    // 返回模块名与需要加载的文件路径, 模块已加载时路径为空
+    fn resolve_import(
+        &self,
+        importer: &SourceFile,
+        spec: &str,
+    ) -> Option<(SmolStr, Option<PathBuf>)> {
+        if self.find_file(spec).is_some() {
+            return Some((spec.to_smolstr(), None));
+        }
+        if let Some(entry) = self.packages.get(spec) {
+            let path = normalize_path(entry);
+            let module = module_name(&path.to_string_lossy());
+            let loaded = self.find_file(&module).is_some();
+            return Some((module, (!loaded).then_some(path)));
+        }
+        let mut target = PathBuf::from(spec);
+        if target.extension().is_none() {
+            target.set_extension(SOURCE_EXTENSION);
+        }
+        let base = Path::new(&importer.name).parent().unwrap_or(Path::new(""));
+        for dir in std::iter::once(base).chain(self.search_paths.iter().map(PathBuf::as_path)) {
+            let path = normalize_path(&dir.join(&target));
+            let module = module_name(&path.to_string_lossy());
+            if self.find_file(&module).is_some() {
+                return Some((module, None));
+            }
+            if path.is_file() {
+                return Some((module, Some(path)));
+            }
+        }
+        None
+    }
+
+    // 记录导入关系, 被导入模块能够经由已有的导入关系回到导入者时构成循环
+    fn add_import(
+        &mut self,
+        importer: &str,
+        module: &str,
+        token: &Token,
+    ) -> Result<(), ParserError> {
+        let edges = self.imports.get(importer);
+        if edges.is_some_and(|edges| edges.iter().any(|edge| edge == module)) {
+            return Ok(());
+        }
+        if let Some(path) = self.import_path(module, importer) {
+            let mut cycle = vec![importer.to_smolstr()];
+            cycle.extend(path);
+            return Err(ParserError::ImportCycle(token.clone(), cycle));
+        }
+        self.imports
+            .entry(importer.to_smolstr())
+            .or_default()
+            .push(module.to_smolstr());
+        Ok(())
+    }
+
+    // 沿导入关系查找从 from 到 to 的模块链 (包含两端)
+    fn import_path(&self, from: &str, to: &str) -> Option<Vec<SmolStr>> {
+        let mut stack = vec![vec![from.to_smolstr()]];
+        let mut visited: Vec<&str> = vec![];
+        while let Some(path) = stack.pop() {
+            let last = path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            let Some((key, edges)) = self.imports.get_key_value(last.as_str()) else {
+                continue;
+            };
+            if visited.contains(&key.as_str()) {
+                continue;
+            }
+            visited.push(key);
+            for edge in edges {
+                let mut next = path.clone();
+                next.push(edge.clone());
+                stack.push(next);
+            }
+        }
+        None
+    }
+
+    // 按需加载的模块视为库文件: 只提供函数与类, 不作为执行入口
+    // 模块内的编译错误在此处输出, 导入语句只返回占位错误
+    fn load_module(
+        &mut self,
+        importer: &SourceFile,
+        token: &Token,
+        path: &Path,
+    ) -> Result<(), ParserError> {
+        let Ok(data) = fs::read_to_string(path) else {
+            return Err(ParserError::NotFoundLibrary(token.clone()));
+        };
+        let name = path.to_string_lossy().into_owned();
+        let mut file = SourceFile::new(name, data, importer.c_data.lints.clone(), true);
+        file.c_data.optimize = self.optimize;
+        match file.compiler(self) {
+            Ok(vm_ir) => {
+                self.report_file(&mut file, vec![]);
+                file.ir_table = Some(Box::new(vm_ir));
+                file.compiled = true;
+                self.files.push(file);
+                Ok(())
+            }
+            Err(errors) => {
+                self.report_file(&mut file, errors);
+                Err(ParserError::Empty)
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::{fmt::Display, str::FromStr};
+
+pub struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown optimization `{}`", self.0)
+    }
+}
+
+macro_rules! declare_pass {
+    ($($name:ident $opt:literal $level:literal),+ $(,)?) => {
+        #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Ord, PartialOrd)]
+        #[repr(u8)]
+        pub enum Pass {
+            $($name),+
+        }
+
+        impl Pass {
+            pub const ALL: &[Self] = &[$(Self::$name),+];
+
+            /// 默认开启该优化的最低优化等级
+            #[must_use]
+            pub const fn level(self) -> u8 {
+                match self {
+                    $(Self::$name => $level,)+
+                }
+            }
+
+            #[must_use]
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$name => $opt,)+
+                }
+            }
+        }
+
+        impl FromStr for Pass {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($opt => Ok(Self::$name),)+
+                    _ => Err(Error(s.into())),
+                }
+            }
+        }
+    };
+}
+
+declare_pass! {
+    ConstFold "const-fold" 1,       // 常量表达式折叠
+    DeadLocals "dead-locals" 1,     // 局部变量表压缩
+    ConstProp "const-prop" 2,       // 局部变量常量传递
+    ArithPeephole "arith-peephole" 2, // 局部变量自增窥孔优化
+    CallMemo "call-memo" 2,         // 运行时记忆调用
+}
+
+pub const MAX_OPT_LEVEL: u8 = 2;
+
+/// 编译器与运行时启用的优化集合, 默认为最高优化等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Optimizations {
+    enabled: u8, // 按 `Pass` 序号索引的位集合
+}
+
+impl Default for Optimizations {
+    fn default() -> Self {
+        Self::with_level(MAX_OPT_LEVEL)
+    }
+}
+
+impl Optimizations {
+    /// 开启指定等级及以下的所有优化, 超过最高等级时按最高等级处理
+    #[must_use]
+    pub fn with_level(level: u8) -> Self {
+        let mut opts = Self { enabled: 0 };
+        for pass in Pass::ALL {
+            opts.set(*pass, pass.level() <= level);
+        }
+        opts
+    }
+
+    pub const fn set(&mut self, pass: Pass, enabled: bool) {
+        if enabled {
+            self.enabled |= 1 << pass as u8;
+        } else {
+            self.enabled &= !(1 << pass as u8);
+        }
+    }
+
+    #[must_use]
+    pub const fn is_enabled(self, pass: Pass) -> bool {
+        self.enabled & (1 << pass as u8) != 0
+    }
+
+    /// 应用 `-Z` 形式的单项开关, `<pass>` 开启而 `no-<pass>` 关闭该优化
+    /// # Errors
+    /// 优化名称不存在时返回错误
+    pub fn apply_flag(&mut self, flag: &str) -> Result<(), Error> {
+        match flag.strip_prefix("no-") {
+            Some(pass) => self.set(pass.parse()?, false),
+            None => self.set(flag.parse()?, true),
+        }
+        Ok(())
+    }
+}
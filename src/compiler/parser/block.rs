@@ -6,24 +6,77 @@ use crate::compiler::parser::expression::expr_eval;
 use crate::compiler::parser::r#for::for_eval;
 use crate::compiler::parser::judgment::if_eval;
 use crate::compiler::parser::r#return::return_eval;
+use crate::compiler::parser::r#try::{throw_eval, try_eval};
 use crate::compiler::parser::var::var_eval;
 use crate::compiler::parser::r#while::while_eval;
 use crate::compiler::parser::{Parser, ParserError, check_char};
 
 fn parser_expr(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
-    let mut tokens: Vec<Token> = vec![];
-    let mut token = parser.next_parser_token()?;
-    tokens.push(token);
-    loop {
-        token = parser.next_parser_token()?;
-        if token.t_type == TokenType::End {
-            break;
-        }
-        tokens.push(token);
-    }
+    let token = parser.next_parser_token()?;
+    let tokens = parser.stmt_tokens(vec![token])?;
     expr_eval(parser, tokens)?.map_or(Ok(ASTStmtTree::Empty), |expr| Ok(ASTStmtTree::Expr(expr)))
 }
 
+// 解析语句块中以 token 开头的一个语句, 空语句返回 None
+fn block_stmt(parser: &mut Parser, mut token: Token) -> Result<Option<ASTStmtTree>, ParserError> {
+    let stmt = match token.t_type {
+        TokenType::Function | TokenType::Class => {
+            return Err(ParserError::NotAStatement(token));
+        }
+        TokenType::If => {
+            parser.last = Some(token);
+            if_eval(parser)?
+        }
+        TokenType::Var => {
+            parser.last = Some(token);
+            var_eval(parser)?
+        }
+        TokenType::While => {
+            parser.last = Some(token);
+            while_eval(parser)?
+        }
+        TokenType::For => {
+            parser.last = Some(token);
+            for_eval(parser)?
+        }
+        TokenType::Try => {
+            parser.last = Some(token.clone());
+            try_eval(parser, token)?
+        }
+        TokenType::Throw => {
+            parser.last = Some(token.clone());
+            throw_eval(parser, token)?
+        }
+        TokenType::End => return Ok(None),
+        TokenType::Return => {
+            parser.last = Some(token);
+            return_eval(parser)?
+        }
+        TokenType::Break | TokenType::Continue => {
+            let stmt = if token.t_type == TokenType::Break {
+                ASTStmtTree::Break(token)
+            } else {
+                ASTStmtTree::Continue(token)
+            };
+            token = parser.next_parser_token()?;
+            if token.t_type != TokenType::End {
+                return Err(ParserError::Expected(token, ';'));
+            }
+            stmt
+        }
+        LP if token.text() == "{" => {
+            parser.cache = Some(token);
+            Context(blk_eval(parser)?)
+        }
+        _ => {
+            parser.cache = Some(token);
+            parser_expr(parser)?
+        }
+    };
+    Ok(Some(stmt))
+}
+
+/// 解析语句块, 出错的语句被跳过并继续解析块内之后的语句
 pub fn blk_eval(parser: &mut Parser) -> Result<Vec<ASTStmtTree>, ParserError> {
     let mut token = parser.next_parser_token()?;
     check_char(&token, LP, '{')?;
@@ -31,63 +84,16 @@ pub fn blk_eval(parser: &mut Parser) -> Result<Vec<ASTStmtTree>, ParserError> {
     let mut stmt: Vec<ASTStmtTree> = vec![];
     loop {
         token = parser.next_parser_token()?;
-        match token.t_type {
-            TokenType::Function => return Err(ParserError::NotAStatement(token)),
-            TokenType::If => {
-                parser.last = Some(token);
-                stmt.push(if_eval(parser)?);
-            }
-            TokenType::Var => {
-                parser.last = Some(token);
-                stmt.push(var_eval(parser)?);
-            }
-            TokenType::While => {
-                parser.last = Some(token);
-                stmt.push(while_eval(parser)?);
-            }
-            TokenType::For => {
-                parser.last = Some(token);
-                stmt.push(for_eval(parser)?);
-            }
-            TokenType::End => {}
-            TokenType::Return => {
-                parser.last = Some(token);
-                stmt.push(return_eval(parser)?);
-            }
-            TokenType::Break => {
-                stmt.push(ASTStmtTree::Break(token));
-                token = parser.next_parser_token()?;
-                if token.t_type != TokenType::End {
-                    return Err(ParserError::Expected(token, ';'));
-                }
-            }
-            TokenType::Continue => {
-                stmt.push(ASTStmtTree::Continue(token));
-                token = parser.next_parser_token()?;
-                if token.t_type != TokenType::End {
-                    return Err(ParserError::Expected(token, ';'));
-                }
-            }
-            LR => {
-                let t = token.clone();
-                parser.cache = Some(token);
-                if t.text() == "}" {
-                    break;
-                }
-            }
-            LP => {
-                let t = token.clone();
-                parser.cache = Some(token);
-                if t.text() == "{" {
-                    stmt.push(Context(blk_eval(parser)?));
-                } else {
-                    stmt.push(parser_expr(parser)?);
-                }
-            }
-            _ => {
-                parser.cache = Some(token);
-                stmt.push(parser_expr(parser)?);
-            }
+        if token.t_type == LR && token.text() == "}" {
+            parser.cache = Some(token);
+            break;
+        }
+        let start = token.index;
+        match block_stmt(parser, token) {
+            Ok(Some(node)) => stmt.push(node),
+            Ok(None) => {}
+            Err(ParserError::Eof) => return Err(ParserError::Eof),
+            Err(error) => parser.recover(error, start),
         }
     }
     token = parser.next_parser_token()?;
@@ -0,0 +1,46 @@
+use crate::compiler::ast::ASTStmtTree;
+use crate::compiler::lexer::TokenType;
+use crate::compiler::lexer::TokenType::{LP, LR};
+use crate::compiler::parser::function::func_eval;
+use crate::compiler::parser::var::var_eval;
+use crate::compiler::parser::{Parser, ParserError, check_char};
+
+// 解析类定义 class Name { ... }, class 关键字已被消费
+// 类体内只允许字段声明 (var) 与方法定义 (function)
+pub fn class_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
+    let name = parser.next_parser_token()?;
+    if name.t_type != TokenType::Identifier {
+        return Err(ParserError::IdentifierExpected(name));
+    }
+    let token = parser.next_parser_token()?;
+    check_char(&token, LP, '{')?;
+
+    let mut fields: Vec<ASTStmtTree> = vec![];
+    let mut methods: Vec<ASTStmtTree> = vec![];
+    loop {
+        let token = parser.next_parser_token()?;
+        match token.t_type {
+            LR if token.text() == "}" => break,
+            TokenType::End => {}
+            TokenType::Var => {
+                parser.last = Some(token);
+                fields.push(var_eval(parser)?);
+            }
+            TokenType::Function => {
+                parser.cache = Some(token);
+                match func_eval(parser)? {
+                    ASTStmtTree::NativeFunction { name, .. } => {
+                        return Err(ParserError::NotAStatement(name));
+                    }
+                    method => methods.push(method),
+                }
+            }
+            _ => return Err(ParserError::NotAStatement(token)),
+        }
+    }
+    Ok(ASTStmtTree::Class {
+        name,
+        fields,
+        methods,
+    })
+}
@@ -5,7 +5,10 @@ use crate::compiler::ast::ASTExprTree::{Call, Expr, Var};
 use crate::compiler::ast::{ASTExprTree, ExprOp};
 use crate::compiler::lexer::TokenType::LP;
 use crate::compiler::lexer::{Token, TokenType};
-use crate::compiler::parser::ParserError::{IllegalExpression, IllegalKey, MissingCondition};
+use crate::compiler::parser::ParserError::{
+    Expected, IdentifierExpected, IllegalArgument, IllegalExpression, IllegalKey, MissingCondition,
+    MissingFunctionBody,
+};
 use crate::compiler::parser::{Parser, ParserError, check_char};
 
 fn prefix_binding_power(token: &Token) -> ((), u8) {
@@ -47,12 +50,14 @@ fn build_head_ast_tree(
     token: Token,
 ) -> Result<ASTExprTree, ParserError> {
     match token.t_type {
+        LP if token.text() == "[" => array_literal_eval(parser, tokens, token),
+        LP if token.text() == "{" => map_literal_eval(parser, tokens, token),
         LP => {
             let t = token;
             if t.text() != "(" {
                 return Err(IllegalExpression(t));
             }
-            let lhs = expr_bp(parser, tokens, 0);
+            let lhs = expr_bp(parser, tokens, 0, &t);
             let Some(n_token) = tokens.next() else {
                 return Err(MissingCondition(t));
             };
@@ -70,7 +75,7 @@ fn build_head_ast_tree(
                 _ => return Err(IllegalExpression(token)),
             };
             parser.last = Some(token.clone());
-            let rhs = expr_bp(parser, tokens, r_bp)?;
+            let rhs = expr_bp(parser, tokens, r_bp, &token)?;
             Ok(ASTExprTree::Unary {
                 token,
                 op,
@@ -85,10 +90,131 @@ fn build_head_ast_tree(
         | TokenType::Null => Ok(ASTExprTree::Literal(token)),
         TokenType::This => Ok(ASTExprTree::This(token)),
         TokenType::Identifier => Ok(Var(token)),
+        TokenType::Function => lambda_eval(parser, tokens, token),
         _ => Err(IllegalKey(token)),
     }
 }
 
+// 读取字面量中元素之后的分隔符, 返回是否已到达右括号 close
+fn literal_separator(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    token: &Token,
+    close: char,
+) -> Result<bool, ParserError> {
+    let Some(tk) = tokens.next() else {
+        return Err(MissingCondition(token.clone()));
+    };
+    if tk.t_type == TokenType::Operator && tk.text() == "," {
+        return Ok(false);
+    }
+    check_char(&tk, TokenType::LR, close)?;
+    Ok(true)
+}
+
+// 解析表达式中的数组字面量 [a, b], 左方括号已被消费
+fn array_literal_eval(
+    parser: &mut Parser,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    token: Token,
+) -> Result<ASTExprTree, ParserError> {
+    let mut elements = vec![];
+    if next_is(tokens, "]") {
+        tokens.next();
+    } else {
+        loop {
+            elements.push(expr_bp(parser, tokens, 0, &token)?);
+            if literal_separator(tokens, &token, ']')? {
+                break;
+            }
+        }
+    }
+    Ok(ASTExprTree::Array { token, elements })
+}
+
+// 解析表达式中的字典字面量 {key: value}, 左花括号已被消费
+fn map_literal_eval(
+    parser: &mut Parser,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    token: Token,
+) -> Result<ASTExprTree, ParserError> {
+    let mut entries = vec![];
+    if next_is(tokens, "}") {
+        tokens.next();
+    } else {
+        loop {
+            let key = expr_bp(parser, tokens, 0, &token)?;
+            let Some(tk) = tokens.next() else {
+                return Err(MissingCondition(token));
+            };
+            check_char(&tk, TokenType::Operator, ':')?;
+            let value = expr_bp(parser, tokens, 0, &tk)?;
+            entries.push((key, value));
+            if literal_separator(tokens, &token, '}')? {
+                break;
+            }
+        }
+    }
+    Ok(ASTExprTree::Map { token, entries })
+}
+
+// 解析匿名函数 function (a, b) { ... }, function 关键字已被消费
+fn lambda_eval(
+    parser: &mut Parser,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    token: Token,
+) -> Result<ASTExprTree, ParserError> {
+    let mut args: Vec<ASTExprTree> = vec![];
+    if next_is(tokens, "(") {
+        tokens.next();
+        let mut is_split = false;
+        loop {
+            let Some(tk) = tokens.next() else {
+                return Err(MissingFunctionBody(token));
+            };
+            match tk.t_type {
+                TokenType::LR if tk.text() == ")" => break,
+                TokenType::Identifier if !is_split => {
+                    args.push(Var(tk));
+                    is_split = true;
+                }
+                TokenType::Identifier => return Err(Expected(tk, ',')),
+                TokenType::Operator if tk.text() == "," && is_split => is_split = false,
+                TokenType::Operator => return Err(IdentifierExpected(tk)),
+                _ => return Err(IllegalArgument(tk)),
+            }
+        }
+    }
+
+    // 收集花括号内的函数体, 交由语句块解析器重放
+    let Some(tk) = tokens.next() else {
+        return Err(MissingFunctionBody(token));
+    };
+    check_char(&tk, LP, '{')?;
+    let mut body_tokens = vec![tk];
+    let mut brace_count: usize = 0;
+    loop {
+        let Some(tk) = tokens.next() else {
+            return Err(MissingFunctionBody(token));
+        };
+        if tk.t_type == LP && tk.text() == "{" {
+            brace_count += 1;
+        }
+        let end = tk.t_type == TokenType::LR && tk.text() == "}";
+        body_tokens.push(tk);
+        if end {
+            if brace_count == 0 {
+                break;
+            }
+            brace_count -= 1;
+        }
+    }
+    let body = parser.replay_block(body_tokens).map_err(|e| match e {
+        ParserError::Eof => MissingFunctionBody(token.clone()),
+        _ => e,
+    })?;
+    Ok(ASTExprTree::Lambda { token, args, body })
+}
+
 macro_rules! match_opcode {
     ($token:expr) => {
         match $token.text() {
@@ -136,13 +262,44 @@ macro_rules! check_operand {
     };
 }
 
+fn next_is(tokens: &mut Peekable<IntoIter<Token>>, text: &str) -> bool {
+    tokens.peek().is_some_and(|token| token.text() == text)
+}
+
+// 解析切片 arr[start:end] 冒号之后的部分
+fn slice_eval(
+    parser: &mut Parser,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    token: Token,
+    target: ASTExprTree,
+    start: Option<ASTExprTree>,
+) -> Result<ASTExprTree, ParserError> {
+    let end = if next_is(tokens, "]") {
+        None
+    } else {
+        Some(expr_bp(parser, tokens, 0, &token)?)
+    };
+    let Some(tk) = tokens.next() else {
+        return Err(MissingCondition(token));
+    };
+    check_char(&tk, TokenType::LR, ']')?;
+    Ok(ASTExprTree::Slice {
+        token,
+        target: Box::new(target),
+        start: start.map(Box::new),
+        end: end.map(Box::new),
+    })
+}
+
+// prev 为要求该操作数的前一个记号, 操作数缺失时以它报告错误位置
 fn expr_bp(
     parser: &mut Parser,
     tokens: &mut Peekable<IntoIter<Token>>,
     min_bp: u8,
+    prev: &Token,
 ) -> Result<ASTExprTree, ParserError> {
     let Some(mut token) = tokens.next() else {
-        return Err(IllegalExpression(parser.last.take().unwrap()));
+        return Err(IllegalExpression(prev.clone()));
     };
 
     let mut expr_tree: ASTExprTree = build_head_ast_tree(parser, tokens, token)?;
@@ -163,20 +320,27 @@ fn expr_bp(
             tokens.next();
             if token.t_type == LP {
                 if matches!(check_char(&token, LP, '['), Ok(())) {
-                    let rhs = expr_bp(parser, tokens, 0)?;
-                    match tokens.next() {
-                        Some(tk) => {
-                            check_char(&tk, TokenType::LR, ']')?;
-                            expr_tree = Expr {
-                                token,
-                                op: ExprOp::AIndex,
-                                left: Box::new(expr_tree),
-                                right: Box::new(rhs),
-                            };
-                        }
-                        None => {
-                            return Err(MissingCondition(token));
-                        }
+                    let rhs = if next_is(tokens, ":") {
+                        None
+                    } else {
+                        Some(expr_bp(parser, tokens, 0, &token)?)
+                    };
+                    let Some(tk) = tokens.next() else {
+                        return Err(MissingCondition(token));
+                    };
+                    if tk.t_type == TokenType::Operator && tk.text() == ":" {
+                        expr_tree = slice_eval(parser, tokens, token, expr_tree, rhs)?;
+                    } else {
+                        check_char(&tk, TokenType::LR, ']')?;
+                        let Some(rhs) = rhs else {
+                            return Err(IllegalExpression(tk));
+                        };
+                        expr_tree = Expr {
+                            token,
+                            op: ExprOp::AIndex,
+                            left: Box::new(expr_tree),
+                            right: Box::new(rhs),
+                        };
                     }
                 } else {
                     check_char(&token, LP, '(')?;
@@ -193,15 +357,15 @@ fn expr_bp(
                             {
                                 break;
                             }
-                            if token.t_type == LP && token.text() == "(" {
+                            if token.t_type == LP {
                                 p_count += 1;
                             }
-                            if token.t_type == TokenType::LR && token.text() == ")" {
-                                if p_count == 0 {
+                            if token.t_type == TokenType::LR {
+                                if p_count == 0 && token.text() == ")" {
                                     done = true;
                                     break;
                                 }
-                                p_count -= 1;
+                                p_count = p_count.saturating_sub(1);
                             }
                             sub_tokens.push(token.clone());
                             token = tokens.next().ok_or(MissingCondition(token))?;
@@ -239,7 +403,7 @@ fn expr_bp(
                 break;
             }
             tokens.next();
-            let rhs = expr_bp(parser, tokens, r_bp)?;
+            let rhs = expr_bp(parser, tokens, r_bp, &token)?;
             let op = match_opcode!(token);
             expr_tree = Expr {
                 token,
@@ -259,9 +423,9 @@ pub fn expr_eval(
     parser: &mut Parser,
     tokens: Vec<Token>,
 ) -> Result<Option<ASTExprTree>, ParserError> {
-    if tokens.is_empty() {
+    let Some(first) = tokens.first().cloned() else {
         return Ok(None);
-    }
+    };
     let mut into_tokens = tokens.into_iter().peekable();
-    Ok(Some(expr_bp(parser, &mut into_tokens, 0)?))
+    Ok(Some(expr_bp(parser, &mut into_tokens, 0, &first)?))
 }
@@ -1,18 +1,39 @@
 use crate::compiler::Compiler;
-use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
-use crate::compiler::lexer::TokenType;
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree, TypeHints};
 use crate::compiler::lexer::TokenType::{LP, LR};
+use crate::compiler::lexer::{Token, TokenType};
 use crate::compiler::lints::Lint::FuncNoArg;
 use crate::compiler::parser::ParserError::{Expected, IdentifierExpected, IllegalArgument};
 use crate::compiler::parser::block::blk_eval;
-use crate::compiler::parser::{Parser, ParserError, check_char};
+use crate::compiler::parser::{Parser, ParserError, check_char, type_eval};
 use smol_str::format_smolstr;
 
-fn parser_argument(parser: &mut Parser) -> Result<Vec<ASTExprTree>, ParserError> {
+// 解析 native 关键字后的 ("库", "符号"), 左括号已被消费
+fn foreign_eval(parser: &mut Parser) -> Result<(Token, Token), ParserError> {
+    let library = parser.next_parser_token()?;
+    if library.t_type != TokenType::LiteralString {
+        return Err(Expected(library, '"'));
+    }
+    let token = parser.next_parser_token()?;
+    check_char(&token, TokenType::Operator, ',')?;
+    let symbol = parser.next_parser_token()?;
+    if symbol.t_type != TokenType::LiteralString {
+        return Err(Expected(symbol, '"'));
+    }
+    let token = parser.next_parser_token()?;
+    check_char(&token, LR, ')')?;
+    Ok((library, symbol))
+}
+
+// 解析形参列表, 同时返回各形参的类型标注
+fn parser_argument(
+    parser: &mut Parser,
+) -> Result<(Vec<ASTExprTree>, Vec<Option<Token>>), ParserError> {
     let mut token = parser.next_parser_token()?;
     check_char(&token, LP, '(')?;
     let mut is_split = false;
     let mut arguments: Vec<ASTExprTree> = Vec::new();
+    let mut types: Vec<Option<Token>> = Vec::new();
     loop {
         token = parser.next_parser_token()?;
         match check_char(&token, LR, ')') {
@@ -23,8 +44,16 @@ fn parser_argument(parser: &mut Parser) -> Result<Vec<ASTExprTree>, ParserError>
                         return Err(Expected(token, ','));
                     }
                     arguments.push(ASTExprTree::Var(token));
+                    types.push(None);
                     is_split = true;
                 }
+                TokenType::Operator if token.text() == ":" => {
+                    let Some(type_) = types.last_mut().filter(|type_| is_split && type_.is_none())
+                    else {
+                        return Err(IdentifierExpected(token));
+                    };
+                    *type_ = Some(type_eval(parser)?);
+                }
                 TokenType::Operator => {
                     if !is_split {
                         return Err(IdentifierExpected(token));
@@ -38,7 +67,7 @@ fn parser_argument(parser: &mut Parser) -> Result<Vec<ASTExprTree>, ParserError>
         }
     }
     parser.last = Some(token);
-    Ok(arguments)
+    Ok((arguments, types))
 }
 
 pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
@@ -47,6 +76,7 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
     let name;
     let is_native;
     let is_sync;
+    let mut foreign = None;
     match token.t_type {
         TokenType::Identifier => {
             name = token;
@@ -55,6 +85,10 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
         }
         TokenType::Native => {
             token = parser.next_parser_token()?;
+            if token.t_type == LP && token.text() == "(" {
+                foreign = Some(foreign_eval(parser)?);
+                token = parser.next_parser_token()?;
+            }
             if token.t_type != TokenType::Identifier {
                 return Err(IdentifierExpected(token));
             }
@@ -79,6 +113,7 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
     token = parser.next_parser_token()?;
 
     let args: Vec<ASTExprTree>;
+    let mut hints = TypeHints::default();
     match token.t_type {
         LP => {
             if token.text() == "{" {
@@ -87,7 +122,7 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
             } else {
                 let pt = token.clone();
                 parser.cache = Some(token);
-                args = parser_argument(parser)?;
+                (args, hints.args) = parser_argument(parser)?;
                 if args.is_empty() && !is_native {
                     Compiler::warning_info_expr(
                         parser.file,
@@ -115,13 +150,25 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
         }
     }
 
-    let result = parser.next_parser_token();
+    let mut result = parser.next_parser_token();
+    if let Ok(token) = &result
+        && token.t_type == TokenType::Operator
+        && token.text() == ":"
+    {
+        hints.ret = Some(type_eval(parser)?);
+        result = parser.next_parser_token();
+    }
     if matches!(result, Err(ParserError::Eof)) {
         return Err(ParserError::MissingFunctionBody(parser.get_last().unwrap()));
     }
     token = result?;
     if token.t_type == TokenType::End && is_native {
-        Ok(ASTStmtTree::NativeFunction { name, args })
+        Ok(ASTStmtTree::NativeFunction {
+            name,
+            args,
+            hints,
+            foreign,
+        })
     } else {
         parser.cache = Some(token);
         let body = blk_eval(parser)?;
@@ -129,6 +176,7 @@ pub fn func_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
             name,
             sync: is_sync,
             args,
+            hints,
             body,
         })
     }
@@ -2,13 +2,18 @@ use crate::compiler::ast::ASTStmtTree;
 use crate::compiler::lexer::TokenType::{End, From, Identifier, LiteralString};
 use crate::compiler::parser::{Parser, ParserError};
 use smol_str::ToSmolStr;
+use std::path::Path;
 
 pub fn import_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
     let mut token = parser.next_parser_token()?;
 
+    // 以路径导入时导入名为文件名去掉扩展名的部分, 如 "net/http.exf" 导入为 http
     if token.t_type == LiteralString {
-        let name = token.text().to_smolstr();
-        return Ok(ASTStmtTree::Import(token, name.clone(), name));
+        let path = token.text().to_smolstr();
+        let name = Path::new(path.as_str())
+            .file_stem()
+            .map_or_else(|| path.clone(), |stem| stem.to_string_lossy().to_smolstr());
+        return Ok(ASTStmtTree::Import(token, name, path));
     }
 
     if token.t_type != Identifier {
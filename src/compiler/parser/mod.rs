@@ -1,4 +1,5 @@
 mod block;
+mod class;
 mod expression;
 mod r#for;
 mod function;
@@ -6,6 +7,7 @@ mod import;
 mod judgment;
 mod r#return;
 pub mod symbol_table;
+mod r#try;
 mod var;
 mod r#while;
 
@@ -15,24 +17,28 @@ mod tests;
 use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
 use crate::compiler::file::SourceFile;
 use crate::compiler::lexer::TokenType::LP;
-use crate::compiler::lexer::{LexerError, Token, TokenType};
+use crate::compiler::lexer::{LexerError, SourcePos, Token, TokenType};
+use crate::compiler::parser::block::blk_eval;
+use crate::compiler::parser::class::class_eval;
 use crate::compiler::parser::expression::expr_eval;
 use crate::compiler::parser::r#for::for_eval;
 use crate::compiler::parser::function::func_eval;
 use crate::compiler::parser::import::import_eval;
 use crate::compiler::parser::judgment::if_eval;
+use crate::compiler::parser::r#try::{throw_eval, try_eval};
 use crate::compiler::parser::var::var_eval;
 use crate::compiler::parser::r#while::while_eval;
 use crate::runtime::RuntimeError;
+use smol_str::SmolStr;
 
 #[derive(Debug)]
 pub enum ParserError {
     NotAStatement(Token),          // 不是一个语句
-    LexError(LexerError),          // 词法分析错误
     IdentifierExpected(Token),     // 需要标识符
     Expected(Token, char),         // 需要指定字符
     MissingFunctionBody(Token),    // 缺少函数体
     MissingLoopBody(Token),        // 缺少循环体
+    MissingCatchBlock(Token),      // try 语句缺少 catch 块
     MissingStatement(Token),       // 语句定义不完整
     MissingCondition(Token),       // 缺少条件表达式
     IllegalArgument(Token),        // 非法参数组合
@@ -44,14 +50,31 @@ pub enum ParserError {
     UnableResolveSymbols(Token),   // 无法解析符号
     NoNativeImplement(Token),      // 无本地实现
     NotFoundLibrary(Token),        // 找不到库
-    Empty,                         // 占位符
-    RuntimeError(RuntimeError),    // 运行时占位符
+    // 词法分析错误 (出错位置)
+    LexError(LexerError, SourcePos),
+    // 循环导入 (导入链上的模块名)
+    ImportCycle(Token, Vec<SmolStr>),
+    // 类型与标注不符 (标注类型) (实际类型)
+    TypeMismatch(Token, SmolStr, SmolStr),
+    // 无法绑定动态库函数 (错误信息)
+    ForeignFunction(Token, SmolStr),
+    // 导入名已绑定到其他模块 (导入名) (导入路径)
+    DuplicateImport(Token, SmolStr, SmolStr),
+    // 被导入的模块中出现了不会被执行的顶层语句
+    LibraryStatement(Token),
+    // 实参个数与形参不符 (形参个数) (实参个数)
+    ArgumentCount(Token, usize, usize),
+    Empty,                      // 占位符
+    RuntimeError(RuntimeError), // 运行时占位符
     Eof,
 }
 
 pub struct Parser<'a> {
     cache: Option<Token>,
     last: Option<Token>,
+    replay: Option<Vec<Token>>, // 重放的 token 序列 (逆序), 用于解析匿名函数体
+    prev: Option<Token>,        // 最近读取的 token, 用于出错后确定重新同步的位置
+    errors: Vec<ParserError>,   // 已恢复的语法错误
     file: &'a mut SourceFile,
 }
 
@@ -62,22 +85,160 @@ pub fn check_char(token: &Token, type_: TokenType, c: char) -> Result<(), Parser
     Ok(())
 }
 
+fn is_block_start(token: &Token) -> bool {
+    token.t_type == LP && token.text() == "{"
+}
+
+fn is_block_end(token: &Token) -> bool {
+    token.t_type == TokenType::LR && token.text() == "}"
+}
+
+// 可以开始一个语句的关键字, 出错后在此处重新开始解析
+const fn is_stmt_keyword(token: &Token) -> bool {
+    matches!(
+        token.t_type,
+        TokenType::Function
+            | TokenType::Class
+            | TokenType::Import
+            | TokenType::If
+            | TokenType::Var
+            | TokenType::While
+            | TokenType::For
+            | TokenType::Try
+            | TokenType::Throw
+            | TokenType::Return
+            | TokenType::Break
+            | TokenType::Continue
+    )
+}
+
+// 解析类型标注中的类型名, 冒号已被消费
+pub fn type_eval(parser: &mut Parser) -> Result<Token, ParserError> {
+    let token = parser.next_parser_token()?;
+    match token.t_type {
+        TokenType::Identifier | TokenType::Function => Ok(token),
+        _ => Err(ParserError::IdentifierExpected(token)),
+    }
+}
+
 impl<'a> Parser<'a> {
     pub const fn new(file: &'a mut SourceFile) -> Self {
         Parser {
             cache: None,
             last: None,
+            replay: None,
+            prev: None,
+            errors: Vec::new(),
             file,
         }
     }
 
     fn next_parser_token(&mut self) -> Result<Token, ParserError> {
-        match self.cache.take() {
-            None => self.file.peek_token(),
-            Some(token) => Ok(token),
+        let token = match self.cache.take() {
+            Some(token) => token,
+            None => match self.replay.as_mut() {
+                Some(replay) => replay.pop().ok_or(ParserError::Eof)?,
+                None => self.file.peek_token()?,
+            },
+        };
+        self.prev = Some(token.clone());
+        Ok(token)
+    }
+
+    // 记录错误并跳过出错语句的剩余部分, 在 `;` `}` 或语句关键字处重新同步
+    // `}` 与关键字留给后续解析, start 为出错语句首个 token 的位置, 该 token 不会被重新解析
+    // 遇到 `{` 时出错的是块的头部 (如循环条件或形参列表), 整个块随之跳过
+    fn recover(&mut self, error: ParserError, start: usize) {
+        self.errors.push(error);
+        let mut token = match (self.cache.take(), self.prev.take()) {
+            (Some(token), _) => token,
+            (None, Some(prev)) if prev.t_type == TokenType::End || is_block_end(&prev) => return,
+            (None, Some(prev)) if is_block_start(&prev) => {
+                self.skip_block();
+                return;
+            }
+            (None, Some(prev)) if is_stmt_keyword(&prev) && prev.index != start => {
+                self.cache = Some(prev);
+                return;
+            }
+            (None, _) => match self.skip_token() {
+                Some(token) => token,
+                None => return,
+            },
+        };
+        loop {
+            if token.t_type == TokenType::End {
+                return;
+            }
+            if is_block_start(&token) {
+                self.skip_block();
+                return;
+            }
+            if is_block_end(&token) || (is_stmt_keyword(&token) && token.index != start) {
+                self.cache = Some(token);
+                return;
+            }
+            token = match self.skip_token() {
+                Some(token) => token,
+                None => return,
+            };
+        }
+    }
+
+    // 跳过 `{` 之后直到与之匹配的 `}` 为止的 token
+    fn skip_block(&mut self) {
+        let mut depth: usize = 1;
+        while let Some(token) = self.skip_token() {
+            if is_block_start(&token) {
+                depth += 1;
+            } else if is_block_end(&token) {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
         }
     }
 
+    // 重新同步时读取下一个 token, 期间的词法错误同样被记录
+    fn skip_token(&mut self) -> Option<Token> {
+        loop {
+            match self.next_parser_token() {
+                Ok(token) => return Some(token),
+                Err(error @ ParserError::LexError(..)) => self.errors.push(error),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    // 读取到语句结束符为止, 花括号内 (匿名函数体) 的结束符不会结束当前语句
+    fn stmt_tokens(&mut self, mut tokens: Vec<Token>) -> Result<Vec<Token>, ParserError> {
+        let mut brace_count: usize = 0;
+        loop {
+            let token = self.next_parser_token()?;
+            match token.t_type {
+                TokenType::End if brace_count == 0 => break,
+                LP if token.text() == "{" => brace_count += 1,
+                TokenType::LR if token.text() == "}" => {
+                    brace_count = brace_count.saturating_sub(1);
+                }
+                _ => {}
+            }
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    // 以给定的 token 序列代替词法分析器的输出解析一个语句块
+    fn replay_block(&mut self, tokens: Vec<Token>) -> Result<Vec<ASTStmtTree>, ParserError> {
+        let cache = self.cache.take();
+        let replay = self.replay.replace(tokens.into_iter().rev().collect());
+        let result = blk_eval(self);
+        self.cache = cache;
+        self.replay = replay;
+        result
+    }
+
     // 解析 () 括号内的表达式 - 需要括号
     pub fn parser_cond(&mut self, last_token: Option<Token>) -> Result<ASTExprTree, ParserError> {
         let mut token;
@@ -132,6 +293,13 @@ impl<'a> Parser<'a> {
                     _ => e,
                 })?)
             }
+            TokenType::Class => {
+                let saved_token = root_token;
+                Ok(class_eval(self).map_err(|e| match e {
+                    ParserError::Eof => ParserError::MissingStatement(saved_token),
+                    _ => e,
+                })?)
+            }
             TokenType::Import => {
                 let saved_token = root_token;
                 Ok(import_eval(self).map_err(|e| match e {
@@ -167,18 +335,24 @@ impl<'a> Parser<'a> {
                     _ => e,
                 })?)
             }
+            TokenType::Try => {
+                let saved_token = root_token.clone();
+                Ok(try_eval(self, root_token).map_err(|e| match e {
+                    ParserError::Eof => ParserError::MissingStatement(saved_token),
+                    _ => e,
+                })?)
+            }
+            TokenType::Throw => {
+                let saved_token = root_token.clone();
+                Ok(throw_eval(self, root_token).map_err(|e| match e {
+                    ParserError::Eof => ParserError::MissingStatement(saved_token),
+                    _ => e,
+                })?)
+            }
             TokenType::End => Ok(ASTStmtTree::Empty),
             TokenType::Continue | TokenType::Break => Err(ParserError::BackOutsideLoop(root_token)),
             _ => {
-                let mut token;
-                let mut tokens: Vec<Token> = vec![root_token];
-                loop {
-                    token = self.next_parser_token()?;
-                    if token.t_type == TokenType::End {
-                        break;
-                    }
-                    tokens.push(token);
-                }
+                let tokens = self.stmt_tokens(vec![root_token])?;
                 expr_eval(self, tokens)?
                     .map_or(Ok(ASTStmtTree::Empty), |expr| Ok(ASTStmtTree::Expr(expr)))
             }
@@ -189,9 +363,22 @@ impl<'a> Parser<'a> {
         self.last.clone()
     }
 
-    pub fn parser(mut self) -> Result<ASTStmtTree, ParserError> {
+    /// 解析整个文件, 出错的语句被跳过并继续解析之后的语句
+    /// # Errors
+    /// 返回按出现顺序排列的全部语法错误
+    pub fn parser(mut self) -> Result<ASTStmtTree, Vec<ParserError>> {
         let mut root_tree: Vec<ASTStmtTree> = vec![];
         loop {
+            let token = match self.next_parser_token() {
+                Ok(token) => token,
+                Err(ParserError::Eof) => break,
+                Err(error) => {
+                    self.errors.push(error);
+                    continue;
+                }
+            };
+            let start = token.index;
+            self.cache = Some(token);
             match self.parse_step() {
                 Ok(node) => {
                     if !matches!(node, ASTStmtTree::Empty) {
@@ -199,9 +386,19 @@ impl<'a> Parser<'a> {
                     }
                 }
                 Err(ParserError::Eof) => break,
-                Err(e) => return Err(e),
+                Err(error) => {
+                    self.recover(error, start);
+                    // 顶层没有可以结束的语句块, 直接丢弃同步到的 `}`
+                    if self.cache.as_ref().is_some_and(is_block_end) {
+                        self.cache = None;
+                    }
+                }
             }
         }
-        Ok(ASTStmtTree::Root(root_tree))
+        if self.errors.is_empty() {
+            Ok(ASTStmtTree::Root(root_tree))
+        } else {
+            Err(self.errors)
+        }
     }
 }
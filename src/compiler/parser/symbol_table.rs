@@ -1,11 +1,16 @@
 use smol_str::SmolStr;
 use std::cmp::PartialEq;
 
+use crate::compiler::diagnostic::Span;
+use crate::compiler::lexer::Token;
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum ContextType {
     Loop,
     Func,
     Root,
+    Try,   // try 块, 块内注册了异常处理器
+    Catch, // catch 块
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -16,6 +21,7 @@ pub enum ElementType {
     Function(usize),  // usize: 形参个数
     Value,
     Func,
+    Class, // 类名, 调用时创建实例
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +29,22 @@ pub enum ElementType {
 pub struct Element {
     name: SmolStr,
     el_type: ElementType,
+    definition: Option<usize>, // 对应的定义在 definitions 中的索引
+}
+
+/// 符号的定义位置, 退出上下文后仍然保留, 供编辑器跳转与悬停提示使用
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: SmolStr,
+    pub el_type: ElementType,
+    pub span: Span,
+}
+
+/// 源码中对某个定义的一次引用
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub span: Span,
+    pub definition: usize, // 定义在 definitions 中的索引
 }
 
 #[derive(Debug, Clone)]
@@ -36,11 +58,17 @@ pub struct Context {
 #[derive(Debug, Clone)]
 pub struct SymbolTable {
     contexts: Vec<Context>,
+    definitions: Vec<Definition>,
+    references: Vec<Reference>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
-        let mut table = Self { contexts: vec![] };
+        let mut table = Self {
+            contexts: vec![],
+            definitions: vec![],
+            references: vec![],
+        };
         table.contexts.push(Context {
             elements: vec![],
             ctxt_type: ContextType::Root,
@@ -89,7 +117,97 @@ impl SymbolTable {
     // 添加一个符号到顶层上下文
     pub fn add_element(&mut self, name: SmolStr, el_type: ElementType) {
         let peek_context = self.contexts.last_mut().unwrap();
-        peek_context.elements.push(Element { name, el_type });
+        peek_context.elements.push(Element {
+            name,
+            el_type,
+            definition: None,
+        });
+    }
+
+    // 记录一个定义的位置, 返回其索引, 函数与类的定义不会加入上下文
+    pub fn add_definition(&mut self, name: SmolStr, token: &Token, el_type: ElementType) -> usize {
+        self.definitions.push(Definition {
+            name,
+            el_type,
+            span: Span::from_token(token),
+        });
+        self.definitions.len() - 1
+    }
+
+    // 添加一个符号到顶层上下文并记录其定义位置
+    pub fn add_symbol(&mut self, name: SmolStr, token: &Token, el_type: ElementType) {
+        let definition = self.add_definition(name.clone(), token, el_type.clone());
+        let peek_context = self.contexts.last_mut().unwrap();
+        peek_context.elements.push(Element {
+            name,
+            el_type,
+            definition: Some(definition),
+        });
+    }
+
+    // 记录一次引用, 内层上下文的符号优先, 其次为顶层的函数与类
+    pub fn add_reference(&mut self, token: &Token) {
+        let name = token.text();
+        let definition = self
+            .contexts
+            .iter()
+            .rev()
+            .flat_map(|context| context.elements.iter().rev())
+            .filter(|el| el.name.as_str() == name)
+            .find_map(|el| el.definition)
+            .or_else(|| {
+                self.definitions.iter().rposition(|definition| {
+                    definition.name.as_str() == name
+                        && matches!(
+                            definition.el_type,
+                            ElementType::Function(_) | ElementType::Class
+                        )
+                })
+            });
+        if let Some(definition) = definition {
+            self.references.push(Reference {
+                span: Span::from_token(token),
+                definition,
+            });
+        }
+    }
+
+    /// 按出现顺序排列的全部定义
+    #[must_use]
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    /// 按分析顺序排列的全部引用
+    #[must_use]
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// 查找覆盖指定位置 (行号与列号从 1 开始) 的定义或引用所指向的定义
+    #[must_use]
+    pub fn definition_at(&self, line: usize, column: usize) -> Option<&Definition> {
+        let contains =
+            |span: &Span| span.line == line && span.column <= column && column < span.end_column;
+        self.definitions
+            .iter()
+            .find(|definition| contains(&definition.span))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|reference| contains(&reference.span))
+                    .map(|reference| &self.definitions[reference.definition])
+            })
+    }
+
+    // 统计最近的 until 类型上下文之内嵌套的 target 类型上下文数量
+    pub fn count_contexts(&self, target: &ContextType, until: &ContextType) -> usize {
+        self.contexts
+            .iter()
+            .rev()
+            .take_while(|c| c.ctxt_type != *until)
+            .filter(|c| c.ctxt_type == *target)
+            .count()
     }
 
     pub fn get_context(&mut self, target_type: &ContextType) -> Option<&mut Context> {
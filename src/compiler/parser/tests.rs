@@ -2,7 +2,7 @@ use expect_test::{Expect, expect};
 
 use crate::compiler::{file::SourceFile, parser::Parser};
 
-fn parse_source(src: &str) -> Result<crate::compiler::ast::ASTStmtTree, super::ParserError> {
+fn parse_source(src: &str) -> Result<crate::compiler::ast::ASTStmtTree, Vec<super::ParserError>> {
     let mut file = SourceFile::new("<test_input>".into(), src.into(), Default::default(), false);
     let parser = Parser::new(&mut file);
     parser.parser()
@@ -110,6 +110,7 @@ num + 34;",
                         index: 6,
                         data: "num",
                     },
+                    annotation: None,
                     value: Some(
                         Literal(
                             Token {
@@ -130,6 +131,7 @@ num + 34;",
                         index: 23,
                         data: "num1",
                     },
+                    annotation: None,
                     value: Some(
                         Literal(
                             Token {
@@ -150,6 +152,7 @@ num + 34;",
                         index: 42,
                         data: "num2",
                     },
+                    annotation: None,
                     value: Some(
                         Literal(
                             Token {
@@ -170,6 +173,7 @@ num + 34;",
                         index: 58,
                         data: "num3",
                     },
+                    annotation: None,
                     value: Some(
                         Literal(
                             Token {
@@ -217,3 +221,247 @@ num + 34;",
     "#]],
     );
 }
+
+#[test]
+fn try_catch() {
+    check(
+        "try { throw 1; } catch (e) { }",
+        expect![[r#"
+            Root(
+                [
+                    Try {
+                        token: Token {
+                            line: 0,
+                            column: 1,
+                            t_type: Try,
+                            index: 1,
+                            data: "try",
+                        },
+                        body: [
+                            Throw(
+                                Token {
+                                    line: 0,
+                                    column: 7,
+                                    t_type: Throw,
+                                    index: 7,
+                                    data: "throw",
+                                },
+                                Literal(
+                                    Token {
+                                        line: 0,
+                                        column: 13,
+                                        t_type: Number,
+                                        index: 13,
+                                        data: "1",
+                                    },
+                                ),
+                            ),
+                        ],
+                        catch_name: Token {
+                            line: 0,
+                            column: 25,
+                            t_type: Identifier,
+                            index: 25,
+                            data: "e",
+                        },
+                        catch_body: [],
+                    },
+                ],
+            )
+        "#]],
+    );
+}
+
+#[test]
+fn map_literal() {
+    check(
+        r#"var m = {"k": 1};"#,
+        expect![[r#"
+        Root(
+            [
+                Map {
+                    token: Token {
+                        line: 0,
+                        column: 5,
+                        t_type: Identifier,
+                        index: 5,
+                        data: "m",
+                    },
+                    annotation: None,
+                    entries: [
+                        (
+                            Literal(
+                                Token {
+                                    line: 0,
+                                    column: 10,
+                                    t_type: LiteralString,
+                                    index: 10,
+                                    data: "k",
+                                },
+                            ),
+                            Literal(
+                                Token {
+                                    line: 0,
+                                    column: 15,
+                                    t_type: Number,
+                                    index: 15,
+                                    data: "1",
+                                },
+                            ),
+                        ),
+                    ],
+                },
+            ],
+        )
+    "#]],
+    );
+}
+
+#[test]
+fn slice() {
+    check(
+        "a[1:];",
+        expect![[r#"
+        Root(
+            [
+                Expr(
+                    Slice {
+                        token: Token {
+                            line: 0,
+                            column: 2,
+                            t_type: LP,
+                            index: 2,
+                            data: "[",
+                        },
+                        target: Var(
+                            Token {
+                                line: 0,
+                                column: 1,
+                                t_type: Identifier,
+                                index: 1,
+                                data: "a",
+                            },
+                        ),
+                        start: Some(
+                            Literal(
+                                Token {
+                                    line: 0,
+                                    column: 3,
+                                    t_type: Number,
+                                    index: 3,
+                                    data: "1",
+                                },
+                            ),
+                        ),
+                        end: None,
+                    },
+                ),
+            ],
+        )
+    "#]],
+    );
+}
+
+#[test]
+fn recover_after_errors() {
+    let errors =
+        parse_source("var a = ;\nfunction f() { ); var b = 1 +* 2; }\nvar c = 1;\nc +;\n-;")
+            .unwrap_err();
+    expect![[r#"
+        [
+            MissingStatement(
+                Token {
+                    line: 0,
+                    column: 9,
+                    t_type: End,
+                    index: 9,
+                    data: ";",
+                },
+            ),
+            IllegalKey(
+                Token {
+                    line: 1,
+                    column: 16,
+                    t_type: LR,
+                    index: 26,
+                    data: ")",
+                },
+            ),
+            IllegalExpression(
+                Token {
+                    line: 1,
+                    column: 30,
+                    t_type: Operator,
+                    index: 40,
+                    data: "*",
+                },
+            ),
+            IllegalExpression(
+                Token {
+                    line: 3,
+                    column: 3,
+                    t_type: Operator,
+                    index: 60,
+                    data: "+",
+                },
+            ),
+            IllegalExpression(
+                Token {
+                    line: 4,
+                    column: 1,
+                    t_type: Operator,
+                    index: 63,
+                    data: "-",
+                },
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+
+    // 头部出错的循环与函数会跳过其后的整个块, 之后语句中的错误仍能报告
+    let errors = parse_source(
+        "while (a <) { var c = 2; }\nvar b = 1 +;\nfunction f( { return 1; }\nvar d = );",
+    )
+    .unwrap_err();
+    expect![[r#"
+        [
+            IllegalExpression(
+                Token {
+                    line: 0,
+                    column: 10,
+                    t_type: Operator,
+                    index: 10,
+                    data: "<",
+                },
+            ),
+            IllegalExpression(
+                Token {
+                    line: 1,
+                    column: 11,
+                    t_type: Operator,
+                    index: 38,
+                    data: "+",
+                },
+            ),
+            IllegalArgument(
+                Token {
+                    line: 2,
+                    column: 13,
+                    t_type: LP,
+                    index: 53,
+                    data: "{",
+                },
+            ),
+            IllegalKey(
+                Token {
+                    line: 3,
+                    column: 9,
+                    t_type: LR,
+                    index: 75,
+                    data: ")",
+                },
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+}
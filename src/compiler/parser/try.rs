@@ -0,0 +1,51 @@
+use crate::compiler::ast::ASTStmtTree;
+use crate::compiler::lexer::TokenType::{LP, LR};
+use crate::compiler::lexer::{Token, TokenType};
+use crate::compiler::parser::block::blk_eval;
+use crate::compiler::parser::expression::expr_eval;
+use crate::compiler::parser::{Parser, ParserError, check_char};
+
+pub fn try_eval(parser: &mut Parser, head: Token) -> Result<ASTStmtTree, ParserError> {
+    let body = blk_eval(parser)?;
+
+    let token = match parser.next_parser_token() {
+        Ok(token) => token,
+        Err(ParserError::Eof) => return Err(ParserError::MissingCatchBlock(head)),
+        Err(err) => return Err(err),
+    };
+    if token.t_type != TokenType::Catch {
+        return Err(ParserError::MissingCatchBlock(token));
+    }
+
+    let token = parser.next_parser_token()?;
+    check_char(&token, LP, '(')?;
+    let catch_name = parser.next_parser_token()?;
+    if catch_name.t_type != TokenType::Identifier {
+        return Err(ParserError::IdentifierExpected(catch_name));
+    }
+    let token = parser.next_parser_token()?;
+    check_char(&token, LR, ')')?;
+
+    let catch_body = blk_eval(parser)?;
+    Ok(ASTStmtTree::Try {
+        token: head,
+        body,
+        catch_name,
+        catch_body,
+    })
+}
+
+pub fn throw_eval(parser: &mut Parser, head: Token) -> Result<ASTStmtTree, ParserError> {
+    let mut tokens: Vec<Token> = vec![];
+    loop {
+        let token = parser.next_parser_token()?;
+        if token.t_type == TokenType::End {
+            break;
+        }
+        tokens.push(token);
+    }
+    match expr_eval(parser, tokens)? {
+        Some(expr) => Ok(ASTStmtTree::Throw(head, expr)),
+        None => Err(ParserError::MissingStatement(head)),
+    }
+}
@@ -3,7 +3,7 @@ use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
 use crate::compiler::lexer::TokenType::{End, LP, LR, Operator};
 use crate::compiler::lexer::{Token, TokenType};
 use crate::compiler::parser::expression::expr_eval;
-use crate::compiler::parser::{Parser, ParserError, check_char};
+use crate::compiler::parser::{Parser, ParserError, check_char, type_eval};
 
 fn parse_fill_len_expr(parser: &mut Parser) -> Result<ASTExprTree, ParserError> {
     let mut token;
@@ -45,10 +45,81 @@ fn try_parse_fill_count(expr: &ASTExprTree) -> Result<Option<usize>, ParserError
     }
     let count = token.value_number();
     let count =
-        usize::try_from(count).map_err(|_| ParserError::IllegalTypeCombination(token.clone()))?;
+        usize::try_from(&count).map_err(|_| ParserError::IllegalTypeCombination(token.clone()))?;
     Ok(Some(count))
 }
 
+// 解析字典字面量 { key: value, ... }, 左花括号已被消费
+fn map_eval(
+    parser: &mut Parser,
+    var_name: Token,
+    annotation: Option<Token>,
+) -> Result<ASTStmtTree, ParserError> {
+    let mut entries: Vec<(ASTExprTree, ASTExprTree)> = Vec::new();
+    loop {
+        let mut p_count = 0;
+        let mut key_exp: Vec<Token> = Vec::new();
+        let mut token;
+        loop {
+            token = parser.next_parser_token()?;
+            if token.t_type == LR && token.text() == "}" && p_count == 0 && key_exp.is_empty() {
+                return Ok(ASTStmtTree::Map {
+                    token: var_name,
+                    annotation,
+                    entries,
+                });
+            }
+            if token.t_type == Operator && token.text() == ":" && p_count == 0 {
+                break;
+            }
+            if token.t_type == End && p_count == 0 {
+                return Err(ParserError::IllegalExpression(token));
+            }
+            if token.t_type == LP {
+                p_count += 1;
+            }
+            if token.t_type == LR {
+                if p_count == 0 {
+                    return Err(ParserError::IllegalExpression(token));
+                }
+                p_count -= 1;
+            }
+            key_exp.push(token);
+        }
+        let key = expr_eval(parser, key_exp)?.ok_or(ParserError::IllegalExpression(token))?;
+
+        let mut value_exp: Vec<Token> = Vec::new();
+        let done = loop {
+            token = parser.next_parser_token()?;
+            if token.t_type == Operator && token.text() == "," && p_count == 0 {
+                break false;
+            }
+            if token.t_type == End && p_count == 0 {
+                return Err(ParserError::IllegalExpression(token));
+            }
+            if token.t_type == LP {
+                p_count += 1;
+            }
+            if token.t_type == LR {
+                if token.text() == "}" && p_count == 0 {
+                    break true;
+                }
+                p_count -= 1;
+            }
+            value_exp.push(token);
+        };
+        let value = expr_eval(parser, value_exp)?.ok_or(ParserError::IllegalExpression(token))?;
+        entries.push((key, value));
+        if done {
+            return Ok(ASTStmtTree::Map {
+                token: var_name,
+                annotation,
+                entries,
+            });
+        }
+    }
+}
+
 pub fn var_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
     let mut token = parser.next_parser_token()?;
     if token.t_type != TokenType::Identifier {
@@ -56,16 +127,37 @@ pub fn var_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
     }
     let var_name = token;
     token = parser.next_parser_token()?;
+    let mut annotation = None;
+    if token.t_type == Operator && token.text() == ":" {
+        annotation = Some(type_eval(parser)?);
+        token = parser.next_parser_token()?;
+    }
     if token.t_type == End {
         return Ok(Var {
             name: var_name,
+            annotation,
             value: None,
         });
     }
     check_char(&token, Operator, '=')?;
 
     token = parser.next_parser_token()?;
+    if token.t_type == End {
+        // 不读取下一个语句作为初始值, 以便在此处恢复解析
+        return Err(ParserError::MissingStatement(token));
+    }
     if token.t_type == LP && token.text() == "[" {
+        // 空数组 []
+        token = parser.next_parser_token()?;
+        if token.t_type == LR && token.text() == "]" {
+            return Ok(ASTStmtTree::Array {
+                token: var_name,
+                annotation,
+                elements: vec![],
+            });
+        }
+        parser.cache = Some(token);
+
         let mut p_count = 0;
         let mut cone: Vec<ASTExprTree> = Vec::new();
         let mut done = false;
@@ -85,11 +177,13 @@ pub fn var_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
                     if let Some(count) = try_parse_fill_count(&len_expr)? {
                         return Ok(ASTStmtTree::Array {
                             token: var_name,
+                            annotation,
                             elements: vec![fill_expr; count],
                         });
                     }
                     return Ok(ASTStmtTree::ArrayFill {
                         token: var_name,
+                        annotation,
                         value: fill_expr,
                         count: len_expr,
                     });
@@ -125,22 +219,16 @@ pub fn var_eval(parser: &mut Parser) -> Result<ASTStmtTree, ParserError> {
 
         Ok(ASTStmtTree::Array {
             token: var_name,
+            annotation,
             elements: cone,
         })
+    } else if token.t_type == LP && token.text() == "{" {
+        map_eval(parser, var_name, annotation)
     } else {
-        parser.cache = Some(token);
-        let mut cone: Vec<Token> = vec![];
-
-        loop {
-            token = parser.next_parser_token()?;
-            if token.t_type == End {
-                break;
-            }
-            cone.push(token);
-        }
-
+        let cone = parser.stmt_tokens(vec![token])?;
         Ok(Var {
             name: var_name,
+            annotation,
             value: expr_eval(parser, cone)?,
         })
     }
@@ -5,10 +5,16 @@ use crate::compiler::ast::ssa_ir::{LocalMap, OpCode, OpCodeTable, Operand, Value
 use crate::compiler::lints::Lint::UnusedExpression;
 use crate::compiler::parser::ParserError;
 use crate::compiler::semantic::Semantic;
-use crate::compiler::semantic::expression::{check_expr_operand, expr_semantic, lower_expr};
+use crate::compiler::semantic::expression::{
+    check_discarded_result, check_expr_operand, expr_semantic, lower_expr,
+};
+use crate::compiler::semantic::function::check_return;
 use crate::compiler::semantic::judgment::judgment_semantic;
 use crate::compiler::semantic::loop_back::loop_back_semantic;
-use crate::compiler::semantic::var::{array_fill_semantic, array_semantic, var_semantic};
+use crate::compiler::semantic::r#try::{throw_semantic, try_semantic};
+use crate::compiler::semantic::var::{
+    array_fill_semantic, array_semantic, map_semantic, var_semantic,
+};
 use crate::compiler::semantic::r#while::while_semantic;
 
 pub fn block_semantic(
@@ -20,6 +26,8 @@ pub fn block_semantic(
 ) -> Result<OpCodeTable, ParserError> {
     let mut opcodes = OpCodeTable::new();
     for stmt in stmt_tree {
+        let token = stmt.token().cloned();
+        let start = opcodes.next_addr();
         match stmt {
             ASTStmtTree::Root(_) => {
                 unreachable!()
@@ -33,15 +41,32 @@ pub fn block_semantic(
                     locals,
                 )?);
             }
-            ASTStmtTree::Var { name, value } => {
-                let opcode =
-                    var_semantic(semantic, name, value, code, global_values, false, locals)?;
+            ASTStmtTree::Var {
+                name,
+                annotation,
+                value,
+            } => {
+                let opcode = var_semantic(
+                    semantic,
+                    name,
+                    annotation,
+                    value,
+                    code,
+                    global_values,
+                    false,
+                    locals,
+                )?;
                 opcodes.append_code(&opcode);
             }
-            ASTStmtTree::Array { token, elements } => {
+            ASTStmtTree::Array {
+                token,
+                annotation,
+                elements,
+            } => {
                 let ret_m = array_semantic(
                     semantic,
                     token,
+                    annotation,
                     elements,
                     code,
                     global_values,
@@ -50,14 +75,33 @@ pub fn block_semantic(
                 )?;
                 opcodes.append_code(&ret_m);
             }
+            ASTStmtTree::Map {
+                token,
+                annotation,
+                entries,
+            } => {
+                let ret_m = map_semantic(
+                    semantic,
+                    token,
+                    annotation,
+                    entries,
+                    code,
+                    global_values,
+                    locals,
+                    false,
+                )?;
+                opcodes.append_code(&ret_m);
+            }
             ASTStmtTree::ArrayFill {
                 token,
+                annotation,
                 value,
                 count,
             } => {
                 let ret_m = array_fill_semantic(
                     semantic,
                     token,
+                    annotation,
                     value,
                     count,
                     code,
@@ -69,6 +113,7 @@ pub fn block_semantic(
             }
             ASTStmtTree::Expr(expr) => {
                 let ref_expr = expr.clone();
+                check_discarded_result(semantic, &ref_expr);
                 let ret_m = expr_semantic(semantic, Some(expr), code, global_values)?;
                 if !check_expr_operand(&ret_m.0, &OpCode::Store(None), 0) {
                     Compiler::warning_info_expr(
@@ -117,6 +162,7 @@ pub fn block_semantic(
             ASTStmtTree::Return(expr) => {
                 if let Some(expr) = expr {
                     let ref_expr = lower_expr(semantic, &expr, code, global_values, None)?;
+                    check_return(semantic, expr.token(), &ref_expr.1)?;
                     opcodes.append_code(&ref_expr.2);
                     opcodes.add_opcode(OpCode::Return(None));
                 } else {
@@ -133,8 +179,32 @@ pub fn block_semantic(
                     locals,
                 )?);
             }
+            ASTStmtTree::Try {
+                token: _token,
+                body,
+                catch_name,
+                catch_body,
+            } => {
+                let ret_m = try_semantic(
+                    semantic,
+                    body,
+                    catch_name,
+                    catch_body,
+                    code,
+                    global_values,
+                    locals,
+                )?;
+                opcodes.append_code(&ret_m);
+            }
+            ASTStmtTree::Throw(_token, expr) => {
+                let ret_m = throw_semantic(semantic, &expr, code, global_values)?;
+                opcodes.append_code(&ret_m);
+            }
             _ => todo!(),
         }
+        if let Some(token) = token {
+            opcodes.mark_pos_since(start, &token);
+        }
     }
     Ok(opcodes)
 }
@@ -0,0 +1,270 @@
+use crate::compiler::ast::ssa_ir::{
+    Code, Function, LocalMap, OpCode, OpCodeTable, Operand, ValueAlloc, ValueGuessType,
+};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
+use crate::compiler::lexer::Token;
+use crate::compiler::parser::ParserError;
+use crate::compiler::parser::symbol_table::{ContextType, ElementType};
+use crate::compiler::semantic::Semantic;
+use crate::compiler::semantic::block::block_semantic;
+use crate::compiler::semantic::function::{Signature, function_semantic, hints_signature};
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+
+// 初始化方法, 由构造函数在字段初始化完成后调用
+const INIT_METHOD: &str = "init";
+
+// 字段声明的名称 token
+fn field_token(field: &ASTStmtTree) -> &Token {
+    match field {
+        ASTStmtTree::Var { name: token, .. }
+        | ASTStmtTree::Array { token, .. }
+        | ASTStmtTree::ArrayFill { token, .. }
+        | ASTStmtTree::Map { token, .. } => token,
+        _ => unreachable!(),
+    }
+}
+
+// 字段初始值存放在构造函数内名为 "类名.字段名" 的局部变量中, 不会与全局变量冲突
+fn rename_field(field: ASTStmtTree, class_name: &str) -> ASTStmtTree {
+    let rename = |token: Token| token.with_text(format_smolstr!("{class_name}.{}", token.text()));
+    match field {
+        ASTStmtTree::Var {
+            name,
+            annotation,
+            value,
+        } => ASTStmtTree::Var {
+            name: rename(name),
+            annotation,
+            value,
+        },
+        ASTStmtTree::Array {
+            token,
+            annotation,
+            elements,
+        } => ASTStmtTree::Array {
+            token: rename(token),
+            annotation,
+            elements,
+        },
+        ASTStmtTree::ArrayFill {
+            token,
+            annotation,
+            value,
+            count,
+        } => ASTStmtTree::ArrayFill {
+            token: rename(token),
+            annotation,
+            value,
+            count,
+        },
+        ASTStmtTree::Map {
+            token,
+            annotation,
+            entries,
+        } => ASTStmtTree::Map {
+            token: rename(token),
+            annotation,
+            entries,
+        },
+        _ => unreachable!(),
+    }
+}
+
+// 构造函数与类同名, 按声明顺序初始化字段后创建对象, 并将实参转交给 init 方法
+fn constructor_semantic(
+    semantic: &mut Semantic,
+    name: &Token,
+    fields: Vec<ASTStmtTree>,
+    init_args: Option<&[ASTExprTree]>,
+    code: &mut Code,
+    global_value: &ValueAlloc,
+) -> Result<(), ParserError> {
+    let class_name = name.text().to_smolstr();
+    let file_base = semantic.file.module_name().to_smolstr();
+    let class_path = format_smolstr!("{file_base}/{class_name}");
+
+    let mut field_names: Vec<SmolStr> = vec![];
+    for field in &fields {
+        let token = field_token(field);
+        if field_names.iter().any(|name| name == token.text()) {
+            return Err(ParserError::SymbolDefined(token.clone()));
+        }
+        field_names.push(token.text().to_smolstr());
+    }
+
+    semantic
+        .compiler_data()
+        .symbol_table
+        .add_context(ContextType::Func);
+    let context = semantic
+        .compiler_data()
+        .symbol_table
+        .get_context(&ContextType::Func)
+        .unwrap();
+    context.func_name = class_name.clone();
+
+    let mut tables = OpCodeTable::new();
+    let mut locals = LocalMap::new();
+    let mut value_alloc = ValueAlloc::new();
+    value_alloc.append_ref(global_value);
+
+    // 构造函数的实参此时仍在操作栈上, 字段初始化语句不会改变栈深度
+    let fields = fields
+        .into_iter()
+        .map(|field| rename_field(field, &class_name))
+        .collect();
+    let blk = block_semantic(
+        semantic,
+        fields,
+        &mut value_alloc,
+        Some(global_value),
+        &mut locals,
+    )?;
+    tables.append_code(&blk);
+    for field in &field_names {
+        let key = value_alloc
+            .find_value_key(&format_smolstr!("{class_name}.{field}"))
+            .unwrap();
+        tables.add_opcode(OpCode::Push(None, Operand::ImmStr(field.clone())));
+        tables.add_opcode(OpCode::StoreLocal(None, key, Operand::Val(key)));
+    }
+    tables.add_opcode(OpCode::NewObject(
+        None,
+        class_path.clone(),
+        field_names.len(),
+    ));
+    let object = value_alloc.alloc_value(name.clone(), ValueGuessType::Object(class_name.clone()));
+    locals.add_local(object);
+    tables.add_opcode(OpCode::LoadLocal(None, object, Operand::Val(object)));
+
+    let has_init = init_args.is_some();
+    let init_args = init_args.unwrap_or_default();
+    let mut arg_keys = vec![];
+    for arg in init_args {
+        let key = value_alloc.alloc_value(arg.token().clone(), ValueGuessType::Unknown);
+        locals.add_local(key);
+        tables.add_opcode(OpCode::LoadLocal(None, key, Operand::Val(key)));
+        arg_keys.push(key);
+    }
+    if has_init {
+        tables.add_opcode(OpCode::StoreLocal(None, object, Operand::Val(object)));
+        let argc = arg_keys.len() + 1;
+        for key in arg_keys {
+            tables.add_opcode(OpCode::StoreLocal(None, key, Operand::Val(key)));
+        }
+        let init = format_smolstr!("{class_name}.{INIT_METHOD}");
+        tables.add_opcode(OpCode::Push(
+            None,
+            Operand::Reference(format_smolstr!("{file_base}/{init}")),
+        ));
+        tables.add_opcode(OpCode::Call(None, init, argc));
+        tables.add_opcode(OpCode::Pop(None, 1));
+    }
+    tables.add_opcode(OpCode::StoreLocal(None, object, Operand::Val(object)));
+    tables.add_opcode(OpCode::Return(None));
+    tables.mark_pos(name);
+
+    semantic.compiler_data().symbol_table.exit_context();
+
+    code.add_function(Function {
+        name: class_name,
+        sync: false,
+        args: init_args.len(),
+        codes: Some(tables),
+        locals,
+    });
+    Ok(())
+}
+
+// 登记方法与构造函数的签名, 方法以类实例为接收者, 构造函数的形参与 init 方法相同
+pub fn declare_class(
+    semantic: &mut Semantic,
+    file_base: &str,
+    name: &Token,
+    methods: &[ASTStmtTree],
+) -> Result<(), ParserError> {
+    let object = ValueGuessType::Object(name.text().to_smolstr());
+    let mut constructor = Signature {
+        args: vec![],
+        ret: object.clone(),
+    };
+    for method in methods {
+        let ASTStmtTree::Function {
+            name: method_name,
+            hints,
+            ..
+        } = method
+        else {
+            unreachable!()
+        };
+        let signature = hints_signature(semantic, hints, Some(object.clone()))?;
+        if method_name.text() == INIT_METHOD {
+            constructor.args = signature.args[1..].to_vec();
+        }
+        semantic.compiler.signatures.insert(
+            format_smolstr!("{file_base}/{}.{}", name.text(), method_name.text()),
+            signature,
+        );
+    }
+    semantic
+        .compiler
+        .signatures
+        .insert(format_smolstr!("{file_base}/{}", name.text()), constructor);
+    Ok(())
+}
+
+// 类编译为同名的构造函数与 "类名.方法名" 形式的方法函数
+// 方法的第一个形参为隐式的接收者 self
+pub fn class_semantic(
+    semantic: &mut Semantic,
+    name: Token,
+    fields: Vec<ASTStmtTree>,
+    methods: Vec<ASTStmtTree>,
+    code: &mut Code,
+    global_value: &ValueAlloc,
+) -> Result<(), ParserError> {
+    let class_name = name.text().to_smolstr();
+    if code.find_function(&class_name).is_some()
+        || semantic
+            .compiler_data()
+            .symbol_table
+            .check_element(&class_name)
+    {
+        return Err(ParserError::SymbolDefined(name));
+    }
+    semantic
+        .compiler_data()
+        .symbol_table
+        .add_element(class_name.clone(), ElementType::Class);
+
+    let mut init_args: Option<Vec<ASTExprTree>> = None;
+    for method in methods {
+        let ASTStmtTree::Function {
+            name: method_name,
+            sync,
+            mut args,
+            body,
+            ..
+        } = method
+        else {
+            unreachable!()
+        };
+        if method_name.text() == INIT_METHOD {
+            init_args = Some(args.clone());
+        }
+        let receiver = method_name.with_text(SmolStr::new_static("self"));
+        args.insert(0, ASTExprTree::Var(receiver));
+        let func_name =
+            method_name.with_text(format_smolstr!("{class_name}.{}", method_name.text()));
+        function_semantic(semantic, func_name, sync, args, body, code, global_value)?;
+    }
+
+    constructor_semantic(
+        semantic,
+        &name,
+        fields,
+        init_args.as_deref(),
+        code,
+        global_value,
+    )
+}
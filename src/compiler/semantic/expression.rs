@@ -7,11 +7,14 @@ use crate::compiler::ast::ssa_ir::ValueGuessType::{
 use crate::compiler::ast::ssa_ir::{OpCode, OpCodeTable, Operand, ValueAlloc, ValueGuessType};
 use crate::compiler::ast::{ASTExprTree, ExprOp};
 use crate::compiler::lexer::{Token, TokenType};
-use crate::compiler::lints::Lint::SyncRecursion;
+use crate::compiler::lints::Lint::{SyncRecursion, UnusedResult};
+use crate::compiler::optimize::Pass;
 use crate::compiler::parser::ParserError;
 use crate::compiler::parser::symbol_table::{ContextType, ElementType};
 use crate::compiler::semantic::Semantic;
+use crate::compiler::semantic::lambda::lambda_semantic;
 use crate::compiler::semantic::optimizer::{expr_optimizer, unary_optimizer};
+use crate::library::type_lib::array_func_index;
 use slotmap::DefaultKey;
 use smol_str::{SmolStr, ToSmolStr, format_smolstr};
 
@@ -35,6 +38,9 @@ macro_rules! check_bool_expr {
     };
 }
 
+// 调用栈顶函数值时使用的调用名, 不会与任何函数重名
+const CLOSURE_CALL: &str = "<closure>";
+
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum ValueScope {
     Local,
@@ -70,6 +76,105 @@ fn resolve_value_type(
     }
 }
 
+// 将类型标注解析为推断类型, any 表示不限制类型
+pub fn resolve_annotation(
+    semantic: &mut Semantic,
+    token: &Token,
+) -> Result<ValueGuessType, ParserError> {
+    match token.text() {
+        "any" => Ok(Unknown),
+        "bool" => Ok(Bool),
+        "int" | "number" => Ok(Number),
+        "float" => Ok(Float),
+        "string" => Ok(String),
+        "array" => Ok(ValueGuessType::Array),
+        "map" => Ok(ValueGuessType::Map),
+        "function" => Ok(ValueGuessType::Function),
+        name if semantic.classes.iter().any(|class| class == name)
+            || matches!(
+                semantic.compiler_data().symbol_table.get_element_type(name),
+                Some(ElementType::Class)
+            ) =>
+        {
+            Ok(ValueGuessType::Object(name.to_smolstr()))
+        }
+        _ => Err(ParserError::UnableResolveSymbols(token.clone())),
+    }
+}
+
+// 检查推断类型是否满足类型标注, 类型未知或为 null 的值不作限制
+pub fn check_type_hint(
+    token: &Token,
+    expected: &ValueGuessType,
+    found: &ValueGuessType,
+) -> Result<(), ParserError> {
+    let matched = match (expected, found) {
+        (Unknown, _) | (_, Unknown | Null) | (ValueGuessType::Function, Ref) => true,
+        _ => expected == found,
+    };
+    if matched {
+        Ok(())
+    } else {
+        Err(ParserError::TypeMismatch(
+            token.clone(),
+            expected.to_smolstr(),
+            found.to_smolstr(),
+        ))
+    }
+}
+
+// 向带类型标注的变量赋值时检查值的类型
+fn check_store_type(
+    code: &ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    name: &Token,
+    value: &Token,
+    found: &ValueGuessType,
+) -> Result<(), ParserError> {
+    let Some((key, scope)) = resolve_value_key(code, global_values, &name.text().to_smolstr())
+    else {
+        return Ok(());
+    };
+    let target = match scope {
+        ValueScope::Local => code.find_value(key),
+        ValueScope::Global => global_values.and_then(|values| values.find_value(key)),
+    };
+    match target {
+        Some(target) if target.declared => check_type_hint(value, &target.type_, found),
+        _ => Ok(()),
+    }
+}
+
+// 按登记的签名检查实参类型, 返回调用结果的类型, skip 为跳过的形参数量 (方法接收者)
+fn check_call(
+    semantic: &Semantic,
+    callee: &Token,
+    path: &str,
+    args: &[ASTExprTree],
+    arg_types: &[ValueGuessType],
+    skip: usize,
+) -> Result<ValueGuessType, ParserError> {
+    let Some(signature) = semantic.compiler.signatures.get(path) else {
+        return Ok(Unknown);
+    };
+    let expected = signature.args.len() - skip;
+    if args.len() != expected {
+        return Err(ParserError::ArgumentCount(
+            callee.clone(),
+            expected,
+            args.len(),
+        ));
+    }
+    for ((arg, found), expected) in args
+        .iter()
+        .zip(arg_types)
+        .zip(signature.args.iter().skip(skip))
+    {
+        check_type_hint(arg.token(), expected, found)?;
+    }
+    Ok(signature.ret.clone())
+}
+
 fn astop_to_opcode(astop: ExprOp) -> OpCode {
     match astop {
         ExprOp::And => OpCode::And(None),
@@ -168,7 +273,14 @@ fn guess_type(
     }
 
     if matches!(op, ExprOp::AIndex) {
-        return if (*first == ValueGuessType::Array || *first == Unknown) && second == Number {
+        // 数组以整数为索引, 字典以字符串为键
+        let index_ok = match first {
+            ValueGuessType::Array => guess_check_type(&second, &[Number, Unknown]),
+            ValueGuessType::Map => guess_check_type(&second, &[String, Unknown]),
+            Unknown => guess_check_type(&second, &[Number, String, Unknown]),
+            _ => false,
+        };
+        return if index_ok {
             Ok(Unknown)
         } else {
             Err(ParserError::IllegalTypeCombination(token.clone()))
@@ -311,97 +423,131 @@ fn guess_type(
     }
 }
 
-fn lower_ref(
+// 引用左侧为 this 或导入的库时返回其脚本名, 此时右侧为函数名而不是对象成员
+fn module_base(semantic: &mut Semantic, left: &ASTExprTree) -> Option<SmolStr> {
+    match left {
+        ASTExprTree::This(_) => Some(semantic.file.module_name().to_smolstr()),
+        ASTExprTree::Var(name) => {
+            let symbol_table = &mut semantic.compiler_data().symbol_table;
+            let Some(ElementType::Library(lib_name)) = symbol_table.get_element_type(name.text())
+            else {
+                return None;
+            };
+            let lib_name = lib_name.clone();
+            symbol_table.add_reference(name);
+            Some(lib_name)
+        }
+        _ => None,
+    }
+}
+
+// type 库中原地修改数组的函数以数组变量为首个实参调用时, 返回函数名与该变量
+fn array_call_target<'a>(
     semantic: &mut Semantic,
-    expr_tree: &ASTExprTree,
-    code: &mut ValueAlloc,
+    name: &'a ASTExprTree,
+    args: &[ASTExprTree],
+    code: &ValueAlloc,
     global_values: Option<&ValueAlloc>,
-) -> Result<(SmolStr, OpCodeTable), ParserError> {
-    let mut opcode_table = OpCodeTable::new();
-    let file_base = semantic
-        .file
-        .name
-        .split('.')
-        .next()
-        .unwrap_or(semantic.file.name.as_str())
-        .to_smolstr();
-
+) -> Option<(&'a Token, DefaultKey, ValueScope)> {
     let ASTExprTree::Expr {
-        token: _,
-        op: _op,
+        op: ExprOp::Ref,
         left,
         right,
-    } = expr_tree
+        ..
+    } = name
     else {
-        unreachable!()
+        return None;
     };
+    let (ASTExprTree::Var(func), Some(ASTExprTree::Var(target))) = (right.as_ref(), args.first())
+    else {
+        return None;
+    };
+    let symbol_table = &semantic.compiler_data().symbol_table;
+    if array_func_index(func.text()).is_none()
+        || !matches!(
+            symbol_table.get_element_type(target.text()),
+            Some(ElementType::Value | ElementType::Argument)
+        )
+    {
+        return None;
+    }
+    let (key, scope) = resolve_value_key(code, global_values, &target.text().to_smolstr())?;
+    module_base(semantic, left)
+        .is_some_and(|base| base == "type")
+        .then_some((func, key, scope))
+}
 
-    let left_tree = left.as_ref();
-    let right_tree = right.as_ref();
-
-    if let ASTExprTree::Var(token) | ASTExprTree::This(token) = right_tree {
-        let base = match left_tree {
-            ASTExprTree::This(_) => Some(file_base.clone()),
-            ASTExprTree::Var(name) => match semantic
-                .compiler_data()
-                .symbol_table
-                .get_element_type(name.text())
-            {
-                Some(ElementType::Library(lib_name)) => Some(lib_name.clone()),
-                _ => None,
-            },
-            _ => None,
-        };
-
-        if let Some(base) = base {
-            let full_path = format_smolstr!("{base}/{}", token.text());
-
-            if file_base == base
-                && let Some(context) = semantic
-                    .compiler_data()
-                    .symbol_table
-                    .get_context(&ContextType::Func)
-                && context.func_sync
-                && context.func_name.as_str() == token.text()
-            {
-                Compiler::warning_info_expr(
-                    semantic.file,
-                    "unsafe recursion in synchronized function.",
-                    right_tree,
-                    SyncRecursion,
-                );
-            }
+fn lower_ref(
+    semantic: &mut Semantic,
+    base: &SmolStr,
+    right_tree: &ASTExprTree,
+) -> Result<(SmolStr, OpCodeTable), ParserError> {
+    let mut opcode_table = OpCodeTable::new();
+    let ASTExprTree::Var(token) = right_tree else {
+        return Err(ParserError::IllegalExpression(right_tree.token().clone()));
+    };
+    let file_base = semantic.file.module_name().to_smolstr();
+    let full_path = format_smolstr!("{base}/{}", token.text());
 
-            opcode_table.add_opcode(Push(None, Operand::Reference(full_path)));
-            return Ok((token.text().to_smolstr(), opcode_table));
-        }
+    if file_base == *base
+        && let Some(context) = semantic
+            .compiler_data()
+            .symbol_table
+            .get_context(&ContextType::Func)
+        && context.func_sync
+        && context.func_name.as_str() == token.text()
+    {
+        Compiler::warning_info_expr(
+            semantic.file,
+            "unsafe recursion in synchronized function.",
+            right_tree,
+            SyncRecursion,
+        );
     }
 
-    assert!(
-        matches!(left_tree, ASTExprTree::Call { .. })
-            || matches!(left_tree, ASTExprTree::This(_token))
-            || matches!(left_tree, ASTExprTree::Var(_token))
-    );
-
-    let table = lower_expr(semantic, left_tree, code, global_values, None)?;
+    opcode_table.add_opcode(Push(None, Operand::Reference(full_path)));
+    Ok((token.text().to_smolstr(), opcode_table))
+}
 
-    opcode_table.append_code(&table.2);
+// 成员访问的目标只能是对象或类型未知的值
+fn check_object_type(token: &Token, type_: &ValueGuessType) -> Result<(), ParserError> {
+    if matches!(type_, Unknown | ValueGuessType::Object(_)) {
+        Ok(())
+    } else {
+        Err(ParserError::IllegalTypeCombination(token.clone()))
+    }
+}
 
-    let code = match right_tree {
-        ASTExprTree::Var(token) => Push(None, Operand::Reference(token.text().to_smolstr())),
-        ASTExprTree::This(_token) => Push(None, Operand::This),
-        _ => unreachable!(),
+// 字段访问 obj.field, store 不为空时将栈顶的值写入字段
+fn expr_field(
+    semantic: &mut Semantic,
+    token: &Token,
+    target: &ASTExprTree,
+    field: &ASTExprTree,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    store: Option<Operand>,
+) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
+    let ASTExprTree::Var(field) = field else {
+        return Err(ParserError::IllegalExpression(token.clone()));
     };
-    opcode_table.add_opcode(code);
-    opcode_table.add_opcode(OpCode::Ref(None));
-
-    if let ASTExprTree::Expr { right, .. } = expr_tree
-        && let ASTExprTree::Var(token) | ASTExprTree::This(token) = right.as_ref()
-    {
-        Ok((token.text().to_smolstr(), opcode_table))
+    let name = field.text().to_smolstr();
+    let mut opcode_table = OpCodeTable::new();
+    let target = lower_expr(semantic, target, code, global_values, None)?;
+    check_object_type(token, &target.1)?;
+    opcode_table.append_code(&target.2);
+    let opcode = if store.is_some() {
+        OpCode::SetField(None, name.clone())
     } else {
-        Ok((SmolStr::new_static(""), opcode_table))
-    }
+        OpCode::GetField(None, name.clone())
+    };
+    opcode_table.add_opcode(opcode.clone());
+    let operand = Operand::Expression(
+        Box::new(target.0),
+        Box::new(Operand::ImmStr(name)),
+        Box::new(opcode),
+    );
+    Ok((operand, Unknown, opcode_table))
 }
 
 const fn operand_to_guess(operand: &Operand) -> ValueGuessType {
@@ -409,7 +555,7 @@ const fn operand_to_guess(operand: &Operand) -> ValueGuessType {
         Operand::ImmBool(_) => Bool,
         Operand::Null => Null,
         Operand::This => This,
-        Operand::ImmNum(_) => Number,
+        Operand::ImmNum(_) | Operand::ImmBigNum(_) => Number,
         Operand::ImmFlot(_) => Float,
         Operand::ImmStr(_) => String,
         Operand::Reference(_) => Ref,
@@ -423,7 +569,7 @@ fn expr_literal(
 ) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
     match tk_lit.t_type {
         TokenType::Number => {
-            let operand = Operand::ImmNum(tk_lit.value_number());
+            let operand = Operand::from_ibig(tk_lit.value_number());
             opcode_table.add_opcode(Push(None, operand.clone()));
             Ok((operand, Number, opcode_table))
         }
@@ -464,11 +610,20 @@ fn expr_unary(
     let load = lower_expr(semantic, u_code, code, global_values, None)?;
     let store = lower_expr(semantic, u_code, code, global_values, Some(ImmNumFlot))?;
     let g_type = guess_type_unary(u_token, store.1, u_op)?;
-    if let Some(operand) = unary_optimizer(u_op, &store.0) {
-        opcode_table.add_opcode(Push(None, operand));
+    let folded = if semantic.is_optimized(Pass::ConstFold) {
+        unary_optimizer(u_op, &store.0)
     } else {
-        opcode_table.append_code(&load.2);
-        opcode_table.add_opcode(astop_to_opcode(u_op));
+        None
+    };
+    if let Some(operand) = folded {
+        // 外层表达式继续折叠时需要使用折叠后的值而不是原操作数
+        opcode_table.add_opcode(Push(None, operand.clone()));
+        return Ok((operand, g_type, opcode_table));
+    }
+    opcode_table.append_code(&load.2);
+    opcode_table.add_opcode(astop_to_opcode(u_op));
+    // 只有自增自减需要写回操作数, 取负与取反的结果留在栈顶
+    if matches!(u_op, ExprOp::SAdd | ExprOp::SSub) {
         opcode_table.append_code(&store.2);
     }
     Ok((store.0, g_type, opcode_table))
@@ -491,13 +646,17 @@ fn expr_var(
     {
         return Err(ParserError::UnableResolveSymbols(u_token.clone()));
     }
+    semantic.compiler_data().symbol_table.add_reference(u_token);
 
     if !check_urs {
         opcode_table.add_opcode(Push(None, Operand::Reference(var_name.clone())));
         return Ok((Operand::Reference(var_name), Ref, opcode_table));
     }
 
-    let (key, scope) = resolve_value_key(code, global_values, &var_name).unwrap();
+    // 函数名与类名等符号没有对应的变量值
+    let Some((key, scope)) = resolve_value_key(code, global_values, &var_name) else {
+        return Err(ParserError::UnableResolveSymbols(u_token.clone()));
+    };
     match scope {
         ValueScope::Local => {
             let value = code.find_value_mut(key).unwrap();
@@ -517,7 +676,7 @@ fn expr_var(
                 }
                 _ => {
                     if let Some(operand) = store {
-                        if operand != ImmNumFlot {
+                        if operand != ImmNumFlot && !value.declared {
                             value.type_ = operand_to_guess(&operand);
                             type_ = value.type_.clone();
                         }
@@ -575,7 +734,7 @@ fn expr_call(
             None,
             Operand::Reference(SmolStr::new("type/array_length")),
         ));
-        opcode_table.add_opcode(OpCode::Call(None, SmolStr::new("array_length")));
+        opcode_table.add_opcode(OpCode::Call(None, SmolStr::new("array_length"), 1));
         return Ok((
             Operand::Call(SmolStr::new("array_length")),
             Unknown,
@@ -583,20 +742,61 @@ fn expr_call(
         ));
     }
 
+    if let Some((func, key, scope)) = array_call_target(semantic, name, args, code, global_values) {
+        let mut arg_types = vec![];
+        for (i, arg) in args.iter().enumerate() {
+            let expr = lower_expr(semantic, arg, code, global_values, None)?;
+            // 数组变量由指令直接访问, 不压入操作栈
+            if i > 0 {
+                opcode_table.append_code(&expr.2);
+            }
+            arg_types.push(expr.1);
+        }
+        let path = format_smolstr!("type/{}", func.text());
+        let type_ = check_call(semantic, func, &path, args, &arg_types, 0)?;
+        let name = func.text().to_smolstr();
+        opcode_table.add_opcode(match scope {
+            ValueScope::Local => OpCode::ArrayCallLocal(None, key, name.clone(), args.len() - 1),
+            ValueScope::Global => OpCode::ArrayCallGlobal(None, key, name.clone(), args.len() - 1),
+        });
+        return Ok((Operand::Call(name), type_, opcode_table));
+    }
+
+    let mut arg_types = vec![];
     for arg in args {
         let expr = lower_expr(semantic, arg, code, global_values, None)?;
         opcode_table.append_code(&expr.2);
+        arg_types.push(expr.1);
     }
 
     match name {
+        // 变量中保存的函数值 (闭包或引用) 通过栈顶的值调用
+        ASTExprTree::Var(token)
+            if matches!(
+                semantic
+                    .compiler_data()
+                    .symbol_table
+                    .get_element_type(token.text()),
+                Some(ElementType::Value | ElementType::Argument)
+            ) =>
+        {
+            let callee = lower_expr(semantic, name, code, global_values, None)?;
+            opcode_table.append_code(&callee.2);
+            opcode_table.add_opcode(OpCode::Call(
+                None,
+                SmolStr::new_static(CLOSURE_CALL),
+                args.len(),
+            ));
+            Ok((
+                Operand::Call(SmolStr::new_static(CLOSURE_CALL)),
+                Unknown,
+                opcode_table,
+            ))
+        }
         ASTExprTree::Var(token) => {
+            semantic.compiler_data().symbol_table.add_reference(token);
             let path = token.clone().value::<SmolStr>().unwrap();
-            let file_base = semantic
-                .file
-                .name
-                .split('.')
-                .next()
-                .unwrap_or(semantic.file.name.as_str());
+            let file_base = semantic.file.module_name();
             let full_path = format_smolstr!("{file_base}/{path}");
 
             // 检查同步函数递归调用
@@ -615,24 +815,72 @@ fn expr_call(
                 );
             }
 
+            // 调用类名即调用其构造函数, 签名的返回类型为该类的实例
+            let type_ = check_call(semantic, token, &full_path, args, &arg_types, 0)?;
             opcode_table.add_opcode(Push(None, Operand::Reference(full_path)));
-            opcode_table.add_opcode(OpCode::Call(None, path.clone()));
-            Ok((Operand::Call(path), Unknown, opcode_table))
+            opcode_table.add_opcode(OpCode::Call(None, path.clone(), args.len()));
+            Ok((Operand::Call(path), type_, opcode_table))
         }
         ASTExprTree::Expr {
-            token: _token,
-            op: _op,
-            left: _left,
-            right: _right,
+            op: ExprOp::Ref,
+            left,
+            right,
+            ..
         } => {
-            let refs = lower_ref(semantic, name, code, global_values)?;
-            opcode_table.append_code(&refs.1);
-            let cl_str = refs.0.clone();
-            opcode_table.add_opcode(OpCode::Call(None, refs.0));
-            Ok((Operand::Call(cl_str), Unknown, opcode_table))
+            if let Some(base) = module_base(semantic, left) {
+                let refs = lower_ref(semantic, &base, right)?;
+                let type_ = check_call(
+                    semantic,
+                    right.token(),
+                    &format_smolstr!("{base}/{}", refs.0),
+                    args,
+                    &arg_types,
+                    0,
+                )?;
+                opcode_table.append_code(&refs.1);
+                let cl_str = refs.0.clone();
+                opcode_table.add_opcode(OpCode::Call(None, refs.0, args.len()));
+                return Ok((Operand::Call(cl_str), type_, opcode_table));
+            }
+            // 方法调用 obj.method(args), 接收者绑定为闭包的捕获值
+            let ASTExprTree::Var(method) = right.as_ref() else {
+                return Err(ParserError::IllegalExpression(right.token().clone()));
+            };
+            let target = lower_expr(semantic, left, code, global_values, None)?;
+            check_object_type(method, &target.1)?;
+            let type_ = if let ValueGuessType::Object(class) = &target.1 {
+                let file_base = semantic.file.module_name();
+                let path = format_smolstr!("{file_base}/{class}.{}", method.text());
+                check_call(semantic, method, &path, args, &arg_types, 1)?
+            } else {
+                Unknown
+            };
+            opcode_table.append_code(&target.2);
+            opcode_table.add_opcode(OpCode::GetMethod(None, method.text().to_smolstr()));
+            opcode_table.add_opcode(OpCode::Call(
+                None,
+                SmolStr::new_static(CLOSURE_CALL),
+                args.len(),
+            ));
+            Ok((
+                Operand::Call(SmolStr::new_static(CLOSURE_CALL)),
+                type_,
+                opcode_table,
+            ))
         }
         _ => {
-            unreachable!()
+            let callee = lower_expr(semantic, name, code, global_values, None)?;
+            opcode_table.append_code(&callee.2);
+            opcode_table.add_opcode(OpCode::Call(
+                None,
+                SmolStr::new_static(CLOSURE_CALL),
+                args.len(),
+            ));
+            Ok((
+                Operand::Call(SmolStr::new_static(CLOSURE_CALL)),
+                Unknown,
+                opcode_table,
+            ))
         }
     }
 }
@@ -653,8 +901,8 @@ fn exp_compound(
     opcode_table.append_code(right_table);
     opcode_table.add_opcode(astop_to_opcode(*expr_op));
 
-    if let ASTExprTree::Var(token) = left {
-        expr_var(
+    match left {
+        ASTExprTree::Var(token) => expr_var(
             semantic,
             token,
             code,
@@ -662,9 +910,15 @@ fn exp_compound(
             opcode_table,
             Some(ImmNumFlot),
             true,
-        )
-    } else {
-        Err(ParserError::IllegalExpression(token))
+        ),
+        ASTExprTree::Expr {
+            op: ExprOp::Ref, ..
+        } => {
+            let store = lower_expr(semantic, left, code, global_values, Some(ImmNumFlot))?;
+            opcode_table.append_code(&store.2);
+            Ok((store.0, store.1, opcode_table))
+        }
+        _ => Err(ParserError::IllegalExpression(token)),
     }
 }
 
@@ -674,6 +928,18 @@ pub fn lower_expr(
     code: &mut ValueAlloc,
     global_values: Option<&ValueAlloc>,
     store: Option<Operand>,
+) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
+    let mut lowered = lower_expr_node(semantic, expr_tree, code, global_values, store)?;
+    lowered.2.mark_pos(expr_tree.token());
+    Ok(lowered)
+}
+
+fn lower_expr_node(
+    semantic: &mut Semantic,
+    expr_tree: &ASTExprTree,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    store: Option<Operand>,
 ) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
     let mut opcode_table = OpCodeTable::new();
     match expr_tree {
@@ -695,6 +961,14 @@ pub fn lower_expr(
             global_values,
             opcode_table,
         ),
+        ASTExprTree::Expr {
+            token,
+            op: ExprOp::Ref,
+            left,
+            right,
+        } if module_base(semantic, left).is_none() => {
+            expr_field(semantic, token, left, right, code, global_values, store)
+        }
         ASTExprTree::Expr {
             token: e_token,
             op: e_op,
@@ -771,6 +1045,7 @@ pub fn lower_expr(
                 {
                     return Err(ParserError::UnableResolveSymbols(name.clone()));
                 }
+                semantic.compiler_data().symbol_table.add_reference(name);
                 let (key, scope) = resolve_value_key(code, global_values, &var_name).unwrap();
                 if scope == ValueScope::Local {
                     let left_type = code
@@ -788,13 +1063,24 @@ pub fn lower_expr(
                 }
             }
 
+            if matches!(e_op, ExprOp::Store)
+                && let ASTExprTree::Var(name) = e_left.as_ref()
+            {
+                check_store_type(code, global_values, name, e_right.token(), &right.1)?;
+            }
             let left = lower_expr(semantic, e_left.as_ref(), code, global_values, stores)?;
 
             let left_opd = Box::new(left.0.clone());
             let guess_type = guess_type(e_token, &left.1, right.1, *e_op)?;
             let n_operand;
 
-            if let Some(operand) = expr_optimizer(&left.0, &right.0, *e_op) {
+            // 引用路径的拼接不属于常量折叠, 关闭折叠时也需要进行
+            let folded = if *e_op == ExprOp::Ref || semantic.is_optimized(Pass::ConstFold) {
+                expr_optimizer(&left.0, &right.0, *e_op)
+            } else {
+                None
+            };
+            if let Some(operand) = folded {
                 n_operand = operand.clone();
                 opcode_table.add_opcode(Push(None, operand));
             } else {
@@ -823,7 +1109,102 @@ pub fn lower_expr(
         ASTExprTree::Call { name, args } => {
             expr_call(semantic, name, args, code, global_values, opcode_table)
         }
+        ASTExprTree::Lambda { token, args, body } => lambda_semantic(
+            semantic,
+            token,
+            args,
+            body.clone(),
+            code,
+            global_values,
+            opcode_table,
+        ),
+        ASTExprTree::Slice {
+            target, start, end, ..
+        } => expr_slice(
+            semantic,
+            target,
+            [start.as_deref(), end.as_deref()],
+            code,
+            global_values,
+            opcode_table,
+        ),
+        ASTExprTree::Array { elements, .. } => {
+            for element in elements {
+                let element = lower_expr(semantic, element, code, global_values, None)?;
+                opcode_table.append_code(&element.2);
+            }
+            opcode_table.add_opcode(OpCode::NewArray(None, elements.len()));
+            Ok((
+                Operand::Call(SmolStr::new("array")),
+                ValueGuessType::Array,
+                opcode_table,
+            ))
+        }
+        ASTExprTree::Map { entries, .. } => {
+            for (key, value) in entries {
+                let key_ret = lower_expr(semantic, key, code, global_values, None)?;
+                check_map_key(key, &key_ret.1)?;
+                opcode_table.append_code(&key_ret.2);
+                let value = lower_expr(semantic, value, code, global_values, None)?;
+                opcode_table.append_code(&value.2);
+            }
+            opcode_table.add_opcode(OpCode::NewMap(None, entries.len()));
+            Ok((
+                Operand::Call(SmolStr::new("map")),
+                ValueGuessType::Map,
+                opcode_table,
+            ))
+        }
+    }
+}
+
+// 字典的键只能是字符串, 推断为其他值类型的键在编译期报错
+pub fn check_map_key(key: &ASTExprTree, found: &ValueGuessType) -> Result<(), ParserError> {
+    if !matches!(
+        found,
+        ValueGuessType::Bool
+            | ValueGuessType::Number
+            | ValueGuessType::Float
+            | Null
+            | ValueGuessType::Array
+            | ValueGuessType::Map
+            | ValueGuessType::Function
+    ) {
+        return Ok(());
+    }
+    Err(ParserError::TypeMismatch(
+        key.token().clone(),
+        SmolStr::new_static("string"),
+        found.to_smolstr(),
+    ))
+}
+
+// 切片 arr[start:end] 翻译为 type.slice(arr, start, end) 调用, 省略的边界传入 null
+fn expr_slice(
+    semantic: &mut Semantic,
+    target: &ASTExprTree,
+    bounds: [Option<&ASTExprTree>; 2],
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    mut opcode_table: OpCodeTable,
+) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
+    let target = lower_expr(semantic, target, code, global_values, None)?;
+    opcode_table.append_code(&target.2);
+    for bound in bounds {
+        if let Some(bound) = bound {
+            let bound = lower_expr(semantic, bound, code, global_values, None)?;
+            opcode_table.append_code(&bound.2);
+        } else {
+            opcode_table.add_opcode(Push(None, Operand::Null));
+        }
     }
+    opcode_table.add_opcode(Push(None, Operand::Reference(SmolStr::new("type/slice"))));
+    opcode_table.add_opcode(OpCode::Call(None, SmolStr::new("slice"), 3));
+    Ok((
+        Operand::Call(SmolStr::new("slice")),
+        ValueGuessType::Array,
+        opcode_table,
+    ))
 }
 
 // 检查表达式是否含有指定操作码同时检查是否含有 call 操作
@@ -832,7 +1213,8 @@ pub fn check_expr_operand(operand: &Operand, op_code: &OpCode, call_count: i32)
         Operand::Expression(right, left, expr_op) => {
             let mut status = check_expr_operand(right.as_ref(), op_code, call_count + 1);
             status &= check_expr_operand(left.as_ref(), op_code, call_count + 1);
-            if expr_op.as_ref() == op_code {
+            // 字段写入与赋值一样具有副作用
+            if expr_op.as_ref() == op_code || matches!(expr_op.as_ref(), OpCode::SetField(..)) {
                 true
             } else {
                 if ((matches!(right.as_ref(), Operand::Call(_))
@@ -850,6 +1232,48 @@ pub fn check_expr_operand(operand: &Operand, op_code: &OpCode, call_count: i32)
     }
 }
 
+// type 库中修改数组或字典的函数, 不能原地修改时只作用于副本
+const COPY_RESULT_FUNCS: [&str; 5] = ["push", "pop", "insert", "remove_at", "remove"];
+
+/// 数组与字典按值传递, 首个实参不是数组变量时丢弃 `type.push` 等函数的返回值不会修改任何变量
+pub fn check_discarded_result(semantic: &mut Semantic, expr: &ASTExprTree) {
+    let ASTExprTree::Call { name, args } = expr else {
+        return;
+    };
+    let ASTExprTree::Expr {
+        op: ExprOp::Ref,
+        left,
+        right,
+        ..
+    } = name.as_ref()
+    else {
+        return;
+    };
+    let ASTExprTree::Var(func) = right.as_ref() else {
+        return;
+    };
+    // 以数组变量为首个实参时在变量上原地修改
+    let in_place = array_func_index(func.text()).is_some()
+        && matches!(args.first(), Some(ASTExprTree::Var(target)) if matches!(
+            semantic.compiler_data().symbol_table.get_element_type(target.text()),
+            Some(ElementType::Value | ElementType::Argument)
+        ));
+    if COPY_RESULT_FUNCS.contains(&func.text())
+        && !in_place
+        && module_base(semantic, left).is_some_and(|base| base == "type")
+    {
+        Compiler::warning_info_expr(
+            semantic.file,
+            &format!(
+                "result of `type.{}` is discarded, arrays and maps are passed by value.",
+                func.text()
+            ),
+            right,
+            UnusedResult,
+        );
+    }
+}
+
 pub fn expr_semantic(
     semantic: &mut Semantic,
     expr: Option<ASTExprTree>,
@@ -866,6 +1290,8 @@ pub fn expr_semantic(
         operand = exp.0;
         guess_type = exp.1;
     } else {
+        // 未给出初始值的变量以 null 初始化
+        opcode_vec.add_opcode(Push(None, Operand::Null));
         guess_type = Null;
         operand = Operand::Null;
     }
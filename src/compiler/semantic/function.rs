@@ -1,8 +1,8 @@
+use crate::ValueTag;
 use crate::compiler::ast::ssa_ir::{
     Code, Function, LocalMap, OpCode, OpCodeTable, Operand, ValueAlloc, ValueGuessType,
 };
-use crate::compiler::ast::vm_ir::Value;
-use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree, TypeHints};
 use crate::compiler::lexer::Token;
 use crate::compiler::parser::ParserError;
 use crate::compiler::parser::ParserError::NoNativeImplement;
@@ -10,16 +10,137 @@ use crate::compiler::parser::symbol_table::ElementType::Argument;
 use crate::compiler::parser::symbol_table::{ContextType, ElementType};
 use crate::compiler::semantic::Semantic;
 use crate::compiler::semantic::block::block_semantic;
-use crate::library::find_library;
-use smol_str::{SmolStr, ToSmolStr};
+use crate::compiler::semantic::class::declare_class;
+use crate::compiler::semantic::expression::{check_type_hint, resolve_annotation};
+use crate::library::ffi::{ForeignFunc, ForeignSignature};
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+
+// 函数签名, 未标注的形参与返回值类型为 Unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub args: Vec<ValueGuessType>, // 方法的第一个形参为接收者 self
+    pub ret: ValueGuessType,
+}
+
+// 解析函数的类型标注, receiver 为方法的接收者类型
+pub fn hints_signature(
+    semantic: &mut Semantic,
+    hints: &TypeHints,
+    receiver: Option<ValueGuessType>,
+) -> Result<Signature, ParserError> {
+    let mut args: Vec<ValueGuessType> = receiver.into_iter().collect();
+    for hint in &hints.args {
+        args.push(match hint {
+            Some(hint) => resolve_annotation(semantic, hint)?,
+            None => ValueGuessType::Unknown,
+        });
+    }
+    let ret = match &hints.ret {
+        Some(hint) => resolve_annotation(semantic, hint)?,
+        None => ValueGuessType::Unknown,
+    };
+    Ok(Signature { args, ret })
+}
+
+// 在分析语句之前登记本文件所有函数的签名, 调用可以先于函数定义出现
+pub fn declare_signatures(
+    semantic: &mut Semantic,
+    stmts: &[ASTStmtTree],
+) -> Result<(), ParserError> {
+    let file_base = semantic.file.module_name().to_smolstr();
+    for stmt in stmts {
+        match stmt {
+            ASTStmtTree::Function {
+                name, args, hints, ..
+            }
+            | ASTStmtTree::NativeFunction {
+                name, args, hints, ..
+            } => {
+                semantic.compiler_data().symbol_table.add_definition(
+                    name.text().to_smolstr(),
+                    name,
+                    ElementType::Function(args.len()),
+                );
+                let signature = hints_signature(semantic, hints, None)?;
+                semantic
+                    .compiler
+                    .signatures
+                    .insert(format_smolstr!("{file_base}/{}", name.text()), signature);
+            }
+            ASTStmtTree::Class { name, methods, .. } => {
+                semantic.compiler_data().symbol_table.add_definition(
+                    name.text().to_smolstr(),
+                    name,
+                    ElementType::Class,
+                );
+                declare_class(semantic, &file_base, name, methods)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// 检查返回值与所在函数标注的返回类型是否相符
+pub fn check_return(
+    semantic: &mut Semantic,
+    token: &Token,
+    found: &ValueGuessType,
+) -> Result<(), ParserError> {
+    let file_base = semantic.file.module_name().to_smolstr();
+    let Some(context) = semantic
+        .compiler_data()
+        .symbol_table
+        .get_context(&ContextType::Func)
+    else {
+        return Ok(());
+    };
+    let path = format_smolstr!("{file_base}/{}", context.func_name);
+    match semantic.compiler.signatures.get(&path) {
+        Some(signature) => check_type_hint(token, &signature.ret, found),
+        None => Ok(()),
+    }
+}
+
+// 由函数的类型标注确定动态库函数的 C 签名, 只允许能够与 C 互相转换的类型
+fn foreign_signature(
+    semantic: &Semantic,
+    name: &Token,
+    lib_name: &str,
+) -> Result<ForeignSignature, ParserError> {
+    let path = format_smolstr!("{lib_name}/{}", name.text());
+    let Some(signature) = semantic.compiler.signatures.get(&path) else {
+        return Err(NoNativeImplement(name.clone()));
+    };
+    let to_tag = |guess: &ValueGuessType| match guess {
+        ValueGuessType::Number => Ok(Some(ValueTag::Int)),
+        ValueGuessType::Float => Ok(Some(ValueTag::Float)),
+        ValueGuessType::Bool => Ok(Some(ValueTag::Bool)),
+        ValueGuessType::String => Ok(Some(ValueTag::String)),
+        ValueGuessType::Unknown => Ok(None),
+        _ => Err(ParserError::ForeignFunction(
+            name.clone(),
+            format_smolstr!("type '{guess}' cannot be passed to C"),
+        )),
+    };
+    Ok(ForeignSignature {
+        args: signature
+            .args
+            .iter()
+            .map(to_tag)
+            .collect::<Result<_, _>>()?,
+        ret: to_tag(&signature.ret)?,
+    })
+}
 
 pub fn native_function_semantic(
     semantic: &mut Semantic,
     name: Token,
     arguments: &[ASTExprTree],
+    foreign: Option<&(Token, Token)>,
     code: &mut Code,
 ) -> Result<(), ParserError> {
-    let lib_name = semantic.file.name.split('.').next().unwrap().to_smolstr();
+    let lib_name = semantic.file.module_name().to_smolstr();
     let func_name = name.text().to_smolstr();
 
     if code.find_function(&func_name).is_some() {
@@ -31,23 +152,49 @@ pub fn native_function_semantic(
         .symbol_table
         .add_element(func_name.clone(), ElementType::Function(arguments.len()));
 
-    find_library(lib_name.as_str(), |module| {
-        if let Some(lib) = module
-            && let Some(func) = lib.find_func(&func_name)
-            && arguments.len() == func.arity
-        {
-            code.add_function(Function {
-                name: func_name,
-                sync: false,
-                args: func.arity,
-                codes: None,
-                locals: LocalMap::new(),
-            });
-            Ok(Value::Null)
-        } else {
-            Err(NoNativeImplement(name))
+    // 动态库在执行引擎启动时才加载, 这里只检查并记录声明
+    if let Some((library, symbol)) = foreign {
+        if semantic.compiler.natives().is_foreign_disabled() {
+            return Err(ParserError::ForeignFunction(
+                library.clone(),
+                SmolStr::new("foreign native functions are disabled"),
+            ));
         }
-    })?;
+        let signature = foreign_signature(semantic, &name, &lib_name)?;
+        signature
+            .check()
+            .map_err(|msg| ParserError::ForeignFunction(library.clone(), msg))?;
+        code.foreign.push(ForeignFunc {
+            name: func_name.clone(),
+            library: library.text().to_smolstr(),
+            symbol: symbol.text().to_smolstr(),
+            signature,
+        });
+        code.add_function(Function {
+            name: func_name,
+            sync: false,
+            args: arguments.len(),
+            codes: None,
+            locals: LocalMap::new(),
+        });
+        return Ok(());
+    }
+
+    let Some(func) = semantic
+        .compiler
+        .natives()
+        .find_func(&lib_name, &func_name)
+        .filter(|func| func.arity == arguments.len())
+    else {
+        return Err(NoNativeImplement(name));
+    };
+    code.add_function(Function {
+        name: func_name,
+        sync: false,
+        args: func.arity,
+        codes: None,
+        locals: LocalMap::new(),
+    });
     Ok(())
 }
 
@@ -82,18 +229,33 @@ pub fn function_semantic(
     let mut value_alloc = ValueAlloc::new();
     value_alloc.append_ref(global_value);
 
+    // 带类型标注的形参以标注类型分配
+    let file_base = semantic.file.module_name();
+    let arg_types = semantic
+        .compiler
+        .signatures
+        .get(&format_smolstr!("{file_base}/{}", name.text()))
+        .map(|signature| signature.args.clone())
+        .unwrap_or_default();
+
     let args_len = arguments.len();
-    for i in arguments {
-        let ASTExprTree::Var(token) = i else {
+    for (i, arg) in arguments.into_iter().enumerate() {
+        let ASTExprTree::Var(token) = arg else {
             unreachable!()
         };
         let token_c = token.clone();
-        let key = value_alloc.alloc_value(token, ValueGuessType::Unknown);
+        let key = match arg_types.get(i) {
+            Some(type_) if *type_ != ValueGuessType::Unknown => {
+                value_alloc.alloc_declared(token, type_.clone())
+            }
+            _ => value_alloc.alloc_value(token, ValueGuessType::Unknown),
+        };
         locals.add_local(key);
-        semantic
-            .compiler_data()
-            .symbol_table
-            .add_element(token_c.value().unwrap(), Argument);
+        semantic.compiler_data().symbol_table.add_symbol(
+            token_c.value().unwrap(),
+            &token_c,
+            Argument,
+        );
         tables.add_opcode(OpCode::LoadLocal(None, key, Operand::Val(key)));
     }
 
@@ -105,6 +267,7 @@ pub fn function_semantic(
         &mut locals,
     )?;
     tables.append_code(&blk);
+    tables.mark_pos(&name);
 
     semantic.compiler_data().symbol_table.exit_context();
 
@@ -0,0 +1,247 @@
+use crate::compiler::ast::ssa_ir::{
+    Function, LocalMap, OpCode, OpCodeTable, Operand, ValueAlloc, ValueGuessType,
+};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree, ExprOp};
+use crate::compiler::lexer::Token;
+use crate::compiler::parser::ParserError;
+use crate::compiler::parser::symbol_table::ContextType;
+use crate::compiler::parser::symbol_table::ElementType::Argument;
+use crate::compiler::semantic::Semantic;
+use crate::compiler::semantic::block::block_semantic;
+use crate::compiler::semantic::expression::lower_expr;
+use smol_str::{SmolStr, format_smolstr};
+
+// 收集语句中引用的变量名, 同时记录语句块内声明的变量
+fn stmts_names(stmts: &[ASTStmtTree], used: &mut Vec<Token>, declared: &mut Vec<SmolStr>) {
+    for stmt in stmts {
+        match stmt {
+            ASTStmtTree::Root(stmts) | ASTStmtTree::Block(stmts) | ASTStmtTree::Context(stmts) => {
+                stmts_names(stmts, used, declared);
+            }
+            ASTStmtTree::Var { name, value, .. } => {
+                declared.push(name.text().into());
+                if let Some(value) = value {
+                    expr_names(value, used);
+                }
+            }
+            ASTStmtTree::Expr(expr)
+            | ASTStmtTree::Return(Some(expr))
+            | ASTStmtTree::Throw(_, expr) => {
+                expr_names(expr, used);
+            }
+            ASTStmtTree::Loop { cond, body, .. } => {
+                expr_names(cond, used);
+                stmts_names(body, used, declared);
+            }
+            ASTStmtTree::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                expr_names(cond, used);
+                stmts_names(then_body, used, declared);
+                stmts_names(else_body, used, declared);
+            }
+            ASTStmtTree::Array {
+                token, elements, ..
+            } => {
+                declared.push(token.text().into());
+                for element in elements {
+                    expr_names(element, used);
+                }
+            }
+            ASTStmtTree::ArrayFill {
+                token,
+                value,
+                count,
+                ..
+            } => {
+                declared.push(token.text().into());
+                expr_names(value, used);
+                expr_names(count, used);
+            }
+            ASTStmtTree::Map { token, entries, .. } => {
+                declared.push(token.text().into());
+                for (key, value) in entries {
+                    expr_names(key, used);
+                    expr_names(value, used);
+                }
+            }
+            ASTStmtTree::Try {
+                body,
+                catch_name,
+                catch_body,
+                ..
+            } => {
+                stmts_names(body, used, declared);
+                declared.push(catch_name.text().into());
+                stmts_names(catch_body, used, declared);
+            }
+            ASTStmtTree::Return(None)
+            | ASTStmtTree::Import(..)
+            | ASTStmtTree::Function { .. }
+            | ASTStmtTree::NativeFunction { .. }
+            | ASTStmtTree::Class { .. }
+            | ASTStmtTree::Break(_)
+            | ASTStmtTree::Continue(_)
+            | ASTStmtTree::Empty => {}
+        }
+    }
+}
+
+fn expr_names(expr: &ASTExprTree, used: &mut Vec<Token>) {
+    match expr {
+        ASTExprTree::Var(token) => used.push(token.clone()),
+        ASTExprTree::Literal(_) | ASTExprTree::This(_) => {}
+        // 引用路径右侧为成员名而不是变量
+        ASTExprTree::Expr {
+            op: ExprOp::Ref,
+            left,
+            ..
+        } => expr_names(left, used),
+        ASTExprTree::Expr { left, right, .. } => {
+            expr_names(left, used);
+            expr_names(right, used);
+        }
+        ASTExprTree::Unary { code, .. } => expr_names(code, used),
+        ASTExprTree::Call { name, args } => {
+            expr_names(name, used);
+            for arg in args {
+                expr_names(arg, used);
+            }
+        }
+        ASTExprTree::Slice {
+            target, start, end, ..
+        } => {
+            expr_names(target, used);
+            for bound in [start, end].into_iter().flatten() {
+                expr_names(bound, used);
+            }
+        }
+        ASTExprTree::Lambda { args, body, .. } => used.extend(free_names(args, body)),
+        ASTExprTree::Array { elements, .. } => {
+            for element in elements {
+                expr_names(element, used);
+            }
+        }
+        ASTExprTree::Map { entries, .. } => {
+            for (key, value) in entries {
+                expr_names(key, used);
+                expr_names(value, used);
+            }
+        }
+    }
+}
+
+// 匿名函数体中引用但未在函数内声明的变量名, 按首次出现的顺序排列
+fn free_names(args: &[ASTExprTree], body: &[ASTStmtTree]) -> Vec<Token> {
+    let mut used = vec![];
+    let mut declared: Vec<SmolStr> = args.iter().map(|arg| arg.token().text().into()).collect();
+    stmts_names(body, &mut used, &mut declared);
+
+    let mut names: Vec<Token> = vec![];
+    for token in used {
+        if !declared.iter().any(|name| name == token.text())
+            && !names.iter().any(|name| name.text() == token.text())
+        {
+            names.push(token);
+        }
+    }
+    names
+}
+
+// 匿名函数编译为隐藏函数, 形参为捕获的变量与声明的参数, 表达式的值为捕获了当前变量值的闭包
+pub fn lambda_semantic(
+    semantic: &mut Semantic,
+    token: &Token,
+    arguments: &[ASTExprTree],
+    body: Vec<ASTStmtTree>,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    mut opcode_table: OpCodeTable,
+) -> Result<(Operand, ValueGuessType, OpCodeTable), ParserError> {
+    // 根上下文的变量均为全局变量, 可以直接访问而不需要捕获
+    let captures: Vec<Token> = if global_values.is_some() {
+        free_names(arguments, &body)
+            .into_iter()
+            .filter(|name| {
+                code.find_value_key(&name.text().into())
+                    .and_then(|key| code.find_value(key))
+                    .is_some_and(|value| value.type_ != ValueGuessType::Ref)
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let func_name = format_smolstr!("<lambda{}>", semantic.next_lambda);
+    semantic.next_lambda += 1;
+    let file_base = semantic.file.module_name();
+    let full_path = format_smolstr!("{file_base}/{func_name}");
+
+    semantic
+        .compiler_data()
+        .symbol_table
+        .add_context(ContextType::Func);
+    let context = semantic
+        .compiler_data()
+        .symbol_table
+        .get_context(&ContextType::Func)
+        .unwrap();
+    context.func_name = func_name.clone();
+
+    let global = global_values.unwrap_or(&*code);
+    let mut tables = OpCodeTable::new();
+    let mut locals = LocalMap::new();
+    let mut value_alloc = ValueAlloc::new();
+    value_alloc.append_ref(global);
+
+    let params = captures
+        .iter()
+        .cloned()
+        .chain(arguments.iter().map(|arg| arg.token().clone()));
+    for (i, param) in params.enumerate() {
+        let name = param.text().into();
+        let symbol_table = &mut semantic.compiler_data().symbol_table;
+        // 捕获的变量沿用外层的定义位置
+        if i < captures.len() {
+            symbol_table.add_element(name, Argument);
+        } else {
+            symbol_table.add_symbol(name, &param, Argument);
+        }
+        let key = value_alloc.alloc_value(param, ValueGuessType::Unknown);
+        locals.add_local(key);
+        tables.add_opcode(OpCode::LoadLocal(None, key, Operand::Val(key)));
+    }
+
+    let blk = block_semantic(semantic, body, &mut value_alloc, Some(global), &mut locals)?;
+    tables.append_code(&blk);
+    tables.mark_pos(token);
+
+    semantic.compiler_data().symbol_table.exit_context();
+
+    semantic.lambdas.push(Function {
+        name: func_name.clone(),
+        sync: false,
+        args: captures.len() + arguments.len(),
+        codes: Some(tables),
+        locals,
+    });
+
+    for capture in &captures {
+        let load = lower_expr(
+            semantic,
+            &ASTExprTree::Var(capture.clone()),
+            code,
+            global_values,
+            None,
+        )?;
+        opcode_table.append_code(&load.2);
+    }
+    opcode_table.add_opcode(OpCode::MakeClosure(None, full_path, captures.len()));
+    Ok((
+        Operand::Call(func_name),
+        ValueGuessType::Function,
+        opcode_table,
+    ))
+}
@@ -9,16 +9,18 @@ pub fn loop_back_semantic(
     is_break: bool,
     token: Token,
 ) -> Result<OpCodeTable, ParserError> {
-    semantic
-        .compiler_data()
-        .symbol_table
-        .get_context(&ContextType::Loop)
-        .map_or_else(
-            || Err(ParserError::BackOutsideLoop(token)),
-            |_context| {
-                let mut table = OpCodeTable::new();
-                table.add_opcode(OpCode::LazyJump(None, None, is_break));
-                Ok(table)
-            },
-        )
+    let symbol_table = &mut semantic.compiler_data().symbol_table;
+    // 跳出循环内的 try 块时需要先注销其异常处理器
+    let try_depth = symbol_table.count_contexts(&ContextType::Try, &ContextType::Loop);
+    symbol_table.get_context(&ContextType::Loop).map_or_else(
+        || Err(ParserError::BackOutsideLoop(token)),
+        |_context| {
+            let mut table = OpCodeTable::new();
+            for _ in 0..try_depth {
+                table.add_opcode(OpCode::EndTry(None));
+            }
+            table.add_opcode(OpCode::LazyJump(None, None, is_break));
+            Ok(table)
+        },
+    )
 }
@@ -1,153 +1,372 @@
 mod block;
+mod class;
 mod expression;
 mod function;
 mod judgment;
+mod lambda;
 mod loop_back;
 mod optimizer;
+mod r#try;
 mod var;
 mod r#while;
-pub(crate) use optimizer::{const_prop_linear, eliminate_dead_locals, local_arith_peephole};
+pub(crate) use function::Signature;
+pub(crate) use optimizer::{
+    block_leaders, const_prop_linear, eliminate_dead_locals, local_arith_peephole,
+};
 
-use crate::compiler::ast::ASTStmtTree;
-use crate::compiler::ast::ssa_ir::{Code, LocalMap, OpCode, ValueAlloc, ValueGuessType};
+use crate::compiler::ast::ssa_ir::{Code, Function, LocalMap, OpCode, ValueAlloc, ValueGuessType};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree, ExprOp};
 use crate::compiler::file::SourceFile;
 use crate::compiler::lints::Lint::UnusedExpression;
+use crate::compiler::optimize::Pass;
 use crate::compiler::parser::ParserError;
 use crate::compiler::parser::symbol_table::ElementType;
 use crate::compiler::semantic::block::block_semantic;
-use crate::compiler::semantic::expression::{check_expr_operand, expr_semantic};
-use crate::compiler::semantic::function::{function_semantic, native_function_semantic};
+use crate::compiler::semantic::class::class_semantic;
+use crate::compiler::semantic::expression::{
+    check_discarded_result, check_expr_operand, expr_semantic,
+};
+use crate::compiler::semantic::function::{
+    declare_signatures, function_semantic, native_function_semantic,
+};
 use crate::compiler::semantic::judgment::judgment_semantic;
-use crate::compiler::semantic::var::{array_fill_semantic, array_semantic, var_semantic};
+use crate::compiler::semantic::r#try::{throw_semantic, try_semantic};
+use crate::compiler::semantic::var::{
+    array_fill_semantic, array_semantic, map_semantic, var_semantic,
+};
 use crate::compiler::semantic::r#while::while_semantic;
 use crate::compiler::{Compiler, CompilerData};
+use smol_str::SmolStr;
+
+// 交互模式下跨输入保留的根上下文状态 (函数定义, 全局变量表)
+#[derive(Debug, Clone, Default)]
+pub struct SemanticState {
+    funcs: Vec<Function>,
+    globals: LocalMap,
+    values: ValueAlloc,
+}
+
+// 交互模式下赋值类表达式不回显结果
+fn is_assign_expr(expr: &ASTExprTree) -> bool {
+    match expr {
+        ASTExprTree::Expr { op, .. } => matches!(
+            op,
+            ExprOp::Store
+                | ExprOp::AddS
+                | ExprOp::SubS
+                | ExprOp::MulS
+                | ExprOp::DivS
+                | ExprOp::RmdS
+                | ExprOp::BAndS
+                | ExprOp::BOrS
+                | ExprOp::BXorS
+        ),
+        ASTExprTree::Unary { op, .. } => matches!(op, ExprOp::SAdd | ExprOp::SSub),
+        _ => false,
+    }
+}
+
+const fn is_declaration(stmt: &ASTStmtTree) -> bool {
+    matches!(
+        stmt,
+        ASTStmtTree::Import(..)
+            | ASTStmtTree::Function { .. }
+            | ASTStmtTree::NativeFunction { .. }
+            | ASTStmtTree::Class { .. }
+            | ASTStmtTree::Empty
+    )
+}
 
 pub struct Semantic<'a> {
     file: &'a mut SourceFile,
     compiler: &'a mut Compiler,
+    lambdas: Vec<Function>, // 匿名函数编译出的隐藏函数, 语义分析结束后追加到函数表
+    next_lambda: usize,     // 下一个隐藏函数的编号, 交互模式下跨输入保持唯一
+    classes: Vec<SmolStr>,  // 本次输入定义的类名, 类型标注可以引用尚未分析的类
 }
 
 impl<'a> Semantic<'a> {
     pub const fn new(file: &'a mut SourceFile, compiler: &'a mut Compiler) -> Self {
-        Self { file, compiler }
+        Self {
+            file,
+            compiler,
+            lambdas: Vec::new(),
+            next_lambda: 0,
+            classes: Vec::new(),
+        }
     }
 
     pub const fn compiler_data(&mut self) -> &mut CompilerData {
         &mut self.file.c_data
     }
 
-    pub fn semantic(&mut self, stmt_tree: ASTStmtTree) -> Result<(Code, LocalMap), ParserError> {
-        let code = &mut Code::new(true);
-        let mut global = LocalMap::new();
-        let value_alloc = &mut ValueAlloc::new();
-
-        let ASTStmtTree::Root(stmts) = stmt_tree else {
-            unreachable!();
-        };
+    pub const fn is_optimized(&self, pass: Pass) -> bool {
+        self.file.c_data.optimize.is_enabled(pass)
+    }
 
-        for stmt in stmts {
-            match stmt {
-                ASTStmtTree::Var { name, value } => {
-                    let opcode =
-                        var_semantic(self, name, value, value_alloc, None, true, &mut global)?;
-                    code.get_code_table().append_code(&opcode);
-                }
-                ASTStmtTree::Expr(expr) => {
-                    let ref_expr = expr.clone();
-                    let ret_m = expr_semantic(self, Some(expr), value_alloc, None)?;
-                    code.get_code_table().append_code(&ret_m.2);
-                    if !check_expr_operand(&ret_m.0, &OpCode::Store(None), 0) {
-                        Compiler::warning_info_expr(
-                            self.file,
-                            "expression result is unused.",
-                            &ref_expr,
-                            UnusedExpression,
-                        );
-                    }
-                }
-                ASTStmtTree::Import(token, use_name, imp_name) => {
-                    let lib_name = imp_name.clone();
-                    if self.compiler.find_file(lib_name.as_str()).is_none() {
-                        return Err(ParserError::NotFoundLibrary(token));
-                    }
-                    let name = use_name;
-                    self.compiler_data()
-                        .symbol_table
-                        .add_element(name, ElementType::Library(imp_name));
-                    value_alloc.alloc_value(token, ValueGuessType::Ref);
-                }
-                ASTStmtTree::Loop {
-                    token: _token,
-                    cond,
-                    body,
-                    is_easy,
-                } => {
-                    let ret_m =
-                        while_semantic(self, &cond, body, value_alloc, None, &mut global, is_easy)?;
-                    code.get_code_table().append_code(&ret_m);
-                }
-                ASTStmtTree::Function {
+    // 分析一个顶层语句, 生成的代码追加到根上下文中
+    fn stmt_semantic(
+        &mut self,
+        stmt: ASTStmtTree,
+        code: &mut Code,
+        value_alloc: &mut ValueAlloc,
+        global: &mut LocalMap,
+        interactive: bool,
+    ) -> Result<(), ParserError> {
+        match stmt {
+            ASTStmtTree::Var {
+                name,
+                annotation,
+                value,
+            } => {
+                let opcode = var_semantic(
+                    self,
                     name,
-                    sync,
-                    args,
-                    body,
-                } => {
-                    function_semantic(self, name, sync, args, body, code, value_alloc)?;
+                    annotation,
+                    value,
+                    value_alloc,
+                    None,
+                    true,
+                    global,
+                )?;
+                code.get_code_table().append_code(&opcode);
+            }
+            ASTStmtTree::Expr(expr) => {
+                let ref_expr = expr.clone();
+                check_discarded_result(self, &ref_expr);
+                let echo = interactive && !is_assign_expr(&expr);
+                let ret_m = expr_semantic(self, Some(expr), value_alloc, None)?;
+                code.get_code_table().append_code(&ret_m.2);
+                if echo {
+                    code.get_code_table().add_opcode(OpCode::Echo(None));
+                } else if !check_expr_operand(&ret_m.0, &OpCode::Store(None), 0) {
+                    Compiler::warning_info_expr(
+                        self.file,
+                        "expression result is unused.",
+                        &ref_expr,
+                        UnusedExpression,
+                    );
                 }
-                ASTStmtTree::NativeFunction { name, args } => {
-                    native_function_semantic(self, name, &args, code)?;
+            }
+            ASTStmtTree::Import(token, use_name, imp_name) => {
+                let module = self.compiler.import_module(self.file, &token, &imp_name)?;
+                // 同一导入名不能绑定到不同的模块, 否则之后的导入会被静默忽略
+                if let Some(ElementType::Library(bound)) = self
+                    .compiler_data()
+                    .symbol_table
+                    .get_element_type(&use_name)
+                    && *bound != module
+                {
+                    return Err(ParserError::DuplicateImport(token, use_name, imp_name));
                 }
-                ASTStmtTree::If {
-                    cond,
+                self.compiler_data().symbol_table.add_symbol(
+                    use_name,
+                    &token,
+                    ElementType::Library(module),
+                );
+                value_alloc.alloc_value(token, ValueGuessType::Ref);
+            }
+            ASTStmtTree::Loop {
+                token: _token,
+                cond,
+                body,
+                is_easy,
+            } => {
+                let ret_m = while_semantic(self, &cond, body, value_alloc, None, global, is_easy)?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Function {
+                name,
+                sync,
+                args,
+                body,
+                ..
+            } => {
+                function_semantic(self, name, sync, args, body, code, value_alloc)?;
+            }
+            ASTStmtTree::NativeFunction {
+                name,
+                args,
+                foreign,
+                ..
+            } => {
+                native_function_semantic(self, name, &args, foreign.as_ref(), code)?;
+            }
+            ASTStmtTree::Class {
+                name,
+                fields,
+                methods,
+            } => {
+                class_semantic(self, name, fields, methods, code, value_alloc)?;
+            }
+            ASTStmtTree::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                let ret_m = judgment_semantic(
+                    self,
+                    &cond,
                     then_body,
                     else_body,
-                } => {
-                    let ret_m = judgment_semantic(
-                        self,
-                        &cond,
-                        then_body,
-                        else_body,
-                        value_alloc,
-                        None,
-                        &mut global,
-                    )?;
-                    code.get_code_table().append_code(&ret_m);
-                }
-                ASTStmtTree::Array { token, elements } => {
-                    let ret_m = array_semantic(
-                        self,
-                        token,
-                        elements,
-                        value_alloc,
-                        None,
-                        &mut global,
-                        true,
-                    )?;
-                    code.get_code_table().append_code(&ret_m);
-                }
-                ASTStmtTree::ArrayFill {
+                    value_alloc,
+                    None,
+                    global,
+                )?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Array {
+                token,
+                annotation,
+                elements,
+            } => {
+                let ret_m = array_semantic(
+                    self,
+                    token,
+                    annotation,
+                    elements,
+                    value_alloc,
+                    None,
+                    global,
+                    true,
+                )?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Map {
+                token,
+                annotation,
+                entries,
+            } => {
+                let ret_m = map_semantic(
+                    self,
+                    token,
+                    annotation,
+                    entries,
+                    value_alloc,
+                    None,
+                    global,
+                    true,
+                )?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::ArrayFill {
+                token,
+                annotation,
+                value,
+                count,
+            } => {
+                let ret_m = array_fill_semantic(
+                    self,
                     token,
+                    annotation,
                     value,
                     count,
-                } => {
-                    let ret_m = array_fill_semantic(
-                        self,
-                        token,
-                        value,
-                        count,
-                        value_alloc,
-                        None,
-                        &mut global,
-                        true,
-                    )?;
-                    code.get_code_table().append_code(&ret_m);
-                }
-                ASTStmtTree::Context(stmts) => {
-                    let ret_m = block_semantic(self, stmts, value_alloc, None, &mut global)?;
-                    code.get_code_table().append_code(&ret_m);
+                    value_alloc,
+                    None,
+                    global,
+                    true,
+                )?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Context(stmts) => {
+                let ret_m = block_semantic(self, stmts, value_alloc, None, global)?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Try {
+                token: _token,
+                body,
+                catch_name,
+                catch_body,
+            } => {
+                let ret_m = try_semantic(
+                    self,
+                    body,
+                    catch_name,
+                    catch_body,
+                    value_alloc,
+                    None,
+                    global,
+                )?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            ASTStmtTree::Throw(_token, expr) => {
+                let ret_m = throw_semantic(self, &expr, value_alloc, None)?;
+                code.get_code_table().append_code(&ret_m);
+            }
+            _ => todo!(),
+        }
+        Ok(())
+    }
+
+    /// 出错的顶层语句被跳过并继续分析之后的语句
+    /// # Errors
+    /// 返回按出现顺序排列的全部语义错误
+    pub fn semantic(
+        &mut self,
+        stmt_tree: ASTStmtTree,
+    ) -> Result<(Code, LocalMap), Vec<ParserError>> {
+        let session = self.file.session.as_deref().cloned();
+        let interactive = session.is_some();
+        let SemanticState {
+            funcs,
+            globals: mut global,
+            values: mut value_alloc,
+        } = session.unwrap_or_default();
+        let code = &mut Code::new(true);
+        code.funcs = funcs;
+        self.next_lambda = code.funcs.len();
+        let value_alloc = &mut value_alloc;
+
+        let ASTStmtTree::Root(stmts) = stmt_tree else {
+            unreachable!();
+        };
+        self.classes = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                ASTStmtTree::Class { name, .. } => Some(name.text().into()),
+                _ => None,
+            })
+            .collect();
+        declare_signatures(self, &stmts).map_err(|error| vec![error])?;
+
+        let mut errors = Vec::new();
+        for stmt in stmts {
+            let token = stmt.token().cloned();
+            let start = code.get_code_table().next_addr();
+            let is_import = matches!(stmt, ASTStmtTree::Import(..));
+            // 库文件只提供声明, 其中的顶层语句不会被执行
+            if self.file.is_library
+                && !is_declaration(&stmt)
+                && let Some(token) = token
+            {
+                errors.push(ParserError::LibraryStatement(token));
+                continue;
+            }
+            if let Err(error) =
+                self.stmt_semantic(stmt, code, value_alloc, &mut global, interactive)
+            {
+                errors.push(error);
+                // 之后的语句多半依赖导入失败的模块, 不再分析以免产生连带的错误
+                if is_import {
+                    break;
                 }
-                _ => todo!(),
+                continue;
             }
+            if let Some(token) = token {
+                code.get_code_table().mark_pos_since(start, &token);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        code.funcs.append(&mut self.lambdas);
+        code.globals = global.names(value_alloc);
+        if let Some(session) = self.file.session.as_deref_mut() {
+            *session = SemanticState {
+                funcs: code.funcs.clone(),
+                globals: global.clone(),
+                values: value_alloc.clone(),
+            };
         }
         Ok((code.clone(), global))
     }
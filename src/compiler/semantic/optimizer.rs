@@ -1,73 +1,45 @@
 use crate::compiler::ast::ExprOp;
-use crate::compiler::ast::ssa_ir::Operand::{ImmBool, ImmFlot, ImmNum, ImmStr, Library, Reference};
+use crate::compiler::ast::integer::IntOp;
+use crate::compiler::ast::ssa_ir::Operand::{
+    ImmBigNum, ImmBool, ImmFlot, ImmNum, ImmStr, Library, Reference,
+};
 use crate::compiler::ast::ssa_ir::{Code, LocalAddr, OpCode, OpCodeTable, Operand};
-use dashu::float::{Context, DBig};
+use crate::compiler::ast::vm_ir::Value;
+use crate::runtime::RuntimeError;
+use crate::runtime::vm_operation::{
+    add_value, div_value, mul_value, neg_number, rmd_value, sub_value,
+};
 use slotmap::DefaultKey;
 use smol_str::{SmolStr, SmolStrBuilder};
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 pub fn unary_optimizer(op: ExprOp, operand: &Operand) -> Option<Operand> {
     match op {
         ExprOp::Not => fold_not(operand),
-        ExprOp::SAdd => fold_num_unary(operand, |n| n + 1, |f| f.clone() + DBig::from(1)),
-        ExprOp::SSub => fold_num_unary(operand, |n| n - 1, |f| f.clone() - DBig::from(1)),
-        ExprOp::Neg => fold_num_unary(operand, |n| -n, |f| -f.clone()),
+        ExprOp::SAdd => fold_num_unary(operand, |n| add_value(n, Value::Int(1))),
+        ExprOp::SSub => fold_num_unary(operand, |n| sub_value(n, Value::Int(1))),
+        ExprOp::Neg => fold_num_unary(operand, neg_number),
         _ => None,
     }
 }
 
 pub fn expr_optimizer(left: &Operand, right: &Operand, op: ExprOp) -> Option<Operand> {
     match op {
-        ExprOp::Add => fold_num_bin(
-            left,
-            right,
-            |a, b| a + b,
-            |a, b| a.clone() + b.clone(),
-            |a, b| a + b,
-        ),
-        ExprOp::Sub => fold_num_bin(
-            left,
-            right,
-            |a, b| a - b,
-            |a, b| a.clone() - b.clone(),
-            |a, b| a - b,
-        ),
-        ExprOp::Mul => fold_num_bin(
-            left,
-            right,
-            |a, b| a * b,
-            |a, b| a.clone() * b.clone(),
-            |a, b| a * b,
-        ),
-        ExprOp::Div => fold_num_bin(
-            left,
-            right,
-            |a, b| a / b,
-            |a, b| a.clone() / b.clone(),
-            |a, b| {
-                let context = Context::new(30);
-                context.div(a.repr(), b.repr()).value()
-            },
-        ),
-        ExprOp::Rmd => fold_num_bin(
-            left,
-            right,
-            |a, b| a % b,
-            |a, b| a.clone() % b.clone(),
-            |a, b| {
-                let context = Context::new(30);
-                context.rem(a.repr(), b.repr()).value()
-            },
-        ),
-        ExprOp::BitAnd => fold_bit_op(left, right, |a, b| a & b),
-        ExprOp::BitOr => fold_bit_op(left, right, |a, b| a | b),
-        ExprOp::BitXor => fold_bit_op(left, right, |a, b| a ^ b),
-        ExprOp::BLeft => fold_bit_op(left, right, |a, b| a << b),
-        ExprOp::BRight => fold_bit_op(left, right, |a, b| a >> b),
-        ExprOp::Big => fold_num_cmp(left, right, |a, b| a > b, |a, b| a > b),
-        ExprOp::Less => fold_num_cmp(left, right, |a, b| a < b, |a, b| a < b),
-        ExprOp::BigEqu => fold_num_cmp(left, right, |a, b| a >= b, |a, b| a >= b),
-        ExprOp::LesEqu => fold_num_cmp(left, right, |a, b| a <= b, |a, b| a <= b),
+        ExprOp::Add => fold_num_bin(left, right, add_value),
+        ExprOp::Sub => fold_num_bin(left, right, sub_value),
+        ExprOp::Mul => fold_num_bin(left, right, mul_value),
+        ExprOp::Div => fold_num_bin(left, right, div_value),
+        ExprOp::Rmd => fold_num_bin(left, right, rmd_value),
+        ExprOp::BitAnd => fold_bit_op(left, right, IntOp::BitAnd),
+        ExprOp::BitOr => fold_bit_op(left, right, IntOp::BitOr),
+        ExprOp::BitXor => fold_bit_op(left, right, IntOp::BitXor),
+        ExprOp::BLeft => fold_bit_op(left, right, IntOp::Shl),
+        ExprOp::BRight => fold_bit_op(left, right, IntOp::Shr),
+        ExprOp::Big => fold_num_cmp(left, right, Ordering::is_gt),
+        ExprOp::Less => fold_num_cmp(left, right, Ordering::is_lt),
+        ExprOp::BigEqu => fold_num_cmp(left, right, Ordering::is_ge),
+        ExprOp::LesEqu => fold_num_cmp(left, right, Ordering::is_le),
         ExprOp::Equ => fold_eq(left, right, false),
         ExprOp::NotEqu => fold_eq(left, right, true),
         ExprOp::And => fold_bool_op(left, right, |a, b| a && b),
@@ -85,57 +57,38 @@ fn fold_not(operand: &Operand) -> Option<Operand> {
     }
 }
 
-fn fold_num_unary<F>(operand: &Operand, num_op: fn(i64) -> i64, float_op: F) -> Option<Operand>
-where
-    F: Fn(&DBig) -> DBig,
-{
-    if let ImmNum(num) = operand {
-        Some(ImmNum(num_op(*num)))
-    } else if let ImmFlot(flot) = operand {
-        Some(ImmFlot(float_op(flot)))
-    } else {
-        None
+// 数值常量的折叠直接使用执行引擎的运算, 运算出错时保留到运行时再报告
+fn value_to_operand(value: Value) -> Option<Operand> {
+    match value {
+        Value::Float(f) => Some(ImmFlot(f)),
+        value => Operand::from_int_value(value),
     }
 }
 
-fn fold_num_bin<F, G>(
+fn fold_num_unary(
+    operand: &Operand,
+    op: fn(Value) -> Result<Value, RuntimeError>,
+) -> Option<Operand> {
+    value_to_operand(op(operand.number_value()?).ok()?)
+}
+
+fn fold_num_bin(
     left: &Operand,
     right: &Operand,
-    int_op: fn(i64, i64) -> i64,
-    float_op: F,
-    mixed_op: G,
-) -> Option<Operand>
-where
-    F: Fn(&DBig, &DBig) -> DBig,
-    G: Fn(DBig, DBig) -> DBig,
-{
-    match (left, right) {
-        (ImmNum(a), ImmNum(b)) => Some(ImmNum(int_op(*a, *b))),
-        (ImmFlot(a), ImmFlot(b)) => Some(ImmFlot(float_op(a, b))),
-        (ImmNum(a), ImmFlot(b)) => Some(ImmFlot(mixed_op(DBig::from(*a), b.clone()))),
-        (ImmFlot(a), ImmNum(b)) => Some(ImmFlot(mixed_op(a.clone(), DBig::from(*b)))),
-        _ => None,
-    }
+    op: fn(Value, Value) -> Result<Value, RuntimeError>,
+) -> Option<Operand> {
+    value_to_operand(op(left.number_value()?, right.number_value()?).ok()?)
 }
 
-fn fold_num_cmp<F, G>(left: &Operand, right: &Operand, int_cmp: F, float_cmp: G) -> Option<Operand>
-where
-    F: Fn(i64, i64) -> bool,
-    G: Fn(&DBig, &DBig) -> bool,
-{
-    match (left, right) {
-        (ImmNum(a), ImmNum(b)) => Some(ImmBool(int_cmp(*a, *b))),
-        (ImmFlot(a), ImmFlot(b)) => Some(ImmBool(float_cmp(a, b))),
-        _ => None,
-    }
+fn fold_num_cmp(left: &Operand, right: &Operand, cmp: fn(Ordering) -> bool) -> Option<Operand> {
+    let ordering = left
+        .number_value()?
+        .compare_number(&right.number_value()?)?;
+    Some(ImmBool(cmp(ordering)))
 }
 
-fn fold_bit_op(left: &Operand, right: &Operand, op: fn(i64, i64) -> i64) -> Option<Operand> {
-    if let (ImmNum(a), ImmNum(b)) = (left, right) {
-        Some(ImmNum(op(*a, *b)))
-    } else {
-        None
-    }
+fn fold_bit_op(left: &Operand, right: &Operand, op: IntOp) -> Option<Operand> {
+    value_to_operand(op.apply(&left.number_value()?, &right.number_value()?)?)
 }
 
 fn fold_bool_op(left: &Operand, right: &Operand, op: fn(bool, bool) -> bool) -> Option<Operand> {
@@ -149,6 +102,7 @@ fn fold_bool_op(left: &Operand, right: &Operand, op: fn(bool, bool) -> bool) ->
 fn fold_eq(left: &Operand, right: &Operand, negate: bool) -> Option<Operand> {
     let eq = match (left, right) {
         (ImmNum(a), ImmNum(b)) => Some(a == b),
+        (ImmBigNum(a), ImmBigNum(b)) => Some(a == b),
         (ImmFlot(a), ImmFlot(b)) => Some(a == b),
         (ImmBool(a), ImmBool(b)) => Some(a == b),
         (ImmStr(a), ImmStr(b)) => Some(a == b),
@@ -173,6 +127,7 @@ fn operand_as_const(operand: &Operand) -> Option<Operand> {
     match operand {
         ImmBool(_)
         | ImmNum(_)
+        | ImmBigNum(_)
         | ImmFlot(_)
         | ImmStr(_)
         | Operand::Null
@@ -186,65 +141,29 @@ fn operand_as_const(operand: &Operand) -> Option<Operand> {
 fn fold_unary(op: &OpCode, operand: &Operand) -> Option<Operand> {
     match op {
         OpCode::Not(_) => fold_not(operand),
-        OpCode::SAdd(_) => fold_num_unary(operand, |n| n + 1, |f| f.clone() + DBig::from(1)),
-        OpCode::SSub(_) => fold_num_unary(operand, |n| n - 1, |f| f.clone() - DBig::from(1)),
-        OpCode::Neg(_) => fold_num_unary(operand, |n| -n, |f| -f.clone()),
+        OpCode::SAdd(_) => fold_num_unary(operand, |n| add_value(n, Value::Int(1))),
+        OpCode::SSub(_) => fold_num_unary(operand, |n| sub_value(n, Value::Int(1))),
+        OpCode::Neg(_) => fold_num_unary(operand, neg_number),
         _ => None,
     }
 }
 
 fn fold_binary(op: &OpCode, left: &Operand, right: &Operand) -> Option<Operand> {
     match op {
-        OpCode::Add(_) => fold_num_bin(
-            left,
-            right,
-            |a, b| a + b,
-            |a, b| a.clone() + b.clone(),
-            |a, b| a + b,
-        ),
-        OpCode::Sub(_) => fold_num_bin(
-            left,
-            right,
-            |a, b| a - b,
-            |a, b| a.clone() - b.clone(),
-            |a, b| a - b,
-        ),
-        OpCode::Mul(_) => fold_num_bin(
-            left,
-            right,
-            |a, b| a * b,
-            |a, b| a.clone() * b.clone(),
-            |a, b| a * b,
-        ),
-        OpCode::Div(_) => fold_num_bin(
-            left,
-            right,
-            |a, b| a / b,
-            |a, b| a.clone() / b.clone(),
-            |a, b| {
-                let context = Context::new(30);
-                context.div(a.repr(), b.repr()).value()
-            },
-        ),
-        OpCode::Rmd(_) => fold_num_bin(
-            left,
-            right,
-            |a, b| a % b,
-            |a, b| a.clone() % b.clone(),
-            |a, b| {
-                let context = Context::new(30);
-                context.rem(a.repr(), b.repr()).value()
-            },
-        ),
-        OpCode::BitAnd(_) => fold_bit_op(left, right, |a, b| a & b),
-        OpCode::BitOr(_) => fold_bit_op(left, right, |a, b| a | b),
-        OpCode::BitXor(_) => fold_bit_op(left, right, |a, b| a ^ b),
-        OpCode::BLeft(_) => fold_bit_op(left, right, |a, b| a << b),
-        OpCode::BRight(_) => fold_bit_op(left, right, |a, b| a >> b),
-        OpCode::Big(_) => fold_num_cmp(left, right, |a, b| a > b, |a, b| a > b),
-        OpCode::Less(_) => fold_num_cmp(left, right, |a, b| a < b, |a, b| a < b),
-        OpCode::BigEqu(_) => fold_num_cmp(left, right, |a, b| a >= b, |a, b| a >= b),
-        OpCode::LesEqu(_) => fold_num_cmp(left, right, |a, b| a <= b, |a, b| a <= b),
+        OpCode::Add(_) => fold_num_bin(left, right, add_value),
+        OpCode::Sub(_) => fold_num_bin(left, right, sub_value),
+        OpCode::Mul(_) => fold_num_bin(left, right, mul_value),
+        OpCode::Div(_) => fold_num_bin(left, right, div_value),
+        OpCode::Rmd(_) => fold_num_bin(left, right, rmd_value),
+        OpCode::BitAnd(_) => fold_bit_op(left, right, IntOp::BitAnd),
+        OpCode::BitOr(_) => fold_bit_op(left, right, IntOp::BitOr),
+        OpCode::BitXor(_) => fold_bit_op(left, right, IntOp::BitXor),
+        OpCode::BLeft(_) => fold_bit_op(left, right, IntOp::Shl),
+        OpCode::BRight(_) => fold_bit_op(left, right, IntOp::Shr),
+        OpCode::Big(_) => fold_num_cmp(left, right, Ordering::is_gt),
+        OpCode::Less(_) => fold_num_cmp(left, right, Ordering::is_lt),
+        OpCode::BigEqu(_) => fold_num_cmp(left, right, Ordering::is_ge),
+        OpCode::LesEqu(_) => fold_num_cmp(left, right, Ordering::is_le),
         OpCode::Equ(_) => fold_eq(left, right, false),
         OpCode::NotEqu(_) => fold_eq(left, right, true),
         OpCode::And(_) => fold_bool_op(left, right, |a, b| a && b),
@@ -303,11 +222,7 @@ fn const_prop_step(
         }
         OpCode::AddLocalImm(_, key, imm) => {
             if let Some(Some(constant)) = env.get(key) {
-                let next = match constant {
-                    ImmNum(v) => Some(ImmNum(*v + *imm)),
-                    ImmFlot(v) => Some(ImmFlot(v + DBig::from(*imm))),
-                    _ => None,
-                };
+                let next = fold_num_bin(constant, &ImmNum(*imm), add_value);
                 env.insert(*key, next.clone());
             } else {
                 env.insert(*key, None);
@@ -336,6 +251,15 @@ fn const_prop_step(
             stack_pop_n(stack, *len);
             None
         }
+        OpCode::LoadMapLocal(_, key, len) => {
+            stack_pop_n(stack, *len * 2);
+            env.insert(*key, None);
+            None
+        }
+        OpCode::LoadMapGlobal(_, _, len) => {
+            stack_pop_n(stack, *len * 2);
+            None
+        }
         OpCode::SetArrayLocal(_, key) => {
             stack_pop_n(stack, 2);
             env.insert(*key, None);
@@ -345,6 +269,17 @@ fn const_prop_step(
             stack_pop_n(stack, 2);
             None
         }
+        OpCode::ArrayCallLocal(_, key, _, argc) => {
+            stack_pop_n(stack, *argc);
+            env.insert(*key, None);
+            stack.push(None);
+            None
+        }
+        OpCode::ArrayCallGlobal(_, _, _, argc) => {
+            stack_pop_n(stack, *argc);
+            stack.push(None);
+            None
+        }
         OpCode::AIndex(_) => {
             stack_pop_n(stack, 2);
             stack.push(None);
@@ -355,7 +290,7 @@ fn const_prop_step(
             stack.push(None);
             None
         }
-        OpCode::Call(_, name) => {
+        OpCode::Call(_, name, _) => {
             if let Some(arity) = arity_map.get(name) {
                 stack_pop_n(stack, *arity + 1);
             } else {
@@ -364,11 +299,43 @@ fn const_prop_step(
             stack.push(None);
             None
         }
-        OpCode::JumpTrue(_, _, _) | OpCode::JumpFalse(_, _, _) => {
+        OpCode::MakeClosure(_, _, len) | OpCode::NewArray(_, len) => {
+            stack_pop_n(stack, *len);
+            stack.push(None);
+            None
+        }
+        OpCode::NewObject(_, _, len) | OpCode::NewMap(_, len) => {
+            stack_pop_n(stack, *len * 2);
+            stack.push(None);
+            None
+        }
+        OpCode::GetField(_, _) | OpCode::GetMethod(_, _) => {
             let _ = stack_pop(stack);
+            stack.push(None);
+            None
+        }
+        OpCode::SetField(_, _) => {
+            stack_pop_n(stack, 2);
             None
         }
-        OpCode::Jump(_, _) | OpCode::LazyJump(_, _, _) | OpCode::Return(_) => None,
+        OpCode::JumpTrue(_, _, _)
+        | OpCode::JumpFalse(_, _, _)
+        | OpCode::Echo(_)
+        | OpCode::Throw(_) => {
+            let _ = stack_pop(stack);
+            None
+        }
+        OpCode::Catch(_) => {
+            // 异常可能在 try 块任意位置抛出, 处理器入口处不能假定任何局部变量的值
+            env.clear();
+            stack.push(None);
+            None
+        }
+        OpCode::Jump(_, _)
+        | OpCode::LazyJump(_, _, _)
+        | OpCode::Return(_)
+        | OpCode::Try(_, _)
+        | OpCode::EndTry(_) => None,
         OpCode::Nop(_) => None,
         OpCode::Not(_) | OpCode::Neg(_) | OpCode::Pos(_) | OpCode::SAdd(_) | OpCode::SSub(_) => {
             let value = stack_pop(stack);
@@ -537,6 +504,16 @@ fn collect_leaders(
     leader_vec
 }
 
+/// 返回每个基本块首条 IR 的逻辑地址, 按偏移升序排列
+pub(crate) fn block_leaders(table: &OpCodeTable) -> Vec<LocalAddr> {
+    let order = build_order(table);
+    let offset_to_index = build_offset_index(&order);
+    collect_leaders(&order, table, &offset_to_index)
+        .into_iter()
+        .map(|idx| order[idx])
+        .collect()
+}
+
 fn build_blocks(order: &[LocalAddr], leaders: &[usize]) -> (Vec<Block>, Vec<usize>) {
     let mut blocks: Vec<Block> = Vec::new();
     let mut instr_block = vec![0; order.len()];
@@ -593,11 +570,11 @@ fn block_successors(
         OpCode::Jump(_, _) | OpCode::LazyJump(_, _, _) => {
             add_jump_succ(&mut succs, op, offset_to_index, instr_block);
         }
-        OpCode::JumpTrue(_, _, _) | OpCode::JumpFalse(_, _, _) => {
+        OpCode::JumpTrue(_, _, _) | OpCode::JumpFalse(_, _, _) | OpCode::Try(_, _) => {
             add_jump_succ(&mut succs, op, offset_to_index, instr_block);
             add_fallthrough_succ(&mut succs, bi, blocks_len);
         }
-        OpCode::Return(_) => {}
+        OpCode::Return(_) | OpCode::Throw(_) => {}
         _ => add_fallthrough_succ(&mut succs, bi, blocks_len),
     }
     succs.sort_unstable();
@@ -695,7 +672,8 @@ fn jump_target(op: &OpCode) -> Option<LocalAddr> {
         OpCode::Jump(_, target)
         | OpCode::JumpTrue(_, target, _)
         | OpCode::JumpFalse(_, target, _)
-        | OpCode::LazyJump(_, target, _) => *target,
+        | OpCode::LazyJump(_, target, _)
+        | OpCode::Try(_, target) => *target,
         _ => None,
     }
 }
@@ -865,6 +843,7 @@ fn local_arith_peephole_table(table: &mut OpCodeTable) {
         i += 1;
     }
 
+    kept.positions = std::mem::take(&mut table.positions);
     let mut new_table = OpCodeTable::new();
     new_table.append_code(&kept);
     *table = new_table;
@@ -888,7 +867,7 @@ fn collect_local_reads(table: &OpCodeTable) -> HashSet<DefaultKey> {
             OpCode::Push(_, Operand::Val(key)) => {
                 reads.insert(*key);
             }
-            OpCode::GetIndexLocal(_, key) => {
+            OpCode::GetIndexLocal(_, key) | OpCode::ArrayCallLocal(_, key, _, _) => {
                 reads.insert(*key);
             }
             _ => {}
@@ -917,15 +896,24 @@ fn rebuild_locals(locals: &mut crate::compiler::ast::ssa_ir::LocalMap, dead: &Ha
 
 fn is_pure_opcode(op: &OpCode) -> bool {
     match op {
-        OpCode::Call(_, _)
+        OpCode::Call(..)
+        | OpCode::ArrayCallLocal(..)
+        | OpCode::ArrayCallGlobal(..)
         | OpCode::Jump(_, _)
         | OpCode::JumpTrue(_, _, _)
         | OpCode::JumpFalse(_, _, _)
         | OpCode::LazyJump(_, _, _)
         | OpCode::Return(_)
+        | OpCode::Echo(_)
+        | OpCode::Try(_, _)
+        | OpCode::EndTry(_)
+        | OpCode::Catch(_)
+        | OpCode::Throw(_)
         | OpCode::LoadGlobal(_, _, _)
+        | OpCode::LoadMapGlobal(_, _, _)
         | OpCode::SetArrayGlobal(_, _)
-        | OpCode::SetArrayLocal(_, _) => false,
+        | OpCode::SetArrayLocal(_, _)
+        | OpCode::SetField(_, _) => false,
         _ => true,
     }
 }
@@ -933,23 +921,37 @@ fn is_pure_opcode(op: &OpCode) -> bool {
 fn is_boundary(op: &OpCode) -> bool {
     matches!(
         op,
-        OpCode::Call(_, _)
+        OpCode::Call(..)
+            | OpCode::ArrayCallLocal(..)
+            | OpCode::ArrayCallGlobal(..)
             | OpCode::Jump(_, _)
             | OpCode::JumpTrue(_, _, _)
             | OpCode::JumpFalse(_, _, _)
             | OpCode::LazyJump(_, _, _)
             | OpCode::Return(_)
+            | OpCode::Try(_, _)
+            | OpCode::Catch(_)
+            | OpCode::Throw(_)
     )
 }
 
 fn stack_effect(op: &OpCode) -> i32 {
     match op {
-        OpCode::Push(_, _) | OpCode::StoreLocal(_, _, _) | OpCode::StoreGlobal(_, _, _) => 1,
+        OpCode::Push(_, _)
+        | OpCode::StoreLocal(_, _, _)
+        | OpCode::StoreGlobal(_, _, _)
+        | OpCode::Catch(_) => 1,
         OpCode::LoadLocal(_, _, _) | OpCode::LoadGlobal(_, _, _) => -1,
         OpCode::LoadArrayLocal(_, _, len) | OpCode::LoadArrayGlobal(_, _, len) => -(*len as i32),
+        OpCode::LoadMapLocal(_, _, len) | OpCode::LoadMapGlobal(_, _, len) => -(*len as i32) * 2,
         OpCode::SetArrayLocal(_, _) | OpCode::SetArrayGlobal(_, _) => -2,
+        OpCode::ArrayCallLocal(_, _, _, argc) | OpCode::ArrayCallGlobal(_, _, _, argc) => {
+            1 - *argc as i32
+        }
         OpCode::AIndex(_) | OpCode::Ref(_) => -1,
-        OpCode::GetIndexLocal(_, _) => 0,
+        OpCode::GetIndexLocal(_, _) | OpCode::GetField(_, _) | OpCode::GetMethod(_, _) => 0,
+        OpCode::SetField(_, _) => -2,
+        OpCode::NewObject(_, _, len) | OpCode::NewMap(_, len) => 1 - *len as i32 * 2,
         OpCode::Not(_)
         | OpCode::Neg(_)
         | OpCode::Pos(_)
@@ -982,14 +984,19 @@ fn stack_effect(op: &OpCode) -> i32 {
         | OpCode::RmdS(_)
         | OpCode::BAndS(_)
         | OpCode::BOrS(_)
-        | OpCode::BXorS(_) => -1,
+        | OpCode::BXorS(_)
+        | OpCode::Echo(_)
+        | OpCode::Throw(_) => -1,
         OpCode::Pop(_, len) => -(*len as i32),
-        OpCode::Call(_, _)
+        OpCode::MakeClosure(_, _, len) | OpCode::NewArray(_, len) => 1 - *len as i32,
+        OpCode::Call(..)
         | OpCode::Jump(_, _)
         | OpCode::JumpTrue(_, _, _)
         | OpCode::JumpFalse(_, _, _)
         | OpCode::LazyJump(_, _, _)
         | OpCode::Return(_)
+        | OpCode::Try(_, _)
+        | OpCode::EndTry(_)
         | OpCode::Nop(_) => 0,
     }
 }
@@ -999,7 +1006,9 @@ fn statement_is_dead_definition(stmt: &[(LocalAddr, OpCode)], dead: &HashSet<Def
         return false;
     };
     let is_dead_store = match last {
-        OpCode::LoadLocal(_, key, _) | OpCode::LoadArrayLocal(_, key, _) => dead.contains(key),
+        OpCode::LoadLocal(_, key, _)
+        | OpCode::LoadArrayLocal(_, key, _)
+        | OpCode::LoadMapLocal(_, key, _) => dead.contains(key),
         _ => false,
     };
     if !is_dead_store {
@@ -1043,6 +1052,7 @@ fn eliminate_dead_locals_in_table(table: &mut OpCodeTable, dead: &HashSet<Defaul
         kept.opcodes.insert(a, o);
     }
 
+    kept.positions = std::mem::take(&mut table.positions);
     let mut new_table = OpCodeTable::new();
     new_table.append_code(&kept);
     *table = new_table;
@@ -1055,8 +1065,9 @@ pub(crate) fn eliminate_dead_locals(code: &mut Code) {
         };
         let reads = collect_local_reads(table);
         let mut dead = HashSet::new();
-        for key in func.locals.locals.keys() {
-            if !reads.contains(key) {
+        for (key, index) in &func.locals.locals {
+            // 形参 (包括方法的 self) 由调用者压栈, 删去其定义会使之后的形参错位
+            if *index >= func.args && !reads.contains(key) {
                 dead.insert(*key);
             }
         }
@@ -0,0 +1,85 @@
+use crate::compiler::ast::ssa_ir::{
+    LocalMap, OpCode, OpCodeTable, Operand, ValueAlloc, ValueGuessType,
+};
+use crate::compiler::ast::{ASTExprTree, ASTStmtTree};
+use crate::compiler::lexer::Token;
+use crate::compiler::parser::ParserError;
+use crate::compiler::parser::symbol_table::{ContextType, ElementType};
+use crate::compiler::semantic::Semantic;
+use crate::compiler::semantic::block::block_semantic;
+use crate::compiler::semantic::expression::lower_expr;
+
+pub fn try_semantic(
+    semantic: &mut Semantic,
+    body: Vec<ASTStmtTree>,
+    catch_name: Token,
+    catch_body: Vec<ASTStmtTree>,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    locals: &mut LocalMap,
+) -> Result<OpCodeTable, ParserError> {
+    let mut code_table = OpCodeTable::new();
+    let try_addr = code_table.add_opcode(OpCode::Try(None, None));
+
+    semantic
+        .compiler_data()
+        .symbol_table
+        .add_context(ContextType::Try);
+    let body_table = block_semantic(semantic, body, code, global_values, locals)?;
+    semantic.compiler_data().symbol_table.exit_context();
+
+    code_table.append_code(&body_table);
+    code_table.add_opcode(OpCode::EndTry(None));
+    let jump_addr = code_table.add_opcode(OpCode::Jump(None, None));
+
+    let symbol_table = &mut semantic.compiler_data().symbol_table;
+    symbol_table.add_context(ContextType::Catch);
+    if symbol_table.check_element(catch_name.text()) {
+        return Err(ParserError::SymbolDefined(catch_name));
+    }
+    symbol_table.add_symbol(catch_name.value().unwrap(), &catch_name, ElementType::Value);
+
+    // 变量按名称解析, 同名的异常变量复用同一个槽位
+    let key = if let Some(key) = code
+        .find_value_key(&catch_name.value().unwrap())
+        .filter(|key| locals.get_index(*key).is_some())
+    {
+        if let Some(value) = code.find_value_mut(key) {
+            value.type_ = ValueGuessType::Array;
+        }
+        key
+    } else {
+        let key = code.alloc_value(catch_name, ValueGuessType::Array);
+        locals.add_local(key);
+        key
+    };
+
+    let handler_addr = code_table.add_opcode(OpCode::Catch(None));
+    code_table.add_opcode(OpCode::LoadLocal(None, key, Operand::Val(key)));
+    let catch_table = block_semantic(semantic, catch_body, code, global_values, locals)?;
+    semantic.compiler_data().symbol_table.exit_context();
+
+    code_table.append_code(&catch_table);
+    let end_addr = code_table.add_opcode(OpCode::Nop(None));
+
+    if let Some(OpCode::Try(_, target)) = code_table.find_code_mut(try_addr) {
+        *target = Some(handler_addr);
+    }
+    if let Some(OpCode::Jump(_, target)) = code_table.find_code_mut(jump_addr) {
+        *target = Some(end_addr);
+    }
+    Ok(code_table)
+}
+
+pub fn throw_semantic(
+    semantic: &mut Semantic,
+    expr: &ASTExprTree,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+) -> Result<OpCodeTable, ParserError> {
+    let mut code_table = OpCodeTable::new();
+    let value = lower_expr(semantic, expr, code, global_values, None)?;
+    code_table.append_code(&value.2);
+    code_table.add_opcode(OpCode::Throw(None));
+    Ok(code_table)
+}
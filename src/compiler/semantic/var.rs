@@ -1,18 +1,40 @@
 use crate::compiler::ast::ASTExprTree;
 use crate::compiler::ast::ssa_ir::OpCode::{
-    Call, LoadArrayGlobal, LoadArrayLocal, LoadGlobal, LoadLocal, Push,
+    Call, LoadArrayGlobal, LoadArrayLocal, LoadGlobal, LoadLocal, LoadMapGlobal, LoadMapLocal, Push,
 };
 use crate::compiler::ast::ssa_ir::{LocalMap, OpCodeTable, Operand, ValueAlloc, ValueGuessType};
 use crate::compiler::lexer::Token;
 use crate::compiler::parser::ParserError;
 use crate::compiler::parser::symbol_table::ElementType::Value;
 use crate::compiler::semantic::Semantic;
-use crate::compiler::semantic::expression::expr_semantic;
+use crate::compiler::semantic::expression::{
+    check_map_key, check_type_hint, expr_semantic, resolve_annotation,
+};
+use slotmap::DefaultKey;
 use smol_str::SmolStr;
 
+// 分配变量, 带类型标注时检查初始值的推断类型, 并以标注的类型固定变量类型
+fn alloc_var(
+    semantic: &mut Semantic,
+    name: Token,
+    annotation: Option<&Token>,
+    init: Option<&Token>,
+    type_: ValueGuessType,
+    code: &mut ValueAlloc,
+) -> Result<DefaultKey, ParserError> {
+    let Some(annotation) = annotation else {
+        return Ok(code.alloc_value(name, type_));
+    };
+    let declared = resolve_annotation(semantic, annotation)?;
+    check_type_hint(init.unwrap_or(annotation), &declared, &type_)?;
+    Ok(code.alloc_declared(name, declared))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn array_semantic(
     semantic: &mut Semantic,
     name: Token,
+    annotation: Option<Token>,
     elements: Vec<ASTExprTree>,
     code: &mut ValueAlloc,
     global_values: Option<&ValueAlloc>,
@@ -23,8 +45,15 @@ pub fn array_semantic(
     if symbol_table.check_element(name.text()) {
         return Err(ParserError::SymbolDefined(name));
     }
-    symbol_table.add_element(name.value().unwrap(), Value);
-    let key = code.alloc_value(name, ValueGuessType::Array);
+    symbol_table.add_symbol(name.value().unwrap(), &name, Value);
+    let key = alloc_var(
+        semantic,
+        name,
+        annotation.as_ref(),
+        None,
+        ValueGuessType::Array,
+        code,
+    )?;
     let mut opcode_vec = OpCodeTable::new();
     locals.add_local(key);
 
@@ -44,9 +73,58 @@ pub fn array_semantic(
     Ok(opcode_vec)
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn map_semantic(
+    semantic: &mut Semantic,
+    name: Token,
+    annotation: Option<Token>,
+    entries: Vec<(ASTExprTree, ASTExprTree)>,
+    code: &mut ValueAlloc,
+    global_values: Option<&ValueAlloc>,
+    locals: &mut LocalMap,
+    root: bool,
+) -> Result<OpCodeTable, ParserError> {
+    let symbol_table = &mut semantic.compiler_data().symbol_table;
+    if symbol_table.check_element(name.text()) {
+        return Err(ParserError::SymbolDefined(name));
+    }
+    symbol_table.add_symbol(name.value().unwrap(), &name, Value);
+    let key = alloc_var(
+        semantic,
+        name,
+        annotation.as_ref(),
+        None,
+        ValueGuessType::Map,
+        code,
+    )?;
+    let mut opcode_vec = OpCodeTable::new();
+    locals.add_local(key);
+
+    let map_length = entries.len();
+
+    // 键值对按顺序交替压栈
+    for (entry_key, entry_value) in entries {
+        let ret_m = expr_semantic(semantic, Some(entry_key.clone()), code, global_values)?;
+        check_map_key(&entry_key, &ret_m.1)?;
+        opcode_vec.append_code(&ret_m.2);
+        let ret_m = expr_semantic(semantic, Some(entry_value), code, global_values)?;
+        opcode_vec.append_code(&ret_m.2);
+    }
+
+    if root {
+        opcode_vec.add_opcode(LoadMapGlobal(None, key, map_length));
+    } else {
+        opcode_vec.add_opcode(LoadMapLocal(None, key, map_length));
+    }
+
+    Ok(opcode_vec)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn array_fill_semantic(
     semantic: &mut Semantic,
     name: Token,
+    annotation: Option<Token>,
     value: ASTExprTree,
     count: ASTExprTree,
     code: &mut ValueAlloc,
@@ -58,8 +136,15 @@ pub fn array_fill_semantic(
     if symbol_table.check_element(name.text()) {
         return Err(ParserError::SymbolDefined(name));
     }
-    symbol_table.add_element(name.value().unwrap(), Value);
-    let key = code.alloc_value(name, ValueGuessType::Array);
+    symbol_table.add_symbol(name.value().unwrap(), &name, Value);
+    let key = alloc_var(
+        semantic,
+        name,
+        annotation.as_ref(),
+        None,
+        ValueGuessType::Array,
+        code,
+    )?;
     locals.add_local(key);
     let mut opcode_vec = OpCodeTable::new();
 
@@ -71,7 +156,7 @@ pub fn array_fill_semantic(
         None,
         Operand::Reference(SmolStr::new("type/array_fill")),
     ));
-    opcode_vec.add_opcode(Call(None, SmolStr::new("array_fill")));
+    opcode_vec.add_opcode(Call(None, SmolStr::new("array_fill"), 2));
 
     let opread = Operand::Call(SmolStr::new("array_fill"));
     if root {
@@ -82,9 +167,11 @@ pub fn array_fill_semantic(
     Ok(opcode_vec)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn var_semantic(
     semantic: &mut Semantic,
     name: Token,
+    annotation: Option<Token>,
     init_var: Option<ASTExprTree>,
     code: &mut ValueAlloc,
     global_values: Option<&ValueAlloc>,
@@ -95,12 +182,20 @@ pub fn var_semantic(
     if symbol_table.check_element(name.text()) {
         return Err(ParserError::SymbolDefined(name));
     }
-    symbol_table.add_element(name.value().unwrap(), Value);
+    symbol_table.add_symbol(name.value().unwrap(), &name, Value);
     let mut opcode_vec = OpCodeTable::new();
+    let init = init_var.as_ref().map(|init| init.token().clone());
     let ret_m = expr_semantic(semantic, init_var, code, global_values)?;
     opcode_vec.append_code(&ret_m.2);
     let opread = ret_m.clone();
-    let key = code.alloc_value(name, ret_m.1);
+    let key = alloc_var(
+        semantic,
+        name,
+        annotation.as_ref(),
+        init.as_ref(),
+        ret_m.1,
+        code,
+    )?;
     locals.add_local(key);
     if root {
         opcode_vec.add_opcode(LoadGlobal(None, key, opread.0));
@@ -1,18 +1,28 @@
 use crate::compiler::Compiler;
 use crate::compiler::ast::vm_ir::Value;
-use crate::compiler::file::SourceFile;
-use crate::library::load_libraries;
+use crate::compiler::diagnostic::{Diagnostic, Severity};
+use crate::compiler::file::{SourceFile, module_name};
+use crate::compiler::optimize::{Optimizations, Pass};
+use crate::library::{disable_library, load_libraries};
 use crate::runtime::executor::call_function;
-use crate::runtime::{GlobalStore, MetadataUnit, MethodInfo, SharedGlobals, SharedSync};
+use crate::runtime::limits::ExecutionLimits;
+use crate::runtime::{
+    GlobalStore, MetadataUnit, MethodInfo, RuntimeEnv, RuntimeError, SharedGlobals, SharedSync,
+    root_method,
+};
 use dashu::float::FBig;
 use dashu::float::round::mode::HalfAway;
-use smol_str::{SmolStr, ToSmolStr};
+use linked_hash_map::LinkedHashMap;
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
 use std::collections::HashSet;
-use std::ffi::{CStr, CString, c_char};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::fmt::Display;
 use std::{ptr, slice};
 
 pub mod compiler;
 pub mod library;
+pub mod lsp;
+pub mod project;
 pub mod runtime;
 
 pub struct OpenEX {
@@ -20,9 +30,49 @@ pub struct OpenEX {
     metadata: Vec<MetadataUnit<'static>>,
     globals: SharedGlobals,
     sync_table: SharedSync,
+    last_error: Option<CString>, // 最近一次失败的错误信息, 由 openex_last_error 读取
+    limits: ExecutionLimits,     // 之后每次执行脚本时使用的执行限制
+}
+
+impl OpenEX {
+    fn runtime_env(&self) -> RuntimeEnv {
+        RuntimeEnv::new(self.compiler.natives().clone())
+            .with_optimizations(self.compiler.get_optimizations())
+            .with_limits(self.limits)
+    }
+
+    fn set_error(&mut self, message: &str) {
+        let message = message.trim_end().replace('\0', "\\0");
+        self.last_error = CString::new(message).ok();
+    }
+
+    // 按模块名查找元数据单元与全局变量索引, 找不到时记录错误信息
+    fn find_global(&mut self, module: &str, name: &str) -> Option<(usize, usize)> {
+        let found = self
+            .metadata
+            .iter()
+            .position(|unit| unit.names == module)
+            .and_then(|unit_index| {
+                let vm_ir = self.compiler.get_files()[unit_index].ir_table.as_ref()?;
+                Some((unit_index, vm_ir.find_global(name)?))
+            });
+        if found.is_none() {
+            self.set_error(&format!("no such global variable: {module}/{name}"));
+        }
+        found
+    }
+}
+
+// 读取以 NUL 结尾的 UTF-8 字符串参数, 空指针或非法编码时返回 None
+unsafe fn c_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
 }
 
 #[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ValueTag {
     Int = 0,
     Bool = 1,
@@ -30,6 +80,23 @@ pub enum ValueTag {
     String = 3,
     Ref = 4,
     Null = 5,
+    Array = 6,
+    Map = 7,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CArray {
+    pub len: usize,
+    pub items: *mut CValue, // 长度为 len 的元素数组
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CMap {
+    pub len: usize,
+    pub keys: *mut *const c_char, // 长度为 len 的键数组
+    pub values: *mut CValue,      // 与 keys 一一对应的值数组
 }
 
 #[repr(C)]
@@ -38,6 +105,8 @@ pub union ValueData {
     pub b: bool,
     pub f: f64,
     pub s: *const c_char, // 字符串和 Ref 都用指针
+    pub a: CArray,
+    pub m: CMap,
 }
 
 #[repr(C)]
@@ -46,20 +115,44 @@ pub struct CValue {
     pub data: ValueData,
 }
 
+/// 值无法在解释器与 C 之间转换的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertError {
+    InteriorNul,    // 字符串或字典键中含有 NUL 字符
+    NonFiniteFloat, // 浮点数为 NaN 或无穷大
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InteriorNul => write!(f, "string contains a NUL character"),
+            Self::NonFiniteFloat => write!(f, "float is NaN or infinite"),
+        }
+    }
+}
+
 impl CValue {
+    const fn null() -> Self {
+        Self {
+            tag: ValueTag::Null,
+            data: ValueData { i: 0 },
+        }
+    }
+
     /// # Safety
     /// 用于将 C ffi 传输过来的类型转换成 `OpenEX` 解释器内部类型表示
-    #[must_use]
-    pub unsafe fn to_value(&self) -> Value {
+    /// # Errors
+    /// 值中含有 NaN 或无穷大的浮点数时返回错误
+    pub unsafe fn to_value(&self) -> Result<Value, ConvertError> {
         unsafe {
-            match self.tag {
+            Ok(match self.tag {
                 ValueTag::Int => Value::Int(self.data.i),
                 ValueTag::Bool => Value::Bool(self.data.b),
                 ValueTag::Float => Value::Float(
                     FBig::<HalfAway, 2>::try_from(self.data.f)
-                        .expect("f64 is NaN or Inf")
+                        .map_err(|_| ConvertError::NonFiniteFloat)?
                         .to_decimal()
-                        .unwrap(),
+                        .value(),
                 ),
                 ValueTag::String => {
                     let c_str = CStr::from_ptr(self.data.s);
@@ -70,15 +163,78 @@ impl CValue {
                     Value::Ref(SmolStr::new(c_str.to_string_lossy()))
                 }
                 ValueTag::Null => Value::Null,
+                ValueTag::Array => {
+                    let array = self.data.a;
+                    let items = c_slice(array.items, array.len);
+                    let elements = items
+                        .iter()
+                        .map(|item| item.to_value())
+                        .collect::<Result<_, _>>()?;
+                    Value::Array(elements)
+                }
+                ValueTag::Map => {
+                    let map = self.data.m;
+                    let keys = c_slice(map.keys, map.len);
+                    let values = c_slice(map.values, map.len);
+                    let mut result = LinkedHashMap::new();
+                    for (key, value) in keys.iter().zip(values) {
+                        let key = CStr::from_ptr(*key).to_string_lossy();
+                        result.insert(SmolStr::new(key), value.to_value()?);
+                    }
+                    Value::Map(result)
+                }
+            })
+        }
+    }
+}
+
+// 空指针或零长度时返回空切片
+unsafe fn c_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(ptr, len) }
+    }
+}
+
+// 将 Vec 的所有权交给 C, 由 openex_free_c_value 回收
+fn into_c_raw<T>(elements: Vec<T>) -> *mut T {
+    Box::into_raw(elements.into_boxed_slice()).cast::<T>()
+}
+
+unsafe fn free_c_raw<T>(ptr: *mut T, len: usize) -> Box<[T]> {
+    unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) }
+}
+
+fn c_string(s: &str) -> Result<*const c_char, ConvertError> {
+    CString::new(s)
+        .map(|c_str| c_str.into_raw().cast_const())
+        .map_err(|_| ConvertError::InteriorNul)
+}
+
+// 逐个转换元素, 失败时释放已经转换的部分
+fn into_c_values(values: impl IntoIterator<Item = Value>) -> Result<Vec<CValue>, ConvertError> {
+    let mut items = vec![];
+    for value in values {
+        match into_c_value(value) {
+            Ok(item) => items.push(item),
+            Err(error) => {
+                for item in &mut items {
+                    unsafe { openex_free_c_value(item) };
+                }
+                return Err(error);
             }
         }
     }
+    Ok(items)
 }
 
-/// # Panics
-#[must_use]
-pub fn into_c_value(value: Value) -> CValue {
-    match value {
+/// 超出 i64 范围的整数以十进制字符串的形式传递
+/// # Errors
+/// 字符串或字典键中含有 NUL 字符时返回错误, 此时不会留下需要释放的内存
+pub fn into_c_value(value: Value) -> Result<CValue, ConvertError> {
+    Ok(match value {
+        Value::BigInt(i) => into_c_value(Value::String(i.to_smolstr()))?,
         Value::Int(i) => CValue {
             tag: ValueTag::Int,
             data: ValueData { i },
@@ -90,34 +246,61 @@ pub fn into_c_value(value: Value) -> CValue {
         Value::Float(f) => CValue {
             tag: ValueTag::Float,
             data: ValueData {
-                f: f.to_f64().unwrap(),
+                f: f.to_f64().value(),
             },
         },
-        Value::String(s) => {
-            // 将字符串转换到堆上，并交出所有权给 C
-            let c_str = CString::new(s.as_str()).unwrap();
+        // 将字符串转换到堆上，并交出所有权给 C
+        Value::String(s) => CValue {
+            tag: ValueTag::String,
+            data: ValueData { s: c_string(&s)? },
+        },
+        Value::Ref(s) => CValue {
+            tag: ValueTag::Ref,
+            data: ValueData { s: c_string(&s)? },
+        },
+        // 闭包以其隐藏函数路径的引用交给 C, 捕获的值不会传出
+        Value::Closure(closure) => into_c_value(Value::Ref(closure.path.clone()))?,
+        // 对象以其字段字典的形式交给 C, 类名不会传出
+        Value::Object(object) => into_c_value(Value::Map(object.fields.lock().unwrap().clone()))?,
+        Value::Null => CValue::null(),
+        Value::Array(elements) => {
+            let items = into_c_values(elements)?;
             CValue {
-                tag: ValueTag::String,
+                tag: ValueTag::Array,
                 data: ValueData {
-                    s: c_str.into_raw(),
+                    a: CArray {
+                        len: items.len(),
+                        items: into_c_raw(items),
+                    },
                 },
             }
         }
-        Value::Ref(s) => {
-            let c_str = CString::new(s.as_str()).unwrap();
+        Value::Map(map) => {
+            let len = map.len();
+            // 先检查全部键, 值转换失败时只需要丢弃尚未交出的键
+            let mut keys = Vec::with_capacity(len);
+            let mut values = Vec::with_capacity(len);
+            for (key, value) in map {
+                keys.push(CString::new(key.as_str()).map_err(|_| ConvertError::InteriorNul)?);
+                values.push(value);
+            }
+            let values = into_c_values(values)?;
+            let keys: Vec<*const c_char> = keys
+                .into_iter()
+                .map(|key| key.into_raw().cast_const())
+                .collect();
             CValue {
-                tag: ValueTag::Ref,
+                tag: ValueTag::Map,
                 data: ValueData {
-                    s: c_str.into_raw(),
+                    m: CMap {
+                        len,
+                        keys: into_c_raw(keys),
+                        values: into_c_raw(values),
+                    },
                 },
             }
         }
-        Value::Null => CValue {
-            tag: ValueTag::Null,
-            data: ValueData { i: 0 },
-        },
-        _ => todo!(),
-    }
+    })
 }
 
 #[repr(C)]
@@ -133,15 +316,187 @@ fn leak_string(s: String) -> &'static str {
     Box::leak(s.into_boxed_str())
 }
 
+#[unsafe(no_mangle)]
+/// 禁用交互实例中指定的本地库 (如 `fs`), 用于沙箱环境下的宿主程序.
+/// 需要在 `openex_compile` 之前调用, 被禁用的库会从该实例中移除, 脚本也无法导入.
+/// # Safety
+/// `name` 是一个有效的 C 字符串指针(以 NUL 结尾)
+pub unsafe extern "C" fn openex_disable_library(
+    handle_raw: *mut OpenEX,
+    name: *const c_char,
+) -> OpenExStatus {
+    if name.is_null() {
+        return OpenExStatus::FfiError;
+    }
+    let c_str = unsafe { CStr::from_ptr(name) };
+    let Ok(name) = c_str.to_str() else {
+        return OpenExStatus::FfiError;
+    };
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+    disable_library(&mut handle.compiler, name);
+    OpenExStatus::Success
+}
+
+#[unsafe(no_mangle)]
+/// 禁止交互实例中的脚本声明 `function native("库", "符号")` 形式的动态库函数, 用于沙箱环境下的宿主程序.
+/// 需要在 `openex_compile` 之前调用, 声明动态库函数的脚本会编译失败.
+/// # Safety
+pub unsafe extern "C" fn openex_disable_foreign(handle_raw: *mut OpenEX) -> OpenExStatus {
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+    handle.compiler.natives_mut().disable_foreign();
+    OpenExStatus::Success
+}
+
+#[unsafe(no_mangle)]
+/// 开启或关闭交互实例中的单项优化 (如 `const-prop`), 用于排查优化导致的错误结果.
+/// 编译期优化需要在 `openex_compile` 之前设置, `call-memo` 对之后的函数调用生效.
+/// # Safety
+/// `pass` 是一个有效的 C 字符串指针(以 NUL 结尾)
+pub unsafe extern "C" fn openex_set_optimization(
+    handle_raw: *mut OpenEX,
+    pass: *const c_char,
+    enabled: bool,
+) -> OpenExStatus {
+    if pass.is_null() {
+        return OpenExStatus::FfiError;
+    }
+    let c_str = unsafe { CStr::from_ptr(pass) };
+    let Ok(Ok(pass)) = c_str.to_str().map(str::parse::<Pass>) else {
+        return OpenExStatus::FfiError;
+    };
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+    let mut optimize = handle.compiler.get_optimizations();
+    optimize.set(pass, enabled);
+    handle.compiler.set_optimizations(optimize);
+    OpenExStatus::Success
+}
+
+#[unsafe(no_mangle)]
+/// 设置之后每次执行脚本 (`openex_run` 与 `openex_call_function`) 时的执行限制, 参数为 0 表示不限制.
+/// 指令数、超时与 `max_length` 按每次执行单独计算, `max_length` 限制累计新建的数组与映射元素数、字符串字节数及任意精度整数字节数.
+/// 超出限制时执行返回 `RuntimeError`, 错误信息中的异常类型为 `LimitExceeded`.
+/// # Safety
+pub unsafe extern "C" fn openex_set_limits(
+    handle_raw: *mut OpenEX,
+    max_instructions: u64,
+    max_call_depth: usize,
+    max_length: usize,
+    timeout_ms: u64,
+) -> OpenExStatus {
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+    handle.limits = ExecutionLimits {
+        max_instructions: (max_instructions != 0).then_some(max_instructions),
+        max_call_depth: (max_call_depth != 0).then_some(max_call_depth),
+        max_length: (max_length != 0).then_some(max_length),
+        timeout: (timeout_ms != 0).then(|| std::time::Duration::from_millis(timeout_ms)),
+    };
+    OpenExStatus::Success
+}
+
+/// 宿主提供的本地函数回调
+/// `args` 为长度 `arg_count` 的参数数组, 仅在回调期间有效
+/// 回调需要向 `out_result` 写入返回值, 返回非 `Success` 时脚本中会抛出 `NativeException`
+/// 在 C 中可以为 NULL, 注册时传入 NULL 会返回 `FfiError`
+pub type OpenExNativeCallback = Option<
+    unsafe extern "C" fn(
+        args: *const CValue,
+        arg_count: usize,
+        out_result: *mut CValue,
+        user_data: *mut c_void,
+    ) -> OpenExStatus,
+>;
+
+// 宿主传入的上下文指针, 跨线程使用时的同步由宿主保证
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    const fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+#[unsafe(no_mangle)]
+/// 向交互实例注册一个由宿主实现的本地函数, 需要在 `openex_compile` 之前调用.
+/// 脚本需要在名为 `module` 的源文件中通过 `function native name(...);` 声明该函数.
+/// # Safety
+/// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+/// `callback` 在交互实例的整个生命周期内都必须可以调用, `user_data` 原样传递给回调
+pub unsafe extern "C" fn openex_register_native(
+    handle_raw: *mut OpenEX,
+    module: *const c_char,
+    name: *const c_char,
+    arity: usize,
+    callback: OpenExNativeCallback,
+    user_data: *mut c_void,
+) -> OpenExStatus {
+    if module.is_null() || name.is_null() {
+        return OpenExStatus::FfiError;
+    }
+    let (Ok(module), Ok(name)) = (
+        unsafe { CStr::from_ptr(module) }.to_str(),
+        unsafe { CStr::from_ptr(name) }.to_str(),
+    ) else {
+        return OpenExStatus::FfiError;
+    };
+    let Some(callback) = callback else {
+        return OpenExStatus::FfiError;
+    };
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+
+    let path = format_smolstr!("{module}/{name}");
+    let user_data = UserData(user_data);
+    handle
+        .compiler
+        .natives_mut()
+        .register_native(module, name, arity, move |args| {
+            let native_error = |reason: &dyn Display| {
+                RuntimeError::Exception(
+                    SmolStr::new("NativeException"),
+                    format_smolstr!("{path}: {reason}"),
+                )
+            };
+            let mut c_args =
+                into_c_values(args.iter().cloned()).map_err(|error| native_error(&error))?;
+            let mut out = CValue::null();
+            let status =
+                unsafe { callback(c_args.as_ptr(), c_args.len(), &raw mut out, user_data.get()) };
+            for arg in &mut c_args {
+                unsafe { openex_free_c_value(arg) };
+            }
+            match status {
+                // 返回值的内存由宿主管理, 这里只做拷贝
+                OpenExStatus::Success => {
+                    unsafe { out.to_value() }.map_err(|error| native_error(&error))
+                }
+                _ => Err(native_error(&"native callback failed")),
+            }
+        });
+    OpenExStatus::Success
+}
+
 #[unsafe(no_mangle)]
 /// 对其他语言提供的本地接口, 该函数负责初始化编译前端环境并编译 `OpenEX` 标准库.
 /// # Safety
 /// `lib_path` 是一个有效的 C 字符串指针(以 NUL 结尾) 其字符串内容为标准库的路径
 /// `lib_path` 可以为 NULL, 为NULL时解释器会在当前目录查找标准库实现
+/// `opt_level` 为优化等级 (0-2), 超过最高等级时按最高等级处理
 ///
 /// 函数返回值不应该被调用者修改内部的任何字段, 仅当句柄传递给各功能函数
-/// C 函数定义: `void *openex_init()`;
-pub unsafe extern "C" fn openex_init(lib_path: *const c_char) -> *mut OpenEX {
+/// C 函数定义: `void *openex_init(const char *lib_path, unsigned char opt_level)`;
+pub unsafe extern "C" fn openex_init(lib_path: *const c_char, opt_level: u8) -> *mut OpenEX {
     let path = if lib_path.is_null() {
         None
     } else {
@@ -153,6 +508,7 @@ pub unsafe extern "C" fn openex_init(lib_path: *const c_char) -> *mut OpenEX {
     };
 
     let mut compiler = Compiler::new();
+    compiler.set_optimizations(Optimizations::with_level(opt_level));
     match load_libraries(&mut compiler, path, &HashSet::new()) {
         Ok(()) => {}
         Err(_e) => {
@@ -166,6 +522,8 @@ pub unsafe extern "C" fn openex_init(lib_path: *const c_char) -> *mut OpenEX {
         metadata: Vec::new(),
         globals: std::sync::Arc::new(std::sync::Mutex::new(GlobalStore::empty())),
         sync_table: std::sync::Arc::new(crate::runtime::context::SyncTable::new(&[])),
+        last_error: None,
+        limits: ExecutionLimits::default(),
     }))
 }
 
@@ -215,7 +573,15 @@ pub unsafe extern "C" fn openex_compile(handle_raw: *mut OpenEX) -> OpenExStatus
     if let Some(handle) = unsafe { handle_raw.as_mut() } {
         match handle.compiler.compile() {
             Ok(()) => OpenExStatus::Success,
-            Err(()) => OpenExStatus::ParseError,
+            Err(()) => {
+                let error = handle
+                    .compiler
+                    .last_error()
+                    .unwrap_or("compile failed")
+                    .to_string();
+                handle.set_error(&error);
+                OpenExStatus::ParseError
+            }
         }
     } else {
         OpenExStatus::FfiError
@@ -239,9 +605,10 @@ pub unsafe extern "C" fn openex_initialize_executor(handle_raw: *mut OpenEX) ->
                 let name = func.name.clone();
                 methods.push(MethodInfo {
                     name,
-                    r_name: func.filename.split('.').next().unwrap().to_smolstr(),
+                    r_name: module_name(&func.filename),
                     locals: func.locals,
                     codes: func.clone_codes().unwrap_or_default(),
+                    lines: func.lines.clone(),
                     is_native: func.is_native,
                     sync: func.sync,
                     args: func.args,
@@ -249,7 +616,7 @@ pub unsafe extern "C" fn openex_initialize_executor(handle_raw: *mut OpenEX) ->
             }
 
             //WARN ffi 主动解控元数据单元名称的内存管理
-            let name_owned = file.name.split('.').next().unwrap().to_string();
+            let name_owned = file.module_name().to_string();
             let static_name: &'static str = leak_string(name_owned);
 
             metadata.push(MetadataUnit {
@@ -257,12 +624,21 @@ pub unsafe extern "C" fn openex_initialize_executor(handle_raw: *mut OpenEX) ->
                 constant_table: Box::leak(Box::new(vm_ir.get_constant_table())),
                 names: static_name,
                 methods,
+                file: file.name.to_smolstr(),
+                source: None,
                 globals: vm_ir.get_locals_len(),
                 root_code: vm_ir.clone_codes(),
+                root_lines: vm_ir.clone_lines(),
                 library: file.is_library,
+                foreign: vm_ir.get_foreign().to_vec(),
             });
         }
 
+        // 动态库函数只在执行引擎初始化时加载, 编译期间不会打开动态库
+        if let Err(e) = handle.compiler.natives_mut().bind_foreign(&metadata) {
+            handle.set_error(&e);
+            return OpenExStatus::RuntimeError;
+        }
         handle.metadata = metadata;
         handle.globals = GlobalStore::shared_new(handle.metadata.as_slice());
         handle.sync_table =
@@ -273,6 +649,39 @@ pub unsafe extern "C" fn openex_initialize_executor(handle_raw: *mut OpenEX) ->
     }
 }
 
+#[unsafe(no_mangle)]
+/// 执行入口文件 (第一个非库源文件) 的全局代码, 全局变量在此之后才具有初始值
+/// 需要在 `openex_initialize_executor` 之后调用, 出现未捕获的运行时错误时返回 `RuntimeError`
+/// # Safety
+pub unsafe extern "C" fn openex_run(handle_raw: *mut OpenEX) -> OpenExStatus {
+    let Some(handle) = (unsafe { handle_raw.as_mut() }) else {
+        return OpenExStatus::FfiError;
+    };
+    let Some(unit_index) = handle.metadata.iter().position(|unit| !unit.library) else {
+        handle.set_error("no entry unit, the executor is not initialized");
+        return OpenExStatus::RuntimeError;
+    };
+    let unit = &handle.metadata[unit_index];
+    let result = call_function(
+        &root_method(unit),
+        unit.constant_table,
+        handle.metadata.as_slice(),
+        unit_index,
+        handle.globals.clone(),
+        handle.sync_table.clone(),
+        handle.runtime_env(),
+        None,
+        vec![],
+    );
+    match result {
+        Ok(_) => OpenExStatus::Success,
+        Err(report) => {
+            handle.set_error(&report);
+            OpenExStatus::RuntimeError
+        }
+    }
+}
+
 #[unsafe(no_mangle)]
 /// 调用一个 `OpenEX` 函数, 需要指定文件名和函数名
 /// # Safety
@@ -310,10 +719,13 @@ pub unsafe extern "C" fn openex_call_function(
         unsafe { slice::from_raw_parts(args_ptr, arg_count) }
     };
 
-    let args: Vec<Value> = c_args_slice
+    let Ok(args) = c_args_slice
         .iter()
         .map(|cv| unsafe { cv.to_value() }) // 调用上一回回复中定义的 to_value 方法
-        .collect();
+        .collect::<Result<Vec<Value>, _>>()
+    else {
+        return OpenExStatus::FfiError;
+    };
 
     if let Some(handle) = unsafe { handle_raw.as_mut() } {
         let (unit_index, main_metadata) = {
@@ -326,7 +738,10 @@ pub unsafe extern "C" fn openex_call_function(
             }
             match ret_file {
                 Some(file) => file,
-                None => return OpenExStatus::RuntimeError,
+                None => {
+                    handle.set_error(&format!("no such unit: {r_file}"));
+                    return OpenExStatus::RuntimeError;
+                }
             }
         };
 
@@ -340,23 +755,39 @@ pub unsafe extern "C" fn openex_call_function(
             }
             match ret_func {
                 Some(func) => func,
-                None => return OpenExStatus::RuntimeError,
+                None => {
+                    let error = format!("no such function: {r_file}/{r_func}");
+                    handle.set_error(&error);
+                    return OpenExStatus::RuntimeError;
+                }
             }
         };
 
         let ret_var = call_function(
-            main_method.get_codes(),
+            main_method,
             main_metadata.constant_table,
-            main_metadata.names,
             handle.metadata.as_slice(),
             unit_index,
-            main_method.locals + args.len(),
             handle.globals.clone(),
             handle.sync_table.clone(),
+            handle.runtime_env(),
             None,
             args,
         );
-        let ret_raw = into_c_value(ret_var);
+        let ret_var = match ret_var {
+            Ok(ret_var) => ret_var,
+            Err(report) => {
+                handle.set_error(&report);
+                return OpenExStatus::RuntimeError;
+            }
+        };
+        let ret_raw = match into_c_value(ret_var) {
+            Ok(ret_raw) => ret_raw,
+            Err(error) => {
+                handle.set_error(&format!("cannot pass the result to C: {error}"));
+                return OpenExStatus::FfiError;
+            }
+        };
 
         if let Some(ret_result) = unsafe { out_result.as_mut() } {
             *ret_result = ret_raw;
@@ -368,6 +799,202 @@ pub unsafe extern "C" fn openex_call_function(
     }
 }
 
+#[unsafe(no_mangle)]
+/// 读取最近一次失败的错误信息, 编译与运行时错误包含出错的文件与行列位置
+/// 没有错误时返回 NULL, 返回的字符串由句柄管理, 在下一次出错或释放句柄前有效
+/// # Safety
+#[must_use]
+pub unsafe extern "C" fn openex_last_error(handle_raw: *const OpenEX) -> *const c_char {
+    match unsafe { handle_raw.as_ref() }.and_then(|handle| handle.last_error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+// 诊断以字典的形式传递, 行号与列号从 1 开始
+fn diagnostic_value(diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let optional = |text: Option<&str>| text.map_or(Value::Null, |text| Value::String(text.into()));
+    let span = diagnostic.span;
+    let mut map = LinkedHashMap::new();
+    map.insert("severity".into(), Value::String(severity.into()));
+    map.insert("code".into(), optional(diagnostic.code));
+    map.insert(
+        "message".into(),
+        Value::String(diagnostic.message.as_str().into()),
+    );
+    map.insert(
+        "file".into(),
+        Value::String(diagnostic.file.as_str().into()),
+    );
+    for (key, value) in [
+        ("line", span.line),
+        ("column", span.column),
+        ("end_line", span.end_line),
+        ("end_column", span.end_column),
+    ] {
+        map.insert(key.into(), Value::Int(value as i64));
+    }
+    let notes = diagnostic
+        .notes
+        .iter()
+        .map(|note| Value::String(note.as_str().into()))
+        .collect();
+    map.insert("notes".into(), Value::Array(notes));
+    map.insert(
+        "suggestion".into(),
+        optional(diagnostic.suggestion.as_deref()),
+    );
+    Value::Map(map)
+}
+
+#[unsafe(no_mangle)]
+/// 读取最近一次 `openex_compile` 输出的全部错误与警告, 以字典数组的形式写入 `out_result`
+/// 字典包含 severity code message file line column end_line end_column notes suggestion
+/// # Safety
+/// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+pub unsafe extern "C" fn openex_get_diagnostics(
+    handle_raw: *const OpenEX,
+    out_result: *mut CValue,
+) -> OpenExStatus {
+    let (Some(handle), Some(out_result)) = (unsafe { handle_raw.as_ref() }, unsafe {
+        out_result.as_mut()
+    }) else {
+        return OpenExStatus::FfiError;
+    };
+    let diagnostics = handle
+        .compiler
+        .diagnostics()
+        .iter()
+        .map(diagnostic_value)
+        .collect();
+    match into_c_value(Value::Array(diagnostics)) {
+        Ok(c_value) => {
+            *out_result = c_value;
+            OpenExStatus::Success
+        }
+        Err(_) => OpenExStatus::FfiError,
+    }
+}
+
+#[unsafe(no_mangle)]
+/// 按名称读取模块中的全局变量, 需要在 `openex_initialize_executor` 之后调用
+/// # Safety
+/// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+/// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+pub unsafe extern "C" fn openex_get_global(
+    handle_raw: *mut OpenEX,
+    module: *const c_char,
+    name: *const c_char,
+    out_result: *mut CValue,
+) -> OpenExStatus {
+    let (Some(module), Some(name)) = (unsafe { c_str(module) }, unsafe { c_str(name) }) else {
+        return OpenExStatus::FfiError;
+    };
+    let (Some(handle), Some(out_result)) = (unsafe { handle_raw.as_mut() }, unsafe {
+        out_result.as_mut()
+    }) else {
+        return OpenExStatus::FfiError;
+    };
+    let Some((unit_index, index)) = handle.find_global(module, name) else {
+        return OpenExStatus::RuntimeError;
+    };
+    let value = handle
+        .globals
+        .lock()
+        .unwrap()
+        .get(unit_index, index)
+        .cloned();
+    match into_c_value(value.unwrap_or(Value::Null)) {
+        Ok(c_value) => {
+            *out_result = c_value;
+            OpenExStatus::Success
+        }
+        Err(error) => {
+            handle.set_error(&format!("cannot pass {module}/{name} to C: {error}"));
+            OpenExStatus::FfiError
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+/// 按名称修改模块中的全局变量, 需要在 `openex_initialize_executor` 之后调用
+/// # Safety
+/// `module` 与 `name` 是有效的 C 字符串指针(以 NUL 结尾)
+/// `value` 指向一个有效的值, 其内容会被复制, 内存仍由调用者管理
+pub unsafe extern "C" fn openex_set_global(
+    handle_raw: *mut OpenEX,
+    module: *const c_char,
+    name: *const c_char,
+    value: *const CValue,
+) -> OpenExStatus {
+    let (Some(module), Some(name)) = (unsafe { c_str(module) }, unsafe { c_str(name) }) else {
+        return OpenExStatus::FfiError;
+    };
+    let (Some(handle), Some(value)) = (unsafe { handle_raw.as_mut() }, unsafe { value.as_ref() })
+    else {
+        return OpenExStatus::FfiError;
+    };
+    let Some((unit_index, index)) = handle.find_global(module, name) else {
+        return OpenExStatus::RuntimeError;
+    };
+    let value = match unsafe { value.to_value() } {
+        Ok(value) => value,
+        Err(error) => {
+            handle.set_error(&format!("cannot set {module}/{name}: {error}"));
+            return OpenExStatus::FfiError;
+        }
+    };
+    if let Some(global) = handle.globals.lock().unwrap().get_mut(unit_index, index) {
+        *global = value;
+    }
+    OpenExStatus::Success
+}
+
+#[unsafe(no_mangle)]
+/// 列出模块中定义的函数名, 以字符串数组的形式写入 `out_result`, 匿名函数不会列出
+/// # Safety
+/// `module` 是有效的 C 字符串指针(以 NUL 结尾)
+/// `out_result` 中的值需要通过 `openex_free_c_value` 释放
+pub unsafe extern "C" fn openex_list_functions(
+    handle_raw: *mut OpenEX,
+    module: *const c_char,
+    out_result: *mut CValue,
+) -> OpenExStatus {
+    let Some(module) = (unsafe { c_str(module) }) else {
+        return OpenExStatus::FfiError;
+    };
+    let (Some(handle), Some(out_result)) = (unsafe { handle_raw.as_mut() }, unsafe {
+        out_result.as_mut()
+    }) else {
+        return OpenExStatus::FfiError;
+    };
+    let functions = handle
+        .compiler
+        .find_file(module)
+        .and_then(|file| file.ir_table.as_ref())
+        .map(|vm_ir| vm_ir.get_functions());
+    let Some(functions) = functions else {
+        handle.set_error(&format!("no such unit: {module}"));
+        return OpenExStatus::RuntimeError;
+    };
+    let names = functions
+        .into_iter()
+        .filter(|func| !func.name.starts_with('<'))
+        .map(|func| Value::String(func.name))
+        .collect();
+    match into_c_value(Value::Array(names)) {
+        Ok(c_value) => {
+            *out_result = c_value;
+            OpenExStatus::Success
+        }
+        Err(_) => OpenExStatus::FfiError,
+    }
+}
+
 #[unsafe(no_mangle)]
 /// 释放掉 `OpenEX` 句柄环境占用的资源
 /// # Safety
@@ -416,6 +1043,27 @@ pub unsafe extern "C" fn openex_free_c_value(c_val: *mut CValue) -> OpenExStatus
                     let _ = CString::from_raw(raw_ptr);
                 }
             }
+            ValueTag::Array => {
+                let array = v.data.a;
+                if !array.items.is_null() {
+                    for item in &mut free_c_raw(array.items, array.len) {
+                        openex_free_c_value(item);
+                    }
+                }
+            }
+            ValueTag::Map => {
+                let map = v.data.m;
+                if !map.keys.is_null() {
+                    for key in free_c_raw(map.keys, map.len) {
+                        let _ = CString::from_raw(key.cast_mut());
+                    }
+                }
+                if !map.values.is_null() {
+                    for value in &mut free_c_raw(map.values, map.len) {
+                        openex_free_c_value(value);
+                    }
+                }
+            }
             _ => {}
         }
     }
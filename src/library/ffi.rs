@@ -0,0 +1,251 @@
+use libloading::Library;
+use smol_str::{SmolStr, format_smolstr};
+use std::ffi::c_void;
+use std::mem::transmute;
+use std::sync::Arc;
+
+use crate::compiler::ast::vm_ir::Value;
+use crate::library::{LibModule, ModuleFunc, NativeFunc, NativeRegistry};
+use crate::runtime::{MetadataUnit, RuntimeError};
+use crate::{CValue, ValueData, ValueTag, into_c_value, openex_free_c_value};
+
+/// 动态库函数允许的最大形参个数
+pub const MAX_FOREIGN_ARGS: usize = 6;
+
+/// 动态库函数的 C 签名, 由脚本中的类型标注决定
+/// 形参为 `None` 时按实参的类型传递, 返回值为 `None` 时视为 `void`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignSignature {
+    pub args: Vec<Option<ValueTag>>,
+    pub ret: Option<ValueTag>,
+}
+
+/// 脚本中 `function native("库", "符号") name(...);` 声明的动态库函数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignFunc {
+    pub name: SmolStr,    // 脚本中的函数名
+    pub library: SmolStr, // 动态库路径, 按 dlopen 的规则查找
+    pub symbol: SmolStr,  // 动态库中的符号名
+    pub signature: ForeignSignature,
+}
+
+// 按 C 调用约定传递的参数: 整数类 (int64_t, bool, 指针) 与浮点类 (double)
+enum Arg {
+    Int(i64),
+    Float(f64),
+}
+
+const fn tag_name(tag: ValueTag) -> &'static str {
+    match tag {
+        ValueTag::Int => "int",
+        ValueTag::Bool => "bool",
+        ValueTag::Float => "float",
+        ValueTag::String => "string",
+        ValueTag::Ref => "ref",
+        ValueTag::Null => "null",
+        ValueTag::Array => "array",
+        ValueTag::Map => "map",
+    }
+}
+
+// 逐个确定参数的类型, 全部确定后以对应的函数指针类型调用
+// 参数个数超过深度标记的数量时返回 None
+macro_rules! call_foreign {
+    ($ptr:ident, $ret:ty, $args:ident, []; [$($ty:ty => $val:expr;)*]) => {
+        match $args {
+            [] => {
+                let func: unsafe extern "C" fn($($ty),*) -> $ret = unsafe { transmute($ptr) };
+                Some(unsafe { func($($val),*) })
+            }
+            _ => None,
+        }
+    };
+    ($ptr:ident, $ret:ty, $args:ident, [$_depth:tt $($depth:tt)*]; [$($ty:ty => $val:expr;)*]) => {
+        match $args {
+            [] => {
+                let func: unsafe extern "C" fn($($ty),*) -> $ret = unsafe { transmute($ptr) };
+                Some(unsafe { func($($val),*) })
+            }
+            [Arg::Int(value), rest @ ..] => {
+                let $args = rest;
+                call_foreign!($ptr, $ret, $args, [$($depth)*]; [$($ty => $val;)* i64 => *value;])
+            }
+            [Arg::Float(value), rest @ ..] => {
+                let $args = rest;
+                call_foreign!($ptr, $ret, $args, [$($depth)*]; [$($ty => $val;)* f64 => *value;])
+            }
+        }
+    };
+}
+
+// 以 C 调用约定调用函数指针, 返回值按签名读取为整数类或浮点类
+unsafe fn call(ptr: *const c_void, args: &[Arg], ret: Option<ValueTag>) -> Option<Arg> {
+    Some(match ret {
+        None => {
+            call_foreign!(ptr, (), args, [_ _ _ _ _ _]; [])?;
+            Arg::Int(0)
+        }
+        Some(ValueTag::Float) => Arg::Float(call_foreign!(ptr, f64, args, [_ _ _ _ _ _]; [])?),
+        Some(_) => Arg::Int(call_foreign!(ptr, i64, args, [_ _ _ _ _ _]; [])?),
+    })
+}
+
+// 将实参转换为 C 值, 字符串的内存在调用结束后由调用者释放
+fn marshal(symbol: &str, value: &Value, tag: Option<ValueTag>) -> Result<CValue, RuntimeError> {
+    let value = match (tag, value) {
+        (Some(ValueTag::Float), Value::Int(int)) => Value::Float((*int).into()),
+        (Some(ValueTag::Float), Value::BigInt(int)) => Value::Float(int.clone().into()),
+        (_, Value::BigInt(_)) => {
+            return Err(RuntimeError::TypeException(format_smolstr!(
+                "{symbol}: integer does not fit in 64 bits"
+            )));
+        }
+        (_, Value::Array(_) | Value::Map(_) | Value::Closure(_) | Value::Object(_)) => {
+            return Err(RuntimeError::TypeException(format_smolstr!(
+                "{symbol}: only int, bool, float, string and null can be passed to C"
+            )));
+        }
+        _ => value.clone(),
+    };
+    let c_value = into_c_value(value)
+        .map_err(|error| RuntimeError::TypeException(format_smolstr!("{symbol}: {error}")))?;
+    match tag {
+        Some(tag)
+            if tag != c_value.tag
+                && !(tag == ValueTag::String && c_value.tag == ValueTag::Null) =>
+        {
+            let found = tag_name(c_value.tag);
+            free(c_value);
+            Err(RuntimeError::TypeException(format_smolstr!(
+                "{symbol}: expected '{}', found '{found}'",
+                tag_name(tag)
+            )))
+        }
+        _ => Ok(c_value),
+    }
+}
+
+fn free(mut c_value: CValue) {
+    unsafe {
+        openex_free_c_value(&raw mut c_value);
+    }
+}
+
+fn to_arg(c_value: &CValue) -> Arg {
+    unsafe {
+        match c_value.tag {
+            ValueTag::Int => Arg::Int(c_value.data.i),
+            ValueTag::Bool => Arg::Int(i64::from(c_value.data.b)),
+            ValueTag::Float => Arg::Float(c_value.data.f),
+            ValueTag::String | ValueTag::Ref => Arg::Int(c_value.data.s as i64),
+            _ => Arg::Int(0),
+        }
+    }
+}
+
+// 按返回值类型将 C 返回值转换为脚本值, 字符串会被复制
+fn unmarshal(symbol: &str, ret: Option<ValueTag>, result: Arg) -> Result<Value, RuntimeError> {
+    let (tag, data) = match (ret, result) {
+        (None, _) => return Ok(Value::Null),
+        (Some(ValueTag::Float), Arg::Float(f)) => (ValueTag::Float, ValueData { f }),
+        (Some(ValueTag::Bool), Arg::Int(int)) => (ValueTag::Bool, ValueData { b: int & 0xff != 0 }),
+        (Some(ValueTag::String), Arg::Int(0)) => return Ok(Value::Null),
+        (Some(ValueTag::String), Arg::Int(ptr)) => {
+            (ValueTag::String, ValueData { s: ptr as *const _ })
+        }
+        (Some(_), Arg::Int(i)) => (ValueTag::Int, ValueData { i }),
+        (Some(_), Arg::Float(_)) => unreachable!(),
+    };
+    unsafe { CValue { tag, data }.to_value() }
+        .map_err(|error| RuntimeError::TypeException(format_smolstr!("{symbol}: {error}")))
+}
+
+impl ForeignSignature {
+    /// 检查签名能否以 C 调用约定调用, 编译期与加载动态库前都会检查
+    /// # Errors
+    /// 形参个数超过 `MAX_FOREIGN_ARGS` 时返回错误信息
+    pub fn check(&self) -> Result<(), SmolStr> {
+        if self.args.len() > MAX_FOREIGN_ARGS {
+            return Err(format_smolstr!(
+                "at most {MAX_FOREIGN_ARGS} arguments are supported"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ForeignFunc {
+    /// 打开动态库并查找符号, 返回可以注册到本地函数表的实现
+    /// 动态库在返回的函数被释放前一直保持加载
+    /// # Errors
+    /// 动态库或符号不存在, 或形参个数超过 `MAX_FOREIGN_ARGS` 时返回错误信息
+    pub fn load(&self) -> Result<NativeFunc, SmolStr> {
+        self.signature.check()?;
+        let library =
+            unsafe { Library::new(self.library.as_str()) }.map_err(|e| format_smolstr!("{e}"))?;
+        let ptr = *unsafe { library.get::<*const c_void>(self.symbol.as_bytes()) }
+            .map_err(|e| format_smolstr!("{e}"))?;
+        if ptr.is_null() {
+            return Err(format_smolstr!("{}: undefined symbol", self.symbol));
+        }
+        let library = Arc::new(library);
+        // 函数指针只在动态库保持加载时有效, 以地址的形式与库句柄一起保存在闭包中
+        let ptr = ptr as usize;
+        let this = self.clone();
+        Ok(Arc::new(move |args: &[Value]| {
+            let _library = &library;
+            let symbol = this.symbol.as_str();
+            let mut c_values = Vec::with_capacity(args.len());
+            for (value, tag) in args.iter().zip(&this.signature.args) {
+                match marshal(symbol, value, *tag) {
+                    Ok(c_value) => c_values.push(c_value),
+                    Err(error) => {
+                        c_values.into_iter().for_each(free);
+                        return Err(error);
+                    }
+                }
+            }
+            let call_args: Vec<Arg> = c_values.iter().map(to_arg).collect();
+            let result = unsafe { call(ptr as *const c_void, &call_args, this.signature.ret) };
+            c_values.into_iter().for_each(free);
+            unmarshal(
+                symbol,
+                this.signature.ret,
+                result.ok_or(RuntimeError::VMError)?,
+            )
+        }))
+    }
+}
+
+impl NativeRegistry {
+    /// 加载动态库函数并注册到声明它的脚本模块中
+    /// # Errors
+    /// 动态库函数已被禁用, 或动态库与符号无法加载时返回错误信息
+    pub fn register_foreign(&mut self, module: &str, func: &ForeignFunc) -> Result<(), SmolStr> {
+        if self.is_foreign_disabled() {
+            return Err(SmolStr::new("foreign native functions are disabled"));
+        }
+        let mut library = LibModule::new(module);
+        library.push_func(ModuleFunc {
+            name: func.name.clone(),
+            arity: func.signature.args.len(),
+            func: func.load()?,
+        });
+        self.register_module(library);
+        Ok(())
+    }
+    /// 加载所有元数据单元中声明的动态库函数, 需要在执行引擎启动前调用
+    /// 编译期间只记录声明, 因此编译、`--emit` 与语言服务器都不会打开动态库
+    /// # Errors
+    /// 任意一个函数无法加载时返回带有函数名的错误信息
+    pub fn bind_foreign(&mut self, units: &[MetadataUnit]) -> Result<(), SmolStr> {
+        for unit in units {
+            for func in &unit.foreign {
+                self.register_foreign(unit.names, func).map_err(|e| {
+                    format_smolstr!("cannot bind native function `{}`: {e}", func.name)
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
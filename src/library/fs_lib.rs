@@ -0,0 +1,173 @@
+use smol_str::{SmolStr, format_smolstr};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::compiler::ast::vm_ir::Value;
+use crate::library::{LibModule, ModuleFunc, NativeRegistry};
+use crate::runtime::RuntimeError;
+
+fn str_arg<'a>(func: &str, args: &'a [Value], index: usize) -> Result<&'a SmolStr, RuntimeError> {
+    let Some(Value::String(value)) = args.get(index) else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{func}: arg {index} is not string."
+        )));
+    };
+    Ok(value)
+}
+
+// 将操作系统错误包装为脚本可捕获的 IOException
+fn io_error(path: &str, error: &std::io::Error) -> RuntimeError {
+    RuntimeError::IOException(format_smolstr!("{path}: {error}"))
+}
+
+fn fs_read_text(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("read_text", args, 0)?;
+    let text = fs::read_to_string(path.as_str()).map_err(|e| io_error(path, &e))?;
+    Ok(Value::String(SmolStr::new(text)))
+}
+
+fn reg_read_text() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("read_text"),
+        arity: 1,
+        func: Arc::new(fs_read_text),
+    }
+}
+
+fn fs_write_text(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("write_text", args, 0)?;
+    let text = str_arg("write_text", args, 1)?;
+    fs::write(path.as_str(), text.as_bytes()).map_err(|e| io_error(path, &e))?;
+    Ok(Value::Null)
+}
+
+fn reg_write_text() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("write_text"),
+        arity: 2,
+        func: Arc::new(fs_write_text),
+    }
+}
+
+fn fs_append_text(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("append_text", args, 0)?;
+    let text = str_arg("append_text", args, 1)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.as_str())
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| io_error(path, &e))?;
+    Ok(Value::Null)
+}
+
+fn reg_append_text() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("append_text"),
+        arity: 2,
+        func: Arc::new(fs_append_text),
+    }
+}
+
+fn fs_exists(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("exists", args, 0)?;
+    Ok(Value::Bool(Path::new(path.as_str()).exists()))
+}
+
+fn reg_exists() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("exists"),
+        arity: 1,
+        func: Arc::new(fs_exists),
+    }
+}
+
+// 删除文件或空文件夹
+fn fs_remove(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("remove", args, 0)?;
+    let result = if Path::new(path.as_str()).is_dir() {
+        fs::remove_dir(path.as_str())
+    } else {
+        fs::remove_file(path.as_str())
+    };
+    result.map_err(|e| io_error(path, &e))?;
+    Ok(Value::Null)
+}
+
+fn reg_remove() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("remove"),
+        arity: 1,
+        func: Arc::new(fs_remove),
+    }
+}
+
+// 返回按名称排序的文件名数组
+fn fs_list_dir(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("list_dir", args, 0)?;
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path.as_str()).map_err(|e| io_error(path, &e))? {
+        let entry = entry.map_err(|e| io_error(path, &e))?;
+        names.push(SmolStr::new(entry.file_name().to_string_lossy()));
+    }
+    names.sort();
+    Ok(Value::Array(names.into_iter().map(Value::String).collect()))
+}
+
+fn reg_list_dir() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("list_dir"),
+        arity: 1,
+        func: Arc::new(fs_list_dir),
+    }
+}
+
+// 递归创建文件夹, 文件夹已存在时不报错
+fn fs_mkdir(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("mkdir", args, 0)?;
+    fs::create_dir_all(path.as_str()).map_err(|e| io_error(path, &e))?;
+    Ok(Value::Null)
+}
+
+fn reg_mkdir() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("mkdir"),
+        arity: 1,
+        func: Arc::new(fs_mkdir),
+    }
+}
+
+fn fs_file_size(args: &[Value]) -> Result<Value, RuntimeError> {
+    let path = str_arg("file_size", args, 0)?;
+    let metadata = fs::metadata(path.as_str()).map_err(|e| io_error(path, &e))?;
+    let size = i64::try_from(metadata.len()).map_err(|_| {
+        RuntimeError::PrecisionLoss(format_smolstr!("file_size: {path} is too large"))
+    })?;
+    Ok(Value::Int(size))
+}
+
+fn reg_file_size() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("file_size"),
+        arity: 1,
+        func: Arc::new(fs_file_size),
+    }
+}
+
+pub fn register_fs_lib(registry: &mut NativeRegistry) {
+    let mut fs_lib = LibModule {
+        name: SmolStr::new("fs"),
+        functions: vec![],
+    };
+    fs_lib.functions.push(reg_read_text());
+    fs_lib.functions.push(reg_write_text());
+    fs_lib.functions.push(reg_append_text());
+    fs_lib.functions.push(reg_exists());
+    fs_lib.functions.push(reg_remove());
+    fs_lib.functions.push(reg_list_dir());
+    fs_lib.functions.push(reg_mkdir());
+    fs_lib.functions.push(reg_file_size());
+    registry.register_module(fs_lib);
+}
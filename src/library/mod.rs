@@ -1,35 +1,47 @@
 use smol_str::SmolStr;
 use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io::Read;
-use std::sync::{LazyLock, RwLock};
+use std::sync::Arc;
 
 use crate::compiler::Compiler;
 use crate::compiler::ast::vm_ir::Value;
-use crate::compiler::file::SourceFile;
+use crate::compiler::file::{SourceFile, module_name};
 use crate::compiler::lints::Lint;
-use crate::compiler::parser::ParserError;
+use crate::library::fs_lib::register_fs_lib;
+use crate::library::string_lib::register_string_lib;
 use crate::library::system::register_system_lib;
 use crate::library::type_lib::register_type_lib;
 use crate::runtime::RuntimeError;
 
+pub mod ffi;
+mod fs_lib;
 pub mod output_capture;
+mod string_lib;
 mod system;
-mod type_lib;
+pub(crate) mod type_lib;
 
-static MODULES: LazyLock<RwLock<BTreeMap<SmolStr, LibModule>>> =
-    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+/// 本地函数实现, 可以是普通函数也可以是捕获了宿主状态的闭包
+pub type NativeFunc = Arc<dyn Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync>;
 
-pub type NativeFunc = fn(&[Value]) -> Result<Value, RuntimeError>;
-
-#[derive(Debug, Clone, Hash)]
+#[derive(Clone)]
 pub struct ModuleFunc {
     pub name: SmolStr,
     pub arity: usize,
     pub func: NativeFunc,
 }
 
+impl fmt::Debug for ModuleFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleFunc")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LibModule {
     name: SmolStr,
@@ -37,30 +49,158 @@ pub struct LibModule {
 }
 
 impl LibModule {
-    pub fn find_func(&mut self, func_name: &SmolStr) -> Option<&mut ModuleFunc> {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: SmolStr::new(name),
+            functions: vec![],
+        }
+    }
+
+    /// 向模块添加一个本地函数, 同名函数会被替换
+    pub fn add_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync + 'static,
+    ) {
+        self.push_func(ModuleFunc {
+            name: SmolStr::new(name),
+            arity,
+            func: Arc::new(func),
+        });
+    }
+
+    fn push_func(&mut self, func: ModuleFunc) {
+        if let Some(entry) = self.functions.iter_mut().find(|f| f.name == func.name) {
+            *entry = func;
+        } else {
+            self.functions.push(func);
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 按注册顺序排列的全部本地函数
+    #[must_use]
+    pub fn functions(&self) -> &[ModuleFunc] {
+        &self.functions
+    }
+
+    #[must_use]
+    pub fn find_func(&self, func_name: &str) -> Option<&ModuleFunc> {
         self.functions
-            .iter_mut()
-            .find(|entry| entry.name.as_str() == func_name.as_str())
+            .iter()
+            .find(|entry| entry.name.as_str() == func_name)
     }
 }
 
-fn register_library(library: LibModule) {
-    if library.functions.is_empty() {
-        println!("warn: {} no functions found", library.name);
-    }
-    MODULES
-        .write()
-        .unwrap()
-        .insert(library.name.clone(), library);
+/// 本地函数注册表, 每个编译器实例 (以及由它启动的执行引擎) 持有独立的一份
+/// 模块名对应声明 `function native` 的脚本文件名 (不含扩展名)
+#[derive(Debug, Clone, Default)]
+pub struct NativeRegistry {
+    modules: BTreeMap<SmolStr, LibModule>,
+    disabled: HashSet<SmolStr>, // 被宿主禁用的本地库, 不会被注册也不会编译对应的标准库源文件
+    foreign_disabled: bool,     // 禁止脚本声明动态库函数, 禁用 `fs` 等库时通常也需要一并禁止
 }
 
-pub fn find_library(
-    name: &str,
-    f: impl FnOnce(Option<&mut LibModule>) -> Result<Value, ParserError>,
-) -> Result<Value, ParserError> {
-    let mut map = MODULES.write().unwrap();
-    let ret_m = map.get_mut(name);
-    f(ret_m)
+/// 执行引擎在线程间共享的只读注册表, 调用本地函数时无需加锁
+pub type SharedNatives = Arc<NativeRegistry>;
+
+impl NativeRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建已注册所有标准本地库的注册表, 直接加载字节码文件时无需编译标准库但仍需要本地函数
+    #[must_use]
+    pub fn with_std() -> Self {
+        let mut registry = Self::new();
+        registry.register_std();
+        registry
+    }
+
+    /// 注册所有标准本地库实现, 已禁用的库会被跳过
+    pub fn register_std(&mut self) {
+        register_system_lib(self);
+        register_type_lib(self);
+        register_string_lib(self);
+        register_fs_lib(self);
+    }
+
+    /// 注册一个本地模块, 与已有模块同名时合并其中的函数
+    pub fn register_module(&mut self, library: LibModule) {
+        if self.is_disabled(&library.name) {
+            return;
+        }
+        if library.functions.is_empty() {
+            println!("warn: {} no functions found", library.name);
+        }
+        match self.modules.get_mut(&library.name) {
+            Some(module) => {
+                for func in library.functions {
+                    module.push_func(func);
+                }
+            }
+            None => {
+                self.modules.insert(library.name.clone(), library);
+            }
+        }
+    }
+
+    /// 向指定模块注册单个本地函数, 模块不存在时自动创建
+    pub fn register_native(
+        &mut self,
+        module: &str,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, RuntimeError> + Send + Sync + 'static,
+    ) {
+        let mut library = LibModule::new(module);
+        library.add_function(name, arity, func);
+        self.register_module(library);
+    }
+
+    /// 禁用指定的本地库 (如 `fs`), 供需要沙箱环境的宿主使用
+    /// 需要在 `load_libraries` 之前调用, 被禁用的库在脚本中无法导入
+    pub fn disable(&mut self, name: &str) {
+        self.disabled.insert(SmolStr::new(name));
+        self.modules.remove(name);
+    }
+
+    #[must_use]
+    pub fn is_disabled(&self, name: &str) -> bool {
+        self.disabled.contains(name)
+    }
+
+    /// 禁止 `function native("库", "符号")` 形式的动态库函数, 声明这类函数的脚本无法通过编译
+    pub const fn disable_foreign(&mut self) {
+        self.foreign_disabled = true;
+    }
+
+    #[must_use]
+    pub const fn is_foreign_disabled(&self) -> bool {
+        self.foreign_disabled
+    }
+
+    /// 按模块名排列的全部已注册模块
+    pub fn modules(&self) -> impl Iterator<Item = &LibModule> {
+        self.modules.values()
+    }
+
+    #[must_use]
+    pub fn find_module(&self, name: &str) -> Option<&LibModule> {
+        self.modules.get(name)
+    }
+
+    #[must_use]
+    pub fn find_func(&self, module: &str, name: &str) -> Option<&ModuleFunc> {
+        self.find_module(module)?.find_func(name)
+    }
 }
 
 pub fn load_libraries(
@@ -76,21 +216,33 @@ pub fn load_libraries(
             continue;
         }
 
-        let mut buf = Vec::new();
-        File::open(&path)?.read_to_end(&mut buf)?;
-
         let name = path
             .file_name()
             .and_then(|s| s.to_str())
             .unwrap_or("<invalid>")
             .to_string();
+        if compiler.natives().is_disabled(&module_name(&name)) {
+            continue;
+        }
+
+        let mut buf = Vec::new();
+        File::open(&path)?.read_to_end(&mut buf)?;
+
         let data = SmolStr::new(std::str::from_utf8(&buf).expect("error: file not UTF-8"));
         compiler.add_file(SourceFile::new(name, data.to_string(), lints.clone(), true));
     }
 
-    register_system_lib();
-    register_type_lib();
+    compiler.natives_mut().register_std();
 
     compiler.compile().expect("error: library has error.");
     Ok(())
 }
+
+/// 禁用指定的本地库, 并移除编译器中已加载的同名标准库源文件
+/// 需要在编译引用该库的脚本之前调用
+pub fn disable_library(compiler: &mut Compiler, name: &str) {
+    compiler.natives_mut().disable(name);
+    compiler
+        .get_files()
+        .retain(|file| !(file.is_library && file.module_name() == name));
+}
@@ -0,0 +1,368 @@
+use smol_str::{SmolStr, SmolStrBuilder, StrExt, ToSmolStr, format_smolstr};
+use std::sync::Arc;
+
+use crate::compiler::ast::vm_ir::Value;
+use crate::library::{LibModule, ModuleFunc, NativeRegistry};
+use crate::runtime::{RuntimeError, check_index, context};
+
+// 字符串的索引与长度均以 Unicode 字符为单位, 而不是字节
+
+fn str_arg<'a>(func: &str, args: &'a [Value], index: usize) -> Result<&'a SmolStr, RuntimeError> {
+    let Some(Value::String(value)) = args.get(index) else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{func}: arg {index} is not string."
+        )));
+    };
+    Ok(value)
+}
+
+fn int_arg(func: &str, args: &[Value], index: usize) -> Result<i64, RuntimeError> {
+    let Some(Value::Int(value)) = args.get(index) else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{func}: arg {index} is not number."
+        )));
+    };
+    Ok(*value)
+}
+
+// 区间边界允许等于字符串长度
+fn bound_arg(func: &str, args: &[Value], index: usize, len: usize) -> Result<usize, RuntimeError> {
+    let bound = int_arg(func, args, index)?;
+    usize::try_from(bound)
+        .ok()
+        .filter(|bound| *bound <= len)
+        .ok_or_else(|| RuntimeError::index_out_of_bounds(bound, len))
+}
+
+fn char_len(value: &str) -> usize {
+    value.chars().count()
+}
+
+fn string_substring(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("substring", args, 0)?;
+    let len = char_len(value);
+    let start = bound_arg("substring", args, 1, len)?;
+    let end = bound_arg("substring", args, 2, len)?;
+    if start > end {
+        return Err(RuntimeError::index_out_of_bounds(
+            i64::try_from(start).unwrap(),
+            end,
+        ));
+    }
+    let result: SmolStr = value.chars().skip(start).take(end - start).collect();
+    Ok(Value::String(result))
+}
+
+fn reg_substring() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("substring"),
+        arity: 3,
+        func: Arc::new(string_substring),
+    }
+}
+
+fn string_split(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("split", args, 0)?;
+    let sep = str_arg("split", args, 1)?;
+    // 分隔符为空时按字符拆分
+    let parts: Vec<Value> = if sep.is_empty() {
+        value
+            .chars()
+            .map(|c| Value::String(c.to_smolstr()))
+            .collect()
+    } else {
+        value
+            .split(sep.as_str())
+            .map(|part| Value::String(SmolStr::new(part)))
+            .collect()
+    };
+    Ok(Value::Array(parts))
+}
+
+fn reg_split() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("split"),
+        arity: 2,
+        func: Arc::new(string_split),
+    }
+}
+
+fn string_join(args: &[Value]) -> Result<Value, RuntimeError> {
+    let Some(Value::Array(elements)) = args.first() else {
+        return Err(RuntimeError::TypeException(
+            "join: arg 0 is not array.".to_smolstr(),
+        ));
+    };
+    let sep = str_arg("join", args, 1)?;
+    let mut builder = SmolStrBuilder::new();
+    for (index, element) in elements.iter().enumerate() {
+        if index > 0 {
+            builder.push_str(sep);
+        }
+        builder.push_str(&element.to_smolstr());
+    }
+    let value = builder.finish();
+    context::check_length(value.len())?;
+    Ok(Value::String(value))
+}
+
+fn reg_join() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("join"),
+        arity: 2,
+        func: Arc::new(string_join),
+    }
+}
+
+// 返回子串第一次出现的字符索引, 找不到时返回 -1
+fn string_find(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("find", args, 0)?;
+    let target = str_arg("find", args, 1)?;
+    let index = value.find(target.as_str()).map_or(-1, |byte_index| {
+        i64::try_from(char_len(&value[..byte_index])).unwrap()
+    });
+    Ok(Value::Int(index))
+}
+
+fn reg_find() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("find"),
+        arity: 2,
+        func: Arc::new(string_find),
+    }
+}
+
+fn string_replace(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("replace", args, 0)?;
+    let from = str_arg("replace", args, 1)?;
+    let to = str_arg("replace", args, 2)?;
+    if from.is_empty() {
+        return Ok(Value::String(value.clone()));
+    }
+    if to.len() > from.len() {
+        let count = value.matches(from.as_str()).count();
+        context::check_length(value.len() + count * (to.len() - from.len()))?;
+    }
+    Ok(Value::String(SmolStr::new(
+        value.replace(from.as_str(), to.as_str()),
+    )))
+}
+
+fn reg_replace() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("replace"),
+        arity: 3,
+        func: Arc::new(string_replace),
+    }
+}
+
+fn string_trim(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("trim", args, 0)?;
+    Ok(Value::String(SmolStr::new(value.trim())))
+}
+
+fn reg_trim() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("trim"),
+        arity: 1,
+        func: Arc::new(string_trim),
+    }
+}
+
+fn string_upper(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("upper", args, 0)?;
+    Ok(Value::String(value.to_uppercase_smolstr()))
+}
+
+fn reg_upper() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("upper"),
+        arity: 1,
+        func: Arc::new(string_upper),
+    }
+}
+
+fn string_lower(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("lower", args, 0)?;
+    Ok(Value::String(value.to_lowercase_smolstr()))
+}
+
+fn reg_lower() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("lower"),
+        arity: 1,
+        func: Arc::new(string_lower),
+    }
+}
+
+fn string_starts_with(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("starts_with", args, 0)?;
+    let prefix = str_arg("starts_with", args, 1)?;
+    Ok(Value::Bool(value.starts_with(prefix.as_str())))
+}
+
+fn reg_starts_with() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("starts_with"),
+        arity: 2,
+        func: Arc::new(string_starts_with),
+    }
+}
+
+fn string_ends_with(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("ends_with", args, 0)?;
+    let suffix = str_arg("ends_with", args, 1)?;
+    Ok(Value::Bool(value.ends_with(suffix.as_str())))
+}
+
+fn reg_ends_with() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("ends_with"),
+        arity: 2,
+        func: Arc::new(string_ends_with),
+    }
+}
+
+fn string_char_at(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("char_at", args, 0)?;
+    let index = check_index(int_arg("char_at", args, 1)?, char_len(value))?;
+    Ok(Value::String(
+        value.chars().nth(index).unwrap().to_smolstr(),
+    ))
+}
+
+fn reg_char_at() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("char_at"),
+        arity: 2,
+        func: Arc::new(string_char_at),
+    }
+}
+
+// 返回第一个字符的 Unicode 码点
+fn string_ord(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("ord", args, 0)?;
+    let Some(c) = value.chars().next() else {
+        return Err(RuntimeError::index_out_of_bounds(0, 0));
+    };
+    Ok(Value::Int(i64::from(u32::from(c))))
+}
+
+fn reg_ord() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("ord"),
+        arity: 1,
+        func: Arc::new(string_ord),
+    }
+}
+
+fn string_chr(args: &[Value]) -> Result<Value, RuntimeError> {
+    let code = int_arg("chr", args, 0)?;
+    let Some(c) = u32::try_from(code).ok().and_then(char::from_u32) else {
+        return Err(RuntimeError::IndexOutOfBounds(format_smolstr!(
+            "Code point {code} out of range"
+        )));
+    };
+    Ok(Value::String(c.to_smolstr()))
+}
+
+fn reg_chr() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("chr"),
+        arity: 1,
+        func: Arc::new(string_chr),
+    }
+}
+
+fn string_repeat(args: &[Value]) -> Result<Value, RuntimeError> {
+    let value = str_arg("repeat", args, 0)?;
+    let count = int_arg("repeat", args, 1)?;
+    let Ok(count) = usize::try_from(count) else {
+        return Err(RuntimeError::IndexOutOfBounds(format_smolstr!(
+            "Count {count} out of range"
+        )));
+    };
+    context::check_length(value.len().saturating_mul(count))?;
+    Ok(Value::String(SmolStr::new(value.repeat(count))))
+}
+
+fn reg_repeat() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("repeat"),
+        arity: 2,
+        func: Arc::new(string_repeat),
+    }
+}
+
+// 依次使用参数替换 {} 占位符, {{ 与 }} 分别转义为 { 和 }
+// 参数为数组时按元素依次替换, 否则视为唯一的参数
+fn string_format(args: &[Value]) -> Result<Value, RuntimeError> {
+    let template = str_arg("format", args, 0)?;
+    let values = match args.get(1) {
+        Some(Value::Array(elements)) => elements.as_slice(),
+        Some(value) => std::slice::from_ref(value),
+        None => &[],
+    };
+
+    let mut builder = SmolStrBuilder::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                builder.push('{');
+            }
+            '{' if chars.peek() == Some(&'}') => {
+                chars.next();
+                let Some(value) = values.get(next) else {
+                    return Err(RuntimeError::index_out_of_bounds(
+                        i64::try_from(next).unwrap(),
+                        values.len(),
+                    ));
+                };
+                builder.push_str(&value.to_smolstr());
+                next += 1;
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                builder.push('}');
+            }
+            _ => builder.push(c),
+        }
+    }
+    let value = builder.finish();
+    context::check_length(value.len())?;
+    Ok(Value::String(value))
+}
+
+fn reg_format() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("format"),
+        arity: 2,
+        func: Arc::new(string_format),
+    }
+}
+
+pub fn register_string_lib(registry: &mut NativeRegistry) {
+    let mut string_lib = LibModule {
+        name: SmolStr::new("string"),
+        functions: vec![],
+    };
+    string_lib.functions.push(reg_substring());
+    string_lib.functions.push(reg_split());
+    string_lib.functions.push(reg_join());
+    string_lib.functions.push(reg_find());
+    string_lib.functions.push(reg_replace());
+    string_lib.functions.push(reg_trim());
+    string_lib.functions.push(reg_upper());
+    string_lib.functions.push(reg_lower());
+    string_lib.functions.push(reg_starts_with());
+    string_lib.functions.push(reg_ends_with());
+    string_lib.functions.push(reg_char_at());
+    string_lib.functions.push(reg_ord());
+    string_lib.functions.push(reg_chr());
+    string_lib.functions.push(reg_repeat());
+    string_lib.functions.push(reg_format());
+    registry.register_module(string_lib);
+}
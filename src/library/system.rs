@@ -1,22 +1,25 @@
 use crate::compiler::ast::vm_ir::Value;
-use crate::library::{LibModule, ModuleFunc, output_capture::print, register_library};
+use crate::library::{LibModule, ModuleFunc, NativeRegistry, output_capture::print};
 use crate::runtime::RuntimeError;
 use crate::runtime::context;
 use smol_str::{SmolStr, ToSmolStr};
 use std::io::Read;
 use std::process::exit;
+use std::sync::Arc;
 use std::time::Duration;
 use std::{io, thread};
 
 fn print_impl(value: Value) {
     match value {
         Value::Int(i) => print(format_args!("{i}")),
+        Value::BigInt(i) => print(format_args!("{i}")),
         Value::Bool(i) => print(format_args!("{i}")),
         Value::Float(i) => print(format_args!("{i}")),
         Value::String(i) => print(format_args!("{i}")),
         Value::Ref(i) => print(format_args!("<ref:{i}>")),
+        Value::Closure(closure) => print(format_args!("<closure:{}>", closure.path)),
         Value::Null => print(format_args!("null")),
-        Value::Array(_i, ele) => {
+        Value::Array(ele) => {
             print(format_args!("["));
             for var in ele {
                 print_impl(var);
@@ -24,6 +27,29 @@ fn print_impl(value: Value) {
             }
             print(format_args!("]"));
         }
+        Value::Map(map) => {
+            print(format_args!("{{"));
+            for (key, var) in map {
+                print(format_args!("{key}:"));
+                print_impl(var);
+                print(format_args!(","));
+            }
+            print(format_args!("}}"));
+        }
+        Value::Object(object) => {
+            print(format_args!("{}{{", object.class_name()));
+            // 对象引用自身时无法再次加锁, 不再展开其字段
+            if let Ok(fields) = object.fields.try_lock() {
+                for (key, var) in fields.iter() {
+                    print(format_args!("{key}:"));
+                    print_impl(var.clone());
+                    print(format_args!(","));
+                }
+            } else {
+                print(format_args!("..."));
+            }
+            print(format_args!("}}"));
+        }
     }
 }
 
@@ -37,7 +63,7 @@ fn reg_println() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("print"),
         arity: 1,
-        func: system_print,
+        func: Arc::new(system_print),
     }
 }
 
@@ -63,7 +89,7 @@ fn reg_exit() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("exit"),
         arity: 1,
-        func: system_exit,
+        func: Arc::new(system_exit),
     }
 }
 
@@ -85,15 +111,17 @@ fn reg_read() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("read"),
         arity: 0,
-        func: system_read,
+        func: Arc::new(system_read),
     }
 }
 
 #[allow(clippy::unnecessary_wraps)]
 fn system_thread(args: &[Value]) -> Result<Value, RuntimeError> {
     let output = args.first().unwrap().clone();
-    let path = match output {
-        Value::String(s) | Value::Ref(s) => s,
+    let (path, arguments) = match output {
+        Value::String(s) | Value::Ref(s) => (s, vec![]),
+        // 闭包的捕获值作为线程入口函数的参数传入
+        Value::Closure(closure) => (closure.path.clone(), closure.captures.clone()),
         _ => {
             return Err(RuntimeError::TypeException(
                 "thread: path not a string.".to_smolstr(),
@@ -103,9 +131,7 @@ fn system_thread(args: &[Value]) -> Result<Value, RuntimeError> {
 
     let ret = context::with_context(|ctx| {
         let units = context::get_units(ctx);
-        let mut sp = path.split('/');
-        let file = sp.next().unwrap_or("");
-        let func = sp.next().unwrap_or("");
+        let (file, func) = path.rsplit_once('/').unwrap_or_default();
         if file.is_empty() || func.is_empty() {
             return Err(RuntimeError::TypeException(
                 "thread: path should be \"file/func\".".to_smolstr(),
@@ -126,12 +152,19 @@ fn system_thread(args: &[Value]) -> Result<Value, RuntimeError> {
         let Some((unit_index, unit, method)) = target else {
             return Err(RuntimeError::NoSuchFunctionException(path));
         };
+        if method.args != arguments.len() {
+            return Err(RuntimeError::TypeException(
+                "thread: function should not take arguments.".to_smolstr(),
+            ));
+        }
         let Some(thread_manager) = context::get_thread_manager(ctx) else {
             return Err(RuntimeError::VMError);
         };
         let globals = context::get_globals(ctx);
         let sync_table = context::get_sync_table(ctx);
-        thread_manager.submit_run_thread(unit_index, unit, method, units, globals, sync_table);
+        thread_manager.submit_run_thread(
+            unit_index, unit, method, units, globals, sync_table, arguments,
+        );
         Ok(Value::Null)
     });
 
@@ -141,7 +174,7 @@ fn reg_thread() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("thread"),
         arity: 1,
-        func: system_thread,
+        func: Arc::new(system_thread),
     }
 }
 
@@ -155,7 +188,7 @@ fn reg_thread_exit() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("thread_exit"),
         arity: 0,
-        func: system_thread_exit,
+        func: Arc::new(system_thread_exit),
     }
 }
 
@@ -174,7 +207,9 @@ fn system_nano_sleep(args: &[Value]) -> Result<Value, RuntimeError> {
             "nano_sleep: time must > 0.".to_smolstr(),
         ));
     };
-    thread::sleep(Duration::from_nanos(time));
+    // 最多睡眠到执行超时为止, 返回后由执行器报告超时
+    let time = Duration::from_nanos(time);
+    thread::sleep(context::remaining_time().map_or(time, |remaining| time.min(remaining)));
     Ok(Value::Null)
 }
 
@@ -182,11 +217,11 @@ fn reg_nano_sleep() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("nano_sleep"),
         arity: 1,
-        func: system_nano_sleep,
+        func: Arc::new(system_nano_sleep),
     }
 }
 
-pub fn register_system_lib() {
+pub fn register_system_lib(registry: &mut NativeRegistry) {
     let mut system_lib = LibModule {
         name: SmolStr::new("system"),
         functions: vec![],
@@ -197,5 +232,5 @@ pub fn register_system_lib() {
     system_lib.functions.push(reg_thread());
     system_lib.functions.push(reg_thread_exit());
     system_lib.functions.push(reg_nano_sleep());
-    register_library(system_lib);
+    registry.register_module(system_lib);
 }
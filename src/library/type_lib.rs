@@ -1,20 +1,22 @@
 use dashu::float::DBig;
-use smol_str::{SmolStr, ToSmolStr};
+use dashu::integer::IBig;
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::compiler::ast::vm_ir::Value;
-use crate::library::{LibModule, ModuleFunc, register_library};
-use crate::runtime::RuntimeError;
+use crate::library::{LibModule, ModuleFunc, NativeRegistry};
+use crate::runtime::{RuntimeError, check_index, context};
 
 #[allow(clippy::unnecessary_wraps)]
 fn type_to_number(args: &[Value]) -> Result<Value, RuntimeError> {
     let auto = args.first().unwrap().clone();
+    // 超出 i64 范围的结果为任意精度整数
     if let Value::String(raw_str) = auto {
-        let i = raw_str.as_str().parse::<i64>().unwrap();
-        Ok(Value::Int(i))
+        let i = IBig::from_str(raw_str.as_str()).unwrap();
+        Ok(Value::from_ibig(i))
     } else if let Value::Float(raw_float) = args.first().unwrap() {
-        let i = raw_float.trunc().to_int().value().try_into().unwrap();
-        Ok(Value::Int(i))
+        Ok(Value::from_ibig(raw_float.trunc().to_int().value()))
     } else {
         Err(RuntimeError::TypeException(
             "to_number: auto not a string or float.".to_smolstr(),
@@ -26,7 +28,7 @@ fn reg_to_number() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("to_number"),
         arity: 1,
-        func: type_to_number,
+        func: Arc::new(type_to_number),
     }
 }
 
@@ -35,8 +37,8 @@ fn type_to_float(args: &[Value]) -> Result<Value, RuntimeError> {
     let auto = args.first().unwrap().clone();
     if let Value::String(raw_str) = auto {
         Ok(Value::Float(DBig::from_str(raw_str.as_str()).unwrap()))
-    } else if let Value::Int(raw_number) = args.first().unwrap() {
-        Ok(Value::Float(DBig::from(*raw_number)))
+    } else if let Some(raw_number @ (Value::Int(_) | Value::BigInt(_))) = args.first() {
+        Ok(Value::Float(raw_number.to_dbig().unwrap()))
     } else {
         Err(RuntimeError::TypeException(
             "to_float: auto not a string or number.".to_smolstr(),
@@ -48,7 +50,7 @@ fn reg_to_float() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("to_float"),
         arity: 1,
-        func: type_to_float,
+        func: Arc::new(type_to_float),
     }
 }
 
@@ -58,10 +60,13 @@ fn type_check_type(args: &[Value]) -> Result<Value, RuntimeError> {
     match auto {
         Value::String(_) => Ok(Value::String("string".to_smolstr())),
         Value::Float(_) => Ok(Value::String("float".to_smolstr())),
-        Value::Int(_) => Ok(Value::String("number".to_smolstr())),
+        Value::Int(_) | Value::BigInt(_) => Ok(Value::String("number".to_smolstr())),
         Value::Bool(_) => Ok(Value::String("bool".to_smolstr())),
         Value::Array(..) => Ok(Value::String("array".to_smolstr())),
+        Value::Map(_) => Ok(Value::String("map".to_smolstr())),
         Value::Ref(_) => Ok(Value::String("ref".to_smolstr())),
+        Value::Closure(_) => Ok(Value::String("function".to_smolstr())),
+        Value::Object(object) => Ok(Value::String(object.class_name().to_smolstr())),
         Value::Null => Ok(Value::String("null".to_smolstr())),
     }
 }
@@ -70,25 +75,25 @@ fn reg_check_type() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("check_type"),
         arity: 1,
-        func: type_check_type,
+        func: Arc::new(type_check_type),
     }
 }
 
 #[allow(clippy::unnecessary_wraps)]
 fn type_array_length(args: &[Value]) -> Result<Value, RuntimeError> {
-    let Value::Array(len, _) = args.first().unwrap().clone() else {
+    let Value::Array(elements) = args.first().unwrap() else {
         return Err(RuntimeError::TypeException(
             "array_length: arg is not array.".to_smolstr(),
         ));
     };
-    Ok(Value::Int(i64::try_from(len).unwrap()))
+    Ok(Value::Int(i64::try_from(elements.len()).unwrap()))
 }
 
 fn reg_array_length() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("array_length"),
         arity: 1,
-        func: type_array_length,
+        func: Arc::new(type_array_length),
     }
 }
 
@@ -111,14 +116,15 @@ fn type_array_fill(args: &[Value]) -> Result<Value, RuntimeError> {
     };
     let len = usize::try_from(*len)
         .map_err(|_| RuntimeError::TypeException("array_fill: length is negative.".to_smolstr()))?;
-    Ok(Value::Array(len, vec![value; len]))
+    context::check_length(len)?;
+    Ok(Value::Array(vec![value; len]))
 }
 
 fn reg_array_fill() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("array_fill"),
         arity: 2,
-        func: type_array_fill,
+        func: Arc::new(type_array_fill),
     }
 }
 
@@ -128,23 +134,275 @@ fn type_length(args: &[Value]) -> Result<Value, RuntimeError> {
         return Ok(Value::Int(raw_str.len() as i64));
     }
 
-    let Value::Array(len, _) = args.first().unwrap().clone() else {
+    if let Value::Map(map) = args.first().unwrap() {
+        return Ok(Value::Int(i64::try_from(map.len()).unwrap()));
+    }
+
+    let Value::Array(elements) = args.first().unwrap() else {
         return Err(RuntimeError::TypeException(
-            "_length: arg is not array or string".to_smolstr(),
+            "_length: arg is not array, map or string".to_smolstr(),
         ));
     };
-    Ok(Value::Int(i64::try_from(len).unwrap()))
+    Ok(Value::Int(i64::try_from(elements.len()).unwrap()))
 }
 
 fn reg_length() -> ModuleFunc {
     ModuleFunc {
         name: SmolStr::new("_length"),
         arity: 1,
-        func: type_length,
+        func: Arc::new(type_length),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn type_keys(args: &[Value]) -> Result<Value, RuntimeError> {
+    let Value::Map(map) = args.first().unwrap() else {
+        return Err(RuntimeError::TypeException(
+            "keys: arg is not map.".to_smolstr(),
+        ));
+    };
+    let keys: Vec<Value> = map.keys().cloned().map(Value::String).collect();
+    Ok(Value::Array(keys))
+}
+
+fn reg_keys() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("keys"),
+        arity: 1,
+        func: Arc::new(type_keys),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn type_has_key(args: &[Value]) -> Result<Value, RuntimeError> {
+    let (Some(Value::Map(map)), Some(Value::String(key))) = (args.first(), args.get(1)) else {
+        return Err(RuntimeError::TypeException(
+            "has_key: args is not map and string.".to_smolstr(),
+        ));
+    };
+    Ok(Value::Bool(map.contains_key(key)))
+}
+
+fn reg_has_key() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("has_key"),
+        arity: 2,
+        func: Arc::new(type_has_key),
+    }
+}
+
+// 参数按值传递, 返回删除指定键后的新字典
+#[allow(clippy::unnecessary_wraps)]
+fn type_remove(args: &[Value]) -> Result<Value, RuntimeError> {
+    let (Some(Value::Map(map)), Some(Value::String(key))) = (args.first(), args.get(1)) else {
+        return Err(RuntimeError::TypeException(
+            "remove: args is not map and string.".to_smolstr(),
+        ));
+    };
+    let mut map = map.clone();
+    map.remove(key);
+    Ok(Value::Map(map))
+}
+
+fn reg_remove() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("remove"),
+        arity: 2,
+        func: Arc::new(type_remove),
+    }
+}
+
+fn array_arg(func: &str, args: &[Value], index: usize) -> Result<Vec<Value>, RuntimeError> {
+    let Some(Value::Array(elements)) = args.get(index) else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{func}: arg {index} is not array."
+        )));
+    };
+    Ok(elements.clone())
+}
+
+fn int_arg(func: &str, args: &[Value], index: usize) -> Result<i64, RuntimeError> {
+    let Some(Value::Int(value)) = args.get(index) else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{func}: arg {index} is not number."
+        )));
+    };
+    Ok(*value)
+}
+
+// 切片边界允许等于数组长度, 为 null 时取默认边界
+fn slice_bound(
+    args: &[Value],
+    index: usize,
+    len: usize,
+    default: usize,
+) -> Result<usize, RuntimeError> {
+    let bound = match args.get(index) {
+        Some(Value::Null) => return Ok(default),
+        _ => int_arg("slice", args, index)?,
+    };
+    usize::try_from(bound)
+        .ok()
+        .filter(|bound| *bound <= len)
+        .ok_or_else(|| RuntimeError::index_out_of_bounds(bound, len))
+}
+
+// 原地修改数组的函数 (函数名, 数组之外的实参个数), 以编号作为 array_call 指令的操作数
+pub const ARRAY_FUNCS: [(&str, usize); 4] =
+    [("push", 1), ("pop", 0), ("insert", 2), ("remove_at", 1)];
+
+#[must_use]
+pub fn array_func_index(name: &str) -> Option<usize> {
+    ARRAY_FUNCS.iter().position(|(func, _)| *func == name)
+}
+
+/// 在数组变量上原地执行 `ARRAY_FUNCS` 中编号为 `func` 的函数, `args` 为数组之外的实参
+/// # Errors
+/// 目标不是数组或索引越界时返回对应的运行时错误
+pub fn call_array_func(
+    func: usize,
+    target: &mut Value,
+    args: &[Value],
+) -> Result<Value, RuntimeError> {
+    let (name, _) = ARRAY_FUNCS[func];
+    let Value::Array(elements) = target else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "{name}: arg 0 is not array."
+        )));
+    };
+    let value = || args.first().cloned().unwrap_or(Value::Null);
+    match name {
+        "push" => {
+            context::charge_length(1)?;
+            elements.push(value());
+            Ok(Value::Null)
+        }
+        "pop" => elements
+            .pop()
+            .ok_or_else(|| RuntimeError::index_out_of_bounds(-1, 0)),
+        "insert" => {
+            let index = int_arg(name, args, 0)?;
+            // 允许插入到数组末尾
+            let usize_index = check_index(index, elements.len() + 1)
+                .map_err(|_| RuntimeError::index_out_of_bounds(index, elements.len()))?;
+            context::charge_length(1)?;
+            elements.insert(usize_index, args.get(1).cloned().unwrap_or(Value::Null));
+            Ok(Value::Null)
+        }
+        _ => {
+            let index = check_index(int_arg(name, args, 0)?, elements.len())?;
+            Ok(elements.remove(index))
+        }
+    }
+}
+
+// 首个实参不是变量时无法原地修改, 只在数组的副本上执行
+fn copy_array_func(func: usize, args: &[Value]) -> Result<Value, RuntimeError> {
+    let (name, _) = ARRAY_FUNCS[func];
+    let mut target = Value::Array(array_arg(name, args, 0)?);
+    call_array_func(func, &mut target, &args[1..])
+}
+
+fn type_push(args: &[Value]) -> Result<Value, RuntimeError> {
+    copy_array_func(0, args)
+}
+
+fn reg_push() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("push"),
+        arity: 2,
+        func: Arc::new(type_push),
+    }
+}
+
+fn type_pop(args: &[Value]) -> Result<Value, RuntimeError> {
+    copy_array_func(1, args)
+}
+
+fn reg_pop() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("pop"),
+        arity: 1,
+        func: Arc::new(type_pop),
+    }
+}
+
+fn type_insert(args: &[Value]) -> Result<Value, RuntimeError> {
+    copy_array_func(2, args)
+}
+
+fn reg_insert() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("insert"),
+        arity: 3,
+        func: Arc::new(type_insert),
+    }
+}
+
+fn type_remove_at(args: &[Value]) -> Result<Value, RuntimeError> {
+    copy_array_func(3, args)
+}
+
+fn reg_remove_at() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("remove_at"),
+        arity: 2,
+        func: Arc::new(type_remove_at),
+    }
+}
+
+fn type_slice(args: &[Value]) -> Result<Value, RuntimeError> {
+    let elements = array_arg("slice", args, 0)?;
+    let start = slice_bound(args, 1, elements.len(), 0)?;
+    let end = slice_bound(args, 2, elements.len(), elements.len())?;
+    // 起始边界大于结束边界时得到空数组
+    Ok(Value::Array(elements[start..end.max(start)].to_vec()))
+}
+
+fn reg_slice() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("slice"),
+        arity: 3,
+        func: Arc::new(type_slice),
+    }
+}
+
+fn type_concat(args: &[Value]) -> Result<Value, RuntimeError> {
+    let mut elements = array_arg("concat", args, 0)?;
+    let tail = array_arg("concat", args, 1)?;
+    context::check_length(elements.len() + tail.len())?;
+    elements.extend(tail);
+    Ok(Value::Array(elements))
+}
+
+fn reg_concat() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("concat"),
+        arity: 2,
+        func: Arc::new(type_concat),
+    }
+}
+
+// 找不到元素时返回 -1
+fn type_index_of(args: &[Value]) -> Result<Value, RuntimeError> {
+    let elements = array_arg("index_of", args, 0)?;
+    let target = args.get(1).cloned().unwrap_or(Value::Null);
+    let index = elements
+        .iter()
+        .position(|element| *element == target)
+        .map_or(-1, |index| i64::try_from(index).unwrap());
+    Ok(Value::Int(index))
+}
+
+fn reg_index_of() -> ModuleFunc {
+    ModuleFunc {
+        name: SmolStr::new("index_of"),
+        arity: 2,
+        func: Arc::new(type_index_of),
     }
 }
 
-pub fn register_type_lib() {
+pub fn register_type_lib(registry: &mut NativeRegistry) {
     let mut type_lib = LibModule {
         name: SmolStr::new("type"),
         functions: vec![],
@@ -155,5 +413,15 @@ pub fn register_type_lib() {
     type_lib.functions.push(reg_array_length());
     type_lib.functions.push(reg_array_fill());
     type_lib.functions.push(reg_length());
-    register_library(type_lib);
+    type_lib.functions.push(reg_keys());
+    type_lib.functions.push(reg_has_key());
+    type_lib.functions.push(reg_remove());
+    type_lib.functions.push(reg_push());
+    type_lib.functions.push(reg_pop());
+    type_lib.functions.push(reg_insert());
+    type_lib.functions.push(reg_remove_at());
+    type_lib.functions.push(reg_slice());
+    type_lib.functions.push(reg_concat());
+    type_lib.functions.push(reg_index_of());
+    registry.register_module(type_lib);
 }
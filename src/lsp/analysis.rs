@@ -0,0 +1,234 @@
+use serde_json::{Value, json};
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashSet};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+
+use crate::compiler::Compiler;
+use crate::compiler::diagnostic::{Diagnostic, Span};
+use crate::compiler::file::{SourceFile, module_name};
+use crate::compiler::lexer::{LexerAnalysis, LexerError, Token, TokenType};
+use crate::compiler::parser::symbol_table::{ElementType, SymbolTable};
+use crate::lsp::document::Document;
+use crate::lsp::path_to_uri;
+
+/// 语义高亮的 token 类型, 下标即协议中的类型编号
+pub const TOKEN_TYPES: [&str; 9] = [
+    "keyword",
+    "number",
+    "string",
+    "variable",
+    "parameter",
+    "function",
+    "class",
+    "namespace",
+    "operator",
+];
+
+/// 库模块中可以补全的函数
+#[derive(Debug, Clone)]
+pub struct LibraryFunction {
+    pub name: SmolStr,
+    pub detail: String,          // 函数的声明, 如 `function println(output)`
+    pub location: Option<Value>, // 声明在库文件中的位置, 宿主注册的本地函数没有源码
+}
+
+// 定义所在行去掉缩进与行尾的 `{` `;`, 作为提示中展示的声明
+pub fn declaration(source: &str, span: &Span) -> String {
+    source
+        .lines()
+        .nth(span.line - 1)
+        .unwrap_or_default()
+        .trim()
+        .trim_end_matches(['{', ';', ' '])
+        .to_string()
+}
+
+/// 按模块名索引的库函数, 来自标准库目录中的源文件与已注册的本地模块
+pub fn library_index(
+    compiler: &mut Compiler,
+    lib_dir: &Path,
+) -> BTreeMap<SmolStr, Vec<LibraryFunction>> {
+    let mut index: BTreeMap<SmolStr, Vec<LibraryFunction>> = BTreeMap::new();
+    for file in compiler.get_files().iter().filter(|file| file.is_library) {
+        let path = lib_dir.join(&file.name);
+        let source = Document::new(file.name.clone(), file.get_data().to_string());
+        let functions = file
+            .symbol_table()
+            .definitions()
+            .iter()
+            .filter(|definition| matches!(definition.el_type, ElementType::Function(_)))
+            .map(|definition| LibraryFunction {
+                name: definition.name.clone(),
+                detail: declaration(file.get_data(), &definition.span),
+                location: Some(json!({
+                    "uri": path_to_uri(&path),
+                    "range": source.range(&definition.span),
+                })),
+            })
+            .collect();
+        index.insert(file.module_name().into(), functions);
+    }
+    for module in compiler.natives().modules() {
+        let functions = index.entry(module.name().into()).or_default();
+        for func in module.functions() {
+            if functions.iter().any(|function| function.name == func.name) {
+                continue;
+            }
+            let args = (0..func.arity)
+                .map(|i| format!("arg{i}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            functions.push(LibraryFunction {
+                name: func.name.clone(),
+                detail: format!("function native {}({args})", func.name),
+                location: None,
+            });
+        }
+    }
+    index
+}
+
+/// 以文档当前的内容重新编译, 更新诊断与符号表
+/// 存在语法错误时不会进行语义分析, 此时保留上一次的符号表供跳转与补全使用
+pub fn analyze(base: &Compiler, lib_dir: &Path, document: &mut Document) {
+    let mut compiler = base.clone();
+    compiler.add_file(SourceFile::new(
+        document.path.clone(),
+        document.text().to_string(),
+        HashSet::new(),
+        false,
+    ));
+    // 编辑中的源码可能触发编译器内部的断言, 不能因此结束语言服务器
+    // 此时报告一条位于文件开头的错误, 而不是清空已有的诊断
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(|| compiler.compile())) {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        let span = Span {
+            line: 1,
+            column: 1,
+            end_line: 1,
+            end_column: 1,
+        };
+        let mut diagnostic =
+            Diagnostic::error(&document.path, span, "internal compiler error".to_string());
+        if !reason.is_empty() {
+            diagnostic.notes.push(reason);
+        }
+        document.diagnostics = vec![diagnostic];
+        return;
+    }
+    document.diagnostics = compiler
+        .diagnostics()
+        .iter()
+        .filter(|diagnostic| diagnostic.file == document.path)
+        .cloned()
+        .collect();
+    let Some(file) = compiler.find_file(&module_name(&document.path)) else {
+        return;
+    };
+    let symbols = file.symbol_table().clone();
+    if symbols.definitions().is_empty() && document.diagnostics.iter().any(Diagnostic::is_error) {
+        return;
+    }
+    document.modules = symbols
+        .definitions()
+        .iter()
+        .filter_map(|definition| {
+            let ElementType::Library(module) = &definition.el_type else {
+                return None;
+            };
+            let file = compiler.find_file(module)?;
+            let path = if base.find_file(module).is_some() {
+                lib_dir.join(&file.name)
+            } else {
+                std::path::absolute(&file.name).ok()?
+            };
+            Some((definition.name.to_string(), path))
+        })
+        .collect();
+    document.symbols = symbols;
+}
+
+// token 在源码中的起始字节偏移, token 记录的是首个字符之后的偏移
+fn token_start(text: &str, token: &Token) -> usize {
+    text[..token.index]
+        .chars()
+        .next_back()
+        .map_or(token.index, |c| token.index - c.len_utf8())
+}
+
+fn token_type(token: &Token, next: Option<&Token>, symbols: &SymbolTable) -> Option<usize> {
+    let name = match token.t_type {
+        TokenType::Number | TokenType::Float => "number",
+        TokenType::LiteralString => "string",
+        TokenType::Operator => "operator",
+        TokenType::LP | TokenType::LR | TokenType::End => return None,
+        TokenType::Identifier => match symbols
+            .definition_at(token.line + 1, token.column)
+            .map(|definition| &definition.el_type)
+        {
+            Some(ElementType::Library(_)) => "namespace",
+            Some(ElementType::Function(_) | ElementType::Func) => "function",
+            Some(ElementType::Class) => "class",
+            Some(ElementType::Argument) => "parameter",
+            Some(ElementType::Value) => "variable",
+            None if next.is_some_and(|next| next.text() == "(") => "function",
+            None => "variable",
+        },
+        _ => "keyword",
+    };
+    TOKEN_TYPES.iter().position(|kind| *kind == name)
+}
+
+/// 按协议的相对位置编码输出整个文档的语义高亮, 跨行的 token 不输出
+/// 词法错误处跳过出错的字符继续分析
+#[must_use]
+pub fn semantic_tokens(document: &Document) -> Vec<usize> {
+    let text = document.text();
+    let mut lexer = LexerAnalysis::new(text.to_string());
+    let mut tokens = vec![];
+    loop {
+        let before = lexer.offset();
+        match lexer.next_token() {
+            Ok(token) => tokens.push((token, lexer.offset())),
+            Err(LexerError::Eof) => break,
+            Err(_) if lexer.offset() > before => {}
+            Err(_) => break,
+        }
+    }
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let mut data = vec![];
+    let (mut last_line, mut last_character) = (0, 0);
+    for (i, (token, end)) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).map(|(next, _)| next);
+        let Some(kind) = token_type(token, next, &document.symbols) else {
+            continue;
+        };
+        let start = token_start(text, token);
+        if text[start..*end].contains('\n') {
+            continue;
+        }
+        let line = line_starts.partition_point(|line_start| *line_start <= start) - 1;
+        let character = text[line_starts[line]..start].encode_utf16().count();
+        let length = text[start..*end].encode_utf16().count();
+        if line != last_line {
+            last_character = 0;
+        }
+        data.extend([
+            line - last_line,
+            character - last_character,
+            length,
+            kind,
+            0,
+        ]);
+        (last_line, last_character) = (line, character);
+    }
+    data
+}
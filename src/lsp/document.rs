@@ -0,0 +1,121 @@
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::compiler::diagnostic::{Diagnostic, Span};
+use crate::compiler::parser::symbol_table::SymbolTable;
+
+/// 编辑器中打开的源文件, 内容以编辑器同步的未保存缓冲区为准
+/// LSP 的位置以 UTF-16 码元计列, 编译器以字符计列, 两者在此转换
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub path: String, // 编译时使用的文件名
+    text: String,
+    pub(super) diagnostics: Vec<Diagnostic>,
+    pub(super) symbols: SymbolTable,
+    pub(super) modules: HashMap<String, PathBuf>, // 导入名对应的模块文件, 用于跳转到导入的模块
+}
+
+impl Document {
+    #[must_use]
+    pub fn new(path: String, text: String) -> Self {
+        Self {
+            path,
+            text,
+            diagnostics: vec![],
+            symbols: SymbolTable::new(),
+            modules: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 应用 `didChange` 中的一项修改, 没有 range 时替换全部内容
+    pub fn apply_change(&mut self, change: &Value) {
+        let text = change["text"].as_str().unwrap_or_default();
+        match change.get("range") {
+            Some(range) => {
+                let start = self.offset_at(&range["start"]);
+                let end = self.offset_at(&range["end"]).max(start);
+                self.text.replace_range(start..end, text);
+            }
+            None => self.text = text.to_string(),
+        }
+    }
+
+    /// 第 line 行 (从 0 开始) 的内容, 不包含换行符
+    #[must_use]
+    pub fn line(&self, line: usize) -> &str {
+        self.text
+            .split('\n')
+            .nth(line)
+            .map_or("", |line| line.strip_suffix('\r').unwrap_or(line))
+    }
+
+    /// LSP 位置对应的字节偏移, 超出范围时取所在行或全文的末尾
+    #[must_use]
+    pub fn offset_at(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let character = position["character"].as_u64().unwrap_or_default() as usize;
+        let mut offset = 0;
+        for (index, content) in self.text.split('\n').enumerate() {
+            if index == line {
+                return offset + utf16_to_byte(content, character);
+            }
+            offset += content.len() + 1;
+        }
+        self.text.len()
+    }
+
+    /// 字节偏移对应的 LSP 位置
+    #[must_use]
+    pub fn position_at(&self, offset: usize) -> Value {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        json!({
+            "line": before.matches('\n').count(),
+            "character": before[line_start..].encode_utf16().count(),
+        })
+    }
+
+    /// LSP 位置对应的编译器行列号 (均从 1 开始)
+    #[must_use]
+    pub fn line_column(&self, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let character = position["character"].as_u64().unwrap_or_default() as usize;
+        let content = self.line(line);
+        let column = content[..utf16_to_byte(content, character)].chars().count();
+        (line + 1, column + 1)
+    }
+
+    /// 编译器源码区间对应的 LSP 区间
+    #[must_use]
+    pub fn range(&self, span: &Span) -> Value {
+        let character = |line: usize, column: usize| {
+            self.line(line - 1)
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(char::len_utf16)
+                .sum::<usize>()
+        };
+        json!({
+            "start": {"line": span.line - 1, "character": character(span.line, span.column)},
+            "end": {"line": span.end_line - 1, "character": character(span.end_line, span.end_column)},
+        })
+    }
+}
+
+// 一行内第 character 个 UTF-16 码元对应的字节偏移
+fn utf16_to_byte(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= character || c == '\r' {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
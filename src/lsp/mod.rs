@@ -0,0 +1,517 @@
+use serde_json::{Value, json};
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::compiler::Compiler;
+use crate::compiler::diagnostic::{Diagnostic, MessageFormat};
+use crate::compiler::lexer::KEYWORDS;
+use crate::compiler::parser::symbol_table::ElementType;
+use crate::library::load_libraries;
+use crate::lsp::analysis::{
+    LibraryFunction, TOKEN_TYPES, analyze, declaration, library_index, semantic_tokens,
+};
+use crate::lsp::document::Document;
+
+mod analysis;
+pub mod document;
+
+#[derive(Debug)]
+enum RpcError {
+    ParseError(String),     // 消息不是合法的 JSON
+    InvalidRequest,         // shutdown 之后收到请求
+    MethodNotFound(String), // 不支持的请求
+    InvalidParams(String),  // 缺少参数或文档未打开
+    NotInitialized,         // initialize 之前收到请求
+}
+
+impl RpcError {
+    const fn code(&self) -> i64 {
+        match self {
+            Self::ParseError(_) => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound(_) => -32601,
+            Self::InvalidParams(_) => -32602,
+            Self::NotInitialized => -32002,
+        }
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ParseError(error) => write!(f, "invalid message: {error}"),
+            Self::InvalidRequest => write!(f, "server is shutting down"),
+            Self::MethodNotFound(method) => write!(f, "unknown method `{method}`"),
+            Self::InvalidParams(error) => write!(f, "invalid params: {error}"),
+            Self::NotInitialized => write!(f, "server is not initialized"),
+        }
+    }
+}
+
+/// 读取一条以 `Content-Length` 头分帧的消息, 输入结束时返回 None
+/// # Errors
+/// 读取失败或消息头不完整
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// 以 `Content-Length` 头分帧写出一条消息
+/// # Errors
+/// 写入失败
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+/// file URI 对应的文件路径, 其他协议的 URI 原样作为文件名
+#[must_use]
+pub fn uri_to_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(escaped) if byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// 文件路径对应的 file URI
+#[must_use]
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// OpenEX 语言服务器, 通过标准输入输出与编辑器交换 JSON-RPC 消息
+/// 文档以编辑器同步的未保存内容为准, 每次修改后重新分析并发布诊断
+pub struct Server {
+    lib_dir: Option<PathBuf>, // 标准库目录, 未指定时在 initialize 时确定
+    compiler: Compiler,       // 已加载标准库的编译器, 每次分析时克隆
+    library: BTreeMap<SmolStr, Vec<LibraryFunction>>,
+    documents: HashMap<String, Document>, // 以 URI 索引的已打开文档
+    outgoing: Vec<Value>,                 // 待发送的响应与通知
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl Server {
+    #[must_use]
+    pub fn new(lib_dir: Option<PathBuf>) -> Self {
+        Self {
+            lib_dir,
+            compiler: Compiler::new(),
+            library: BTreeMap::new(),
+            documents: HashMap::new(),
+            outgoing: vec![],
+            initialized: false,
+            shutdown: false,
+        }
+    }
+
+    /// 处理消息直到收到 exit 通知或输入结束, 返回进程的退出码
+    /// 先收到 shutdown 请求再退出时退出码为 0, 否则为 1
+    /// # Errors
+    /// 读写消息失败
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+        while let Some(message) = read_message(input)? {
+            let exit = match serde_json::from_str::<Value>(&message) {
+                Ok(message) => self.handle(&message),
+                Err(error) => {
+                    let error = RpcError::ParseError(error.to_string());
+                    self.respond(Value::Null, Err(error));
+                    false
+                }
+            };
+            for message in self.outgoing.drain(..) {
+                write_message(output, &message)?;
+            }
+            if exit {
+                return Ok(if self.shutdown { 0 } else { 1 });
+            }
+        }
+        Ok(1)
+    }
+
+    // 返回是否收到 exit 通知
+    fn handle(&mut self, message: &Value) -> bool {
+        let params = &message["params"];
+        match (message.get("id"), message["method"].as_str()) {
+            (Some(id), Some(method)) => {
+                let result = self.request(method, params);
+                self.respond(id.clone(), result);
+            }
+            (None, Some("exit")) => return true,
+            (None, Some(method)) if self.initialized => self.notification(method, params),
+            // 客户端的响应与初始化之前的通知直接忽略
+            _ => {}
+        }
+        false
+    }
+
+    fn respond(&mut self, id: Value, result: Result<Value, RpcError>) {
+        let message = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": error.code(), "message": error.to_string()},
+            }),
+        };
+        self.outgoing.push(message);
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.outgoing
+            .push(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        if self.shutdown {
+            return Err(RpcError::InvalidRequest);
+        }
+        if !self.initialized && method != "initialize" {
+            return Err(RpcError::NotInitialized);
+        }
+        match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/semanticTokens/full" => {
+                let document = self.document(params)?;
+                Ok(json!({"data": semantic_tokens(document)}))
+            }
+            _ => Err(RpcError::MethodNotFound(method.to_string())),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let document = Document::new(uri_to_path(uri), text.to_string());
+                self.documents.insert(uri.to_string(), document);
+                self.update(uri);
+            }
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(uri) else {
+                    return;
+                };
+                let changes = params["contentChanges"].as_array();
+                for change in changes.into_iter().flatten() {
+                    document.apply_change(change);
+                }
+                self.update(uri);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    // 标准库目录依次取命令行参数, 初始化选项中的 lib, 工作区下的 lib 目录, 当前目录下的 lib 目录
+    fn initialize(&mut self, params: &Value) -> Value {
+        let root = params["rootUri"]
+            .as_str()
+            .map(uri_to_path)
+            .or_else(|| params["rootPath"].as_str().map(String::from))
+            .map(PathBuf::from);
+        let lib_dir = params["initializationOptions"]["lib"]
+            .as_str()
+            .map(PathBuf::from)
+            .or_else(|| self.lib_dir.clone())
+            .or_else(|| {
+                root.as_ref()
+                    .map(|root| root.join("lib"))
+                    .filter(|dir| dir.is_dir())
+            })
+            .unwrap_or_else(|| PathBuf::from("./lib"));
+
+        let mut compiler = Compiler::new();
+        compiler.set_message_format(MessageFormat::Silent);
+        if let Some(root) = root {
+            compiler.add_search_path(root);
+        }
+        let path = Some(lib_dir.to_string_lossy().into());
+        // 没有标准库目录时仍然可以补全本地函数
+        if load_libraries(&mut compiler, path, &HashSet::new()).is_err() {
+            compiler.natives_mut().register_std();
+        }
+        self.library = library_index(&mut compiler, &lib_dir);
+        self.compiler = compiler;
+        self.lib_dir = Some(lib_dir);
+        self.initialized = true;
+
+        json!({
+            "capabilities": {
+                "textDocumentSync": {"openClose": true, "change": 2},
+                "definitionProvider": true,
+                "hoverProvider": true,
+                "completionProvider": {"triggerCharacters": ["."]},
+                "semanticTokensProvider": {
+                    "legend": {"tokenTypes": TOKEN_TYPES, "tokenModifiers": []},
+                    "full": true,
+                },
+            },
+            "serverInfo": {"name": "openex-lsp", "version": Compiler::get_version()},
+        })
+    }
+
+    // 重新分析文档并发布诊断
+    fn update(&mut self, uri: &str) {
+        let Some(document) = self.documents.get_mut(uri) else {
+            return;
+        };
+        let lib_dir = self.lib_dir.as_deref().unwrap_or(Path::new("./lib"));
+        analyze(&self.compiler, lib_dir, document);
+        let diagnostics: Vec<Value> = document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| Self::diagnostic(document, diagnostic))
+            .collect();
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        );
+    }
+
+    // 补充说明与修改建议附加在信息之后
+    fn diagnostic(document: &Document, diagnostic: &Diagnostic) -> Value {
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push_str(&format!("\nnote: {note}"));
+        }
+        if let Some(suggestion) = &diagnostic.suggestion {
+            message.push_str(&format!("\nhelp: {suggestion}"));
+        }
+        let mut value = json!({
+            "range": document.range(&diagnostic.span),
+            "severity": if diagnostic.is_error() { 1 } else { 2 },
+            "source": "openex",
+            "message": message,
+        });
+        if let Some(code) = diagnostic.code {
+            value["code"] = code.into();
+        }
+        value
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document, RpcError> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .ok_or_else(|| RpcError::InvalidParams(format!("document `{uri}` is not open")))
+    }
+
+    // 导入名对应的模块名, 文档尚未成功分析时按库模块名处理
+    fn module(&self, document: &Document, name: &str) -> Option<SmolStr> {
+        document
+            .symbols
+            .definitions()
+            .iter()
+            .rev()
+            .find_map(|definition| match &definition.el_type {
+                ElementType::Library(module) if definition.name == name => Some(module.clone()),
+                _ => None,
+            })
+            .or_else(|| self.library.contains_key(name).then(|| name.into()))
+    }
+
+    // 光标处 `库.函数` 形式引用的库函数
+    fn member(&self, document: &Document, position: &Value) -> Option<&LibraryFunction> {
+        let (line, column) = document.line_column(position);
+        let chars: Vec<char> = document.line(line - 1).chars().collect();
+        let cursor = (column - 1).min(chars.len());
+        let start = chars[..cursor]
+            .iter()
+            .rposition(|c| !is_identifier_char(*c))
+            .map_or(0, |index| index + 1);
+        let end = chars[cursor..]
+            .iter()
+            .position(|c| !is_identifier_char(*c))
+            .map_or(chars.len(), |index| cursor + index);
+        let name: String = chars[start..end].iter().collect();
+        let module = self.member_base(document, &chars[..start])?;
+        self.library
+            .get(&module)?
+            .iter()
+            .find(|function| function.name == name)
+    }
+
+    // `库.` 之后的位置对应的模块名, before 为该位置之前的内容
+    fn member_base(&self, document: &Document, before: &[char]) -> Option<SmolStr> {
+        let (dot, before) = before.split_last()?;
+        if *dot != '.' {
+            return None;
+        }
+        let start = before
+            .iter()
+            .rposition(|c| !is_identifier_char(*c))
+            .map_or(0, |index| index + 1);
+        let base: String = before[start..].iter().collect();
+        self.module(document, &base)
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, RpcError> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        if let Some(function) = self.member(document, position) {
+            return Ok(function.location.clone().unwrap_or(Value::Null));
+        }
+        let (line, column) = document.line_column(position);
+        let Some(definition) = document.symbols.definition_at(line, column) else {
+            return Ok(Value::Null);
+        };
+        let uri = &params["textDocument"]["uri"];
+        // 导入名跳转到被导入模块的文件开头
+        if matches!(definition.el_type, ElementType::Library(_))
+            && let Some(path) = document.modules.get(definition.name.as_str())
+        {
+            let start = json!({"line": 0, "character": 0});
+            return Ok(json!({
+                "uri": path_to_uri(path),
+                "range": {"start": start, "end": start},
+            }));
+        }
+        Ok(json!({"uri": uri, "range": document.range(&definition.span)}))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, RpcError> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        let detail = if let Some(function) = self.member(document, position) {
+            function.detail.clone()
+        } else {
+            let (line, column) = document.line_column(position);
+            let Some(definition) = document.symbols.definition_at(line, column) else {
+                return Ok(Value::Null);
+            };
+            match definition.el_type {
+                ElementType::Argument => format!("(argument) {}", definition.name),
+                _ => declaration(document.text(), &definition.span),
+            }
+        };
+        Ok(json!({
+            "contents": {"kind": "markdown", "value": format!("```openex\n{detail}\n```")},
+        }))
+    }
+
+    // 在 `库.` 之后补全库函数, 否则补全关键字, 文档中定义的符号与库模块名
+    fn completion(&self, params: &Value) -> Result<Value, RpcError> {
+        let document = self.document(params)?;
+        let (line, column) = document.line_column(&params["position"]);
+        let chars: Vec<char> = document.line(line - 1).chars().collect();
+        let cursor = (column - 1).min(chars.len());
+        let start = chars[..cursor]
+            .iter()
+            .rposition(|c| !is_identifier_char(*c))
+            .map_or(0, |index| index + 1);
+        if start > 0 && chars[start - 1] == '.' {
+            let functions = self
+                .member_base(document, &chars[..start])
+                .and_then(|module| self.library.get(&module));
+            let items: Vec<Value> = functions
+                .into_iter()
+                .flatten()
+                .map(|function| {
+                    json!({"label": function.name.as_str(), "kind": 3, "detail": function.detail})
+                })
+                .collect();
+            return Ok(Value::Array(items));
+        }
+
+        let mut labels = HashSet::new();
+        let mut items = vec![];
+        for (keyword, _) in &KEYWORDS {
+            labels.insert((*keyword).to_string());
+            items.push(json!({"label": keyword, "kind": 14}));
+        }
+        for definition in document.symbols.definitions() {
+            if !labels.insert(definition.name.to_string()) {
+                continue;
+            }
+            let kind = match definition.el_type {
+                ElementType::Function(_) | ElementType::Func => 3,
+                ElementType::Class => 7,
+                ElementType::Library(_) => 9,
+                ElementType::Value | ElementType::Argument => 6,
+            };
+            items.push(json!({
+                "label": definition.name.as_str(),
+                "kind": kind,
+                "detail": declaration(document.text(), &definition.span),
+            }));
+        }
+        for module in self.library.keys() {
+            if labels.insert(module.to_string()) {
+                items.push(json!({"label": module.as_str(), "kind": 9}));
+            }
+        }
+        Ok(Value::Array(items))
+    }
+}
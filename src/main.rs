@@ -1,14 +1,25 @@
 use getopts_macro::getopts_options;
 use mimalloc::MiMalloc;
+use openex::compiler::bytecode::{BYTECODE_EXTENSION, decode_units, encode_units};
+use openex::compiler::diagnostic::{Diagnostic, MessageFormat};
+use openex::compiler::dump::EmitKind;
 use openex::compiler::file::SourceFile;
+use openex::compiler::format::format_source;
+use openex::compiler::optimize::{MAX_OPT_LEVEL, Optimizations};
 use openex::compiler::{Compiler, lints};
-use openex::library::load_libraries;
-use openex::runtime::initialize_executor;
+use openex::library::{NativeRegistry, SharedNatives, load_libraries};
+use openex::project::Project;
+use openex::runtime::debugger::{Breakpoint, ConsoleDebugger, Debugger};
+use openex::runtime::{
+    GlobalStore, MetadataUnit, RuntimeEnv, SharedGlobals, bind_natives, build_metadata,
+    execute_units, execute_units_with,
+};
 use smol_str::SmolStr;
 use std::collections::HashSet;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::Arc;
 use std::{fs, io};
 
 #[global_allocator]
@@ -16,23 +27,38 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 struct Args {
     input: Vec<String>,
-    #[expect(unused)]
     debug: bool,
+    breakpoints: Vec<Breakpoint>,
     cli: bool,
     allow: HashSet<lints::Lint>,
     lib: Option<SmolStr>,
+    output: Option<PathBuf>,
     version: bool,
+    build: bool,
+    run: bool,
+    fmt: bool,
+    check: bool,
+    emit: Option<EmitKind>,
+    message_format: MessageFormat,
+    optimize: Optimizations,
 }
 
 impl Args {
     fn parse() -> Self {
         let options = getopts_options! {
-            -d, --debug         "";
+            -d, --debug         "Run with the step debugger";
+            -b, --break*=LOC    "Set a debugger breakpoint (file:line)";
                 --cli           "terminal mode";
             -A, --allow*=LINT   "Disable compiler warning";
             -v, --version       "Print version";
             -h, --help*         "Print help";
-            -l, --lib*          "Set libraries directory";
+            -l, --lib*=DIR      "Set libraries directory";
+            -o, --output=FILE   "Set bytecode output file (build mode)";
+            -O, --opt-level=LEVEL "Set optimization level (0-2, default 2)";
+            -Z, --pass*=FLAG    "Enable or disable an optimization (<pass>|no-<pass>)";
+                --emit=KIND     "Print the compiled IR instead of running (ast|ssa|bytecode)";
+                --message-format=FMT "Set the format of compiler diagnostics (human|json)";
+                --check         "Check formatting without writing files (fmt mode)";
         };
         let m = match options.parse(std::env::args().skip(1)) {
             Ok(m) => m,
@@ -45,8 +71,20 @@ impl Args {
             Self::help(&options);
             exit(1)
         }
+        let mut input = m.free.clone();
+        let build = input.first().is_some_and(|arg| arg == "build");
+        let run = input.first().is_some_and(|arg| arg == "run");
+        let fmt = input.first().is_some_and(|arg| arg == "fmt");
+        if build || run || fmt {
+            input.remove(0);
+        }
         let args = Self {
-            debug: m.opt_present("debug"),
+            debug: m.opt_present("debug") || m.opt_present("break"),
+            breakpoints: m
+                .opt_strs("break")
+                .iter()
+                .filter_map(Self::parse_breakpoint)
+                .collect(),
             cli: m.opt_present("cli"),
             allow: m
                 .opt_strs("allow")
@@ -55,14 +93,39 @@ impl Args {
                 .collect(),
             version: m.opt_present("version"),
             lib: m.opt_strs("lib").iter().find_map(Self::parse_lib_path),
-            input: m.free,
+            output: m.opt_str("output").map(PathBuf::from),
+            optimize: Self::parse_optimize(m.opt_str("opt-level"), &m.opt_strs("pass")),
+            emit: m.opt_str("emit").map(|kind| {
+                kind.parse().unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    exit(2)
+                })
+            }),
+            message_format: m.opt_str("message-format").map_or_else(
+                MessageFormat::default,
+                |format| {
+                    format.parse().unwrap_or_else(|e| {
+                        eprintln!("error: {e}");
+                        exit(2)
+                    })
+                },
+            ),
+            input,
+            build,
+            run,
+            fmt,
+            check: m.opt_present("check"),
         };
         args.check();
         args
     }
 
     fn check(&self) {
-        if self.input.is_empty() && !self.cli && !self.version {
+        if self.input.is_empty()
+            && !self.run
+            && !self.fmt
+            && (self.build || (!self.cli && !self.version))
+        {
             eprintln!("error: required arguments were not provided: <INPUT>...");
             exit(2)
         }
@@ -75,6 +138,31 @@ impl Args {
             .ok()
     }
 
+    fn parse_breakpoint(breakpoint: impl AsRef<str>) -> Option<Breakpoint> {
+        breakpoint
+            .as_ref()
+            .parse()
+            .map_err(|e| eprintln!("warning: {e}"))
+            .ok()
+    }
+
+    fn parse_optimize(level: Option<String>, flags: &[String]) -> Optimizations {
+        let mut optimize = match level.map(|level| level.parse::<u8>()) {
+            None => Optimizations::default(),
+            Some(Ok(level)) if level <= MAX_OPT_LEVEL => Optimizations::with_level(level),
+            Some(_) => {
+                eprintln!("error: optimization level should be 0-{MAX_OPT_LEVEL}");
+                exit(2)
+            }
+        };
+        for flag in flags {
+            if let Err(e) = optimize.apply_flag(flag) {
+                eprintln!("warning: {e}");
+            }
+        }
+        optimize
+    }
+
     fn parse_allow(lint: impl AsRef<str>) -> Option<lints::Lint> {
         lint.as_ref()
             .parse()
@@ -84,9 +172,16 @@ impl Args {
 
     fn help(options: &getopts_macro::getopts::Options) {
         let brief = format!(
-            "Usage: {} [OPTIONS] [INPUT]...\n\n\
-            Arguments:\n  [INPUT]...  the filename of the file to compile",
+            "Usage: {0} [OPTIONS] [INPUT]...\n       \
+            {0} build [OPTIONS] [INPUT]...\n       \
+            {0} run [OPTIONS] [DIR]\n       \
+            {0} fmt [--check] [FILE]...\n\n\
+            Arguments:\n  [INPUT]...  the filename of the file to compile, \
+            or a single .{1} bytecode file to run\n  \
+            [DIR]       the project directory containing openex.toml (default: .)\n  \
+            [FILE]...   the source files to format in place (default: stdin to stdout)",
             Self::prog_name(),
+            BYTECODE_EXTENSION,
         );
         print!("{}", options.usage(&brief));
     }
@@ -105,6 +200,252 @@ impl Args {
     }
 }
 
+fn is_bytecode_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext == BYTECODE_EXTENSION)
+}
+
+/// 调试模式下为执行引擎挂载命令行调试器
+fn runtime_env(args: &Args, natives: SharedNatives) -> RuntimeEnv {
+    let env = RuntimeEnv::new(natives).with_optimizations(args.optimize);
+    if args.debug {
+        env.with_debugger(Debugger::new(args.breakpoints.clone(), ConsoleDebugger))
+    } else {
+        env
+    }
+}
+
+// 动态库函数只在执行前加载, 编译与 `--emit` 不会打开动态库
+fn executor_natives(natives: &SharedNatives, units: &[MetadataUnit]) -> SharedNatives {
+    bind_natives(natives, units).unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        exit(1)
+    })
+}
+
+// 字节码不经过编译, 直接使用标准本地库
+fn bytecode_natives(units: &[MetadataUnit]) -> SharedNatives {
+    executor_natives(&Arc::new(NativeRegistry::with_std()), units)
+}
+
+fn run_bytecode(path: &str, args: &Args) -> io::Result<()> {
+    let data = fs::read(path)?;
+    let units = decode_units(&data).unwrap_or_else(|e| {
+        eprintln!("error: {path}: {e}");
+        exit(1)
+    });
+    let env = runtime_env(args, bytecode_natives(&units));
+    execute_units(&units, &env);
+    Ok(())
+}
+
+fn build_bytecode(compiler: &mut Compiler, args: &Args) -> io::Result<()> {
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| Path::new(&args.input[0]).with_extension(BYTECODE_EXTENSION));
+    fs::write(output, encode_units(compiler.get_files()))
+}
+
+// 编译结束时输出的错误与警告数, 如 `2 errors, 1 warning`
+fn diagnostic_summary(compiler: &Compiler) -> String {
+    let count = |count: usize, noun: &str| match count {
+        1 => format!("1 {noun}"),
+        _ => format!("{count} {noun}s"),
+    };
+    format!(
+        "{}, {}",
+        count(compiler.error_count(), "error"),
+        count(compiler.warning_count(), "warning")
+    )
+}
+
+// 编译缓存只在编译器版本, 优化选项, 标准库目录与搜索路径都未改变时有效
+fn cache_key(args: &Args) -> String {
+    let std_lib = std_lib_path(args);
+    format!(
+        "{} {:?} {:?} {:?}",
+        Compiler::get_version(),
+        args.optimize,
+        fs::canonicalize(std_lib).unwrap_or_else(|_| std_lib.into()),
+        std::env::var_os("OPENEX_PATH")
+    )
+}
+
+fn load_project(args: &Args) -> Project {
+    let root = args.input.first().map_or(".", String::as_str);
+    Project::load(Path::new(root)).unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        exit(1)
+    })
+}
+
+/// 加载项目及其依赖声明的库目录, 与标准库目录相同的会被跳过
+fn load_project_libraries(
+    compiler: &mut Compiler,
+    project: &Project,
+    args: &Args,
+) -> io::Result<()> {
+    let std_lib = fs::canonicalize(std_lib_path(args)).ok();
+    for path in &project.lib_paths {
+        if fs::canonicalize(path).ok() == std_lib {
+            continue;
+        }
+        load_libraries(compiler, Some(path.to_string_lossy().into()), &args.allow)?;
+    }
+    project.configure(compiler);
+    Ok(())
+}
+
+fn std_lib_path(args: &Args) -> &str {
+    args.lib.as_deref().unwrap_or("./lib")
+}
+
+/// 检查输入是否还需要续行: 存在未闭合的括号、字符串或块注释
+fn is_incomplete(source: &str) -> bool {
+    let mut depth: isize = 0;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => loop {
+                match chars.next() {
+                    None => return true,
+                    Some('"') => break,
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some(_) => {}
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = '\0';
+                loop {
+                    match chars.next() {
+                        None => return true,
+                        Some('/') if last == '*' => break,
+                        Some(c) => last = c,
+                    }
+                }
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+fn report_diagnostics(args: &Args, source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        match args.message_format {
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json()),
+            _ => eprintln!("{}", diagnostic.render(source)),
+        }
+    }
+}
+
+/// 格式化输入的源文件, 未指定文件时格式化标准输入并写到标准输出
+/// `--check` 模式下不写入文件, 存在需要格式化的文件时以非零状态退出
+fn format_files(args: &Args) -> io::Result<()> {
+    let mut failed = false;
+    if args.input.is_empty() {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        match format_source("<stdin>", &source) {
+            Ok(formatted) if args.check => failed = formatted != source,
+            Ok(formatted) => print!("{formatted}"),
+            Err(diagnostics) => {
+                report_diagnostics(args, &source, &diagnostics);
+                failed = true;
+            }
+        }
+    }
+    for path in &args.input {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: cannot read file `{path}`: {e}");
+                failed = true;
+                continue;
+            }
+        };
+        match format_source(path, &source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if args.check => {
+                println!("would reformat {path}");
+                failed = true;
+            }
+            Ok(formatted) => fs::write(path, formatted)?,
+            Err(diagnostics) => {
+                report_diagnostics(args, &source, &diagnostics);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+    Ok(())
+}
+
+fn eval_console(compiler: &mut Compiler, globals: &SharedGlobals, source: String) {
+    // 按需导入的模块会追加在会话文件之后
+    let console = compiler
+        .get_files()
+        .iter()
+        .position(SourceFile::is_interactive)
+        .unwrap();
+    let snapshot = compiler.get_files()[console].clone();
+    compiler.get_files()[console].feed(source);
+    if compiler.compile().is_err() {
+        // 编译失败的输入不应影响已有的定义
+        compiler.get_files()[console] = snapshot;
+        return;
+    }
+    let natives = compiler.natives().clone();
+    let optimize = compiler.get_optimizations();
+    let metadata = build_metadata(compiler);
+    // 交互模式下加载失败只报告错误, 不结束会话
+    match bind_natives(&natives, &metadata) {
+        Ok(natives) => {
+            let env = RuntimeEnv::new(natives).with_optimizations(optimize);
+            execute_units_with(&metadata, globals, &env);
+        }
+        Err(e) => eprintln!("error: {e}"),
+    }
+}
+
+fn run_console(compiler: &mut Compiler, lints: HashSet<lints::Lint>) -> io::Result<()> {
+    compiler.add_file(SourceFile::interactive("<console>".to_string(), lints));
+    let globals = GlobalStore::shared_new(&[]);
+    let stdin = io::stdin();
+    let prompt = stdin.is_terminal();
+    let mut source = String::new();
+
+    loop {
+        if prompt {
+            print!("{}", if source.is_empty() { "> " } else { "... " });
+            io::stdout().flush()?;
+        }
+        let eof = stdin.read_line(&mut source)? == 0;
+        if !eof && is_incomplete(&source) {
+            continue;
+        }
+        if !source.trim().is_empty() {
+            eval_console(compiler, &globals, std::mem::take(&mut source));
+        }
+        source.clear();
+        if eof {
+            return Ok(());
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
     let args = Args::parse();
     let mut compiler = Compiler::new();
@@ -128,38 +469,75 @@ fn main() -> io::Result<()> {
         return Ok(());
     }
 
-    load_libraries(&mut compiler, args.lib, &args.allow)?;
+    if args.fmt {
+        return format_files(&args);
+    }
+
+    if !args.build && !args.run && args.input.len() == 1 && is_bytecode_file(&args.input[0]) {
+        return run_bytecode(&args.input[0], &args);
+    }
+
+    let project = args.run.then(|| load_project(&args));
+    if let Some(project) = &project
+        && args.emit.is_none()
+        && let Some(units) = project
+            .read_cache(&cache_key(&args))
+            .and_then(|data| decode_units(&data).ok())
+    {
+        let env = runtime_env(&args, bytecode_natives(&units));
+        execute_units(&units, &env);
+        return Ok(());
+    }
+
+    compiler.set_optimizations(args.optimize);
+    compiler.set_message_format(args.message_format);
+    load_libraries(&mut compiler, args.lib.clone(), &args.allow)?;
+    if let Some(project) = &project {
+        load_project_libraries(&mut compiler, project, &args)?;
+    }
 
     if args.cli {
-        print!("> ");
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                compiler.add_file(SourceFile::new(
-                    "<console>".to_string(),
-                    input,
-                    args.allow,
-                    false,
-                ));
-            }
-            Err(_) => {
-                eprintln!("error: cannot read from stdin.");
-            }
-        }
-    } else {
-        for file in args.input {
-            let file_name = file.clone();
-            let data =
-                fs::read_to_string(file).unwrap_or_else(|e| panic!("error: cannot read file{e}"));
-            compiler.add_file(SourceFile::new(file_name, data, args.allow.clone(), false));
-        }
+        return run_console(&mut compiler, args.allow);
     }
 
-    if compiler.compile().is_err() {
+    compiler.set_emit(args.emit);
+    let input = project.as_ref().map_or_else(
+        || args.input.clone(),
+        |project| vec![project.entry.to_string_lossy().into_owned()],
+    );
+    for file in input {
+        let file_name = file.clone();
+        let data =
+            fs::read_to_string(file).unwrap_or_else(|e| panic!("error: cannot read file{e}"));
+        compiler.add_file(SourceFile::new(file_name, data, args.allow.clone(), false));
+    }
+
+    let failed = compiler.compile().is_err();
+    if args.message_format == MessageFormat::Human
+        && compiler.error_count() + compiler.warning_count() > 0
+    {
+        eprintln!("{}", diagnostic_summary(&compiler));
+    }
+    if failed {
         exit(1);
     }
-    initialize_executor(&mut compiler);
+    if args.emit.is_some() {
+        print!("{}", compiler.emitted());
+        return Ok(());
+    }
+    if args.build {
+        return build_bytecode(&mut compiler, &args);
+    }
+    if let Some(project) = &project {
+        let inputs = project.cache_inputs(&mut compiler, Path::new(std_lib_path(&args)));
+        let data = encode_units(compiler.get_files());
+        if let Err(e) = project.write_cache(&cache_key(&args), &inputs, &data) {
+            eprintln!("warning: cannot write compile cache: {e}");
+        }
+    }
+    let natives = compiler.natives().clone();
+    let metadata = build_metadata(&mut compiler);
+    let env = runtime_env(&args, executor_natives(&natives, &metadata));
+    execute_units(&metadata, &env);
     Ok(())
 }
@@ -0,0 +1,263 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::compiler::Compiler;
+use crate::compiler::bytecode::BYTECODE_EXTENSION;
+use crate::compiler::file::normalize_path;
+
+/// 项目清单的文件名, 位于项目根目录
+pub const MANIFEST_NAME: &str = "openex.toml";
+
+// 编译缓存所在的目录 (相对项目根目录)
+const CACHE_DIR: &str = ".openex";
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(PathBuf, io::Error),          // 无法读取清单文件
+    Parse(PathBuf, toml::de::Error), // 清单格式错误
+    MissingEntry(PathBuf),           // 根项目未声明入口文件
+    DependencyCycle(String),         // 依赖包之间循环依赖 (包名)
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "cannot read {}: {error}", path.display()),
+            Self::Parse(path, error) => write!(f, "invalid manifest {}: {error}", path.display()),
+            Self::MissingEntry(path) => {
+                write!(f, "manifest {} has no entry file", path.display())
+            }
+            Self::DependencyCycle(name) => write!(f, "cyclic dependency on package `{name}`"),
+        }
+    }
+}
+
+/// `openex.toml` 的内容, 路径均相对于清单所在目录
+/// ```toml
+/// [package]
+/// name = "demo"
+/// entry = "src/main.exf"
+/// src = ["src"]
+/// lib = ["lib"]
+///
+/// [dependencies]
+/// util = { path = "../util" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    pub entry: Option<PathBuf>, // 入口文件, 作为依赖包时可以省略
+    #[serde(default = "default_src")]
+    pub src: Vec<PathBuf>, // 源码目录, 作为导入的搜索路径
+    #[serde(default)]
+    pub lib: Vec<PathBuf>, // 库目录, 其中的文件与标准库一样预先加载
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    pub path: PathBuf, // 本地依赖包的根目录
+}
+
+fn default_src() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+impl Manifest {
+    /// 读取指定目录下的 `openex.toml`
+    /// # Errors
+    pub fn load(dir: &Path) -> Result<Self, ManifestError> {
+        let path = dir.join(MANIFEST_NAME);
+        let data = fs::read_to_string(&path).map_err(|e| ManifestError::Io(path.clone(), e))?;
+        toml::from_str(&data).map_err(|e| ManifestError::Parse(path, e))
+    }
+}
+
+/// 解析清单及其全部本地依赖后得到的项目, 路径均已与项目根目录拼接
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+    pub entry: PathBuf,
+    pub search_paths: Vec<PathBuf>,       // 项目与依赖包的源码目录
+    pub lib_paths: Vec<PathBuf>,          // 项目与依赖包的库目录
+    pub packages: Vec<(String, PathBuf)>, // 依赖包名及其入口文件
+}
+
+impl Project {
+    /// 读取项目清单并递归解析本地依赖
+    /// # Errors
+    pub fn load(root: &Path) -> Result<Self, ManifestError> {
+        let root = normalize_path(root);
+        let manifest = Manifest::load(&root)?;
+        let Some(entry) = &manifest.package.entry else {
+            return Err(ManifestError::MissingEntry(root.join(MANIFEST_NAME)));
+        };
+        let mut project = Self {
+            entry: normalize_path(&root.join(entry)),
+            root: root.clone(),
+            manifest: manifest.clone(),
+            search_paths: vec![],
+            lib_paths: vec![],
+            packages: vec![],
+        };
+        project.add_package(&root, &manifest, &mut vec![])?;
+        Ok(project)
+    }
+
+    // 依赖包的源码与库目录追加在依赖者之后, 同一个包只解析一次
+    fn add_package(
+        &mut self,
+        dir: &Path,
+        manifest: &Manifest,
+        parents: &mut Vec<String>,
+    ) -> Result<(), ManifestError> {
+        let package = &manifest.package;
+        for src in &package.src {
+            self.search_paths.push(normalize_path(&dir.join(src)));
+        }
+        for lib in &package.lib {
+            self.lib_paths.push(normalize_path(&dir.join(lib)));
+        }
+        parents.push(package.name.clone());
+        for (name, dependency) in &manifest.dependencies {
+            if parents.contains(name) {
+                return Err(ManifestError::DependencyCycle(name.clone()));
+            }
+            if self.packages.iter().any(|(package, _)| package == name) {
+                continue;
+            }
+            let dep_dir = normalize_path(&dir.join(&dependency.path));
+            let dep_manifest = Manifest::load(&dep_dir)?;
+            if let Some(entry) = &dep_manifest.package.entry {
+                self.packages
+                    .push((name.clone(), normalize_path(&dep_dir.join(entry))));
+            }
+            self.add_package(&dep_dir, &dep_manifest, parents)?;
+        }
+        parents.pop();
+        Ok(())
+    }
+
+    /// 向编译器登记源码目录与依赖包, 库目录需要通过 `load_libraries` 单独加载
+    pub fn configure(&self, compiler: &mut Compiler) {
+        for path in &self.search_paths {
+            compiler.add_search_path(path);
+        }
+        for (name, entry) in &self.packages {
+            compiler.add_package(name, entry);
+        }
+    }
+
+    /// 编译缓存的字节码文件路径
+    #[must_use]
+    pub fn cache_path(&self) -> PathBuf {
+        self.root.join(CACHE_DIR).join(format!(
+            "{}.{BYTECODE_EXTENSION}",
+            self.manifest.package.name
+        ))
+    }
+
+    fn stamp_path(&self) -> PathBuf {
+        self.cache_path().with_extension("stamp")
+    }
+
+    /// 读取仍然有效的编译缓存: 编译选项 `key` 未变且所有输入文件都不晚于缓存
+    #[must_use]
+    pub fn read_cache(&self, key: &str) -> Option<Vec<u8>> {
+        let stamp = fs::read_to_string(self.stamp_path()).ok()?;
+        let mut lines = stamp.lines();
+        if lines.next() != Some(key) {
+            return None;
+        }
+        let cache = self.cache_path();
+        let built = modified(&cache)?;
+        if modified(&self.root.join(MANIFEST_NAME))? > built {
+            return None;
+        }
+        for input in lines {
+            if modified(Path::new(input))? > built {
+                return None;
+            }
+        }
+        fs::read(cache).ok()
+    }
+
+    /// 收集编译读取过的输入: 各库目录及其中的文件, 入口与按需导入的源文件,
+    /// 以及导入时查找过的目录, 目录中新增的文件可能遮蔽已解析的导入
+    pub fn cache_inputs(&self, compiler: &mut Compiler, std_lib: &Path) -> Vec<PathBuf> {
+        let mut inputs = vec![];
+        for dir in std::iter::once(std_lib).chain(self.lib_paths.iter().map(PathBuf::as_path)) {
+            inputs.push(dir.to_path_buf());
+            if let Ok(entries) = fs::read_dir(dir) {
+                inputs.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        }
+        // 项目的源码目录连同子目录一起记录, 其余搜索路径只记录目录本身
+        for dir in &self.search_paths {
+            source_dirs(dir, &mut inputs);
+        }
+        inputs.extend(compiler.search_paths().iter().cloned());
+        // 库目录中的文件只以文件名命名, 其余文件名即为读取时的路径
+        for file in compiler.get_files() {
+            let path = PathBuf::from(&file.name);
+            if path.is_file() {
+                if let Some(dir) = path.parent() {
+                    inputs.push(dir.to_path_buf());
+                }
+                inputs.push(path);
+            }
+        }
+        // 缓存可能在其他工作目录下读取, 统一记录规范化的绝对路径
+        let mut canonical: Vec<PathBuf> = vec![];
+        for input in inputs {
+            let input = fs::canonicalize(&input).unwrap_or(input);
+            if !canonical.contains(&input) {
+                canonical.push(input);
+            }
+        }
+        canonical
+    }
+
+    /// 写入编译缓存, `inputs` 为编译所读取的全部文件与库目录
+    /// # Errors
+    pub fn write_cache(&self, key: &str, inputs: &[PathBuf], data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(self.root.join(CACHE_DIR))?;
+        let mut stamp = format!("{key}\n");
+        for input in inputs {
+            stamp.push_str(&format!("{}\n", input.display()));
+        }
+        fs::write(self.cache_path(), data)?;
+        fs::write(self.stamp_path(), stamp)
+    }
+}
+
+// 递归记录目录及其全部子目录, 跳过编译缓存所在的目录
+fn source_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) {
+    dirs.push(dir.to_path_buf());
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) && entry.file_name() != CACHE_DIR {
+                source_dirs(&entry.path(), dirs);
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
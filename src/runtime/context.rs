@@ -2,9 +2,11 @@ use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::Duration;
 
+use crate::runtime::limits::ExecutionBudget;
 use crate::runtime::thread::ThreadManager;
-use crate::runtime::{MetadataUnit, SharedGlobals};
+use crate::runtime::{MetadataUnit, RuntimeError, SharedGlobals};
 
 struct LockState {
     owner: Option<thread::ThreadId>,
@@ -106,6 +108,7 @@ pub struct RuntimeContext {
     globals: SharedGlobals,
     sync_table: SharedSync,
     thread_manager: Option<usize>,
+    budget: Option<Arc<ExecutionBudget>>,
 }
 
 thread_local! {
@@ -118,6 +121,7 @@ pub fn set_context(
     globals: SharedGlobals,
     sync_table: SharedSync,
     thread_manager: Option<usize>,
+    budget: Option<Arc<ExecutionBudget>>,
 ) {
     let units_ptr: *const MetadataUnit<'static> = units.as_ptr() as *const MetadataUnit<'static>;
     let units_len = units.len();
@@ -128,6 +132,7 @@ pub fn set_context(
             globals,
             sync_table,
             thread_manager,
+            budget,
         });
     });
 }
@@ -159,6 +164,43 @@ pub fn get_thread_manager(ctx: &RuntimeContext) -> Option<&ThreadManager<'_, '_>
         .map(|ptr| unsafe { &*(ptr as *const ThreadManager<'_, '_>) })
 }
 
+fn with_budget(
+    f: impl FnOnce(&ExecutionBudget) -> Result<(), RuntimeError>,
+) -> Result<(), RuntimeError> {
+    with_context(|ctx| ctx.budget.as_deref().map_or(Ok(()), f)).unwrap_or(Ok(()))
+}
+
+/// 在新建数组、映射或字符串之前检查剩余的长度额度, 不计入用量
+/// # Errors
+/// 超出 `max_length` 时返回 `LimitExceeded`, 不在执行器中调用时不做检查
+pub fn check_length(len: usize) -> Result<(), RuntimeError> {
+    with_budget(|budget| budget.check_length(len))
+}
+
+/// 将新建的数组、映射元素或字符串字节计入当前执行的用量
+/// # Errors
+/// 累计用量超出 `max_length` 时返回 `LimitExceeded`, 不在执行器中调用时不做检查
+pub fn charge_length(len: usize) -> Result<(), RuntimeError> {
+    with_budget(|budget| budget.charge_length(len))
+}
+
+/// 检查当前执行的运行时间, 用于不经过指令计数的耗时操作之后
+/// # Errors
+/// 运行时间超时时返回 `LimitExceeded`, 不在执行器中调用时不做检查
+pub fn check_time() -> Result<(), RuntimeError> {
+    with_budget(ExecutionBudget::check_time)
+}
+
+/// 当前执行距离超时剩余的运行时间, 没有设置超时时返回 `None`
+pub fn remaining_time() -> Option<Duration> {
+    with_context(|ctx| {
+        ctx.budget
+            .as_deref()
+            .and_then(ExecutionBudget::remaining_time)
+    })
+    .flatten()
+}
+
 pub fn request_thread_exit() {
     THREAD_EXIT.set(true);
 }
@@ -0,0 +1,261 @@
+use smol_str::SmolStr;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::compiler::Compiler;
+use crate::runtime::MetadataUnit;
+use crate::runtime::executor::StackFrame;
+
+/// 源码断点, 行号从 1 开始
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Breakpoint {
+    pub file: SmolStr,
+    pub line: usize,
+}
+
+impl Breakpoint {
+    // 断点文件可以写作完整路径、文件名或不带后缀的导入名
+    fn matches(&self, unit: &MetadataUnit) -> bool {
+        self.file == unit.file
+            || self.file == unit.names
+            || Path::new(unit.file.as_str())
+                .file_name()
+                .is_some_and(|name| name == self.file.as_str())
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((file, line)) = s.rsplit_once(':') else {
+            return Err(format!("breakpoint `{s}` should be written as file:line"));
+        };
+        match line.parse::<usize>() {
+            Ok(line) if line > 0 && !file.is_empty() => Ok(Self {
+                file: SmolStr::new(file),
+                line,
+            }),
+            _ => Err(format!("invalid breakpoint `{s}`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Entry,      // 未设置断点时在第一行暂停
+    Breakpoint, // 命中断点
+    Step,       // 单步执行完成
+}
+
+/// 暂停后由前端决定如何继续执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    Continue, // 继续执行到下一个断点
+    StepInto, // 执行到下一行, 遇到函数调用时进入函数
+    StepOver, // 执行到当前函数或调用者的下一行
+    StepOut,  // 执行到当前函数返回调用者
+}
+
+/// 暂停时的执行现场, 仅在 `on_pause` 调用期间有效
+pub struct PauseEvent<'a> {
+    pub reason: PauseReason,
+    pub file: &'a str,
+    pub source: Option<&'a str>,
+    pub line: usize, // 从 1 开始
+    pub column: usize,
+    pub depth: usize, // 调用栈深度, 入口栈帧为 1
+    pub frame: &'a StackFrame<'a>,
+}
+
+/// 调试前端接口, 命令行调试器与 DAP 等外部适配器都通过该接口控制执行
+/// 脚本创建的线程会共享同一个前端, 因此需要满足 `Send + Sync`
+pub trait DebugController: Send + Sync {
+    fn on_pause(&self, event: &PauseEvent) -> DebugCommand;
+}
+
+pub struct Debugger {
+    breakpoints: HashSet<Breakpoint>,
+    controller: Box<dyn DebugController>,
+}
+
+impl Debugger {
+    pub fn new(
+        breakpoints: impl IntoIterator<Item = Breakpoint>,
+        controller: impl DebugController + 'static,
+    ) -> Self {
+        Self {
+            breakpoints: breakpoints.into_iter().collect(),
+            controller: Box::new(controller),
+        }
+    }
+
+    fn is_breakpoint(&self, unit: &MetadataUnit, line: usize) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.line == line && breakpoint.matches(unit))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StepMode {
+    Entry,
+    Run,
+    Into,
+    Over(usize), // 发起单步时的调用栈深度
+    Out(usize),
+}
+
+/// 单个执行线程的调试状态
+pub struct DebugSession<'d> {
+    debugger: &'d Debugger,
+    mode: StepMode,
+    depth: usize,
+    lines: Vec<(usize, usize)>, // 每层栈帧最后执行到的 (单元索引, 行号)
+}
+
+impl<'d> DebugSession<'d> {
+    #[must_use]
+    pub fn new(debugger: &'d Debugger) -> Self {
+        let mode = if debugger.breakpoints.is_empty() {
+            StepMode::Entry
+        } else {
+            StepMode::Run
+        };
+        Self {
+            debugger,
+            mode,
+            depth: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    pub const fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    /// 在执行栈帧当前指令前调用, 仅在进入新的源码行时检查断点与单步状态
+    pub fn check(&mut self, units: &[MetadataUnit], frame: &StackFrame) {
+        let Some(pos) = frame.current_pos(true) else {
+            return;
+        };
+        if self.depth == 0 {
+            return;
+        }
+        let here = (frame.get_unit_index(), pos.line);
+        self.lines.truncate(self.depth);
+        let new_line = self.lines.len() < self.depth || self.lines.last() != Some(&here);
+        self.lines.resize(self.depth, (usize::MAX, usize::MAX));
+        self.lines[self.depth - 1] = here;
+
+        // 跳出函数时返回调用者即暂停, 即使调用者仍停留在发起调用的那一行
+        let unit = &units[frame.get_unit_index()];
+        let reason = match self.mode {
+            StepMode::Out(depth) if self.depth < depth => PauseReason::Step,
+            _ if !new_line => return,
+            _ if self.debugger.is_breakpoint(unit, pos.line + 1) => PauseReason::Breakpoint,
+            StepMode::Entry => PauseReason::Entry,
+            StepMode::Into => PauseReason::Step,
+            StepMode::Over(depth) if self.depth <= depth => PauseReason::Step,
+            _ => return,
+        };
+
+        let event = PauseEvent {
+            reason,
+            file: unit.file.as_str(),
+            source: unit.source,
+            line: pos.line + 1,
+            column: pos.column,
+            depth: self.depth,
+            frame,
+        };
+        self.mode = match self.debugger.controller.on_pause(&event) {
+            DebugCommand::Continue => StepMode::Run,
+            DebugCommand::StepInto => StepMode::Into,
+            DebugCommand::StepOver => StepMode::Over(self.depth),
+            DebugCommand::StepOut => StepMode::Out(self.depth),
+        };
+    }
+}
+
+/// 基于标准输入输出的命令行调试前端, 调试信息输出到标准错误以免与脚本输出混在一起
+pub struct ConsoleDebugger;
+
+impl ConsoleDebugger {
+    fn help() {
+        eprintln!("  c, continue  continue to the next breakpoint");
+        eprintln!("  s, step      step into the next line");
+        eprintln!("  n, next      step over to the next line");
+        eprintln!("  o, out       step out of the current function");
+        eprintln!("  l, locals    print local variables");
+        eprintln!("  t, stack     print the operand stack");
+        eprintln!("  p, code      print the current bytecode");
+        eprintln!("  w, where     print the current location");
+    }
+
+    fn print_location(event: &PauseEvent) {
+        eprintln!(
+            "at <{}> ({}:{}:{}) depth {}",
+            event.frame.get_frame_name(),
+            event.file,
+            event.line,
+            event.column,
+            event.depth
+        );
+        if let Some(source) = event.source {
+            eprintln!(
+                "{}",
+                Compiler::highlight_line_and_column(source, event.line - 1, event.column)
+            );
+        }
+    }
+}
+
+impl DebugController for ConsoleDebugger {
+    fn on_pause(&self, event: &PauseEvent) -> DebugCommand {
+        let reason = match event.reason {
+            PauseReason::Entry => "entry",
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+        };
+        eprintln!("Paused ({reason})");
+        Self::print_location(event);
+
+        let stdin = io::stdin();
+        let mut input = String::new();
+        loop {
+            eprint!("(debug) ");
+            let _ = io::stderr().flush();
+            input.clear();
+            // 输入结束时不再暂停等待命令
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 {
+                return DebugCommand::Continue;
+            }
+            match input.trim() {
+                "c" | "continue" => return DebugCommand::Continue,
+                "s" | "step" => return DebugCommand::StepInto,
+                "n" | "next" => return DebugCommand::StepOver,
+                "o" | "out" => return DebugCommand::StepOut,
+                "l" | "locals" => {
+                    for (index, value) in event.frame.get_locals().iter().enumerate() {
+                        eprintln!("  [{index}] {value}");
+                    }
+                }
+                "t" | "stack" => {
+                    for value in event.frame.get_op_stack().iter().rev() {
+                        eprintln!("  {value}");
+                    }
+                }
+                "p" | "code" => match event.frame.current_code() {
+                    Some(code) => eprintln!("  {code:?}"),
+                    None => eprintln!("  <end>"),
+                },
+                "w" | "where" => Self::print_location(event),
+                "" => {}
+                _ => Self::help(),
+            }
+        }
+    }
+}
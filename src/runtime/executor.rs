@@ -1,14 +1,21 @@
 use dashu::float::DBig;
-use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+use smol_str::{SmolStr, format_smolstr};
 
+use crate::compiler::Compiler;
 use crate::compiler::ast::vm_ir::{ByteCode, Value};
-use crate::compiler::parser::ParserError;
-use crate::library::find_library;
+use crate::compiler::lexer::SourcePos;
+use crate::compiler::optimize::Pass;
+use crate::library::output_capture::print;
+use crate::library::type_lib::call_array_func;
 use crate::runtime::context;
 use crate::runtime::context::SyncTable;
+use crate::runtime::debugger::DebugSession;
+use crate::runtime::limits::{LimitMeter, allocation_size};
 use crate::runtime::vm_operation::*;
 use crate::runtime::vm_table_opt::*;
-use crate::runtime::{MetadataUnit, RuntimeError, SharedGlobals, SharedSync};
+use crate::runtime::{
+    MetadataUnit, MethodInfo, RuntimeEnv, RuntimeError, SharedGlobals, SharedSync,
+};
 use crate::runtime::RuntimeError::NoSuchFunctionException;
 
 pub struct StackFrame<'a> {
@@ -16,6 +23,7 @@ pub struct StackFrame<'a> {
     local: Vec<Value>,
     op_stack: Vec<Value>,
     codes: &'a [ByteCode],
+    lines: &'a [SourcePos],
     const_table: &'a [Value],
     is_native: Option<SmolStr>,
     pub name: &'a str,
@@ -25,6 +33,8 @@ pub struct StackFrame<'a> {
     memo_target: Option<(usize, usize)>,
     memo_key: Option<Vec<MemoKey>>,
     sync_lock: Option<(usize, usize)>,
+    handlers: Vec<(usize, usize)>, // 异常处理器 (处理器 pc, 注册时的操作栈深度)
+    caught: Option<Value>,         // 已捕获待 Catch 取出的异常
 }
 
 pub struct Executor<'a> {
@@ -67,28 +77,27 @@ impl<'a> Executor<'a> {
 impl<'a> StackFrame<'a> {
     pub fn new(
         unit_index: usize,
-        local_size: usize,
-        codes: &'a [ByteCode],
+        method: &'a MethodInfo,
         const_table: &'a [Value],
-        name: &'a str,
-        r_name: &'a str,
         is_native: Option<SmolStr>,
-        args: usize,
     ) -> Self {
         Self {
             pc: 0,
-            local: vec![Value::Null; local_size],
+            local: vec![Value::Null; method.locals],
             op_stack: Vec::new(),
-            r_name,
-            codes,
+            r_name: method.r_name.as_str(),
+            codes: method.get_codes(),
+            lines: method.lines.as_slice(),
             const_table,
-            name,
+            name: method.name.as_str(),
             is_native,
             unit_index,
-            args,
+            args: method.args,
             memo_target: None,
             memo_key: None,
             sync_lock: None,
+            handlers: Vec::new(),
+            caught: None,
         }
     }
 
@@ -122,6 +131,16 @@ impl<'a> StackFrame<'a> {
         &mut self.local[index]
     }
 
+    #[must_use]
+    pub fn get_locals(&self) -> &[Value] {
+        &self.local
+    }
+
+    #[must_use]
+    pub fn get_op_stack(&self) -> &[Value] {
+        &self.op_stack
+    }
+
     pub fn push_op_stack(&mut self, value: Value) {
         self.op_stack.push(value);
     }
@@ -148,6 +167,17 @@ impl<'a> StackFrame<'a> {
         self.pc = pc;
     }
 
+    // 栈帧当前所在的源码位置, 调用者栈帧的 pc 已越过调用指令需要回退一位
+    #[must_use]
+    pub fn current_pos(&self, top: bool) -> Option<SourcePos> {
+        let pc = if top {
+            self.pc
+        } else {
+            self.pc.checked_sub(1)?
+        };
+        self.lines.get(pc).or_else(|| self.lines.last()).copied()
+    }
+
     pub fn current_code(&self) -> Option<&ByteCode> {
         if self.pc >= self.codes.len() {
             None
@@ -187,6 +217,33 @@ impl<'a> StackFrame<'a> {
     pub fn take_sync_lock(&mut self) -> Option<(usize, usize)> {
         self.sync_lock.take()
     }
+
+    pub fn push_handler(&mut self, pc: usize) {
+        self.handlers.push((pc, self.op_stack.len()));
+    }
+
+    pub fn pop_handler(&mut self) {
+        self.handlers.pop();
+    }
+
+    pub const fn has_handler(&self) -> bool {
+        !self.handlers.is_empty()
+    }
+
+    // 由最近注册的异常处理器接管异常, 恢复操作栈并跳转到处理器入口
+    pub fn catch_error(&mut self, error: RuntimeError) -> Result<(), RuntimeError> {
+        let Some((pc, depth)) = self.handlers.pop() else {
+            return Err(error);
+        };
+        self.op_stack.truncate(depth);
+        self.caught = Some(error.into_value());
+        self.pc = pc;
+        Ok(())
+    }
+
+    pub fn take_caught(&mut self) -> Value {
+        self.caught.take().unwrap_or(Value::Null)
+    }
 }
 
 pub enum RunState<'a> {
@@ -212,13 +269,21 @@ fn run_code<'a>(
     globals: &SharedGlobals,
     call_cache: &CallCache,
     sync_table: &SyncTable,
+    mut debug: Option<&mut DebugSession>,
+    mut meter: Option<&mut LimitMeter>,
 ) -> Result<RunState<'a>, RuntimeError> {
     while let Some(code) = stack_frame.current_code() {
         if context::take_thread_exit() {
             return Ok(RunState::ThreadExit);
         }
+        if let Some(meter) = meter.as_deref_mut() {
+            meter.tick()?;
+        }
+        if let Some(session) = debug.as_deref_mut() {
+            session.check(units, stack_frame);
+        }
         match code {
-            ByteCode::Push(const_index) => push_stack(stack_frame, *const_index),
+            ByteCode::Push(const_index) => push_stack(stack_frame, *const_index)?,
             ByteCode::Pop(len) => {
                 for _ in 0..*len {
                     let _ = stack_frame.pop_op_stack();
@@ -230,8 +295,11 @@ fn run_code<'a>(
                 let imm = *imm;
                 let value = stack_frame.get_local_mut(index);
                 match value {
-                    Value::Int(i) => {
-                        *i += imm;
+                    Value::Int(i) if let Some(sum) = i.checked_add(imm) => {
+                        *i = sum;
+                    }
+                    Value::Int(_) | Value::BigInt(_) => {
+                        *value = add_value(value.clone(), Value::Int(imm))?;
                     }
                     Value::Float(f) => {
                         *f += DBig::from(imm);
@@ -253,8 +321,11 @@ fn run_code<'a>(
                     return Err(RuntimeError::VMError);
                 };
                 match value {
-                    Value::Int(i) => {
-                        *i += imm;
+                    Value::Int(i) if let Some(sum) = i.checked_add(imm) => {
+                        *i = sum;
+                    }
+                    Value::Int(_) | Value::BigInt(_) => {
+                        *value = add_value(value.clone(), Value::Int(imm))?;
                     }
                     Value::Float(f) => {
                         *f += DBig::from(imm);
@@ -275,19 +346,61 @@ fn run_code<'a>(
             ByteCode::Mul => do_bin_op!(stack_frame, mul_value),
             ByteCode::Div => do_bin_op!(stack_frame, div_value),
             ByteCode::Rmd => do_bin_op!(stack_frame, rmd_value),
-            ByteCode::Call => match call_func(stack_frame, units, call_cache, sync_table) {
-                Ok(RunState::Continue) => continue,
-                Ok(state) => return Ok(state),
-                Err(err) => return Err(err),
-            },
-            ByteCode::CallConst(const_index) => {
-                match call_const(stack_frame, units, call_cache, sync_table, *const_index) {
+            ByteCode::Call(argc) => {
+                match call_func(stack_frame, units, call_cache, sync_table, *argc) {
+                    Ok(RunState::Continue) => continue,
+                    Ok(state) => return Ok(state),
+                    Err(err) => return Err(err),
+                }
+            }
+            ByteCode::CallConst(const_index, argc) => {
+                match call_const(
+                    stack_frame,
+                    units,
+                    call_cache,
+                    sync_table,
+                    *const_index,
+                    *argc,
+                ) {
                     Ok(RunState::Continue) => continue,
                     Ok(state) => return Ok(state),
                     Err(err) => return Err(err),
                 }
             }
+            ByteCode::MakeClosure(const_index, len) => {
+                make_closure(stack_frame, *const_index, *len)?
+            }
+            ByteCode::NewObject(const_index, len) => new_object(stack_frame, *const_index, *len)?,
+            ByteCode::NewArray(len) => new_array(stack_frame, *len)?,
+            ByteCode::NewMap(len) => new_map(stack_frame, *len)?,
+            ByteCode::GetField(const_index) => get_field(stack_frame, *const_index)?,
+            ByteCode::SetField(const_index) => set_field(stack_frame, *const_index)?,
+            ByteCode::GetMethod(const_index) => get_method(stack_frame, *const_index)?,
             ByteCode::Return => return Ok(RunState::Return),
+            ByteCode::Echo => {
+                let value = stack_frame.pop_op_stack();
+                if value != Value::Null {
+                    print(format_args!("{value}\n"));
+                }
+                stack_frame.next_pc();
+            }
+            ByteCode::Try(pc) => {
+                stack_frame.push_handler(*pc);
+                stack_frame.next_pc();
+            }
+            ByteCode::EndTry => {
+                stack_frame.pop_handler();
+                stack_frame.next_pc();
+            }
+            ByteCode::Catch => {
+                let caught = stack_frame.take_caught();
+                stack_frame.push_op_stack(caught);
+                stack_frame.next_pc();
+            }
+            ByteCode::Throw => {
+                let value = stack_frame.pop_op_stack();
+                return Err(RuntimeError::from_value(value));
+            }
             ByteCode::Jump(pc) => jump(stack_frame, *pc),
             ByteCode::JumpTrue(pc) => jump_true(stack_frame, *pc),
             ByteCode::JumpFalse(pc) => jump_false(stack_frame, *pc),
@@ -337,8 +450,9 @@ fn run_code<'a>(
                 }
 
                 let reversed_values: Vec<Value> = elements.into_iter().rev().collect();
+                context::charge_length(reversed_values.len())?;
 
-                let result = Value::Array(len_s, reversed_values);
+                let result = Value::Array(reversed_values);
                 let unit_index = stack_frame.get_unit_index();
                 let mut globals = globals.lock().unwrap();
                 let Some(slot) = globals.get_mut(unit_index, index) else {
@@ -357,27 +471,56 @@ fn run_code<'a>(
                 let Some(result) = globals.get_mut(unit_index, index) else {
                     return Err(RuntimeError::VMError);
                 };
-                if let Value::Array(len, elements) = result
-                    && let Value::Int(a_index) = arr_index
-                {
-                    let usize_index = usize::try_from(a_index).unwrap();
-                    if usize_index >= *len {
-                        return Err(RuntimeError::IndexOutOfBounds(
-                            format_args!("Index {a_index} out of bounds for length {len}")
-                                .to_smolstr(),
-                        ));
-                    }
-                    elements[usize_index] = value;
-                    stack_frame.next_pc();
-                } else {
-                    return Err(RuntimeError::TypeException(
-                        "cannot set unknown type for array.".to_smolstr(),
-                    ));
-                }
+                set_index_value(result, arr_index, value)?;
+                stack_frame.next_pc();
+            }
+            ByteCode::ArrayCall(var_index, func) => {
+                array_call_local(stack_frame, *var_index, *func)?
             }
-            ByteCode::LoadArray(var_index, len) => load_array_local(stack_frame, *len, *var_index),
+            ByteCode::ArrayCallGlobal(var_index, func) => {
+                let (index, func) = (*var_index, *func);
+                let args = array_call_args(stack_frame, func);
+                let unit_index = stack_frame.get_unit_index();
+                let result = {
+                    let mut globals = globals.lock().unwrap();
+                    let Some(slot) = globals.get_mut(unit_index, index) else {
+                        return Err(RuntimeError::VMError);
+                    };
+                    call_array_func(func, slot, &args)?
+                };
+                stack_frame.push_op_stack(result);
+                stack_frame.next_pc();
+            }
+            ByteCode::LoadMapGlobal(var_index, len) => {
+                let index = *var_index;
+                let result = load_map(stack_frame, *len)?;
+                let unit_index = stack_frame.get_unit_index();
+                let mut globals = globals.lock().unwrap();
+                let Some(slot) = globals.get_mut(unit_index, index) else {
+                    return Err(RuntimeError::VMError);
+                };
+                *slot = result;
+                stack_frame.next_pc();
+            }
+            ByteCode::LoadMap(var_index, len) => load_map_local(stack_frame, *len, *var_index)?,
+            ByteCode::LoadArray(var_index, len) => load_array_local(stack_frame, *len, *var_index)?,
             ByteCode::GetIndex => get_index_array(stack_frame)?,
             ByteCode::GetIndexLocal(var_index) => get_index_local(stack_frame, *var_index)?,
+            ByteCode::GetIndexGlobal(var_index) => {
+                let index = *var_index;
+                let unit_index = stack_frame.get_unit_index();
+                let array = {
+                    let globals = globals.lock().unwrap();
+                    let Some(array) = globals.get(unit_index, index) else {
+                        return Err(RuntimeError::VMError);
+                    };
+                    array.clone()
+                };
+                let arr_index = stack_frame.pop_op_stack();
+                stack_frame.push_op_stack(array);
+                stack_frame.push_op_stack(arr_index);
+                get_index_array(stack_frame)?;
+            }
             ByteCode::Nol | ByteCode::Pos => stack_frame.next_pc(),
             _ => todo!(),
         }
@@ -385,54 +528,125 @@ fn run_code<'a>(
     Ok(RunState::None)
 }
 
-fn print_and_return(executor: &Executor, failed_status: Option<RuntimeError>) -> Value {
-    if let Some(error) = failed_status {
-        eprintln!("RuntimeError: {error:?}");
-        for frame in &executor.call_stack {
-            let name = frame.get_frame_name();
-            eprintln!("\t at <{name}>");
+// 自栈顶向下寻找异常处理器, 弹出途经的栈帧并释放其持有的同步锁
+// 没有任何处理器时保留调用栈, 由调用方打印错误信息
+// 超出执行限制的错误不交给处理器, 以免脚本捕获后继续执行
+fn unwind(
+    executor: &mut Executor,
+    sync_table: &SyncTable,
+    error: RuntimeError,
+) -> Result<(), RuntimeError> {
+    if matches!(error, RuntimeError::LimitExceeded(_)) {
+        return Err(error);
+    }
+    let Some(depth) = executor
+        .call_stack
+        .iter()
+        .rposition(StackFrame::has_handler)
+    else {
+        return Err(error);
+    };
+    while executor.call_stack.len() > depth + 1 {
+        let mut frame = executor.call_stack.pop().unwrap();
+        if let Some((unit_index, func_index)) = frame.take_sync_lock() {
+            sync_table.unlock(unit_index, func_index);
         }
+        executor.frame_index -= 1;
     }
-    Value::Null
+    executor.call_stack.last_mut().unwrap().catch_error(error)
 }
 
-fn print_error(executor: &Executor, error: RuntimeError) {
-    eprintln!(
-        "RuntimeError: {error:?}",
-    );
-    for frame in &executor.call_stack {
+fn print_and_return(
+    executor: &Executor,
+    units: &[MetadataUnit],
+    failed_status: Option<RuntimeError>,
+) -> Result<Value, String> {
+    match failed_status {
+        Some(error) => Err(print_error(executor, units, error)),
+        None => Ok(Value::Null),
+    }
+}
+
+// 输出未捕获的运行时错误及调用栈, 并返回输出的完整报告
+fn print_error(executor: &Executor, units: &[MetadataUnit], error: RuntimeError) -> String {
+    let mut report = String::new();
+    let frames: Vec<(&StackFrame, Option<SourcePos>)> = executor
+        .call_stack
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(depth, frame)| (frame, frame.current_pos(depth == 0)))
+        .collect();
+
+    // 定位到最内层拥有行号信息的栈帧并高亮出错的源码行
+    if let Some((frame, Some(pos))) = frames.iter().find(|(_, pos)| pos.is_some()) {
+        let unit = &units[frame.get_unit_index()];
+        report.push_str(&format!(
+            "RuntimeError({}-line: {} column: {}): {error:?}\n",
+            unit.file,
+            pos.line + 1,
+            pos.column
+        ));
+        if let Some(source) = unit.source {
+            report.push_str(&format!(
+                "{}\n",
+                Compiler::highlight_line_and_column(source, pos.line, pos.column)
+            ));
+        }
+    } else {
+        report.push_str(&format!("RuntimeError: {error:?}\n"));
+    }
+
+    for (frame, pos) in frames {
         let name = frame.get_frame_name();
-        eprintln!("\t at <{name}>");
+        if let Some(path) = frame.is_native() {
+            report.push_str(&format!("\t at <{path}> (native)\n"));
+        } else if let Some(pos) = pos {
+            let file = &units[frame.get_unit_index()].file;
+            report.push_str(&format!(
+                "\t at <{name}> ({file}:{}:{})\n",
+                pos.line + 1,
+                pos.column
+            ));
+        } else {
+            report.push_str(&format!("\t at <{name}>\n"));
+        }
     }
+    eprint!("{report}");
+    report
 }
 
+/// 在新的执行器中调用函数并返回其返回值
+/// # Errors
+/// 存在未捕获的运行时错误时返回已输出到标准错误的错误报告 (包含出错位置与调用栈)
+#[allow(clippy::too_many_arguments)]
 pub fn call_function(
-    codes: &[ByteCode],
+    method: &MethodInfo,
     const_table: &[Value],
-    name: &str,
     units: &[MetadataUnit],
     unit_index: usize,
-    local_size: usize,
     globals: SharedGlobals,
     sync_table: SharedSync,
+    env: RuntimeEnv,
     thread_manager: Option<usize>,
     arguments: Vec<Value>,
-) -> Value {
+) -> Result<Value, String> {
     let mut executor = Executor::new();
-    let mut call_cache = CallCache::new(units);
-    context::set_context(units, globals.clone(), sync_table.clone(), thread_manager);
-    executor.push_frame(StackFrame::new(
-        unit_index,
-        local_size,
-        codes,
-        const_table,
-        name,
-        name,
-        None,
-        0,
-    ));
+    let mut call_cache = CallCache::new(units, env.optimize.is_enabled(Pass::CallMemo));
+    let limits = env.limits();
+    context::set_context(
+        units,
+        globals.clone(),
+        sync_table.clone(),
+        thread_manager,
+        env.budget.clone(),
+    );
+    executor.push_frame(StackFrame::new(unit_index, method, const_table, None));
+    let mut debug = env.debugger.as_deref().map(DebugSession::new);
+    let mut meter = env.budget.as_deref().map(LimitMeter::new);
     let mut failed_status = None;
-    for arg in arguments {
+    // 函数序言按形参顺序从栈顶取值, 第一个参数需要位于栈顶
+    for arg in arguments.into_iter().rev() {
         executor.get_top_frame().unwrap().push_op_stack(arg);
     }
 
@@ -450,31 +664,33 @@ pub fn call_function(
         if executor.call_stack.is_empty() {
             break;
         }
+        if let Some(session) = debug.as_mut() {
+            session.set_depth(executor.frame_index);
+        }
         let stack_frame = executor.get_top_frame().unwrap();
 
         if let Some(path) = stack_frame.is_native() {
             let path = path.clone();
-            let mut sp = path.split('/');
-            let file = sp.next().unwrap();
-            let func = sp.next().unwrap();
+            let (file, func) = path.rsplit_once('/').unwrap();
 
             let mut argument = vec![];
             for _i in 0..stack_frame.get_args() {
                 argument.push(stack_frame.pop_op_stack());
             }
 
-            match find_library(file, |f| {
-                if let Some(lib) = f
-                    && let Some(func) = lib.find_func(&SmolStr::new(func))
-                {
-                    match (func.func)(&argument) {
-                        Err(error) => Err(ParserError::RuntimeError(error)),
-                        Ok(ok ) => Ok(ok),
-                    }
-                } else {
-                    Err(ParserError::RuntimeError(NoSuchFunctionException(path.clone())))
-                }
-            }) {
+            let result = match env.natives.find_func(file, func) {
+                Some(func) => (func.func)(&argument),
+                None => Err(NoSuchFunctionException(path.clone())),
+            };
+            // 本地函数的执行不经过指令计数, 返回后计入其新建的值并检查超时
+            let result = match (result, env.budget.as_deref()) {
+                (Ok(value), Some(budget)) => budget
+                    .charge_length(allocation_size(&value))
+                    .and_then(|()| budget.check_time())
+                    .map(|()| value),
+                (result, _) => result,
+            };
+            match result {
                 Ok(lib) => {
                     let mut frame = executor.call_stack.pop().unwrap();
                     if let Some((unit_index, func_index)) = frame.take_sync_lock() {
@@ -484,18 +700,34 @@ pub fn call_function(
                     executor.frame_index -= 1;
                 },
                 Err(error) => {
-                    let ParserError::RuntimeError(error) = error else {
-                        unreachable!()
-                    };
-                    print_error(&executor, error);
-                    break;
+                    if let Err(error) = unwind(&mut executor, &sync_table, error) {
+                        failed_status = Some(error);
+                        break;
+                    }
                 }
             }
         } else {
-            match run_code(units, stack_frame, &globals, &call_cache, &sync_table) {
+            match run_code(
+                units,
+                stack_frame,
+                &globals,
+                &call_cache,
+                &sync_table,
+                debug.as_mut(),
+                meter.as_mut(),
+            ) {
                 Ok(state) => match state {
                     RunState::CallRequest(frame) => {
                         executor.push_frame(frame);
+                        if let Err(error) = limits.check_call_depth(executor.frame_index) {
+                            for frame in executor.call_stack.iter_mut() {
+                                if let Some((unit_index, func_index)) = frame.take_sync_lock() {
+                                    sync_table.unlock(unit_index, func_index);
+                                }
+                            }
+                            failed_status = Some(error);
+                            break;
+                        }
                         let mut stack_frame = executor.call_stack.pop().unwrap();
                         if stack_frame.get_args() > 0 {
                             let mut parent_frame = executor.call_stack.pop().unwrap();
@@ -518,7 +750,7 @@ pub fn call_function(
                             call_cache.store_memo(unit_index, func_index, key, ret_var.clone());
                         }
                         if executor.call_stack.is_empty() {
-                            return ret_var;
+                            return Ok(ret_var);
                         }
                         executor
                             .call_stack
@@ -549,7 +781,9 @@ pub fn call_function(
                     }
                 },
                 Err(state) => {
-                    //TODO 需要做栈帧异常回溯
+                    let Err(state) = unwind(&mut executor, &sync_table, state) else {
+                        continue;
+                    };
                     for frame in executor.call_stack.iter_mut() {
                         if let Some((unit_index, func_index)) = frame.take_sync_lock() {
                             sync_table.unlock(unit_index, func_index);
@@ -563,30 +797,30 @@ pub fn call_function(
     }
 
     context::clear_context();
-    print_and_return(&executor, failed_status)
+    print_and_return(&executor, units, failed_status)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn interpretive(
-    codes: &[ByteCode],
+    method: &MethodInfo,
     const_table: &[Value],
-    name: &str,
     units: &[MetadataUnit],
     unit_index: usize,
-    local_size: usize,
     globals: SharedGlobals,
     sync_table: SharedSync,
+    env: RuntimeEnv,
     thread_manager: Option<usize>,
+    arguments: Vec<Value>,
 ) {
-    call_function(
-        codes,
+    let _ = call_function(
+        method,
         const_table,
-        name,
         units,
         unit_index,
-        local_size,
         globals,
         sync_table,
+        env,
         thread_manager,
-        Vec::new(),
+        arguments,
     );
 }
@@ -0,0 +1,225 @@
+use smol_str::format_smolstr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::compiler::ast::vm_ir::Value;
+use crate::runtime::RuntimeError;
+
+// 执行器每执行这么多条指令才汇总一次指令计数并检查超时
+const CHECK_INTERVAL: u64 = 1024;
+
+/// 沙箱执行限制, 字段为 `None` 时不做对应的限制
+/// 超出任意一项限制时抛出无法被脚本捕获的 `LimitExceeded`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    pub max_instructions: Option<u64>, // 所有线程合计执行的最大指令数
+    pub max_call_depth: Option<usize>, // 单个线程的最大调用栈深度
+    pub max_length: Option<usize>, // 所有线程合计新建的数组与映射元素数、字符串字节数及任意精度整数字节数
+    pub timeout: Option<Duration>, // 自开始执行起的最长运行时间
+}
+
+impl ExecutionLimits {
+    #[must_use]
+    pub const fn is_unlimited(&self) -> bool {
+        self.max_instructions.is_none()
+            && self.max_call_depth.is_none()
+            && self.max_length.is_none()
+            && self.timeout.is_none()
+    }
+
+    /// 检查调用栈深度
+    /// # Errors
+    /// 深度超过 `max_call_depth` 时返回 `LimitExceeded`
+    pub fn check_call_depth(&self, depth: usize) -> Result<(), RuntimeError> {
+        match self.max_call_depth {
+            Some(max) if depth > max => Err(RuntimeError::LimitExceeded(format_smolstr!(
+                "call depth exceeds the limit of {max}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 一次执行中所有线程共享的限制与用量, 计时从第一次执行代码开始
+#[derive(Debug, Default)]
+pub struct ExecutionBudget {
+    limits: ExecutionLimits,
+    executed: AtomicU64,
+    allocated: AtomicUsize,
+    started: OnceLock<Instant>,
+}
+
+impl ExecutionBudget {
+    #[must_use]
+    pub fn new(limits: ExecutionLimits) -> Self {
+        Self {
+            limits,
+            executed: AtomicU64::new(0),
+            allocated: AtomicUsize::new(0),
+            started: OnceLock::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn limits(&self) -> &ExecutionLimits {
+        &self.limits
+    }
+
+    /// 所有线程已经执行的指令数, 各线程未汇总的计数不包含在内
+    #[must_use]
+    pub fn executed(&self) -> u64 {
+        self.executed.load(Ordering::Relaxed)
+    }
+
+    /// 所有线程已经新建的数组与映射元素数及字符串字节数
+    #[must_use]
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    /// 在分配之前检查剩余的长度额度, 不计入用量
+    /// # Errors
+    /// 新建 `len` 个元素或字节后会超出 `max_length` 时返回 `LimitExceeded`
+    pub fn check_length(&self, len: usize) -> Result<(), RuntimeError> {
+        self.length_error(self.allocated().saturating_add(len))
+    }
+
+    /// 将新建的 `len` 个元素或字节计入用量
+    /// # Errors
+    /// 累计用量超出 `max_length` 时返回 `LimitExceeded`
+    pub fn charge_length(&self, len: usize) -> Result<(), RuntimeError> {
+        if self.limits.max_length.is_none() || len == 0 {
+            return Ok(());
+        }
+        let total = self
+            .allocated
+            .fetch_add(len, Ordering::Relaxed)
+            .saturating_add(len);
+        self.length_error(total)
+    }
+
+    fn length_error(&self, total: usize) -> Result<(), RuntimeError> {
+        match self.limits.max_length {
+            Some(max) if total > max => Err(RuntimeError::LimitExceeded(format_smolstr!(
+                "allocated length {total} exceeds the limit of {max}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// 距离超时剩余的运行时间, 没有设置超时时返回 `None`
+    #[must_use]
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let timeout = self.limits.timeout?;
+        let started = self.started.get_or_init(Instant::now);
+        Some(timeout.saturating_sub(started.elapsed()))
+    }
+
+    /// 检查运行时间, 用于本地函数这类不经过指令计数的长时间操作之后
+    /// # Errors
+    /// 运行时间超时时返回 `LimitExceeded`
+    pub fn check_time(&self) -> Result<(), RuntimeError> {
+        match (self.limits.timeout, self.started.get()) {
+            (Some(timeout), Some(started)) if started.elapsed() >= timeout => {
+                Err(RuntimeError::LimitExceeded(format_smolstr!(
+                    "execution time exceeds the limit of {timeout:?}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 下一批次的指令数, 保证单线程执行时恰好在超出指令上限的那条指令处停下
+    fn next_batch(&self, executed: u64) -> u64 {
+        match self.limits.max_instructions {
+            Some(max) => CHECK_INTERVAL.min(max.saturating_sub(executed).saturating_add(1)),
+            None => CHECK_INTERVAL,
+        }
+    }
+}
+
+/// 单个线程上的指令计数器, 只在每批指令结束时访问共享的用量
+pub struct LimitMeter<'a> {
+    budget: &'a ExecutionBudget,
+    batch: u64,     // 当前批次的指令数
+    remaining: u64, // 当前批次中尚未执行的指令数
+}
+
+impl<'a> LimitMeter<'a> {
+    #[must_use]
+    pub fn new(budget: &'a ExecutionBudget) -> Self {
+        budget.started.get_or_init(Instant::now);
+        let batch = budget.next_batch(budget.executed());
+        Self {
+            budget,
+            batch,
+            remaining: batch,
+        }
+    }
+
+    /// 在执行每条指令前调用
+    /// # Errors
+    /// 超出指令上限或运行时间超时时返回 `LimitExceeded`
+    #[inline]
+    pub fn tick(&mut self) -> Result<(), RuntimeError> {
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.check()
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cold]
+    fn check(&mut self) -> Result<(), RuntimeError> {
+        let executed = self
+            .budget
+            .executed
+            .fetch_add(self.batch, Ordering::Relaxed)
+            + self.batch;
+        let limits = &self.budget.limits;
+        if let Some(max) = limits.max_instructions
+            && executed > max
+        {
+            // 保持计数器停在批次末尾, 之后的每条指令都会再次报错
+            self.batch = 0;
+            self.remaining = 1;
+            return Err(RuntimeError::LimitExceeded(format_smolstr!(
+                "instruction count exceeds the limit of {max}"
+            )));
+        }
+        if let Err(error) = self.budget.check_time() {
+            self.batch = 0;
+            self.remaining = 1;
+            return Err(error);
+        }
+        self.batch = self.budget.next_batch(executed);
+        self.remaining = self.batch;
+        Ok(())
+    }
+}
+
+impl Drop for LimitMeter<'_> {
+    // 汇总当前批次中已经执行的指令
+    fn drop(&mut self) {
+        let used = self.batch.saturating_sub(self.remaining);
+        self.budget.executed.fetch_add(used, Ordering::Relaxed);
+    }
+}
+
+/// 值中包含的数组与映射元素数、字符串字节数及任意精度整数的字节数, 嵌套的数组与映射逐层累加
+#[must_use]
+pub fn allocation_size(value: &Value) -> usize {
+    match value {
+        Value::String(value) => value.len(),
+        Value::BigInt(_) => value.bit_len().div_ceil(8),
+        Value::Array(elements) => elements.iter().fold(elements.len(), |total, element| {
+            total.saturating_add(allocation_size(element))
+        }),
+        Value::Map(map) => map.values().fold(map.len(), |total, element| {
+            total.saturating_add(allocation_size(element))
+        }),
+        _ => 0,
+    }
+}
@@ -1,15 +1,24 @@
-use smol_str::{SmolStr, ToSmolStr};
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
 use std::sync::{Arc, Mutex};
 
 use crate::compiler::Compiler;
 use crate::compiler::ast::vm_ir::{ByteCode, Value};
+use crate::compiler::file::module_name;
+use crate::compiler::lexer::SourcePos;
+use crate::compiler::optimize::Optimizations;
+use crate::library::ffi::ForeignFunc;
+use crate::library::{NativeRegistry, SharedNatives};
 use crate::runtime::context::SyncTable;
+use crate::runtime::debugger::Debugger;
+use crate::runtime::limits::{ExecutionBudget, ExecutionLimits};
 use crate::runtime::thread::ThreadManager;
 
 pub mod context;
+pub mod debugger;
 pub mod executor;
+pub mod limits;
 pub mod thread;
-mod vm_operation;
+pub(crate) mod vm_operation;
 mod vm_table_opt;
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
@@ -18,14 +27,95 @@ pub enum RuntimeError {
     TypeException(SmolStr),           // 类型检查错误
     PrecisionLoss(SmolStr),           // 精度转换损失
     IndexOutOfBounds(SmolStr),        // 索引越界
+    IOException(SmolStr),             // 输入输出错误 (操作系统错误信息)
     VMError,                          // 解释器内部错误
+    Exception(SmolStr, SmolStr),      // 脚本抛出的异常 (异常类型, 异常信息)
+    LimitExceeded(SmolStr),           // 超出执行限制, 脚本无法捕获
+}
+
+/// 检查数组索引是否在 `0..len` 范围内
+/// # Errors
+/// 越界 (包括负数索引) 时返回携带索引与长度的 `IndexOutOfBounds`
+pub fn check_index(index: i64, len: usize) -> Result<usize, RuntimeError> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| RuntimeError::index_out_of_bounds(index, len))
+}
+
+impl RuntimeError {
+    #[must_use]
+    pub fn index_out_of_bounds(index: i64, len: usize) -> Self {
+        Self::IndexOutOfBounds(format_smolstr!(
+            "Index {index} out of bounds for length {len}"
+        ))
+    }
+
+    /// 异常类型名称, 即脚本捕获到的异常值的第一个元素
+    #[must_use]
+    pub fn kind(&self) -> &str {
+        match self {
+            Self::NoSuchFunctionException(_) => "NoSuchFunctionException",
+            Self::TypeException(_) => "TypeException",
+            Self::PrecisionLoss(_) => "PrecisionLoss",
+            Self::IndexOutOfBounds(_) => "IndexOutOfBounds",
+            Self::IOException(_) => "IOException",
+            Self::VMError => "VMError",
+            Self::Exception(kind, _) => kind.as_str(),
+            Self::LimitExceeded(_) => "LimitExceeded",
+        }
+    }
+
+    #[must_use]
+    pub fn message(&self) -> SmolStr {
+        match self {
+            Self::NoSuchFunctionException(message)
+            | Self::TypeException(message)
+            | Self::PrecisionLoss(message)
+            | Self::IndexOutOfBounds(message)
+            | Self::IOException(message)
+            | Self::Exception(_, message)
+            | Self::LimitExceeded(message) => message.clone(),
+            Self::VMError => SmolStr::new_static(""),
+        }
+    }
+
+    /// 转换为脚本中 catch 捕获到的异常值 `[类型, 信息]`
+    #[must_use]
+    pub fn into_value(self) -> Value {
+        let kind = Value::String(self.kind().to_smolstr());
+        Value::Array(vec![kind, Value::String(self.message())])
+    }
+
+    /// 由脚本 throw 的值构造异常
+    /// 形如 `[类型, 信息]` 的值会还原为对应的异常类型, 因此捕获后可以原样重新抛出
+    /// `LimitExceeded` 只能由执行器产生, 脚本抛出同名的值时视为普通异常
+    #[must_use]
+    pub fn from_value(value: Value) -> Self {
+        if let Value::Array(elements) = &value
+            && let [Value::String(kind), Value::String(message)] = elements.as_slice()
+        {
+            let message = message.clone();
+            return match kind.as_str() {
+                "NoSuchFunctionException" => Self::NoSuchFunctionException(message),
+                "TypeException" => Self::TypeException(message),
+                "PrecisionLoss" => Self::PrecisionLoss(message),
+                "IndexOutOfBounds" => Self::IndexOutOfBounds(message),
+                "IOException" => Self::IOException(message),
+                "VMError" => Self::VMError,
+                _ => Self::Exception(kind.clone(), message),
+            };
+        }
+        Self::Exception(SmolStr::new_static("Exception"), value.to_smolstr())
+    }
 }
 
 pub struct MethodInfo {
     pub name: SmolStr,
     pub r_name: SmolStr,
     pub codes: Vec<ByteCode>,
-    pub locals: usize, // 局部变量表
+    pub lines: Vec<SourcePos>, // 行号表, 与 codes 一一对应
+    pub locals: usize,         // 局部变量表
     pub is_native: bool,
     pub sync: bool,
     pub args: usize, // 形参个数
@@ -35,9 +125,13 @@ pub struct MetadataUnit<'a> {
     pub constant_table: &'a [Value],
     pub methods: Vec<MethodInfo>,
     pub names: &'a str,
-    pub globals: usize,           // 全局变量表
-    pub root_code: Vec<ByteCode>, // 全局代码
+    pub file: SmolStr,              // 源文件名
+    pub source: Option<&'a str>,    // 源码内容, 用于运行时错误定位 (字节码文件中不保留)
+    pub globals: usize,             // 全局变量表
+    pub root_code: Vec<ByteCode>,   // 全局代码
+    pub root_lines: Vec<SourcePos>, // 全局代码行号表
     pub library: bool,
+    pub foreign: Vec<ForeignFunc>, // 动态库函数声明, 直接加载字节码时需要重新注册
 }
 
 pub struct GlobalStore {
@@ -47,6 +141,53 @@ pub struct GlobalStore {
 pub type SharedGlobals = Arc<Mutex<GlobalStore>>;
 pub type SharedSync = context::SharedSync;
 
+/// 由宿主提供、在一次执行的所有线程间共享的运行环境
+#[derive(Clone, Default)]
+pub struct RuntimeEnv {
+    pub natives: SharedNatives,
+    pub debugger: Option<Arc<Debugger>>, // 为空时不进行任何调试检查
+    pub optimize: Optimizations,         // 仅使用其中的运行时优化开关
+    pub budget: Option<Arc<ExecutionBudget>>, // 为空时不做任何执行限制
+}
+
+impl RuntimeEnv {
+    #[must_use]
+    pub fn new(natives: SharedNatives) -> Self {
+        Self {
+            natives,
+            debugger: None,
+            optimize: Optimizations::default(),
+            budget: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_optimizations(mut self, optimize: Optimizations) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    #[must_use]
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(Arc::new(debugger));
+        self
+    }
+
+    /// 设置执行限制, 同一个运行环境派生出的所有线程共享指令计数与计时
+    #[must_use]
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.budget = (!limits.is_unlimited()).then(|| Arc::new(ExecutionBudget::new(limits)));
+        self
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> ExecutionLimits {
+        self.budget
+            .as_ref()
+            .map_or_else(ExecutionLimits::default, |budget| *budget.limits())
+    }
+}
+
 impl GlobalStore {
     pub fn new(units: &[MetadataUnit<'_>]) -> Self {
         let globals = units
@@ -66,6 +207,14 @@ impl GlobalStore {
         }
     }
 
+    /// 按元数据单元扩容全局变量表, 已有的全局变量值保持不变
+    pub fn resize(&mut self, units: &[MetadataUnit<'_>]) {
+        self.globals.resize_with(units.len(), Vec::new);
+        for (unit, globals) in units.iter().zip(&mut self.globals) {
+            globals.resize(unit.globals, Value::Null);
+        }
+    }
+
     pub fn get(&self, unit_index: usize, index: usize) -> Option<&Value> {
         self.globals
             .get(unit_index)
@@ -91,7 +240,9 @@ impl MethodInfo {
     }
 }
 
-pub fn initialize_executor(compiler: &mut Compiler) {
+/// 将编译器中所有已编译的源文件转换为执行引擎使用的元数据单元
+/// # Panics
+pub fn build_metadata(compiler: &mut Compiler) -> Vec<MetadataUnit<'_>> {
     let mut metadata: Vec<MetadataUnit> = Vec::new();
     for file in compiler.get_files() {
         let vm_ir = file.ir_table.as_ref().unwrap();
@@ -100,9 +251,10 @@ pub fn initialize_executor(compiler: &mut Compiler) {
         for func in vm_ir.get_functions() {
             methods.push(MethodInfo {
                 name: func.name.clone(),
-                r_name: func.filename.split('.').next().unwrap().to_smolstr(),
+                r_name: module_name(&func.filename),
                 locals: func.locals,
                 codes: func.clone_codes().unwrap_or_default(),
+                lines: func.lines.clone(),
                 is_native: func.is_native,
                 sync: func.sync,
                 args: func.args,
@@ -111,41 +263,91 @@ pub fn initialize_executor(compiler: &mut Compiler) {
 
         metadata.push(MetadataUnit {
             constant_table: vm_ir.get_constant_table(),
-            names: file.name.split('.').next().unwrap(),
+            names: file.module_name(),
+            file: file.name.to_smolstr(),
+            // 交互模式下早先输入的函数行号属于已被替换的源码, 不做源码高亮
+            source: (!file.is_interactive()).then(|| file.get_data()),
             methods,
             globals: vm_ir.get_locals_len(),
             root_code: vm_ir.clone_codes(),
+            root_lines: vm_ir.clone_lines(),
             library: file.is_library,
+            foreign: vm_ir.get_foreign().to_vec(),
         });
     }
+    metadata
+}
+
+/// 元数据单元的全局代码, 作为入口函数执行
+#[must_use]
+pub fn root_method(unit: &MetadataUnit) -> MethodInfo {
+    MethodInfo {
+        name: "<main_root>".to_smolstr(),
+        r_name: SmolStr::new(unit.names),
+        locals: unit.globals,
+        codes: unit.root_code.clone(),
+        lines: unit.root_lines.clone(),
+        is_native: false,
+        sync: false,
+        args: 0,
+    }
+}
 
+/// 以第一个非库单元作为入口执行所有元数据单元
+pub fn execute_units(metadata: &[MetadataUnit], env: &RuntimeEnv) {
+    execute_units_with(metadata, &GlobalStore::shared_new(metadata), env);
+}
+
+/// 在给定的全局变量表上执行入口单元, 交互模式借此在多次执行间保留全局变量
+/// # Panics
+pub fn execute_units_with(metadata: &[MetadataUnit], globals: &SharedGlobals, env: &RuntimeEnv) {
     let (main_index, main_metadata) = metadata
         .iter()
         .enumerate()
         .find(|(_, file)| !file.library)
         .unwrap();
 
-    let main_method = &MethodInfo {
-        name: "<main_root>".to_smolstr(),
-        r_name: SmolStr::new(main_metadata.names),
-        locals: main_metadata.globals,
-        codes: main_metadata.root_code.clone(),
-        is_native: false,
-        sync: false,
-        args: 0,
-    };
+    let main_method = &root_method(main_metadata);
 
-    let globals = GlobalStore::shared_new(&metadata);
-    let sync_table = SyncTable::shared_new(&metadata);
+    globals.lock().unwrap().resize(metadata);
+    let sync_table = SyncTable::shared_new(metadata);
     std::thread::scope(|scope| {
-        let thread_manager = ThreadManager::new(scope);
+        let thread_manager = ThreadManager::new(scope, env.clone());
         thread_manager.submit_join_thread(
             main_index,
             main_metadata,
             main_method,
-            metadata.as_slice(),
-            globals,
+            metadata,
+            globals.clone(),
             sync_table,
         );
     });
 }
+
+/// 在宿主的注册表上加载元数据单元中声明的动态库函数, 没有声明时直接共享原注册表
+/// # Errors
+/// 动态库函数被禁用或无法加载时返回错误信息
+pub fn bind_natives(
+    natives: &SharedNatives,
+    units: &[MetadataUnit],
+) -> Result<SharedNatives, SmolStr> {
+    if units.iter().all(|unit| unit.foreign.is_empty()) {
+        return Ok(natives.clone());
+    }
+    let mut natives = NativeRegistry::clone(natives);
+    natives.bind_foreign(units)?;
+    Ok(Arc::new(natives))
+}
+
+pub fn initialize_executor(compiler: &mut Compiler) {
+    let natives = compiler.natives().clone();
+    let optimize = compiler.get_optimizations();
+    let metadata = build_metadata(compiler);
+    match bind_natives(&natives, &metadata) {
+        Ok(natives) => {
+            let env = RuntimeEnv::new(natives).with_optimizations(optimize);
+            execute_units(&metadata, &env);
+        }
+        Err(e) => eprintln!("error: {e}"),
+    }
+}
@@ -1,17 +1,20 @@
 use std::thread::Scope;
 
+use crate::compiler::ast::vm_ir::Value;
 use crate::runtime::executor::interpretive;
-use crate::runtime::{MetadataUnit, MethodInfo, SharedGlobals, SharedSync};
+use crate::runtime::{MetadataUnit, MethodInfo, RuntimeEnv, SharedGlobals, SharedSync};
 
 pub struct ThreadManager<'scope, 'env> {
     scope: &'scope Scope<'scope, 'env>,
+    env: RuntimeEnv, // 同一次执行中的所有线程共享本地函数注册表与调试器
 }
 
 impl<'scope, 'env> ThreadManager<'scope, 'env> {
-    pub const fn new(scope: &'scope Scope<'scope, 'env>) -> Self {
-        Self { scope }
+    pub const fn new(scope: &'scope Scope<'scope, 'env>, env: RuntimeEnv) -> Self {
+        Self { scope, env }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn submit_run_thread(
         &self,
         unit_index: usize,
@@ -20,21 +23,23 @@ impl<'scope, 'env> ThreadManager<'scope, 'env> {
         units: &'env [MetadataUnit],
         globals: SharedGlobals,
         sync_table: SharedSync,
+        arguments: Vec<Value>,
     ) {
         let globals = globals.clone();
         let thread_manager = self as *const _ as usize;
         let sync_table = sync_table.clone();
+        let env = self.env.clone();
         self.scope.spawn(move || {
             interpretive(
-                unit.get_codes(),
+                unit,
                 metadata.constant_table,
-                metadata.names,
                 units,
                 unit_index,
-                metadata.globals,
                 globals,
                 sync_table,
+                env,
                 Some(thread_manager),
+                arguments,
             );
         });
     }
@@ -51,18 +56,19 @@ impl<'scope, 'env> ThreadManager<'scope, 'env> {
         let globals = globals.clone();
         let thread_manager = self as *const _ as usize;
         let sync_table = sync_table.clone();
+        let env = self.env.clone();
         self.scope
             .spawn(move || {
                 interpretive(
-                    unit.get_codes(),
+                    unit,
                     metadata.constant_table,
-                    metadata.names,
                     units,
                     unit_index,
-                    metadata.globals,
                     globals,
                     sync_table,
+                    env,
                     Some(thread_manager),
+                    Vec::new(),
                 );
             })
             .join()
@@ -1,10 +1,15 @@
 use dashu::float::{Context, DBig};
-use smol_str::{ToSmolStr, format_smolstr};
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+use std::cmp::Ordering;
+use std::sync::Arc;
 
+use crate::compiler::ast::integer::IntOp;
 use crate::compiler::ast::vm_ir::Value;
-use crate::compiler::ast::vm_ir::Value::{Bool, Float, Int, Null, String};
+use crate::compiler::ast::vm_ir::Value::{BigInt, Bool, Float, Int, Null, String};
 use crate::runtime::RuntimeError;
+use crate::runtime::context;
 use crate::runtime::executor::StackFrame;
+use crate::runtime::limits::allocation_size;
 
 pub fn get_ref(stack_frame: &mut StackFrame) {
     let ref1 = stack_frame.pop_op_stack();
@@ -20,36 +25,73 @@ pub fn get_ref(stack_frame: &mut StackFrame) {
     stack_frame.next_pc();
 }
 
+// 整数之间的运算, 溢出时提升为任意精度整数
+fn int_value(op: IntOp, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+    // 任意精度整数的运算耗时随位数增长, 运算之前检查长度额度, 运算之后计入用量并检查超时
+    if matches!((left, right), (BigInt(_), _) | (_, BigInt(_))) || op == IntOp::Shl {
+        context::check_length(op.result_bytes(left, right))?;
+    }
+    let result = op.apply(left, right).ok_or_else(|| match op {
+        IntOp::Div | IntOp::Rmd => RuntimeError::Exception(
+            SmolStr::new_static("ArithmeticException"),
+            SmolStr::new_static("/ by zero"),
+        ),
+        _ => RuntimeError::Exception(
+            SmolStr::new_static("ArithmeticException"),
+            format_smolstr!("shift amount {right} out of range"),
+        ),
+    })?;
+    if matches!(result, BigInt(_)) {
+        context::charge_length(allocation_size(&result))?;
+        context::check_time()?;
+    }
+    Ok(result)
+}
+
+// 与浮点数混合运算的整数, 调用前已确认其为整数
+fn int_to_float(value: &Value) -> DBig {
+    value.to_dbig().unwrap()
+}
+
 pub fn add_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
     match (left, right) {
-        // Int + Int → Int
-        (Int(l), Int(r)) => Ok(Int(l + r)),
+        // Int + Int → Int, 溢出时为 BigInt
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => int_value(IntOp::Add, &l, &r),
 
         // Int + Float → Float
-        (Int(l), Float(r)) => Ok(Float(DBig::from(l) + r)),
+        (l @ (Int(_) | BigInt(_)), Float(r)) => Ok(Float(int_to_float(&l) + r)),
 
         // Float + Int → Float
-        (Float(l), Int(r)) => Ok(Float(l + DBig::from(r))),
+        (Float(l), r @ (Int(_) | BigInt(_))) => Ok(Float(l + int_to_float(&r))),
 
         // Float + Float → Float
         (Float(l), Float(r)) => Ok(Float(l + r)),
 
-        (String(l), String(r)) => Ok(String(format_smolstr!("{l}{r}"))),
+        (String(l), String(r)) => {
+            context::charge_length(l.len() + r.len())?;
+            Ok(String(format_smolstr!("{l}{r}")))
+        }
         // any + String → String
-        (lhs, String(r)) => Ok(String(format_smolstr!("{lhs}{r}"))),
+        (lhs, String(r)) => concat_string(format_smolstr!("{lhs}{r}")),
         // String + any → String
-        (String(l), rhs) => Ok(String(format_smolstr!("{l}{rhs}"))),
+        (String(l), rhs) => concat_string(format_smolstr!("{l}{rhs}")),
         (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
             "{auto} to {auto1}"
         ))),
     }
 }
 
+// 与非字符串值拼接时无法预先得知结果的长度, 拼接后再计入用量
+fn concat_string(value: SmolStr) -> Result<Value, RuntimeError> {
+    context::charge_length(value.len())?;
+    Ok(String(value))
+}
+
 pub fn sub_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
     match (left, right) {
-        (Int(l), Int(r)) => Ok(Int(l - r)),
-        (Int(l), Float(r)) => Ok(Float(DBig::from(l) - r)),
-        (Float(l), Int(r)) => Ok(Float(l - DBig::from(r))),
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => int_value(IntOp::Sub, &l, &r),
+        (l @ (Int(_) | BigInt(_)), Float(r)) => Ok(Float(int_to_float(&l) - r)),
+        (Float(l), r @ (Int(_) | BigInt(_))) => Ok(Float(l - int_to_float(&r))),
         (Float(l), Float(r)) => Ok(Float(l - r)),
         (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
             "{auto} to {auto1}"
@@ -59,9 +101,9 @@ pub fn sub_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
 
 pub fn mul_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
     match (left, right) {
-        (Int(l), Int(r)) => Ok(Int(l * r)),
-        (Int(l), Float(r)) => Ok(Float(DBig::from(l) * r)),
-        (Float(l), Int(r)) => Ok(Float(l * DBig::from(r))),
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => int_value(IntOp::Mul, &l, &r),
+        (l @ (Int(_) | BigInt(_)), Float(r)) => Ok(Float(int_to_float(&l) * r)),
+        (Float(l), r @ (Int(_) | BigInt(_))) => Ok(Float(l * int_to_float(&r))),
         (Float(l), Float(r)) => Ok(Float(l * r)),
         (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
             "{auto} to {auto1}"
@@ -71,14 +113,18 @@ pub fn mul_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
 
 pub fn div_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
     match (left, right) {
-        (Int(l), Int(r)) => Ok(Int(l / r)),
-        (Int(l), Float(r)) => {
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => int_value(IntOp::Div, &l, &r),
+        (l @ (Int(_) | BigInt(_)), Float(r)) => {
             let context = Context::new(30);
-            Ok(Float(context.div(DBig::from(l).repr(), r.repr()).value()))
+            Ok(Float(
+                context.div(int_to_float(&l).repr(), r.repr()).value(),
+            ))
         }
-        (Float(l), Int(r)) => {
+        (Float(l), r @ (Int(_) | BigInt(_))) => {
             let context = Context::new(30);
-            Ok(Float(context.div(l.repr(), DBig::from(r).repr()).value()))
+            Ok(Float(
+                context.div(l.repr(), int_to_float(&r).repr()).value(),
+            ))
         }
         (Float(l), Float(r)) => Ok(Float(l / r)),
         (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
@@ -89,14 +135,18 @@ pub fn div_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
 
 pub fn rmd_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
     match (left, right) {
-        (Int(l), Int(r)) => Ok(Int(l % r)),
-        (Int(l), Float(r)) => {
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => int_value(IntOp::Rmd, &l, &r),
+        (l @ (Int(_) | BigInt(_)), Float(r)) => {
             let context = Context::new(30);
-            Ok(Float(context.rem(DBig::from(l).repr(), r.repr()).value()))
+            Ok(Float(
+                context.rem(int_to_float(&l).repr(), r.repr()).value(),
+            ))
         }
-        (Float(l), Int(r)) => {
+        (Float(l), r @ (Int(_) | BigInt(_))) => {
             let context = Context::new(30);
-            Ok(Float(context.rem(l.repr(), DBig::from(r).repr()).value()))
+            Ok(Float(
+                context.rem(l.repr(), int_to_float(&r).repr()).value(),
+            ))
         }
         (Float(l), Float(r)) => Ok(Float(l % r)),
         (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
@@ -110,10 +160,14 @@ pub fn equ_value(stack_frame: &mut StackFrame) {
     let left = stack_frame.pop_op_stack();
     let value = match (left, right) {
         (Int(l), Int(r)) => Bool(l == r),
+        (BigInt(l), BigInt(r)) => Bool(l == r),
         (Float(l), Float(r)) => Bool(l == r),
         (String(l), String(r)) => Bool(l.as_str() == r.as_str()),
         (Null, Null) => Bool(true),
         (Bool(l), Bool(r)) => Bool(l == r),
+        // 对象与闭包按引用比较
+        (Value::Object(l), Value::Object(r)) => Bool(Arc::ptr_eq(&l, &r)),
+        (Value::Closure(l), Value::Closure(r)) => Bool(Arc::ptr_eq(&l, &r)),
         _ => Bool(false),
     };
     stack_frame.push_op_stack(value);
@@ -125,10 +179,13 @@ pub fn not_equ_value(stack_frame: &mut StackFrame) {
     let left = stack_frame.pop_op_stack();
     let value = match (left, right) {
         (Int(l), Int(r)) => Bool(l != r),
+        (BigInt(l), BigInt(r)) => Bool(l != r),
         (Float(l), Float(r)) => Bool(l != r),
         (String(l), String(r)) => Bool(l.as_str() != r.as_str()),
         (Null, Null) => Bool(false),
         (Bool(l), Bool(r)) => Bool(l != r),
+        (Value::Object(l), Value::Object(r)) => Bool(!Arc::ptr_eq(&l, &r)),
+        (Value::Closure(l), Value::Closure(r)) => Bool(!Arc::ptr_eq(&l, &r)),
         _ => Bool(true),
     };
     stack_frame.push_op_stack(value);
@@ -150,34 +207,26 @@ pub fn not_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     Ok(())
 }
 
+// 比较两个数值, 整数之间精确比较
+fn compare_value(left: &Value, right: &Value) -> Result<Ordering, RuntimeError> {
+    left.compare_number(right)
+        .ok_or_else(|| RuntimeError::TypeException(format_smolstr!("{left} to {right}")))
+}
+
 pub fn big_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
-    match (left, right) {
-        (Int(l), Int(r)) => Ok(Bool(l > r)),
-        (Int(l), Float(r)) => Ok(Bool(DBig::from(l) > r)),
-        (Float(l), Int(r)) => Ok(Bool(l > DBig::from(r))),
-        (Float(l), Float(r)) => Ok(Bool(l > r)),
-        (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
-            "{auto} to {auto1}"
-        ))),
-    }
+    compare_value(&left, &right).map(|ordering| Bool(ordering.is_gt()))
 }
 
 pub fn less_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
-    match (left, right) {
-        (Int(l), Int(r)) => Ok(Bool(l < r)),
-        (Int(l), Float(r)) => Ok(Bool(DBig::from(l) < r)),
-        (Float(l), Int(r)) => Ok(Bool(l < DBig::from(r))),
-        (Float(l), Float(r)) => Ok(Bool(l < r)),
-        (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
-            "{auto} to {auto1}"
-        ))),
-    }
+    compare_value(&left, &right).map(|ordering| Bool(ordering.is_lt()))
 }
 
 pub fn self_add_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let var = stack_frame.pop_op_stack();
     match var {
-        Int(i) => stack_frame.push_op_stack(Int(i + 1)),
+        var @ (Int(_) | BigInt(_)) => {
+            stack_frame.push_op_stack(int_value(IntOp::Add, &var, &Int(1))?);
+        }
         Float(f) => stack_frame.push_op_stack(Float(f + DBig::from(1))),
         auto => {
             return Err(RuntimeError::TypeException(format_smolstr!(
@@ -192,7 +241,9 @@ pub fn self_add_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError>
 pub fn self_sub_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let var = stack_frame.pop_op_stack();
     match var {
-        Int(i) => stack_frame.push_op_stack(Int(i - 1)),
+        var @ (Int(_) | BigInt(_)) => {
+            stack_frame.push_op_stack(int_value(IntOp::Sub, &var, &Int(1))?);
+        }
         Float(f) => stack_frame.push_op_stack(Float(f - DBig::from(1))),
         auto => {
             return Err(RuntimeError::TypeException(format_smolstr!(
@@ -205,29 +256,22 @@ pub fn self_sub_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError>
 }
 
 pub fn less_equ_value(left: Value, right: Value) -> Result<Value, RuntimeError> {
-    match (left, right) {
-        (Int(l), Int(r)) => Ok(Bool(l <= r)),
-        (Int(l), Float(r)) => Ok(Bool(DBig::from(l) <= r)),
-        (Float(l), Int(r)) => Ok(Bool(l <= DBig::from(r))),
-        (Float(l), Float(r)) => Ok(Bool(l <= r)),
-        (auto, auto1) => Err(RuntimeError::TypeException(format_smolstr!(
-            "{auto} to {auto1}"
+    compare_value(&left, &right).map(|ordering| Bool(ordering.is_le()))
+}
+
+pub fn neg_number(value: Value) -> Result<Value, RuntimeError> {
+    match value {
+        var @ (Int(_) | BigInt(_)) => int_value(IntOp::Sub, &Int(0), &var),
+        Float(f) => Ok(Float(-f)),
+        auto => Err(RuntimeError::TypeException(format_smolstr!(
+            "{auto} to float or number"
         ))),
     }
 }
 
 pub fn neg_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let var = stack_frame.pop_op_stack();
-    let value = match var {
-        Int(l) => Int(-l),
-        Float(f) => Float(-f),
-        auto => {
-            return Err(RuntimeError::TypeException(format_smolstr!(
-                "{auto} to float or number"
-            )));
-        }
-    };
-    stack_frame.push_op_stack(value);
+    stack_frame.push_op_stack(neg_number(var)?);
     stack_frame.next_pc();
     Ok(())
 }
@@ -264,8 +308,8 @@ pub fn bit_left_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError>
     let right = stack_frame.pop_op_stack();
     let left = stack_frame.pop_op_stack();
     match (left, right) {
-        (Int(i), Int(r)) => {
-            stack_frame.push_op_stack(Int(i << r));
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => {
+            stack_frame.push_op_stack(int_value(IntOp::Shl, &l, &r)?);
         }
         _ => {
             return Err(RuntimeError::TypeException(
@@ -281,8 +325,8 @@ pub fn bit_right_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError>
     let right = stack_frame.pop_op_stack();
     let left = stack_frame.pop_op_stack();
     match (left, right) {
-        (Int(i), Int(r)) => {
-            stack_frame.push_op_stack(Int(i >> r));
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => {
+            stack_frame.push_op_stack(int_value(IntOp::Shr, &l, &r)?);
         }
         _ => {
             return Err(RuntimeError::TypeException(
@@ -298,8 +342,8 @@ pub fn bit_and_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let right = stack_frame.pop_op_stack();
     let left = stack_frame.pop_op_stack();
     match (left, right) {
-        (Int(i), Int(r)) => {
-            stack_frame.push_op_stack(Int(i & r));
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => {
+            stack_frame.push_op_stack(int_value(IntOp::BitAnd, &l, &r)?);
         }
         _ => {
             return Err(RuntimeError::TypeException(
@@ -315,8 +359,8 @@ pub fn bit_or_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let right = stack_frame.pop_op_stack();
     let left = stack_frame.pop_op_stack();
     match (left, right) {
-        (Int(i), Int(r)) => {
-            stack_frame.push_op_stack(Int(i | r));
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => {
+            stack_frame.push_op_stack(int_value(IntOp::BitOr, &l, &r)?);
         }
         _ => {
             return Err(RuntimeError::TypeException(
@@ -332,8 +376,8 @@ pub fn bit_xor_value(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let right = stack_frame.pop_op_stack();
     let left = stack_frame.pop_op_stack();
     match (left, right) {
-        (Int(i), Int(r)) => {
-            stack_frame.push_op_stack(Int(i ^ r));
+        (l @ (Int(_) | BigInt(_)), r @ (Int(_) | BigInt(_))) => {
+            stack_frame.push_op_stack(int_value(IntOp::BitXor, &l, &r)?);
         }
         _ => {
             return Err(RuntimeError::TypeException(
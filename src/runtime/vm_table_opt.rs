@@ -1,14 +1,20 @@
+use dashu::integer::IBig;
+use linked_hash_map::LinkedHashMap;
 use smol_str::{SmolStr, ToSmolStr, format_smolstr};
 use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
-use crate::compiler::ast::vm_ir::{ByteCode, Value};
+use crate::compiler::ast::vm_ir::{ByteCode, Closure, Object, Value};
+use crate::library::type_lib::{ARRAY_FUNCS, call_array_func};
+use crate::runtime::context;
 use crate::runtime::context::SyncTable;
 use crate::runtime::executor::{RunState, StackFrame};
-use crate::runtime::{MetadataUnit, RuntimeError};
+use crate::runtime::{MetadataUnit, MethodInfo, RuntimeError, check_index};
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub enum MemoKey {
     Int(i64),
+    BigInt(IBig),
     Bool(bool),
     String(SmolStr),
     Ref(SmolStr),
@@ -19,6 +25,7 @@ impl MemoKey {
     fn from_value(value: &Value) -> Option<Self> {
         match value {
             Value::Int(v) => Some(MemoKey::Int(*v)),
+            Value::BigInt(v) => Some(MemoKey::BigInt(v.clone())),
             Value::Bool(v) => Some(MemoKey::Bool(*v)),
             Value::String(v) => Some(MemoKey::String(v.clone())),
             Value::Ref(v) => Some(MemoKey::Ref(v.clone())),
@@ -36,7 +43,8 @@ pub struct CallCache {
 }
 
 impl CallCache {
-    pub fn new(units: &[MetadataUnit]) -> Self {
+    /// `memoize` 为假时不对任何函数进行记忆缓存
+    pub fn new(units: &[MetadataUnit], memoize: bool) -> Self {
         let mut map = HashMap::new();
         let mut memoizable = HashSet::new();
         let mut memo = HashMap::new();
@@ -45,7 +53,7 @@ impl CallCache {
             for (func_index, func) in unit.methods.iter().enumerate() {
                 let self_path = format_smolstr!("{}/{}", unit.names, func.name);
                 map.insert(self_path.clone(), (unit_index, func_index));
-                if is_pure_self_recursive(unit, func, &self_path) {
+                if memoize && is_pure_self_recursive(unit, func, &self_path) {
                     let idx = (unit_index, func_index);
                     memoizable.insert(idx);
                     memo.insert(idx, HashMap::new());
@@ -131,11 +139,15 @@ fn is_pure_self_recursive(
             ByteCode::StoreGlobal(_)
             | ByteCode::LoadGlobal(_)
             | ByteCode::LoadArrayGlobal(_, _)
+            | ByteCode::LoadMapGlobal(_, _)
             | ByteCode::SetArrayGlobal(_)
-            | ByteCode::AddGlobalImm(_, _) => {
+            | ByteCode::GetIndexGlobal(_)
+            | ByteCode::AddGlobalImm(_, _)
+            | ByteCode::NewObject(_, _)
+            | ByteCode::SetField(_) => {
                 return false;
             }
-            ByteCode::Call => {
+            ByteCode::Call(_) => {
                 if idx == 0 {
                     return false;
                 }
@@ -149,7 +161,7 @@ fn is_pure_self_recursive(
                     _ => return false,
                 }
             }
-            ByteCode::CallConst(const_index) => {
+            ByteCode::CallConst(const_index, _) => {
                 match unit.constant_table.get(*const_index) {
                     Some(Value::Ref(path)) if path == self_path => {}
                     _ => return false,
@@ -161,9 +173,121 @@ fn is_pure_self_recursive(
     true
 }
 
-pub fn push_stack(stack_frame: &mut StackFrame, index: usize) {
+pub fn make_closure(
+    stack_frame: &mut StackFrame,
+    index: usize,
+    len: usize,
+) -> Result<(), RuntimeError> {
+    let Some(Value::Ref(path)) = stack_frame.get_const(index) else {
+        return Err(RuntimeError::VMError);
+    };
+    let path = path.clone();
+    let mut captures: Vec<Value> = (0..len).map(|_| stack_frame.pop_op_stack()).collect();
+    captures.reverse();
+    stack_frame.push_op_stack(Value::Closure(Arc::new(Closure { path, captures })));
+    stack_frame.next_pc();
+    Ok(())
+}
+
+pub fn new_object(
+    stack_frame: &mut StackFrame,
+    index: usize,
+    len: usize,
+) -> Result<(), RuntimeError> {
+    let Some(Value::Ref(class)) = stack_frame.get_const(index) else {
+        return Err(RuntimeError::VMError);
+    };
+    let class = class.clone();
+    let Value::Map(fields) = load_map(stack_frame, len)? else {
+        return Err(RuntimeError::VMError);
+    };
+    let object = Object {
+        class,
+        fields: Mutex::new(fields),
+    };
+    stack_frame.push_op_stack(Value::Object(Arc::new(object)));
+    stack_frame.next_pc();
+    Ok(())
+}
+
+fn member_name(stack_frame: &StackFrame, index: usize) -> Result<SmolStr, RuntimeError> {
+    match stack_frame.get_const(index) {
+        Some(Value::String(name)) => Ok(name.clone()),
+        _ => Err(RuntimeError::VMError),
+    }
+}
+
+fn no_such_field(object: &Object, name: &str) -> RuntimeError {
+    RuntimeError::TypeException(format_smolstr!(
+        "{} has no field '{name}'",
+        object.class_name()
+    ))
+}
+
+pub fn get_field(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
+    let name = member_name(stack_frame, index)?;
+    let target = stack_frame.pop_op_stack();
+    let Value::Object(object) = target else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "cannot get field '{name}' of non-object value: {target}"
+        )));
+    };
+    let value = object.fields.lock().unwrap().get(&name).cloned();
+    let value = value.ok_or_else(|| no_such_field(&object, &name))?;
+    stack_frame.push_op_stack(value);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+// 只能写入类中声明过的字段
+pub fn set_field(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
+    let name = member_name(stack_frame, index)?;
+    let target = stack_frame.pop_op_stack();
+    let value = stack_frame.pop_op_stack();
+    let Value::Object(object) = target else {
+        return Err(RuntimeError::TypeException(format_smolstr!(
+            "cannot set field '{name}' of non-object value: {target}"
+        )));
+    };
+    let mut fields = object.fields.lock().unwrap();
+    let Some(slot) = fields.get_mut(&name) else {
+        drop(fields);
+        return Err(no_such_field(&object, &name));
+    };
+    *slot = value;
+    drop(fields);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+// 对象的方法编译为 "类名.方法名" 函数, 接收者作为闭包的捕获值传入
+// 引用值 (导入的库) 则与 GetRef 一样拼接为函数路径
+pub fn get_method(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
+    let name = member_name(stack_frame, index)?;
+    let target = stack_frame.pop_op_stack();
+    let method = match target {
+        Value::Object(ref object) => {
+            let path = format_smolstr!("{}.{name}", object.class);
+            Value::Closure(Arc::new(Closure {
+                path,
+                captures: vec![target],
+            }))
+        }
+        Value::Ref(path) => Value::Ref(format_smolstr!("{path}/{name}")),
+        _ => {
+            return Err(RuntimeError::TypeException(format_smolstr!(
+                "cannot call method '{name}' on value: {target}"
+            )));
+        }
+    };
+    stack_frame.push_op_stack(method);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+pub fn push_stack(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
     let Some(value_ref) = stack_frame.get_const(index) else {
-        unimplemented!()
+        return Err(RuntimeError::VMError);
     };
     let final_value = if let Value::Ref(path) = value_ref {
         if path.as_str() == "this" {
@@ -177,6 +301,7 @@ pub fn push_stack(stack_frame: &mut StackFrame, index: usize) {
     };
     stack_frame.push_op_stack(final_value);
     stack_frame.next_pc();
+    Ok(())
 }
 
 pub fn load_local(stack_frame: &mut StackFrame, index: usize) {
@@ -219,16 +344,36 @@ pub const fn jump(stack_frame: &mut StackFrame, jpc: usize) {
     stack_frame.set_next_pc(jpc);
 }
 
+// 实参个数须与形参个数一致, 闭包的捕获值 (包括方法的接收者) 不计入实参
+fn check_arity(func: &MethodInfo, argc: usize, captured: usize) -> Result<(), RuntimeError> {
+    let expected = func.args.saturating_sub(captured);
+    if argc == expected && captured <= func.args {
+        Ok(())
+    } else {
+        Err(RuntimeError::TypeException(format_smolstr!(
+            "{} expects {expected} argument(s), found {argc}",
+            func.name
+        )))
+    }
+}
+
 pub fn call_func<'a>(
     stack_frame: &mut StackFrame,
     units: &'a [MetadataUnit],
     call_cache: &CallCache,
     sync_table: &SyncTable,
+    argc: usize,
 ) -> Result<RunState<'a>, RuntimeError> {
     let result = stack_frame.pop_op_stack();
 
-    let Value::Ref(path) = result else {
-        return Err(RuntimeError::VMError);
+    let (path, captures) = match result {
+        Value::Ref(path) => (path, None),
+        Value::Closure(closure) => (closure.path.clone(), Some(closure)),
+        _ => {
+            return Err(RuntimeError::TypeException(format_smolstr!(
+                "cannot call non-function value: {result}"
+            )));
+        }
     };
 
     let Some((unit_index, func_index)) = call_cache.resolve(&path) else {
@@ -237,7 +382,20 @@ pub fn call_func<'a>(
 
     let unit = &units[unit_index];
     let func = &unit.methods[func_index];
-    let codes = func.get_codes();
+    let captured = captures
+        .as_ref()
+        .map_or(0, |closure| closure.captures.len());
+    check_arity(func, argc, captured)?;
+    if let Some(closure) = captures {
+        // 捕获值作为隐藏函数的前几个参数, 需要插入到调用者传入的参数之前
+        let mut args: Vec<Value> = (0..argc).map(|_| stack_frame.pop_op_stack()).collect();
+        for value in &closure.captures {
+            stack_frame.push_op_stack(value.clone());
+        }
+        while let Some(value) = args.pop() {
+            stack_frame.push_op_stack(value);
+        }
+    }
     let sync_locked = sync_table.lock_if_sync(unit_index, func_index);
     if call_cache.is_memoizable(unit_index, func_index)
         && let Some(args) = stack_frame.peek_args(func.args)
@@ -255,16 +413,7 @@ pub fn call_func<'a>(
             return Ok(RunState::Continue);
         }
         let native = if func.is_native { Some(path) } else { None };
-        let mut frame = StackFrame::new(
-            unit_index,
-            func.locals,
-            codes,
-            unit.constant_table,
-            func.name.as_str(),
-            func.r_name.as_str(),
-            native,
-            func.args,
-        );
+        let mut frame = StackFrame::new(unit_index, func, unit.constant_table, native);
         frame.set_memo((unit_index, func_index), key);
         if sync_locked {
             frame.set_sync_lock((unit_index, func_index));
@@ -274,16 +423,7 @@ pub fn call_func<'a>(
     }
     stack_frame.next_pc();
     let native = if func.is_native { Some(path) } else { None };
-    let mut frame = StackFrame::new(
-        unit_index,
-        func.locals,
-        codes,
-        unit.constant_table,
-        func.name.as_str(),
-        func.r_name.as_str(),
-        native,
-        func.args,
-    );
+    let mut frame = StackFrame::new(unit_index, func, unit.constant_table, native);
     if sync_locked {
         frame.set_sync_lock((unit_index, func_index));
     }
@@ -296,6 +436,7 @@ pub fn call_const<'a>(
     call_cache: &CallCache,
     sync_table: &SyncTable,
     const_index: usize,
+    argc: usize,
 ) -> Result<RunState<'a>, RuntimeError> {
     let current_unit = stack_frame.get_unit_index();
     let mut path_ref: Option<SmolStr> = None;
@@ -317,7 +458,7 @@ pub fn call_const<'a>(
 
     let unit = &units[unit_index];
     let func = &unit.methods[func_index];
-    let codes = func.get_codes();
+    check_arity(func, argc, 0)?;
     let sync_locked = sync_table.lock_if_sync(unit_index, func_index);
 
     if call_cache.is_memoizable(unit_index, func_index)
@@ -348,16 +489,7 @@ pub fn call_const<'a>(
         } else {
             None
         };
-        let mut frame = StackFrame::new(
-            unit_index,
-            func.locals,
-            codes,
-            unit.constant_table,
-            func.name.as_str(),
-            func.r_name.as_str(),
-            native,
-            func.args,
-        );
+        let mut frame = StackFrame::new(unit_index, func, unit.constant_table, native);
         frame.set_memo((unit_index, func_index), key);
         if sync_locked {
             frame.set_sync_lock((unit_index, func_index));
@@ -380,101 +512,176 @@ pub fn call_const<'a>(
     } else {
         None
     };
-    let mut frame = StackFrame::new(
-        unit_index,
-        func.locals,
-        codes,
-        unit.constant_table,
-        func.name.as_str(),
-        func.r_name.as_str(),
-        native,
-        func.args,
-    );
+    let mut frame = StackFrame::new(unit_index, func, unit.constant_table, native);
     if sync_locked {
         frame.set_sync_lock((unit_index, func_index));
     }
     Ok(RunState::CallRequest(frame))
 }
 
-pub fn load_array_local(stack_frame: &mut StackFrame, len: usize, index: usize) {
+fn load_array(stack_frame: &mut StackFrame, len: usize) -> Result<Value, RuntimeError> {
     let mut elements: Vec<Value> = Vec::new();
     for _ in 0..len {
         elements.push(stack_frame.pop_op_stack());
     }
     let reversed_values: Vec<Value> = elements.into_iter().rev().collect();
-    let result = Value::Array(len, reversed_values);
+    context::charge_length(reversed_values.len())?;
+    Ok(Value::Array(reversed_values))
+}
+
+pub fn load_array_local(
+    stack_frame: &mut StackFrame,
+    len: usize,
+    index: usize,
+) -> Result<(), RuntimeError> {
+    let result = load_array(stack_frame, len)?;
+    stack_frame.set_local(index, result);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+pub fn new_array(stack_frame: &mut StackFrame, len: usize) -> Result<(), RuntimeError> {
+    let result = load_array(stack_frame, len)?;
+    stack_frame.push_op_stack(result);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+pub fn load_map(stack_frame: &mut StackFrame, len: usize) -> Result<Value, RuntimeError> {
+    context::charge_length(len)?;
+    let mut entries: Vec<(Value, Value)> = Vec::new();
+    for _ in 0..len {
+        let value = stack_frame.pop_op_stack();
+        let key = stack_frame.pop_op_stack();
+        entries.push((key, value));
+    }
+    let mut map = LinkedHashMap::new();
+    for (key, value) in entries.into_iter().rev() {
+        map.insert(map_key(key)?, value);
+    }
+    Ok(Value::Map(map))
+}
+
+pub fn load_map_local(
+    stack_frame: &mut StackFrame,
+    len: usize,
+    index: usize,
+) -> Result<(), RuntimeError> {
+    let result = load_map(stack_frame, len)?;
     stack_frame.set_local(index, result);
     stack_frame.next_pc();
+    Ok(())
+}
+
+pub fn new_map(stack_frame: &mut StackFrame, len: usize) -> Result<(), RuntimeError> {
+    let result = load_map(stack_frame, len)?;
+    stack_frame.push_op_stack(result);
+    stack_frame.next_pc();
+    Ok(())
+}
+
+fn map_key(key: Value) -> Result<SmolStr, RuntimeError> {
+    if let Value::String(key) = key {
+        Ok(key)
+    } else {
+        Err(RuntimeError::TypeException(format_smolstr!(
+            "map key must be a string, found: {key}"
+        )))
+    }
+}
+
+// 数组按整数索引写入, 字典按字符串键写入 (键不存在时插入)
+pub fn set_index_value(target: &mut Value, index: Value, value: Value) -> Result<(), RuntimeError> {
+    match (target, index) {
+        (Value::Array(elements), Value::Int(a_index)) => {
+            let usize_index = check_index(a_index, elements.len())?;
+            elements[usize_index] = value;
+            Ok(())
+        }
+        (Value::Array(elements), Value::BigInt(a_index)) => {
+            Err(RuntimeError::IndexOutOfBounds(format_smolstr!(
+                "Index {a_index} out of bounds for length {}",
+                elements.len()
+            )))
+        }
+        (Value::Map(map), key) => {
+            // 覆盖已有键时保持其原有的插入顺序
+            let key = map_key(key)?;
+            if let Some(slot) = map.get_mut(&key) {
+                *slot = value;
+            } else {
+                context::charge_length(1)?;
+                map.insert(key, value);
+            }
+            Ok(())
+        }
+        _ => Err(RuntimeError::TypeException(
+            "cannot set unknown type for array.".to_smolstr(),
+        )),
+    }
+}
+
+pub fn get_index_value(target: &Value, index: &Value) -> Result<Value, RuntimeError> {
+    match (target, index) {
+        (Value::Array(element), Value::Int(a_index)) => {
+            let usize_index = check_index(*a_index, element.len())?;
+            Ok(element[usize_index].clone())
+        }
+        (Value::Array(element), Value::BigInt(a_index)) => Err(RuntimeError::IndexOutOfBounds(
+            format_smolstr!("Index {a_index} out of bounds for length {}", element.len()),
+        )),
+        (Value::Map(map), Value::String(key)) => map.get(key).cloned().ok_or_else(|| {
+            RuntimeError::IndexOutOfBounds(format_smolstr!("Key \"{key}\" not found in map"))
+        }),
+        _ => Err(RuntimeError::TypeException(
+            "cannot get_index unknown type.".to_smolstr(),
+        )),
+    }
 }
 
 pub fn set_index_array(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
     let arr_index = stack_frame.pop_op_stack();
     let value = stack_frame.pop_op_stack();
     let result = stack_frame.get_local_mut(index);
+    set_index_value(result, arr_index, value)?;
+    stack_frame.next_pc();
+    Ok(())
+}
 
-    if let Value::Array(len, elements) = result
-        && let Value::Int(a_index) = arr_index
-    {
-        let usize_index = usize::try_from(a_index).unwrap();
-        if usize_index >= *len {
-            return Err(RuntimeError::IndexOutOfBounds(
-                format_args!("Index {a_index} out of bounds for length {len}").to_smolstr(),
-            ));
-        }
-        elements[usize_index] = value;
-        stack_frame.next_pc();
-        Ok(())
-    } else {
-        Err(RuntimeError::TypeException(
-            "cannot set unknown type for array.".to_smolstr(),
-        ))
-    }
+// 弹出 array_call 指令中数组之外的实参, 按源码顺序排列
+pub fn array_call_args(stack_frame: &mut StackFrame, func: usize) -> Vec<Value> {
+    let (_, argc) = ARRAY_FUNCS[func];
+    let mut args: Vec<Value> = (0..argc).map(|_| stack_frame.pop_op_stack()).collect();
+    args.reverse();
+    args
+}
+
+pub fn array_call_local(
+    stack_frame: &mut StackFrame,
+    index: usize,
+    func: usize,
+) -> Result<(), RuntimeError> {
+    let args = array_call_args(stack_frame, func);
+    let result = call_array_func(func, stack_frame.get_local_mut(index), &args)?;
+    stack_frame.push_op_stack(result);
+    stack_frame.next_pc();
+    Ok(())
 }
 
 pub fn get_index_array(stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
     let index = stack_frame.pop_op_stack();
     let array = stack_frame.pop_op_stack();
-    if let Value::Int(index) = index
-        && let Value::Array { 0: len, 1: element } = array
-    {
-        if usize::try_from(index).unwrap() >= len {
-            return Err(RuntimeError::IndexOutOfBounds(
-                format_args!("Index {index} out of bounds for length {len}").to_smolstr(),
-            ));
-        }
-        let result = element
-            .get(usize::try_from(index).unwrap())
-            .unwrap()
-            .clone();
-        stack_frame.push_op_stack(result);
-        stack_frame.next_pc();
-        Ok(())
-    } else {
-        Err(RuntimeError::TypeException(
-            "cannot get_index unknown type.".to_smolstr(),
-        ))
-    }
+    let result = get_index_value(&array, &index)?;
+    stack_frame.push_op_stack(result);
+    stack_frame.next_pc();
+    Ok(())
 }
 
 pub fn get_index_local(stack_frame: &mut StackFrame, index: usize) -> Result<(), RuntimeError> {
     let arr_index = stack_frame.pop_op_stack();
     let array = stack_frame.get_local(index);
-    if let Value::Int(arr_index) = arr_index
-        && let Value::Array { 0: len, 1: element } = array
-    {
-        let usize_index = usize::try_from(arr_index).unwrap();
-        if usize_index >= *len {
-            return Err(RuntimeError::IndexOutOfBounds(
-                format_args!("Index {arr_index} out of bounds for length {len}").to_smolstr(),
-            ));
-        }
-        let result = element.get(usize_index).unwrap().clone();
-        stack_frame.push_op_stack(result);
-        stack_frame.next_pc();
-        Ok(())
-    } else {
-        Err(RuntimeError::TypeException(
-            "cannot get_index unknown type.".to_smolstr(),
-        ))
-    }
+    let result = get_index_value(array, &arr_index)?;
+    stack_frame.push_op_stack(result);
+    stack_frame.next_pc();
+    Ok(())
 }
@@ -0,0 +1,186 @@
+/* 嵌入接口的 C 测试程序, 由 tests/test_c_api.rs 编译并运行 */
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "openex.h"
+
+static const char *HOST = "function native twice(n);\n"
+                          "function native nan();";
+
+static const char *MAIN =
+    "import host;\n"
+    "import string;\n"
+    "var counter = 1;\n"
+    "var names = [\"a\", \"b\"];\n"
+    "function bump(n) { counter = counter + host.twice(n); return counter; }\n"
+    "function count(ary) {\n"
+    "    var total = 0;\n"
+    "    for (var i = 0; i < ary.length(); i++) { total = total + ary[i]; }\n"
+    "    return total;\n"
+    "}\n"
+    "function fail { throw \"boom\"; }\n"
+    "function spin { while {} }\n"
+    "function nul { return string.chr(0); }\n"
+    "function host_nan { return host.nan(); }\n";
+
+static OpenExStatus twice(const CValue *args, size_t arg_count, CValue *out_result,
+                          void *user_data) {
+    (void) user_data;
+    if (arg_count != 1 || args[0].tag != Int) {
+        return RuntimeError;
+    }
+    out_result->tag = Int;
+    out_result->data.i = args[0].data.i * 2;
+    return Success;
+}
+
+static OpenExStatus not_a_number(const CValue *args, size_t arg_count, CValue *out_result,
+                                 void *user_data) {
+    (void) args;
+    (void) arg_count;
+    (void) user_data;
+    out_result->tag = Float;
+    out_result->data.f = NAN;
+    return Success;
+}
+
+static void print_value(const CValue *value) {
+    switch (value->tag) {
+    case Int:
+        printf("%lld", (long long) value->data.i);
+        break;
+    case String:
+        printf("\"%s\"", value->data.s);
+        break;
+    case Null:
+        printf("null");
+        break;
+    case Array:
+        printf("[");
+        for (size_t i = 0; i < value->data.a.len; i++) {
+            printf(i == 0 ? "" : ", ");
+            print_value(&value->data.a.items[i]);
+        }
+        printf("]");
+        break;
+    default:
+        printf("<tag %d>", value->tag);
+    }
+}
+
+static void check(OpenExStatus status, const char *step) {
+    if (status != Success) {
+        printf("%s failed (%d)\n", step, status);
+    }
+}
+
+static const CValue *map_get(const CValue *map, const char *key) {
+    for (size_t i = 0; i < map->data.m.len; i++) {
+        if (strcmp(map->data.m.keys[i], key) == 0) {
+            return &map->data.m.values[i];
+        }
+    }
+    return NULL;
+}
+
+static void print_diagnostics(OpenEX *handle) {
+    CValue diagnostics;
+    check(openex_get_diagnostics(handle, &diagnostics), "diagnostics");
+    for (size_t i = 0; i < diagnostics.data.a.len; i++) {
+        const CValue *diagnostic = &diagnostics.data.a.items[i];
+        printf("diagnostic: %s %s:%lld:%lld %s\n", map_get(diagnostic, "severity")->data.s,
+               map_get(diagnostic, "file")->data.s,
+               (long long) map_get(diagnostic, "line")->data.i,
+               (long long) map_get(diagnostic, "column")->data.i,
+               map_get(diagnostic, "message")->data.s);
+    }
+    openex_free_c_value(&diagnostics);
+}
+
+static void print_global(OpenEX *handle, const char *name) {
+    CValue value;
+    if (openex_get_global(handle, "main", name, &value) != Success) {
+        printf("get %s: %s\n", name, openex_last_error(handle));
+        return;
+    }
+    printf("%s = ", name);
+    print_value(&value);
+    printf("\n");
+    openex_free_c_value(&value);
+}
+
+int main(void) {
+    OpenEX *broken = openex_init(NULL, 2);
+    openex_add_file(broken, "var x = ;", "broken.exf");
+    if (openex_compile(broken) == ParseError) {
+        printf("compile error: %s\n", openex_last_error(broken));
+    }
+    print_diagnostics(broken);
+    openex_free(broken);
+
+    OpenEX *handle = openex_init(NULL, 2);
+    check(openex_register_native(handle, "host", "twice", 1, twice, NULL), "register");
+    check(openex_register_native(handle, "host", "nan", 0, not_a_number, NULL), "register");
+    check(openex_add_file(handle, MAIN, "main.exf"), "add main");
+    check(openex_add_file(handle, HOST, "host.exf"), "add host");
+    check(openex_compile(handle), "compile");
+    check(openex_initialize_executor(handle), "initialize");
+    print_global(handle, "counter");
+    check(openex_run(handle), "run");
+    print_global(handle, "counter");
+    print_global(handle, "names");
+    print_global(handle, "missing");
+
+    CValue functions;
+    check(openex_list_functions(handle, "main", &functions), "list");
+    printf("functions = ");
+    print_value(&functions);
+    printf("\n");
+    openex_free_c_value(&functions);
+
+    CValue arg = {.tag = Int, .data = {.i = 20}};
+    CValue result;
+    check(openex_call_function(handle, "main", "bump", &arg, 1, &result), "bump");
+    printf("bump(20) = ");
+    print_value(&result);
+    printf("\n");
+    openex_free_c_value(&result);
+
+    CValue counter = {.tag = Int, .data = {.i = 100}};
+    check(openex_set_global(handle, "main", "counter", &counter), "set counter");
+    print_global(handle, "counter");
+
+    CValue items[] = {{.tag = Int, .data = {.i = 1}},
+                      {.tag = Int, .data = {.i = 2}},
+                      {.tag = Int, .data = {.i = 39}}};
+    CValue array = {.tag = Array, .data = {.a = {.len = 3, .items = items}}};
+    check(openex_call_function(handle, "main", "count", &array, 1, &result), "count");
+    printf("count([1, 2, 39]) = ");
+    print_value(&result);
+    printf("\n");
+    openex_free_c_value(&result);
+
+    if (openex_call_function(handle, "main", "fail", NULL, 0, &result) == RuntimeError) {
+        printf("runtime error: %s\n", openex_last_error(handle));
+    }
+    if (openex_call_function(handle, "main", "nope", NULL, 0, &result) == RuntimeError) {
+        printf("call error: %s\n", openex_last_error(handle));
+    }
+    if (openex_call_function(handle, "main", "nul", NULL, 0, &result) == FfiError) {
+        printf("nul error: %s\n", openex_last_error(handle));
+    }
+    if (openex_call_function(handle, "main", "host_nan", NULL, 0, &result) == RuntimeError) {
+        printf("nan error: %s\n", openex_last_error(handle));
+    }
+    CValue nan_value = {.tag = Float, .data = {.f = NAN}};
+    if (openex_set_global(handle, "main", "counter", &nan_value) == FfiError) {
+        printf("set nan: %s\n", openex_last_error(handle));
+    }
+    check(openex_set_limits(handle, 10000, 0, 0, 0), "limits");
+    if (openex_call_function(handle, "main", "spin", NULL, 0, &result) == RuntimeError) {
+        printf("limit error: %s\n", openex_last_error(handle));
+    }
+    openex_free(handle);
+    return 0;
+}
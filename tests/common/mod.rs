@@ -0,0 +1,37 @@
+// 集成测试共用的临时文件与子进程辅助函数, 各测试只用到其中的一部分
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 系统临时目录下本测试进程专用的路径, 避免并行运行的测试进程互相覆盖
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("openex_{}_{name}", std::process::id()))
+}
+
+/// 写入文件, 父目录不存在时一并创建
+pub fn write(path: &Path, data: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, data).unwrap();
+}
+
+/// 将源码写入临时的 `.exf` 文件并返回其路径
+pub fn write_source(name: &str, source: &str) -> PathBuf {
+    let path = temp_path(&format!("{name}.exf"));
+    write(&path, source);
+    path
+}
+
+/// 以给定参数运行 `openex` 的命令, 调用者可以继续设置环境变量
+pub fn openex_command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_openex"));
+    command.args(args);
+    command
+}
+
+pub fn openex(args: &[&str]) -> Output {
+    openex_command(args)
+        .output()
+        .expect("failed to spawn process")
+}
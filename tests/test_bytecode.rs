@@ -0,0 +1,128 @@
+mod common;
+
+use common::{openex, temp_path, write_source};
+use expect_test::{Expect, expect};
+use std::fs;
+use std::path::PathBuf;
+
+fn build_source(name: &str, source: &str) -> PathBuf {
+    let source_path = write_source(name, source);
+    let output_path = temp_path(&format!("{name}.exb"));
+    let output = openex(&[
+        "build",
+        source_path.to_str().unwrap(),
+        "-o",
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    fs::remove_file(source_path).unwrap();
+    output_path
+}
+
+#[track_caller]
+fn check(name: &str, source: &str, expect: Expect) {
+    let path = build_source(name, source);
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    expect.assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_run_bytecode() {
+    check(
+        "run",
+        "import system;\
+        var pi = 3.14;\
+        var ary = [3, \"two\", 1.5];\
+        function fib(n) {\
+            if (n < 2) { return n; }\
+            return this.fib(n - 1) + this.fib(n - 2);\
+        }\
+        system.println(this.fib(20));\
+        system.println(pi * 2);\
+        system.println(ary);",
+        expect![[r#"
+            6765
+            6.28
+            [3, two, 1.5, ]
+        "#]],
+    );
+}
+
+#[test]
+fn test_big_integer_constant() {
+    check(
+        "big",
+        "import system;\
+        var big = 170141183460469231731687303715884105727;\
+        system.println(big);\
+        system.println(big - 170141183460469231731687303715884105726);",
+        expect![[r#"
+            170141183460469231731687303715884105727
+            1
+        "#]],
+    );
+}
+
+#[test]
+fn test_reject_incompatible_version() {
+    let path = build_source("version", "import system; system.println(1);");
+    let mut data = fs::read(&path).unwrap();
+    // 头部: magic(4) + 格式版本(2) + 版本字符串长度(4) + 版本字符串
+    let version = env!("CARGO_PKG_VERSION").as_bytes();
+    assert_eq!(&data[10..10 + version.len()], version);
+    data[10..10 + version.len()].fill(b'9');
+    fs::write(&path, data).unwrap();
+
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("bytecode was built by OpenEX v9"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_reject_truncated_file() {
+    let path = build_source("truncated", "import system; system.println(1);");
+    let data = fs::read(&path).unwrap();
+    fs::write(&path, &data[..data.len() / 2]).unwrap();
+
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unexpected end of bytecode file"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_reject_corrupted_operand() {
+    let path = build_source("operand", "import system; system.println(1);");
+    let mut data = fs::read(&path).unwrap();
+    // 入口单元的全局代码以 `push #0` 开头, 紧随其后的是 `call_const` 指令 (标签 16)
+    let mut push = vec![0];
+    push.extend_from_slice(&0u64.to_le_bytes());
+    push.push(16);
+    let offsets: Vec<usize> = data
+        .windows(push.len())
+        .enumerate()
+        .filter(|(_, window)| *window == push.as_slice())
+        .map(|(offset, _)| offset)
+        .collect();
+    assert_eq!(offsets.len(), 1);
+    data[offsets[0] + 1..offsets[0] + 9].copy_from_slice(&1000u64.to_le_bytes());
+    fs::write(&path, data).unwrap();
+
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("illegal operand data"), "{stderr}");
+}
@@ -0,0 +1,59 @@
+#![cfg(target_os = "linux")]
+
+use expect_test::expect;
+use std::path::Path;
+use std::process::Command;
+
+// 使用系统的 C 编译器编译 tests/c/embed.c, 并链接 target 目录中的 libopenex.so
+#[test]
+fn test_c_embedding() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let target = Path::new(env!("CARGO_BIN_EXE_openex")).parent().unwrap();
+    let program = target.join(format!("embed_c_{}", std::process::id()));
+    let compiler = std::env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let output = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .arg(format!("{root}/tests/c/embed.c"))
+        .arg(format!("-I{root}/include"))
+        .arg(format!("-L{}", target.display()))
+        .arg(format!("-Wl,-rpath,{}", target.display()))
+        .arg("-lopenex")
+        .output()
+        .unwrap_or_else(|e| panic!("C compiler `{compiler}` is not available: {e}"));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // 标准库按相对路径 lib 查找
+    let output = Command::new(&program).current_dir(root).output().unwrap();
+    std::fs::remove_file(program).unwrap();
+    assert!(output.status.success());
+    expect![[r#"
+        compile error: SyntaxError(broken.exf-line: 1 column: 9): statement is incomplete.
+        1    | var x = ;
+                       ^
+        diagnostic: error broken.exf:1:9 statement is incomplete.
+        counter = null
+        counter = 1
+        names = ["a", "b"]
+        get missing: no such global variable: main/missing
+        functions = ["bump", "count", "fail", "spin", "nul", "host_nan"]
+        bump(20) = 41
+        counter = 100
+        count([1, 2, 39]) = 42
+        runtime error: RuntimeError(main.exf-line: 11 column: 17): Exception("Exception", "boom")
+        	 at <fail> (main.exf:11:17)
+        call error: no such function: main/nope
+        nul error: cannot pass the result to C: string contains a NUL character
+        nan error: RuntimeError(main.exf-line: 14 column: 32): Exception("NativeException", "host/nan: float is NaN or infinite")
+        	 at <host/nan> (native)
+        	 at <host_nan> (main.exf:14:32)
+        set nan: cannot set main/counter: float is NaN or infinite
+        limit error: RuntimeError(main.exf-line: 12 column: 23): LimitExceeded("instruction count exceeds the limit of 10000")
+        	 at <spin> (main.exf:12:23)
+    "#]]
+    .assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
@@ -2,9 +2,26 @@ use expect_test::{Expect, expect};
 use std::io::Write;
 use std::process::{Command, Stdio};
 
-fn run_source(buf: &[u8]) -> String {
+// 每个示例在各优化等级与单项优化关闭时都应得到相同的输出
+const OPT_MATRIX: &[&[&str]] = &[
+    &["-O0"],
+    &["-O1"],
+    &["-O2"],
+    &["-Z", "no-const-fold"],
+    &["-Z", "no-dead-locals"],
+    &["-Z", "no-const-prop"],
+    &["-Z", "no-arith-peephole"],
+    &["-Z", "no-call-memo"],
+];
+
+fn run_console(buf: &[u8]) -> (String, String) {
+    run_console_with(&[], buf)
+}
+
+fn run_console_with(args: &[&str], buf: &[u8]) -> (String, String) {
     let mut child = Command::new(env!("CARGO_BIN_EXE_openex"))
         .arg("--cli")
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -16,15 +33,47 @@ fn run_source(buf: &[u8]) -> String {
     }
     let output = child.wait_with_output().unwrap();
 
-    let stdout = String::from_utf8(output.stdout).expect("invalid utf-8 in stdout");
     assert!(output.status.success());
-    assert!(output.stderr.is_empty());
+    let stdout = String::from_utf8(output.stdout).expect("invalid utf-8 in stdout");
+    let stderr = String::from_utf8(output.stderr).expect("invalid utf-8 in stderr");
+    (stdout, stderr)
+}
+
+fn run_source(args: &[&str], buf: &[u8]) -> String {
+    let (stdout, stderr) = run_console_with(args, buf);
+    assert!(stderr.is_empty());
     stdout
 }
 
 #[track_caller]
 fn check(buf: &[u8], expect: Expect) {
-    expect.assert_eq(run_source(buf).as_str());
+    let stdout = run_source(&[], buf);
+    for args in OPT_MATRIX {
+        assert_eq!(
+            run_source(args, buf),
+            stdout,
+            "output differs with {args:?}"
+        );
+    }
+    expect.assert_eq(stdout.as_str());
+}
+
+// 递归次数过多的示例在关闭记忆调用时耗时过长, 仅在保留记忆调用的前提下检查其他优化
+#[track_caller]
+fn check_memoized(buf: &[u8], expect: Expect) {
+    let stdout = run_source(&[], buf);
+    for args in OPT_MATRIX
+        .iter()
+        .filter(|args| !args.contains(&"no-call-memo"))
+    {
+        let args = [args, ["-Z", "call-memo"].as_slice()].concat();
+        assert_eq!(
+            run_source(&args, buf),
+            stdout,
+            "output differs with {args:?}"
+        );
+    }
+    expect.assert_eq(stdout.as_str());
 }
 
 #[test]
@@ -37,7 +86,7 @@ pub fn test_var_define() {
     system.println(a);\
     system.println(b);",
         expect![[r#"
-        > 3.1415926535
+        3.1415926535
         12
     "#]],
     );
@@ -63,7 +112,7 @@ pub fn test_collatz() {
     var result = this.test_collatz(7);\
     system.println(result);",
         expect![[r#"
-        > 16
+        16
     "#]],
     );
 }
@@ -90,7 +139,7 @@ pub fn test_count_primes() {
     var total = this.count_primes(100);\
     system.println(total);",
         expect![[r#"
-        > 25
+        25
     "#]],
     );
 }
@@ -98,7 +147,7 @@ pub fn test_count_primes() {
 /// 递归式斐波那契
 #[test]
 pub fn test_fib_1() {
-    check(
+    check_memoized(
         b"import system;\
     function fib(n) {\
     if (n < 2) {\
@@ -108,7 +157,7 @@ pub fn test_fib_1() {
     }}\
     system.println(this.fib(35));",
         expect![[r#"
-        > 9227465
+        9227465
     "#]],
     );
     // fib(30) == 832040
@@ -116,6 +165,24 @@ pub fn test_fib_1() {
     // fib(40) == 102334155
 }
 
+/// 递归式斐波那契 (规模较小, 用于检查关闭记忆调用后的结果)
+#[test]
+pub fn test_fib_no_memo() {
+    check(
+        b"import system;\
+    function fib(n) {\
+    if (n < 2) {\
+    return n;\
+    }\
+    return this.fib(n - 1) + this.fib(n - 2);\
+    }\
+    system.println(this.fib(20));",
+        expect![[r#"
+        6765
+    "#]],
+    );
+}
+
 /// 循环式斐波那契
 #[test]
 pub fn test_fib_2() {
@@ -138,7 +205,7 @@ pub fn test_fib_2() {
     }\
     system.println(this.fib(40));",
         expect![[r#"
-        > 102334155
+        102334155
     "#]],
     )
 }
@@ -170,7 +237,859 @@ pub fn test_array() {
     }\
     system.println(bubble_sort(ary));",
         expect![[r#"
-        > [11, 12, 22, 25, 34, 64, 90, ]
+        [11, 12, 22, 25, 34, 64, 90, ]
     "#]],
     )
 }
+
+/// 交互模式: 全局变量与函数在多次输入间保留, 表达式语句回显结果
+#[test]
+pub fn test_console_session() {
+    check(
+        b"import system;
+var a = 1 + 2;
+a * 10;
+function twice(x) {
+    return x * 2;
+}
+this.twice(a);
+a = a + 1;
+a;
+var s = \"multi
+line\";
+s;
+system.println(\"hi\");
+!true;
+",
+        expect![[r#"
+            30
+            6
+            4
+            multi
+            line
+            hi
+            false
+        "#]],
+    );
+}
+
+/// 交互模式: 编译错误不会终止会话, 也不会留下半完成的定义
+#[test]
+pub fn test_console_error_recovery() {
+    let (stdout, stderr) = run_console(
+        b"var a = 1;
+var b = 2 +* 3;
+a +;
+var b = a + 1;
+b;
+",
+    );
+    expect![[r#"
+        2
+    "#]]
+    .assert_eq(&stdout);
+    expect![[r#"
+        SyntaxError(<console>-line: 1 column: 12): illegal combination of expressions.
+        1    | var b = 2 +* 3;
+                          ^
+        SyntaxError(<console>-line: 1 column: 3): illegal combination of expressions.
+        1    | a +;
+                 ^
+    "#]]
+    .assert_eq(&stderr);
+}
+
+/// 运行时错误输出带有源码位置的调用栈
+#[test]
+pub fn test_runtime_stack_trace() {
+    let (stdout, stderr) = run_console(
+        b"var arr = [1, 2, 3];
+var idx = 5;
+function get {
+    return arr[idx];
+}
+get();
+idx = 1;
+get();
+",
+    );
+    expect![[r#"
+        2
+    "#]]
+    .assert_eq(&stdout);
+    expect![[r#"
+        RuntimeError(<console>-line: 2 column: 15): IndexOutOfBounds("Index 5 out of bounds for length 3")
+        	 at <get> (<console>:2:15)
+        	 at <<main_root>> (<console>:1:1)
+    "#]]
+    .assert_eq(&stderr);
+}
+
+#[test]
+pub fn test_loop_with_native_call() {
+    check(
+        b"import system;
+import type;
+var i = 0;
+while (i < 3) {
+    system.println(type.to_string(i) + \"!\");
+    i = i + 1;
+}
+system.println(\"end\");
+",
+        expect![[r#"
+            0!
+            1!
+            2!
+            end
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_try_catch() {
+    check(
+        b"import system;
+var arr = [1, 2, 3];
+var idx = 7;
+function get {
+    return arr[idx];
+}
+function risky(n) {
+    if (n > 2) {
+        throw \"too big\";
+    }
+    return n * 10;
+}
+function safe(n) {
+    try {
+        return risky(n);
+    } catch (e) {
+        system.println(e[0] + \": \" + e[1]);
+    }
+    return -1;
+}
+try {
+    get();
+    system.println(\"unreachable\");
+} catch (e) {
+    system.println(e[0]);
+}
+system.println(safe(1));
+system.println(safe(3));
+var i = 0;
+while (i < 5) {
+    try {
+        i = i + 1;
+        if (i == 3) {
+            break;
+        }
+        throw i;
+    } catch (e) {
+        system.println(e[1]);
+    }
+}
+try {
+    throw i;
+} catch (e) {
+    system.println(\"after loop \" + e[1]);
+}
+",
+        expect![[r#"
+            IndexOutOfBounds
+            10
+            Exception: too big
+            -1
+            1
+            2
+            after loop 3
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_map() {
+    check(
+        b"import system;
+import type;
+var m = {\"a\": 1, \"b\": \"two\", \"c\": 1 + 2};
+m[\"d\"] = 4.5;
+m[\"a\"] = 10;
+system.println(m);
+system.println(m[\"b\"]);
+system.println(type.has_key(m, \"c\"));
+m = type.remove(m, \"c\");
+system.println(type.has_key(m, \"c\"));
+system.println(type.check_type(m));
+function describe(name) {
+    var x = {\"name\": name};
+    x[\"size\"] = 3;
+    var keys = type.keys(x);
+    for (var i = 0; i < type._length(keys); i++) {
+        system.println(keys[i] + \"=\" + x[keys[i]]);
+    }
+    try {
+        return x[\"missing\"];
+    } catch (e) {
+        return e[0];
+    }
+}
+system.println(describe(\"box\"));
+",
+        expect![[r#"
+            {a: 10, b: two, c: 3, d: 4.5, }
+            two
+            true
+            false
+            map
+            name=box
+            size=3
+            IndexOutOfBounds
+        "#]],
+    );
+}
+
+/// 字典与数组字面量可以嵌套, 也可以直接作为表达式使用
+#[test]
+pub fn test_nested_literals() {
+    check(
+        b"import system;
+var m = {\"x\": {\"y\": 1}, \"b\": [1, 2], \"e\": {}};
+system.println(m);
+system.println(m[\"x\"][\"y\"] + m[\"b\"][1]);
+var a = [[1, 2], {\"k\": \"v\"}, []];
+system.println(a[1][\"k\"]);
+function first(v) {
+    return v[0];
+}
+system.println(first([3, 4]));
+m = {\"key\" + \"s\": [{\"n\": 5}]};
+system.println(m[\"keys\"][0][\"n\"]);
+",
+        expect![[r#"
+            {x: {y: 1, }, b: [1, 2, ], e: {}, }
+            3
+            v
+            3
+            5
+        "#]],
+    );
+}
+
+/// 字典的键推断为非字符串类型时在编译时报错
+#[test]
+pub fn test_map_key_type() {
+    let (stdout, stderr) = run_console(
+        b"var m = {1: 2};
+var n = {\"a\": {null: 1}};
+var key = \"k\";
+var ok = {key: 1};
+import system;
+system.println(ok);
+",
+    );
+    expect![[r#"
+        {k: 1, }
+    "#]]
+    .assert_eq(&stdout);
+    expect![[r#"
+        SyntaxError(<console>-line: 1 column: 10): mismatched types: expected 'string', found 'int'.
+        1    | var m = {1: 2};
+                        ^
+        SyntaxError(<console>-line: 1 column: 16): mismatched types: expected 'string', found 'null'.
+        1    | var n = {"a": {null: 1}};
+                              ^
+    "#]]
+    .assert_eq(&stderr);
+}
+
+#[test]
+pub fn test_growable_array() {
+    check(
+        b"import system;
+import type;
+var a = [];
+for (var i = 1; i < 5; i++) {
+    type.push(a, i);
+}
+system.println(type.pop(a));
+type.insert(a, 0, 0);
+system.println(type.remove_at(a, 1));
+system.println(a);
+function append(list) {
+    type.push(list, 9);
+    return list;
+}
+system.println(append(a));
+system.println(type.pop([7, 8]));
+system.println(a[1:]);
+system.println(a[:2]);
+system.println(type.concat(a[0:1], a[2:3]));
+system.println(type.index_of(a, 3));
+try {
+    a[5] = 1;
+} catch (e) {
+    system.println(e[1]);
+}
+try {
+    system.println(type.remove_at(a, -1));
+} catch (e) {
+    system.println(e[1]);
+}
+",
+        expect![[r#"
+            4
+            1
+            [0, 2, 3, ]
+            [0, 2, 3, 9, ]
+            8
+            [2, 3, ]
+            [0, 2, ]
+            [0, 3, ]
+            2
+            Index 5 out of bounds for length 3
+            Index -1 out of bounds for length 3
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_string_lib() {
+    check(
+        b"import system;
+import string;
+var s = string.trim(\"  H\xc3\xa9llo, \xe4\xb8\x96\xe7\x95\x8c!  \");
+system.println(string.substring(s, 7, 9));
+system.println(string.char_at(s, 1));
+system.println(string.find(s, \"\xe4\xb8\x96\"));
+system.println(string.join(string.split(\"a,b,c\", \",\"), \"-\"));
+system.println(string.upper(string.replace(s, \"llo\", \"y\")));
+system.println(string.ord(\"A\") + string.repeat(string.chr(66), 3));
+var args = [1, \"two\"];
+system.println(string.format(\"{} and {{{}}}\", args));
+try {
+    string.substring(s, 3, 99);
+} catch (e) {
+    system.println(e[1]);
+}
+",
+        expect![[r#"
+            世界
+            é
+            7
+            a-b-c
+            HÉY, 世界!
+            65BBB
+            1 and {two}
+            Index 99 out of bounds for length 10
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_fs_lib() {
+    check(
+        b"import system;
+import fs;
+var dir = \"target/test_fs_lib\";
+fs.mkdir(dir + \"/sub\");
+fs.write_text(dir + \"/a.txt\", \"hello\");
+fs.append_text(dir + \"/a.txt\", \" world\");
+system.println(fs.read_text(dir + \"/a.txt\"));
+system.println(fs.file_size(dir + \"/a.txt\"));
+system.println(fs.list_dir(dir));
+fs.remove(dir + \"/sub\");
+fs.remove(dir + \"/a.txt\");
+fs.remove(dir);
+system.println(fs.exists(dir));
+try {
+    fs.read_text(dir + \"/missing.txt\");
+} catch (e) {
+    system.println(e[0]);
+}
+",
+        expect![[r#"
+            hello world
+            11
+            [a.txt, sub, ]
+            false
+            IOException
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_closure() {
+    check(
+        b"import system;
+import type;
+function make_adder(n) {
+    return function (x) { return x + n; };
+}
+function outer {
+    var y = 1;
+    var get = function () { return y; };
+    y = 5;
+    return get() + y;
+}
+function apply(f, x) {
+    return f(x);
+}
+var double = function (x) { return x * 2; };
+var add3 = make_adder(3);
+var adders = [make_adder(1), make_adder(2)];
+system.println(double(21));
+system.println(apply(double, 5));
+system.println(add3(4));
+system.println(apply(make_adder(10), 1));
+system.println(adders[1](5));
+system.println(function (a, b) { return a - b; }(9, 4));
+system.println(outer());
+system.println(type.check_type(add3));
+var same = add3;
+system.println(same == add3);
+system.println(add3 != make_adder(3));
+",
+        expect![[r#"
+            42
+            10
+            7
+            11
+            7
+            5
+            6
+            function
+            true
+            true
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_closure_sort() {
+    check(
+        b"import system;
+function sort(arr, compare) {
+    var i = 1;
+    while (i < arr.length()) {
+        var j = i;
+        while (j > 0) {
+            if (compare(arr[j - 1], arr[j]) > 0) {
+                var t = arr[j];
+                arr[j] = arr[j - 1];
+                arr[j - 1] = t;
+            }
+            j--;
+        }
+        i++;
+    }
+    return arr;
+}
+function sort_by_distance(arr, center) {
+    return sort(arr, function (a, b) {
+        var da = a - center;
+        var db = b - center;
+        return da * da - db * db;
+    });
+}
+var nums = [5, 1, 4, 2, 3];
+system.println(sort(nums, function (a, b) { return b - a; }));
+system.println(sort_by_distance(nums, 4));
+",
+        expect![[r#"
+            [5, 4, 3, 2, 1, ]
+            [4, 5, 3, 2, 1, ]
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_closure_thread() {
+    check(
+        b"import system;
+function spawn(name, times) {
+    system.thread(function () {
+        var i = 0;
+        while (i < times) {
+            system.println(name + \" \" + i);
+            i++;
+        }
+    });
+}
+spawn(\"worker\", 3);
+",
+        expect![[r#"
+            worker 0
+            worker 1
+            worker 2
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_call_arity() {
+    check(
+        b"import system;
+function make_adder(n) {
+    return function (x) { return x + n; };
+}
+function call(f) {
+    try {
+        return f(1);
+    } catch (e) {
+        system.println(e[0] + \": \" + e[1]);
+    }
+    return null;
+}
+var pair = function (a, b) { return a + b; };
+var add = make_adder(2);
+var n = 3;
+system.println(call(pair));
+system.println(call(add));
+try {
+    add(1, 2);
+} catch (e) {
+    system.println(e[0] + \": \" + e[1]);
+}
+system.println(call(n));
+",
+        expect![[r#"
+            TypeException: <lambda3> expects 2 argument(s), found 1
+            null
+            3
+            TypeException: <lambda0> expects 1 argument(s), found 2
+            TypeException: cannot call non-function value: 3
+            null
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_class() {
+    check(
+        b"import system;
+import type;
+var scale = 10;
+class Point {
+    var x = 0;
+    var y = 0;
+    var tags = [1, 2];
+    function init(x, y) {
+        self.x = x;
+        self.y = y;
+    }
+    function sum {
+        return self.x + self.y;
+    }
+    function move(dx, dy) {
+        self.x += dx;
+        self.y = self.y + dy;
+        return self;
+    }
+    function scaled {
+        return self.sum() * scale;
+    }
+}
+class Counter {
+    var count = 0;
+    function inc {
+        self.count++;
+    }
+}
+function make(n) {
+    var pt = Point(n, n);
+    return pt.move(1, 1).sum();
+}
+var p = Point(1, 2);
+system.println(p.sum());
+p.move(3, 4);
+system.println(p.x + \", \" + p.y);
+system.println(p.scaled());
+system.println(p.tags[1]);
+system.println(type.check_type(p));
+var q = p;
+q.x = 100;
+system.println(p.x);
+var c = Counter();
+c.inc();
+c.inc();
+system.println(c.count);
+system.println(p == q);
+system.println(p != c);
+system.println(p == Point(100, 6));
+var pts = [0, 0];
+pts[1] = p;
+system.println(type.index_of(pts, q));
+system.println(make(5));
+system.println(this.Point(7, 8).y);
+try {
+    p.z = 1;
+} catch (e) {
+    system.println(e[1]);
+}
+",
+        expect![[r#"
+            3
+            Point {x: 4, y: 6, tags: [1, 2, ], }
+            4, 6
+            100
+            2
+            Point
+            100
+            2
+            true
+            true
+            false
+            1
+            12
+            8
+            Point has no field 'z'
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_method_arity() {
+    check(
+        b"import system;
+class Pair {
+    var first;
+    var second;
+    function init(a, b) {
+        self.first = a;
+        self.second = b;
+    }
+    function pick(a, b) {
+        return b;
+    }
+}
+function call(obj) {
+    try {
+        return obj.pick(1);
+    } catch (e) {
+        system.println(e[0] + \": \" + e[1]);
+    }
+    return obj.pick(1, 2);
+}
+system.println(call(Pair(3, 4)));
+system.println(Pair(5, 6).first);
+",
+        expect![[r#"
+            TypeException: Pair.pick expects 2 argument(s), found 1
+            2
+            5
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_type_annotation() {
+    check(
+        b"import system;
+class Point {
+    var x: int = 0;
+    var y: int = 0;
+    function init(x: int, y: int) {
+        self.x = x;
+        self.y = y;
+    }
+    function dist: int {
+        return self.x * self.x + self.y * self.y;
+    }
+}
+function repeat(text: string, times: int): string {
+    var out: string = \"\";
+    var i: int = 0;
+    while (i < times) {
+        out = out + text;
+        i++;
+    }
+    return out;
+}
+function apply(f: function, value: any) {
+    return f(value);
+}
+function twice(n: int): int {
+    return n * 2;
+}
+var p: Point = Point(3, 4);
+var names: array = [\"a\", \"b\"];
+var ages: map = {\"a\": 1};
+var label: string = repeat(\"ab\", twice(1));
+system.println(label);
+system.println(p.dist());
+system.println(apply(function (x) { return x + 1; }, 41));
+",
+        expect![[r#"
+            abab
+            25
+            42
+        "#]],
+    );
+}
+
+/// 与类型标注不符的赋值, 实参与返回值在编译时报错
+#[test]
+pub fn test_type_mismatch() {
+    let (stdout, stderr) = run_console(
+        b"var count: int = 1;
+count = \"one\";
+function greet(name: string): string {
+    return name;
+}
+greet(1);
+function valid: bool {
+    return 1;
+}
+import fs;
+fs.exists(1);
+greet(\"ok\");
+",
+    );
+    expect![[r#"
+        ok
+    "#]]
+    .assert_eq(&stdout);
+    expect![[r#"
+        SyntaxError(<console>-line: 1 column: 9): mismatched types: expected 'int', found 'string'.
+        1    | count = "one";
+                       ^
+        SyntaxError(<console>-line: 1 column: 7): mismatched types: expected 'string', found 'int'.
+        1    | greet(1);
+                     ^
+        SyntaxError(<console>-line: 2 column: 12): mismatched types: expected 'bool', found 'int'.
+        2    |     return 1;
+                          ^
+        SyntaxError(<console>-line: 1 column: 11): mismatched types: expected 'string', found 'int'.
+        1    | fs.exists(1);
+                         ^
+    "#]]
+    .assert_eq(&stderr);
+}
+
+#[test]
+pub fn test_argument_count() {
+    let (stdout, stderr) = run_console(
+        b"function add(a, b) {
+    return a + b;
+}
+add(1);
+class Empty {
+    var v = 0;
+}
+Empty(1);
+class Point {
+    var x = 0;
+    function init(x) {
+        self.x = x;
+    }
+    function move(dx) {
+        self.x += dx;
+    }
+}
+var p = Point();
+Point(1).move(1, 2);
+import fs;
+fs.exists();
+add(1, 2);
+",
+    );
+    expect![[r#"
+        3
+    "#]]
+    .assert_eq(&stdout);
+    expect![[r#"
+        SyntaxError(<console>-line: 1 column: 1): expected 2 argument(s), found 1.
+        1    | add(1);
+               ^
+        SyntaxError(<console>-line: 1 column: 1): expected 0 argument(s), found 1.
+        1    | Empty(1);
+               ^
+        SyntaxError(<console>-line: 1 column: 9): expected 1 argument(s), found 0.
+        1    | var p = Point();
+                       ^
+        SyntaxError(<console>-line: 1 column: 10): expected 1 argument(s), found 2.
+        1    | Point(1).move(1, 2);
+                        ^
+        SyntaxError(<console>-line: 1 column: 4): expected 1 argument(s), found 0.
+        1    | fs.exists();
+                  ^
+    "#]]
+    .assert_eq(&stderr);
+}
+
+#[test]
+pub fn test_big_integer() {
+    check(
+        b"import system;
+import type;
+var max = 9223372036854775807;
+system.println(max + 1);
+system.println(max + 1 - 1 == max);
+system.println(-max - 1 - 1);
+function factorial(n) {
+    var result = 1;
+    for (var i = 2; i <= n; i++) { result = result * i; }
+    return result;
+}
+var big = factorial(30);
+system.println(big);
+system.println(big / factorial(28));
+system.println(big % 1000000007);
+system.println(big > max);
+system.println(big == 265252859812191058636308480000000);
+system.println(1 << 70);
+system.println((1 << 70) >> 69);
+system.println(type.to_number(\"123456789012345678901234567890\") + 1);
+system.println(type.check_type(big));
+var count = max;
+count++;
+system.println(count);
+system.println(big * 0.5);
+try {
+    system.println(big / 0);
+} catch (e) {
+    system.println(e[1]);
+}
+",
+        expect![[r#"
+            9223372036854775808
+            true
+            -9223372036854775809
+            265252859812191058636308480000000
+            870
+            109361473
+            true
+            true
+            1180591620717411303424
+            2
+            123456789012345678901234567891
+            number
+            9223372036854775808
+            132626429906095529318154240000000.0
+            / by zero
+        "#]],
+    );
+}
+
+#[test]
+pub fn test_unary_fold() {
+    check(
+        b"import system;
+system.println(-5 - 1);
+system.println(-5 * 2);
+system.println(1 - -5);
+system.println(-(2 + 3) * 2);
+system.println(!true == false);
+system.println(-9223372036854775807 - 1 - 1);
+",
+        expect![[r#"
+            -6
+            -10
+            6
+            -10
+            true
+            -9223372036854775809
+        "#]],
+    );
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::{openex_command, write_source};
+use expect_test::{Expect, expect};
+use std::fs;
+
+// 编译失败时的标准错误输出, 临时文件的路径替换为 main.exf
+#[track_caller]
+fn check_errors(name: &str, args: &[&str], source: &str, expect: Expect) {
+    let path = write_source(name, source);
+    let output = openex_command(args)
+        .arg(&path)
+        .output()
+        .expect("failed to spawn process");
+    fs::remove_file(&path).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    expect.assert_eq(&stderr.replace(path.to_str().unwrap(), "main.exf"));
+}
+
+#[test]
+fn test_parser_recovery() {
+    check_errors(
+        "parser",
+        &[],
+        "import system;
+var a = 1 +* 2;
+var b = ;
+function f(x) {
+    var y = x $ 1;
+    break;
+    return y;
+}
+if x { }
+system.println(a);
+",
+        expect![[r#"
+            SyntaxError(main.exf-line: 2 column: 12): illegal combination of expressions.
+            2    | var a = 1 +* 2;
+                              ^
+            SyntaxError(main.exf-line: 3 column: 9): statement is incomplete.
+            3    | var b = ;
+                           ^
+            SyntaxError(main.exf-line: 5 column: 15): unexpected character $
+            5    |     var y = x $ 1;
+                                 ^
+            SyntaxError(main.exf-line: 9 column: 4): '(' expected.
+            9    | if x { }
+                      ^
+            help: insert '(' before 'x'
+            4 errors, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_semantic_errors() {
+    check_errors(
+        "semantic",
+        &[],
+        "import system;
+var c: int = \"one\";
+function greet(name: string) { system.println(name); }
+greet(1);
+1 + 2;
+greet(\"ok\");
+",
+        expect![[r#"
+            SyntaxError(main.exf-line: 2 column: 14): mismatched types: expected 'int', found 'string'.
+            2    | var c: int = "one";
+                                ^
+            SyntaxError(main.exf-line: 4 column: 7): mismatched types: expected 'string', found 'int'.
+            4    | greet(1);
+                         ^
+            2 errors, 1 warning
+        "#]],
+    );
+}
+
+#[test]
+fn test_warning_summary() {
+    let path = write_source("warning", "import system;\n1 + 2;\nsystem.println(3);\n");
+    let output = openex_command(&[])
+        .arg(&path)
+        .output()
+        .expect("failed to spawn process");
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    // 警告输出到标准输出, 总数与错误一样输出到标准错误
+    expect![[r#"
+        warning: expression result is unused.
+        2    | 1 + 2;
+                 ^
+        3
+    "#]]
+    .assert_eq(&String::from_utf8(output.stdout).unwrap());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "0 errors, 1 warning\n"
+    );
+}
+
+#[test]
+fn test_json_diagnostics() {
+    check_errors(
+        "json",
+        &["--message-format=json"],
+        "import system;
+import type;
+1 + 2;
+var m = {\"a\": 1};
+type.remove(m, \"a\");
+var c: int = \"one\";
+",
+        expect![[r#"
+            {"severity":"warning","code":"unused-expression","message":"expression result is unused.","file":"main.exf","span":{"line":3,"column":3,"end_line":3,"end_column":4},"notes":[],"suggestion":null}
+            {"severity":"warning","code":"unused-result","message":"result of `type.remove` is discarded, arrays and maps are passed by value.","file":"main.exf","span":{"line":5,"column":6,"end_line":5,"end_column":12},"notes":[],"suggestion":null}
+            {"severity":"error","code":null,"message":"mismatched types: expected 'int', found 'string'.","file":"main.exf","span":{"line":6,"column":14,"end_line":6,"end_column":17},"notes":[],"suggestion":null}
+        "#]],
+    );
+}
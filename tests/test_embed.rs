@@ -0,0 +1,264 @@
+use openex::compiler::Compiler;
+use openex::compiler::ast::vm_ir::Value;
+use openex::compiler::file::SourceFile;
+use openex::library::load_libraries;
+use openex::runtime::debugger::{Breakpoint, DebugCommand, DebugController, Debugger, PauseEvent};
+use openex::runtime::{RuntimeEnv, build_metadata, execute_units, initialize_executor};
+use openex::{
+    CValue, OpenExStatus, ValueData, ValueTag, openex_add_file, openex_call_function,
+    openex_compile, openex_disable_foreign, openex_free, openex_free_c_value, openex_init,
+    openex_initialize_executor, openex_last_error, openex_register_native,
+};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+fn compiler_with(host: &str, main: &str, setup: impl FnOnce(&mut Compiler)) -> Compiler {
+    let mut compiler = Compiler::new();
+    setup(&mut compiler);
+    load_libraries(&mut compiler, None, &HashSet::new()).unwrap();
+    compiler.add_file(SourceFile::new(
+        "main.exf".to_string(),
+        main.to_string(),
+        HashSet::new(),
+        false,
+    ));
+    compiler.add_file(SourceFile::new(
+        "host.exf".to_string(),
+        host.to_string(),
+        HashSet::new(),
+        false,
+    ));
+    compiler
+}
+
+#[test]
+fn test_closure_native() {
+    let total = Arc::new(AtomicI64::new(0));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let captured = (total.clone(), seen.clone());
+    let mut compiler = compiler_with(
+        "function native add(n);",
+        "import host;\
+        host.add(1);\
+        host.add(2);\
+        host.add(39);",
+        |compiler| {
+            compiler
+                .natives_mut()
+                .register_native("host", "add", 1, move |args| {
+                    let Some(Value::Int(n)) = args.first() else {
+                        return Ok(Value::Null);
+                    };
+                    captured.1.lock().unwrap().push(*n);
+                    Ok(Value::Int(captured.0.fetch_add(*n, Ordering::SeqCst) + n))
+                });
+        },
+    );
+    assert!(compiler.compile().is_ok());
+    initialize_executor(&mut compiler);
+    assert_eq!(total.load(Ordering::SeqCst), 42);
+    assert_eq!(*seen.lock().unwrap(), vec![1, 2, 39]);
+}
+
+#[test]
+fn test_registry_per_instance() {
+    let mut registered = compiler_with("function native add(n);", "import host;", |compiler| {
+        compiler
+            .natives_mut()
+            .register_native("host", "add", 1, |_| Ok(Value::Null));
+    });
+    assert!(registered.compile().is_ok());
+
+    // 另一个实例没有注册该本地函数, 声明应当编译失败
+    let mut plain = compiler_with("function native add(n);", "import host;", |_| {});
+    assert!(plain.compile().is_err());
+}
+
+#[test]
+fn test_disable_foreign() {
+    const FOREIGN: &str = "function native(\"libc.so.6\", \"abs\") abs(x: int): int;";
+    let mut compiler = compiler_with(FOREIGN, "import host;", |compiler| {
+        compiler.natives_mut().disable_foreign();
+    });
+    assert!(compiler.compile().is_err());
+    assert_eq!(
+        compiler.diagnostics()[0].message,
+        "cannot bind native function: foreign native functions are disabled."
+    );
+
+    unsafe {
+        let handle = openex_init(std::ptr::null(), 2);
+        assert!(matches!(
+            openex_disable_foreign(handle),
+            OpenExStatus::Success
+        ));
+        openex_add_file(handle, c"import host;".as_ptr(), c"main.exf".as_ptr());
+        openex_add_file(
+            handle,
+            c"function native(\"libc.so.6\", \"abs\") abs(x: int): int;".as_ptr(),
+            c"host.exf".as_ptr(),
+        );
+        assert!(matches!(openex_compile(handle), OpenExStatus::ParseError));
+        let error = std::ffi::CStr::from_ptr(openex_last_error(handle));
+        assert!(
+            error
+                .to_str()
+                .unwrap()
+                .contains("foreign native functions are disabled")
+        );
+        openex_free(handle);
+    }
+}
+
+unsafe extern "C" fn ffi_scale(
+    args: *const CValue,
+    arg_count: usize,
+    out_result: *mut CValue,
+    user_data: *mut c_void,
+) -> OpenExStatus {
+    let factor = unsafe { *user_data.cast::<i64>() };
+    let args = unsafe { std::slice::from_raw_parts(args, arg_count) };
+    let ValueTag::Int = args[0].tag else {
+        return OpenExStatus::RuntimeError;
+    };
+    unsafe {
+        *out_result = CValue {
+            tag: ValueTag::Int,
+            data: ValueData {
+                i: args[0].data.i * factor,
+            },
+        };
+    }
+    OpenExStatus::Success
+}
+
+#[test]
+fn test_ffi_register_native() {
+    let mut factor: i64 = 3;
+    unsafe {
+        let handle = openex_init(std::ptr::null(), 2);
+        assert!(!handle.is_null());
+        assert!(matches!(
+            openex_register_native(
+                handle,
+                c"host".as_ptr(),
+                c"scale".as_ptr(),
+                1,
+                Some(ffi_scale),
+                (&raw mut factor).cast(),
+            ),
+            OpenExStatus::Success
+        ));
+        openex_add_file(
+            handle,
+            c"function native scale(n);".as_ptr(),
+            c"host.exf".as_ptr(),
+        );
+        openex_add_file(
+            handle,
+            c"import host; function run(n) { return host.scale(n) + 1; }".as_ptr(),
+            c"main.exf".as_ptr(),
+        );
+        assert!(matches!(openex_compile(handle), OpenExStatus::Success));
+        assert!(matches!(
+            openex_initialize_executor(handle),
+            OpenExStatus::Success
+        ));
+
+        let args = [CValue {
+            tag: ValueTag::Int,
+            data: ValueData { i: 14 },
+        }];
+        let mut result = CValue {
+            tag: ValueTag::Null,
+            data: ValueData { i: 0 },
+        };
+        openex_call_function(
+            handle,
+            c"main".as_ptr(),
+            c"run".as_ptr(),
+            args.as_ptr(),
+            args.len(),
+            &raw mut result,
+        );
+        assert!(matches!(result.tag, ValueTag::Int));
+        assert_eq!(result.data.i, 43);
+        openex_free_c_value(&raw mut result);
+        openex_free(handle);
+    }
+}
+
+// 按顺序回放调试命令并记录每次暂停的位置
+struct ScriptedController {
+    commands: Mutex<VecDeque<DebugCommand>>,
+    pauses: Arc<Mutex<Vec<String>>>,
+}
+
+impl DebugController for ScriptedController {
+    fn on_pause(&self, event: &PauseEvent) -> DebugCommand {
+        let locals: Vec<String> = event
+            .frame
+            .get_locals()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        self.pauses.lock().unwrap().push(format!(
+            "{:?} {}:{} <{}> depth {} locals [{}]",
+            event.reason,
+            event.file,
+            event.line,
+            event.frame.get_frame_name(),
+            event.depth,
+            locals.join(", ")
+        ));
+        self.commands
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(DebugCommand::Continue)
+    }
+}
+
+#[test]
+fn test_debugger_steps() {
+    let pauses = Arc::new(Mutex::new(Vec::new()));
+    let controller = ScriptedController {
+        commands: Mutex::new(VecDeque::from([
+            DebugCommand::StepOver,
+            DebugCommand::StepOut,
+            DebugCommand::StepOver,
+            DebugCommand::Continue,
+        ])),
+        pauses: pauses.clone(),
+    };
+    let mut compiler = compiler_with(
+        "function twice(n) {\n\
+            var m = n * 2;\n\
+            return m;\n\
+        }",
+        "import host;\n\
+        var a = host.twice(3);\n\
+        var b = host.twice(a);\n\
+        var c = host.twice(b);",
+        |_| {},
+    );
+    assert!(compiler.compile().is_ok());
+    let breakpoint: Breakpoint = "host:2".parse().unwrap();
+    let env = RuntimeEnv::new(compiler.natives().clone())
+        .with_debugger(Debugger::new([breakpoint], controller));
+    let metadata = build_metadata(&mut compiler);
+    execute_units(&metadata, &env);
+    assert_eq!(
+        *pauses.lock().unwrap(),
+        vec![
+            "Breakpoint host.exf:2 <twice> depth 2 locals [3, null]",
+            "Step host.exf:3 <twice> depth 2 locals [3, 6]",
+            "Step main.exf:2 <<main_root>> depth 1 locals [null, null, null]",
+            "Step main.exf:3 <<main_root>> depth 1 locals [null, null, null]",
+            "Breakpoint host.exf:2 <twice> depth 2 locals [6, null]",
+            "Breakpoint host.exf:2 <twice> depth 2 locals [12, null]",
+        ]
+    );
+}
@@ -0,0 +1,166 @@
+use expect_test::{Expect, expect};
+use openex::compiler::Compiler;
+use openex::compiler::dump::EmitKind;
+use openex::compiler::file::SourceFile;
+use std::collections::HashSet;
+
+const SOURCE: &str = "var a = 1 + 2;\n\
+    function pick(x, y) {\n\
+        if (x > y) { return x; }\n\
+        return \"none\";\n\
+    }\n\
+    while (a < 10) { a++; }\n\
+    pick(a, 3);";
+
+#[track_caller]
+fn check(kind: EmitKind, expect: Expect) {
+    let mut compiler = Compiler::new();
+    compiler.set_emit(Some(kind));
+    compiler.add_file(SourceFile::new(
+        "main.exf".to_string(),
+        SOURCE.to_string(),
+        HashSet::new(),
+        false,
+    ));
+    assert!(compiler.compile().is_ok());
+    expect.assert_eq(&compiler.emitted());
+}
+
+#[test]
+fn test_emit_ast() {
+    check(
+        EmitKind::Ast,
+        expect![[r#"
+        == main.exf ==
+        Root
+          Var a
+            Add
+              Literal 1
+              Literal 2
+          Function pick
+            arg x
+            arg y
+            body
+              If
+                cond
+                  Big
+                    Var x
+                    Var y
+                then
+                  Return
+                    Var x
+              Return
+                Literal "none"
+          Loop
+            cond
+              Less
+                Var a
+                Literal 10
+            body
+              Expr
+                SAdd
+                  Var a
+          Expr
+            Call
+              Var pick
+              Var a
+              Literal 3
+    "#]],
+    );
+}
+
+#[test]
+fn test_emit_ssa() {
+    check(
+        EmitKind::Ssa,
+        expect![[r#"
+        == main.exf ==
+        root (globals 1):
+          block 0:
+            %0    Push 3  ; 1:11
+            %1    LoadGlobal g0, 3  ; 1:5
+          block 1:
+            %2    Nop  ; 6:7
+            %3    StoreLocal g0, g0  ; 6:8
+            %4    Push 10  ; 6:12
+            %5    Less  ; 6:10
+            %6    JumpFalse -> %11, (g0 Less 10)  ; 6:7
+          block 2:
+            %7    StoreLocal g0, g0  ; 6:18
+            %8    SAdd  ; 6:19
+            %9    LoadLocal g0, g0  ; 6:18
+            %10   Jump -> %2  ; 6:7
+          block 3:
+            %11   Nop  ; 6:7
+            %12   StoreLocal g0, g0  ; 7:6
+            %13   Push 3  ; 7:9
+            %14   Push ref:main/pick  ; 7:1
+            %15   Call pick, 2  ; 7:1
+        function pick(args 2, locals 2):
+          block 0:
+            %0    LoadLocal l0, l0  ; 2:10
+            %1    LoadLocal l1, l1  ; 2:10
+            %2    StoreLocal l0, l0  ; 3:5
+            %3    StoreLocal l1, l1  ; 3:9
+            %4    Big  ; 3:7
+            %5    JumpFalse -> %8, (l0 Big l1)  ; 3:7
+          block 1:
+            %6    StoreLocal l0, l0  ; 3:21
+            %7    Return  ; 3:21
+          block 2:
+            %8    Nop  ; 3:7
+            %9    Push "none"  ; 4:8
+            %10   Return  ; 4:8
+    "#]],
+    );
+}
+
+#[test]
+fn test_emit_bytecode() {
+    check(
+        EmitKind::Bytecode,
+        expect![[r#"
+        == main.exf ==
+        constants:
+          #0 = 3
+          #1 = 10
+          #2 = 3
+          #3 = ref:main/pick
+          #4 = "none"
+        root (globals 1):
+          0000  Push #0 (3)  ; 1:11
+          0001  LoadGlobal 0  ; 1:5
+          0002  Nol  ; 6:7
+          0003  StoreGlobal 0  ; 6:8
+          0004  Push #1 (10)  ; 6:12
+          0005  Less  ; 6:10
+          0006  JumpFalse -> 0011  ; 6:7
+          0007  StoreGlobal 0  ; 6:18
+          0008  SAdd  ; 6:19
+          0009  LoadGlobal 0  ; 6:18
+          0010  Jump -> 0002  ; 6:7
+          0011  Nol  ; 6:7
+          0012  StoreGlobal 0  ; 7:6
+          0013  Push #2 (3)  ; 7:9
+          0014  CallConst #3 (ref:main/pick), 2  ; 7:1
+        function pick(args 2, locals 2):
+          0000  Load 0  ; 2:10
+          0001  Load 1  ; 2:10
+          0002  Store 0  ; 3:5
+          0003  Store 1  ; 3:9
+          0004  Big  ; 3:7
+          0005  JumpFalse -> 0008  ; 3:7
+          0006  Store 0  ; 3:21
+          0007  Return  ; 3:21
+          0008  Nol  ; 3:7
+          0009  Push #4 ("none")  ; 4:8
+          0010  Return  ; 4:8
+    "#]],
+    );
+}
+
+#[test]
+fn test_emit_kind() {
+    assert_eq!("ssa".parse(), Ok(EmitKind::Ssa));
+    assert!("llvm".parse::<EmitKind>().is_err());
+}
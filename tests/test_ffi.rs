@@ -0,0 +1,128 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{openex_command, temp_path, write_source};
+use expect_test::{Expect, expect};
+use std::fs;
+use std::process::Output;
+
+// 调用系统的 libm 与 libc, 库名按 glibc 的 soname 书写
+const SOURCE: &str = "import system;\n\
+    function native(\"libm.so.6\", \"cos\") cos(x: float): float;\n\
+    function native(\"libm.so.6\", \"pow\") pow(x: float, y: float): float;\n\
+    function native(\"libc.so.6\", \"labs\") labs(x: int): int;\n\
+    function native(\"libc.so.6\", \"strlen\") strlen(s: string): int;\n\
+    function native(\"libc.so.6\", \"getenv\") getenv(name: string): string;\n\
+    system.println(cos(0.0));\n\
+    system.println(pow(2.0, 10.0));\n\
+    system.println(labs(-7));\n\
+    system.println(strlen(\"hello\"));\n\
+    system.println(getenv(\"OPENEX_FFI_TEST\"));\n\
+    system.println(getenv(\"OPENEX_FFI_UNSET\"));\n";
+
+fn openex(args: &[&str]) -> Output {
+    openex_command(args)
+        .env("OPENEX_FFI_TEST", "ffi")
+        .env_remove("OPENEX_FFI_UNSET")
+        .output()
+        .expect("failed to spawn process")
+}
+
+fn expect_output() -> Expect {
+    expect![[r#"
+        1.0
+        1024.0
+        7
+        5
+        ffi
+        null
+    "#]]
+}
+
+#[track_caller]
+fn check_error(name: &str, source: &str, expect: Expect) {
+    let path = write_source(name, source);
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    expect.assert_eq(stderr.split_once("): ").unwrap().1);
+}
+
+#[test]
+fn test_foreign_call() {
+    let path = write_source("ffi_call", SOURCE);
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    expect_output().assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_foreign_bytecode() {
+    let path = write_source("ffi_build", SOURCE);
+    let output_path = temp_path("ffi_build.exb");
+    let output = openex(&[
+        "build",
+        path.to_str().unwrap(),
+        "-o",
+        output_path.to_str().unwrap(),
+    ]);
+    fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    // 直接执行字节码时重新加载动态库
+    let output = openex(&[output_path.to_str().unwrap()]);
+    fs::remove_file(output_path).unwrap();
+    assert!(output.status.success());
+    expect_output().assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_foreign_deferred_loading() {
+    // 编译与 --emit 只记录声明, 动态库在执行前才加载
+    let path = write_source(
+        "ffi_library",
+        "function native(\"libopenex_missing.so\", \"f\") f(x: int): int;\n",
+    );
+    let emit = openex(&["--emit", "ssa", path.to_str().unwrap()]);
+    let run = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    assert!(emit.status.success());
+    assert!(emit.stderr.is_empty());
+    assert!(!run.status.success());
+    expect![[r#"
+        error: cannot bind native function `f`: libopenex_missing.so: cannot open shared object file: No such file or directory
+    "#]]
+    .assert_eq(&String::from_utf8(run.stderr).unwrap());
+}
+
+#[test]
+fn test_foreign_errors() {
+    check_error(
+        "ffi_type",
+        "function native(\"libc.so.6\", \"labs\") labs(x: array): int;\n",
+        expect![[r#"
+            cannot bind native function: type 'array' cannot be passed to C.
+            1    | function native("libc.so.6", "labs") labs(x: array): int;
+                                                        ^
+            1 error, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_foreign_argument_type() {
+    // 未标注类型的值在调用时才按形参的标注检查
+    let path = write_source(
+        "ffi_argument",
+        "function native(\"libc.so.6\", \"labs\") labs(x: int): int;\n\
+        function id(v) { return v; }\n\
+        labs(id(\"x\"));\n",
+    );
+    let output = openex(&[path.to_str().unwrap()]);
+    fs::remove_file(path).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("TypeException(\"labs: expected 'int', found 'string'\")"));
+}
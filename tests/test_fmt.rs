@@ -0,0 +1,250 @@
+use expect_test::{Expect, expect};
+use openex::compiler::format::format_source;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+#[track_caller]
+fn check(source: &str, expect: Expect) {
+    let formatted = format_source("main.exf", source).unwrap();
+    expect.assert_eq(&formatted);
+    assert_eq!(
+        format_source("main.exf", &formatted).unwrap(),
+        formatted,
+        "formatting is not idempotent"
+    );
+}
+
+fn openex_fmt(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_openex"))
+        .arg("fmt")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn process");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_indent_and_spacing() {
+    check(
+        "import system;\n\
+        var   a=1+2*-3;\n\
+        var arr=[1,2,  3]; var tail = arr[ 1: ];\n\
+        var m = {\"k\":1,\"j\" : 2};\n\
+        function add(a: int,b: int): int{return a+b;}\n\
+        if(a>1){a--;}elif(a<0)\n\
+        {\n\
+        a = - -a;\n\
+        }\n\
+        else { ++a; }\n\
+        try{throw \"x\";}catch(e){system.println(e[0]);}\n\
+        for(var i=0;i<3;i++){}\n\
+        for(;;){break;}\n\
+        system.println(!true);\n",
+        expect![[r#"
+            import system;
+            var a = 1 + 2 * -3;
+            var arr = [1, 2, 3];
+            var tail = arr[1:];
+            var m = {"k": 1, "j": 2};
+            function add(a: int, b: int): int {
+                return a + b;
+            }
+            if (a > 1) {
+                a--;
+            } elif (a < 0) {
+                a = - -a;
+            } else {
+                ++a;
+            }
+            try {
+                throw "x";
+            } catch (e) {
+                system.println(e[0]);
+            }
+            for (var i = 0; i < 3; i++) {}
+            for (;;) {
+                break;
+            }
+            system.println(!true);
+        "#]],
+    );
+}
+
+#[test]
+fn test_optional_parens() {
+    check(
+        "function readline{\n\
+        while{\n\
+        if (read() == \"\") { break; }\n\
+        }\n\
+        }\n\
+        class Point{\n\
+        var x=0;\n\
+        function init{self.x=1;}\n\
+        }\n",
+        expect![[r#"
+            function readline {
+                while {
+                    if (read() == "") {
+                        break;
+                    }
+                }
+            }
+            class Point {
+                var x = 0;
+                function init {
+                    self.x = 1;
+                }
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn test_lambdas_and_line_breaks() {
+    check(
+        "var twice = function(x){return x*2;};\n\
+        apply(function (x) { return x * 2; }, 21);\n\
+        var add = function (a, b) {\n\
+        return a + b;\n\
+        };\n\
+        var total = sum(1,\n\
+        2,\n\
+                3);\n\
+        var map = {\n\
+          \"a\": 1,\n\
+          \"b\": 2\n\
+        };\n",
+        expect![[r#"
+            var twice = function (x) { return x * 2; };
+            apply(function (x) { return x * 2; }, 21);
+            var add = function (a, b) {
+                return a + b;
+            };
+            var total = sum(1,
+                2,
+                3);
+            var map = {
+                "a": 1,
+                "b": 2
+            };
+        "#]],
+    );
+}
+
+#[test]
+fn test_comments_and_blank_lines() {
+    check(
+        "\n\
+        // 文件头注释\n\
+        import system;   // 行尾注释\n\
+        \n\
+        \n\
+        \n\
+        /* 多行\n   块注释 */\n\
+        function main() {\n\
+        \n\
+        var x = /* 行内 */ 1;\n\
+              // 块末尾的注释\n\
+        \n\
+        }\n",
+        expect![[r#"
+            // 文件头注释
+            import system; // 行尾注释
+
+            /* 多行
+               块注释 */
+            function main() {
+                var x = /* 行内 */ 1;
+                // 块末尾的注释
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn test_syntax_error() {
+    let diagnostics = format_source("main.exf", "var a = ;\nvar b = 1;\n").unwrap_err();
+    let messages = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render("var a = ;\nvar b = 1;\n"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    expect![[r#"
+        SyntaxError(main.exf-line: 1 column: 9): statement is incomplete.
+        1    | var a = ;
+                       ^"#]]
+    .assert_eq(&messages);
+}
+
+#[test]
+fn test_sources_are_formatted() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = vec![];
+    for dir in ["tests/modules", "tests/modules/net", "tests/modules/search"] {
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "exf") {
+                paths.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    let args = paths.iter().map(String::as_str).collect::<Vec<_>>();
+    let output = openex_fmt(&[&["--check"], args.as_slice()].concat(), "");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn test_fmt_check_and_write() {
+    let path = std::env::temp_dir().join(format!("openex_fmt_{}.exf", std::process::id()));
+    fs::write(&path, "var a=1;\n").unwrap();
+    let file = path.to_str().unwrap();
+
+    let output = openex_fmt(&["--check", file], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("would reformat {file}\n")
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "var a=1;\n");
+
+    assert!(openex_fmt(&[file], "").status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "var a = 1;\n");
+    assert!(openex_fmt(&["--check", file], "").status.success());
+    fs::remove_file(&path).unwrap();
+
+    let output = openex_fmt(&[], "var b=[1,2];");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "var b = [1, 2];\n"
+    );
+    assert_eq!(
+        openex_fmt(&["--check"], "var b=[1,2];").status.code(),
+        Some(1)
+    );
+
+    let output = openex_fmt(&[], "var a = ;");
+    assert_eq!(output.status.code(), Some(1));
+    expect![[r#"
+        SyntaxError(<stdin>-line: 1 column: 9): statement is incomplete.
+        1    | var a = ;
+                       ^
+    "#]]
+    .assert_eq(&String::from_utf8(output.stderr).unwrap());
+}
@@ -0,0 +1,16 @@
+use expect_test::expect_file;
+
+// 头文件与导出接口保持同步, 接口变化时需要更新 include/openex.h
+#[test]
+fn test_header_up_to_date() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let config = cbindgen::Config::from_file(format!("{root}/cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_crate(root)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate C header");
+    let mut header = Vec::new();
+    bindings.write(&mut header);
+    expect_file!["../include/openex.h"].assert_eq(&String::from_utf8(header).unwrap());
+}
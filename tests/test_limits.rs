@@ -0,0 +1,230 @@
+use expect_test::{Expect, expect};
+use openex::compiler::Compiler;
+use openex::compiler::ast::vm_ir::Value;
+use openex::compiler::file::SourceFile;
+use openex::library::load_libraries;
+use openex::runtime::context::SyncTable;
+use openex::runtime::executor::call_function;
+use openex::runtime::limits::ExecutionLimits;
+use openex::runtime::{GlobalStore, RuntimeEnv, build_metadata, root_method};
+use std::collections::HashSet;
+use std::time::Duration;
+
+fn compile(source: &str) -> Compiler {
+    let mut compiler = Compiler::new();
+    load_libraries(&mut compiler, None, &HashSet::new()).unwrap();
+    compiler.add_file(SourceFile::new(
+        "main.exf".to_string(),
+        source.to_string(),
+        HashSet::new(),
+        false,
+    ));
+    assert!(compiler.compile().is_ok());
+    compiler
+}
+
+// 在给定的运行环境中执行入口文件的全局代码
+fn run(compiler: &mut Compiler, env: &RuntimeEnv) -> Result<Value, String> {
+    let natives = compiler.natives().clone();
+    let metadata = build_metadata(compiler);
+    let (unit_index, unit) = metadata
+        .iter()
+        .enumerate()
+        .find(|(_, unit)| !unit.library)
+        .unwrap();
+    call_function(
+        &root_method(unit),
+        unit.constant_table,
+        &metadata,
+        unit_index,
+        GlobalStore::shared_new(&metadata),
+        SyncTable::shared_new(&metadata),
+        RuntimeEnv {
+            natives,
+            ..env.clone()
+        },
+        None,
+        vec![],
+    )
+}
+
+#[track_caller]
+fn check_limit(source: &str, limits: ExecutionLimits, expect: Expect) {
+    let mut compiler = compile(source);
+    let env = RuntimeEnv::default().with_limits(limits);
+    let report = run(&mut compiler, &env).unwrap_err();
+    expect.assert_eq(report.lines().next().unwrap().split_once("): ").unwrap().1);
+}
+
+#[test]
+fn test_instruction_limit() {
+    let limits = ExecutionLimits {
+        max_instructions: Some(10_000),
+        ..ExecutionLimits::default()
+    };
+    check_limit(
+        "while {}",
+        limits,
+        expect![[r#"LimitExceeded("instruction count exceeds the limit of 10000")"#]],
+    );
+    // 脚本无法捕获超出限制的错误
+    check_limit(
+        "try { while {} } catch (e) { while {} }",
+        limits,
+        expect![[r#"LimitExceeded("instruction count exceeds the limit of 10000")"#]],
+    );
+}
+
+#[test]
+fn test_instruction_limit_is_exact() {
+    let source = "var total = 0;\n\
+        for (var i = 0; i < 100; i++) { total = total + i; }\n";
+    let mut compiler = compile(source);
+    let env = RuntimeEnv::default().with_limits(ExecutionLimits {
+        max_instructions: Some(u64::MAX),
+        ..ExecutionLimits::default()
+    });
+    assert!(run(&mut compiler, &env).is_ok());
+    let executed = env.budget.as_ref().unwrap().executed();
+    assert!(executed > 100);
+
+    let limited = |max_instructions| {
+        RuntimeEnv::default().with_limits(ExecutionLimits {
+            max_instructions: Some(max_instructions),
+            ..ExecutionLimits::default()
+        })
+    };
+    assert!(run(&mut compile(source), &limited(executed)).is_ok());
+    assert!(run(&mut compile(source), &limited(executed - 1)).is_err());
+}
+
+#[test]
+fn test_timeout() {
+    check_limit(
+        "while {}",
+        ExecutionLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..ExecutionLimits::default()
+        },
+        expect![[r#"LimitExceeded("execution time exceeds the limit of 50ms")"#]],
+    );
+}
+
+#[test]
+fn test_call_depth_limit() {
+    let limits = ExecutionLimits {
+        max_call_depth: Some(64),
+        ..ExecutionLimits::default()
+    };
+    check_limit(
+        "function down(n) { return down(n + 1); }\ndown(0);",
+        limits,
+        expect![[r#"LimitExceeded("call depth exceeds the limit of 64")"#]],
+    );
+    // 未超出深度的递归正常返回
+    let mut compiler = compile(
+        "function sum(n) { if (n == 0) { return 0; } return n + sum(n - 1); }\n\
+        var result = sum(50);\n",
+    );
+    assert!(run(&mut compiler, &RuntimeEnv::default().with_limits(limits)).is_ok());
+}
+
+#[test]
+fn test_length_limit() {
+    let limits = ExecutionLimits {
+        max_length: Some(1000),
+        ..ExecutionLimits::default()
+    };
+    check_limit(
+        "import type;\ntype.array_fill(0, 1000000000);",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 1000000000 exceeds the limit of 1000")"#]],
+    );
+    check_limit(
+        "var s = \"ab\";\nwhile { s = s + s; }",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 1020 exceeds the limit of 1000")"#]],
+    );
+    check_limit(
+        "import string;\nstring.repeat(\"abc\", 1000);",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 3000 exceeds the limit of 1000")"#]],
+    );
+    check_limit(
+        "import type;\nvar a = [];\nwhile { type.push(a, 1); }",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 1001 exceeds the limit of 1000")"#]],
+    );
+}
+
+#[test]
+fn test_length_limit_is_total() {
+    let limits = ExecutionLimits {
+        max_length: Some(100),
+        ..ExecutionLimits::default()
+    };
+    // 每个值都不超过上限, 但累计新建的元素超出上限
+    check_limit(
+        "import type;\nvar rows = [];\n\
+        while { var row = []; type.push(row, 1); type.push(rows, row); }",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 101 exceeds the limit of 100")"#]],
+    );
+    // 本地函数返回的值同样计入用量
+    check_limit(
+        "import string;\nwhile { string.split(\"a,b,c,d,e,f,g,h,i,j\", \",\"); }",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 120 exceeds the limit of 100")"#]],
+    );
+    check_limit(
+        "import string;\nwhile { string.upper(\"abcdefghij\"); }",
+        limits,
+        expect![[r#"LimitExceeded("allocated length 110 exceeds the limit of 100")"#]],
+    );
+
+    let mut compiler = compile("import string;\nvar s = string.repeat(\"ab\", 10);\n");
+    let env = RuntimeEnv::default().with_limits(limits);
+    assert!(run(&mut compiler, &env).is_ok());
+    assert_eq!(env.budget.as_ref().unwrap().allocated(), 20);
+}
+
+#[test]
+fn test_bigint_length_limit() {
+    // 任意精度整数的字节数计入长度用量, 位数过大的运算在进行之前被拒绝
+    check_limit(
+        "var x = 3;\nwhile { x = x * x; }",
+        ExecutionLimits {
+            max_length: Some(10_000),
+            ..ExecutionLimits::default()
+        },
+        expect![[r#"LimitExceeded("allocated length 12977 exceeds the limit of 10000")"#]],
+    );
+    check_limit(
+        "import type;\nvar x = 1 << type.to_number(\"100000\");",
+        ExecutionLimits {
+            max_length: Some(10_000),
+            ..ExecutionLimits::default()
+        },
+        expect![[r#"LimitExceeded("allocated length 12501 exceeds the limit of 10000")"#]],
+    );
+}
+
+#[test]
+fn test_timeout_in_native() {
+    check_limit(
+        "import system;\nsystem.nano_sleep(10000000000);",
+        ExecutionLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..ExecutionLimits::default()
+        },
+        expect![[r#"LimitExceeded("execution time exceeds the limit of 50ms")"#]],
+    );
+}
+
+#[test]
+fn test_unlimited() {
+    let env = RuntimeEnv::default().with_limits(ExecutionLimits::default());
+    assert!(env.budget.is_none());
+    let mut compiler = compile("import type;\nvar a = type.array_fill(0, 100000);");
+    assert!(run(&mut compiler, &env).is_ok());
+}
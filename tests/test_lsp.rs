@@ -0,0 +1,245 @@
+use expect_test::{Expect, expect};
+use serde_json::{Value, json};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const URI: &str = "file:///workspace/main.exf";
+
+const SOURCE: &str = "import system;
+var count = 1;
+function add(a, b) {
+    return a + b;
+}
+system.println(add(count, 2));
+";
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+fn position(id: u64, method: &str, line: u64, character: u64) -> Value {
+    request(
+        id,
+        method,
+        json!({
+            "textDocument": {"uri": URI},
+            "position": {"line": line, "character": character},
+        }),
+    )
+}
+
+fn open(text: &str) -> Vec<Value> {
+    vec![
+        request(1, "initialize", json!({"capabilities": {}})),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": URI, "languageId": "openex", "version": 1, "text": text}}),
+        ),
+    ]
+}
+
+fn shutdown() -> Vec<Value> {
+    vec![
+        request(99, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]
+}
+
+// 依次发送消息并返回退出码与服务器输出的消息, 每行一条, initialize 的响应不输出
+fn session(messages: &[Value]) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_openex-lsp"))
+        .arg("--lib=lib")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn process");
+    {
+        let stdin = child.stdin.as_mut().expect("failed to open stdin");
+        for message in messages {
+            let body = message.to_string();
+            write!(stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).expect("invalid utf-8 in stdout");
+
+    let mut transcript = String::new();
+    let mut rest = stdout.as_str();
+    while let Some((header, tail)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let (body, tail) = tail.split_at(length);
+        let message: Value = serde_json::from_str(body).unwrap();
+        if message["id"] != 1 {
+            transcript.push_str(&format!("{message}\n"));
+        }
+        rest = tail;
+    }
+    (output.status.code().unwrap(), transcript)
+}
+
+#[track_caller]
+fn check(messages: &[Value], expect: Expect) {
+    let (code, transcript) = session(messages);
+    assert_eq!(code, 0);
+    expect.assert_eq(&transcript);
+}
+
+#[test]
+fn test_diagnostics_on_change() {
+    let mut messages = open("import system;\nvar x = 1;\n1 + 2;\n");
+    messages.push(notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 2},
+            "contentChanges": [
+                {"range": {"start": {"line": 1, "character": 8}, "end": {"line": 1, "character": 9}}, "text": ""},
+            ],
+        }),
+    ));
+    messages.push(notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 3},
+            "contentChanges": [{"text": "var s = \"😀\"; s = t;\n"}],
+        }),
+    ));
+    messages.push(notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 4},
+            "contentChanges": [{"text": "var a = 1;\na +;\n"}],
+        }),
+    ));
+    messages.extend(shutdown());
+    check(
+        &messages,
+        expect![[r#"
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[{"code":"unused-expression","message":"expression result is unused.","range":{"end":{"character":3,"line":2},"start":{"character":2,"line":2}},"severity":2,"source":"openex"}],"uri":"file:///workspace/main.exf"}}
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[{"message":"statement is incomplete.","range":{"end":{"character":9,"line":1},"start":{"character":8,"line":1}},"severity":1,"source":"openex"}],"uri":"file:///workspace/main.exf"}}
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[{"message":"unable to resolve symbols.","range":{"end":{"character":19,"line":0},"start":{"character":18,"line":0}},"severity":1,"source":"openex"}],"uri":"file:///workspace/main.exf"}}
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[{"message":"illegal combination of expressions.","range":{"end":{"character":3,"line":1},"start":{"character":2,"line":1}},"severity":1,"source":"openex"}],"uri":"file:///workspace/main.exf"}}
+            {"id":99,"jsonrpc":"2.0","result":null}
+        "#]],
+    );
+}
+
+#[test]
+fn test_definition_and_hover() {
+    let mut messages = open(SOURCE);
+    messages.extend([
+        position(2, "textDocument/definition", 5, 20),
+        position(3, "textDocument/definition", 5, 16),
+        position(4, "textDocument/definition", 3, 15),
+        position(5, "textDocument/hover", 5, 9),
+        position(6, "textDocument/hover", 3, 11),
+        position(7, "textDocument/hover", 1, 5),
+        position(8, "textDocument/hover", 0, 0),
+    ]);
+    messages.extend(shutdown());
+    check(
+        &messages,
+        expect![[r#"
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///workspace/main.exf"}}
+            {"id":2,"jsonrpc":"2.0","result":{"range":{"end":{"character":9,"line":1},"start":{"character":4,"line":1}},"uri":"file:///workspace/main.exf"}}
+            {"id":3,"jsonrpc":"2.0","result":{"range":{"end":{"character":12,"line":2},"start":{"character":9,"line":2}},"uri":"file:///workspace/main.exf"}}
+            {"id":4,"jsonrpc":"2.0","result":{"range":{"end":{"character":17,"line":2},"start":{"character":16,"line":2}},"uri":"file:///workspace/main.exf"}}
+            {"id":5,"jsonrpc":"2.0","result":{"contents":{"kind":"markdown","value":"```openex\nfunction println(output)\n```"}}}
+            {"id":6,"jsonrpc":"2.0","result":{"contents":{"kind":"markdown","value":"```openex\n(argument) a\n```"}}}
+            {"id":7,"jsonrpc":"2.0","result":{"contents":{"kind":"markdown","value":"```openex\nvar count = 1\n```"}}}
+            {"id":8,"jsonrpc":"2.0","result":null}
+            {"id":99,"jsonrpc":"2.0","result":null}
+        "#]],
+    );
+}
+
+#[test]
+fn test_completion() {
+    let mut messages = open(SOURCE);
+    messages.push(notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 2},
+            "contentChanges": [
+                {"range": {"start": {"line": 6, "character": 0}, "end": {"line": 6, "character": 0}}, "text": "system.pr"},
+            ],
+        }),
+    ));
+    messages.extend([
+        position(2, "textDocument/completion", 6, 9),
+        position(3, "textDocument/completion", 6, 2),
+    ]);
+    messages.extend(shutdown());
+    check(
+        &messages,
+        expect![[r#"
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///workspace/main.exf"}}
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///workspace/main.exf"}}
+            {"id":2,"jsonrpc":"2.0","result":[{"detail":"function native print(output)","kind":3,"label":"print"},{"detail":"function native exit(code: int)","kind":3,"label":"exit"},{"detail":"function native read()","kind":3,"label":"read"},{"detail":"function native thread(func)","kind":3,"label":"thread"},{"detail":"function native thread_exit()","kind":3,"label":"thread_exit"},{"detail":"function native nano_sleep(time: int)","kind":3,"label":"nano_sleep"},{"detail":"function sleep(time)","kind":3,"label":"sleep"},{"detail":"function println(output)","kind":3,"label":"println"},{"detail":"function readline","kind":3,"label":"readline"}]}
+            {"id":3,"jsonrpc":"2.0","result":[{"kind":14,"label":"for"},{"kind":14,"label":"while"},{"kind":14,"label":"if"},{"kind":14,"label":"elif"},{"kind":14,"label":"else"},{"kind":14,"label":"return"},{"kind":14,"label":"break"},{"kind":14,"label":"continue"},{"kind":14,"label":"import"},{"kind":14,"label":"function"},{"kind":14,"label":"true"},{"kind":14,"label":"false"},{"kind":14,"label":"var"},{"kind":14,"label":"this"},{"kind":14,"label":"null"},{"kind":14,"label":"native"},{"kind":14,"label":"from"},{"kind":14,"label":"sync"},{"kind":14,"label":"try"},{"kind":14,"label":"catch"},{"kind":14,"label":"throw"},{"kind":14,"label":"class"},{"detail":"function add(a, b)","kind":3,"label":"add"},{"detail":"import system","kind":9,"label":"system"},{"detail":"var count = 1","kind":6,"label":"count"},{"detail":"function add(a, b)","kind":6,"label":"a"},{"detail":"function add(a, b)","kind":6,"label":"b"},{"kind":9,"label":"fs"},{"kind":9,"label":"math"},{"kind":9,"label":"string"},{"kind":9,"label":"type"}]}
+            {"id":99,"jsonrpc":"2.0","result":null}
+        "#]],
+    );
+}
+
+#[test]
+fn test_semantic_tokens() {
+    let mut messages =
+        open("import system;\nfunction f(a) { return a + 1.5; }\nsystem.println(f(\"x\"));\n");
+    messages.push(request(
+        2,
+        "textDocument/semanticTokens/full",
+        json!({"textDocument": {"uri": URI}}),
+    ));
+    messages.extend(shutdown());
+    check(
+        &messages,
+        expect![[r#"
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///workspace/main.exf"}}
+            {"id":2,"jsonrpc":"2.0","result":{"data":[0,0,6,0,0,0,7,6,7,0,1,0,8,0,0,0,9,1,5,0,0,2,1,4,0,0,5,6,0,0,0,7,1,4,0,0,2,1,8,0,0,2,3,1,0,1,0,6,7,0,0,6,1,8,0,0,1,7,5,0,0,8,1,5,0,0,2,3,2,0]}}
+            {"id":99,"jsonrpc":"2.0","result":null}
+        "#]],
+    );
+}
+
+#[test]
+fn test_foreign_not_loaded() {
+    // 分析时只记录动态库函数的声明, 不存在的动态库也不会产生诊断
+    let mut messages =
+        open("function native(\"/tmp/openex_missing_lsp.so\", \"x\") f(x: int): int;\nf(1);\n");
+    messages.extend(shutdown());
+    check(
+        &messages,
+        expect![[r#"
+            {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///workspace/main.exf"}}
+            {"id":99,"jsonrpc":"2.0","result":null}
+        "#]],
+    );
+}
+
+#[test]
+fn test_protocol_errors() {
+    let (code, transcript) = session(&[
+        request(2, "textDocument/hover", json!({})),
+        request(1, "initialize", json!({"capabilities": {}})),
+        request(3, "workspace/unknown", json!({})),
+        position(4, "textDocument/hover", 0, 0),
+        notification("exit", Value::Null),
+    ]);
+    assert_eq!(code, 1);
+    expect![[r#"
+        {"error":{"code":-32002,"message":"server is not initialized"},"id":2,"jsonrpc":"2.0"}
+        {"error":{"code":-32601,"message":"unknown method `workspace/unknown`"},"id":3,"jsonrpc":"2.0"}
+        {"error":{"code":-32602,"message":"invalid params: document `file:///workspace/main.exf` is not open"},"id":4,"jsonrpc":"2.0"}
+    "#]]
+    .assert_eq(&transcript);
+}
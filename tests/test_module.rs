@@ -0,0 +1,115 @@
+use expect_test::{Expect, expect};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// 模块示例位于 tests/modules, 导入路径相对于导入文件所在目录解析
+fn openex(args: &[&str], search_path: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_openex"));
+    command.args(args).env_remove("OPENEX_PATH");
+    if let Some(path) = search_path {
+        command.env("OPENEX_PATH", path);
+    }
+    command.output().expect("failed to spawn process")
+}
+
+#[track_caller]
+fn check_error(file: &str, expect: Expect) {
+    let output = openex(&[file], None);
+    assert!(!output.status.success());
+    expect.assert_eq(&String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn test_path_import() {
+    let output = openex(&["tests/modules/main.exf"], Some("tests/modules/search"));
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    expect![[r#"
+        GET /index from util
+        util
+        net/util
+        {"value": 42}
+    "#]]
+    .assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_import_not_found() {
+    check_error(
+        "tests/modules/main.exf",
+        expect![[r#"
+            SyntaxError(tests/modules/main.exf-line: 5 column: 8): not found import library.
+            5    | import json;
+                          ^
+            1 error, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_import_cycle() {
+    check_error(
+        "tests/modules/cycle/a.exf",
+        expect![[r#"
+            SyntaxError(tests/modules/cycle/b.exf-line: 1 column: 8): import cycle detected: tests/modules/cycle/b -> tests/modules/cycle/a -> tests/modules/cycle/b.
+            1    | import "a.exf";
+                          ^
+            note: 'tests/modules/cycle/b' imports 'tests/modules/cycle/a'
+            note: 'tests/modules/cycle/a' imports 'tests/modules/cycle/b'
+            1 error, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_duplicate_import() {
+    // 不同目录下的同名模块需要以不同的导入名导入
+    check_error(
+        "tests/modules/duplicate/main.exf",
+        expect![[r#"
+            SyntaxError(tests/modules/duplicate/main.exf-line: 2 column: 8): import name 'util' is already bound to another module.
+            2    | import "../net/util.exf";
+                          ^
+            help: import it under another name: import <name> from "../net/util.exf";
+            1 error, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_library_statement() {
+    check_error(
+        "tests/modules/state/main.exf",
+        expect![[r#"
+            SyntaxError(tests/modules/state/counter.exf-line: 1 column: 5): top-level statement in an imported module is never executed.
+            1    | var count = 5;
+                       ^
+            note: imported modules only provide functions and classes
+            help: move the statement into a function
+            1 error, 0 warnings
+        "#]],
+    );
+}
+
+#[test]
+fn test_console_import() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_openex"))
+        .arg("--cli")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn process");
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(b"import \"tests/modules/net/http.exf\";\nhttp.get(\"/\");\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.stderr.is_empty());
+    expect![[r#"
+        GET / from util
+    "#]]
+    .assert_eq(&String::from_utf8(output.stdout).unwrap());
+}
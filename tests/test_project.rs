@@ -0,0 +1,151 @@
+mod common;
+
+use common::{openex_command, temp_path, write};
+use expect_test::expect;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn temp_project(name: &str) -> PathBuf {
+    let root = temp_path(name);
+    let _ = fs::remove_dir_all(&root);
+    root
+}
+
+fn openex_run(dir: &Path) -> Output {
+    openex_command(&["run", dir.to_str().unwrap()])
+        .args(["--lib", concat!(env!("CARGO_MANIFEST_DIR"), "/lib")])
+        .env_remove("OPENEX_PATH")
+        .output()
+        .expect("failed to spawn process")
+}
+
+fn run_stdout(dir: &Path) -> String {
+    let output = openex_run(dir);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// 应用依赖本地的 util 包, 并使用自身的源码目录与库目录
+fn create_app(root: &Path) -> PathBuf {
+    let app = root.join("app");
+    write(
+        &app.join("openex.toml"),
+        "[package]\n\
+        name = \"app\"\n\
+        entry = \"src/main.exf\"\n\
+        lib = [\"lib\"]\n\
+        \n\
+        [dependencies]\n\
+        util = { path = \"../util\" }\n",
+    );
+    write(
+        &app.join("src/main.exf"),
+        "import system;\n\
+        import util;\n\
+        import http;\n\
+        import colors;\n\
+        system.println(util.greet(\"app\"));\n\
+        system.println(http.get());\n\
+        system.println(colors.red());\n",
+    );
+    write(
+        &app.join("src/http.exf"),
+        "function get { return \"GET\"; }\n",
+    );
+    write(
+        &app.join("lib/colors.exf"),
+        "function red { return \"red\"; }\n",
+    );
+    write(
+        &root.join("util/openex.toml"),
+        "[package]\nname = \"util\"\nentry = \"src/lib.exf\"\n",
+    );
+    write(
+        &root.join("util/src/lib.exf"),
+        "import strings;\n\
+        function greet(who) { return strings.hello() + who; }\n",
+    );
+    write(
+        &root.join("util/src/strings.exf"),
+        "function hello { return \"hello \"; }\n",
+    );
+    app
+}
+
+#[test]
+fn test_run_project() {
+    let root = temp_project("run");
+    let app = create_app(&root);
+    let expect = expect![[r#"
+        hello app
+        GET
+        red
+    "#]];
+    expect.assert_eq(&run_stdout(&app));
+    assert!(app.join(".openex/app.exb").is_file());
+    // 第二次运行直接执行缓存的字节码
+    expect.assert_eq(&run_stdout(&app));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_project_cache_invalidation() {
+    let root = temp_project("cache");
+    let app = create_app(&root);
+    run_stdout(&app);
+    // 保证修改时间晚于缓存
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write(
+        &root.join("util/src/strings.exf"),
+        "function hello { return \"hi \"; }\n",
+    );
+    expect![[r#"
+        hi app
+        GET
+        red
+    "#]]
+    .assert_eq(&run_stdout(&app));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_project_cache_shadowed_import() {
+    let root = temp_project("shadow");
+    let app = create_app(&root);
+    // strings 位于依赖包的源码目录中, 之后在应用的源码目录中新建的同名文件优先
+    write(
+        &app.join("src/main.exf"),
+        "import system;\nimport strings;\nsystem.println(strings.hello());\n",
+    );
+    expect![[r#"
+        hello 
+    "#]]
+    .assert_eq(&run_stdout(&app));
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write(
+        &app.join("src/strings.exf"),
+        "function hello { return \"shadowed\"; }\n",
+    );
+    expect![[r#"
+        shadowed
+    "#]]
+    .assert_eq(&run_stdout(&app));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_project_missing_entry() {
+    let root = temp_project("entry");
+    write(
+        &root.join("openex.toml"),
+        "[package]\nname = \"lib_only\"\n",
+    );
+    let output = openex_run(&root);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: manifest "));
+    assert!(stderr.ends_with("openex.toml has no entry file\n"));
+    fs::remove_dir_all(root).unwrap();
+}